@@ -0,0 +1,76 @@
+//! Status scanning, plan generation and hub parsing on synthetic stores of
+//! several sizes. Each measurement runs `dothub bench run` in a child process
+//! and takes the time it reports for the work itself, so process startup and
+//! store generation stay out of the numbers.
+//!
+//! Run with `cargo bench --bench hot_paths`; criterion compares each run
+//! against the previous one.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+const SIZES: [usize; 2] = [10, 100];
+const TARGETS: [&str; 4] = ["status", "status-cold", "plan", "hub"];
+
+fn dothub(args: &[&str]) -> String {
+    let out = Command::new(env!("CARGO_BIN_EXE_dothub"))
+        .args(args)
+        .output()
+        .expect("running dothub");
+    assert!(
+        out.status.success(),
+        "dothub {} failed: {}",
+        args.join(" "),
+        String::from_utf8_lossy(&out.stderr)
+    );
+    String::from_utf8_lossy(&out.stdout).into_owned()
+}
+
+/// A synthetic store of `repos` repos, generated once and reused across runs.
+fn store(repos: usize) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("bench-store-{}", repos));
+    if !dir.join("hub.yml").exists() {
+        let _ = std::fs::remove_dir_all(&dir);
+        let dir = dir.to_string_lossy();
+        dothub(&["bench", "generate", &dir, "--repos", &repos.to_string()]);
+    }
+    dir
+}
+
+/// Time `iters` runs of `target` on the store in `dir`.
+fn timed(target: &str, dir: &Path, iters: u64) -> Duration {
+    let out = dothub(&[
+        "bench",
+        "run",
+        target,
+        "--dir",
+        &dir.to_string_lossy(),
+        "--iterations",
+        &iters.to_string(),
+        "--json",
+    ]);
+    let report: serde_json::Value = serde_json::from_str(out.trim()).expect("parsing timings");
+    Duration::from_nanos(report["total_ns"].as_u64().expect("total_ns"))
+}
+
+fn hot_paths(c: &mut Criterion) {
+    for target in TARGETS {
+        let mut group = c.benchmark_group(target);
+        for repos in SIZES {
+            let dir = store(repos);
+            group.bench_with_input(BenchmarkId::from_parameter(repos), &dir, |b, dir| {
+                b.iter_custom(|iters| timed(target, dir, iters))
+            });
+        }
+        group.finish();
+    }
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(20);
+    targets = hot_paths
+}
+criterion_main!(benches);
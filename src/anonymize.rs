@@ -0,0 +1,409 @@
+//! Making a config repo publishable: your name, email and hostname out of its
+//! commits, and files with secrets out of git.
+//!
+//! The real values stay in the files you use. A git filter, set up in the
+//! clone's own config, swaps them for placeholders as files are committed
+//! (`clean`) and back as they are checked out (`smudge`); the values live in
+//! the clone's config under `dothub.anonymize.*`. Files the secret scan
+//! flags become local overrides: still deployed with the rest, but listed in
+//! `.git/info/exclude` so they are never committed. Adopting a target offers
+//! all of this before its first commit; `dothub anonymize` does it later.
+
+use crate::git;
+use crate::output::{self, Tone};
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Read, Write};
+use std::path::Path;
+use std::process::Command;
+
+/// Name of the git filter.
+const FILTER: &str = "dothub-anonymize";
+
+/// What is replaced, in the order it is replaced, and its placeholder.
+const PLACEHOLDERS: &[(&str, &str)] = &[
+    ("email", "you@example.com"),
+    ("name", "Your Name"),
+    ("hostname", "my-host"),
+];
+
+/// Header of the local overrides in `.git/info/exclude`.
+const OVERRIDES: &str = "# dothub: local overrides, never committed";
+
+/// What a repo gives away, found by [`plan`].
+struct Plan {
+    /// Kind from [`PLACEHOLDERS`], the real value and the files it is in
+    values: Vec<(&'static str, String, Vec<String>)>,
+    /// Files with possible secrets
+    flagged: Vec<String>,
+}
+
+/// `dothub anonymize <name>`: `name` is a stored repo, or a path to a
+/// checkout anywhere.
+pub fn cmd_anonymize(name: &str, yes: bool) -> Result<()> {
+    let repo = crate::verify_repo::repo_dir(name)?;
+    if !git::is_repo(&repo) {
+        bail!("{} is not a git repository", repo.display());
+    }
+    if !offer(&repo, yes)? {
+        return Ok(());
+    }
+    // Files committed before the filter existed are cleaned on the next commit
+    git::output(&repo, &["add", "--renormalize", "."])?;
+    if git::head(&repo).is_some() {
+        println!(
+            "Commit the staged changes to publish them anonymized; earlier commits still have the real values"
+        );
+    }
+    Ok(())
+}
+
+/// Show what the git repo at `repo` gives away and apply the
+/// transformations, each after a yes, or all of them with `yes`. Without a
+/// terminal and without `yes` nothing changes. Returns whether anything did.
+pub fn offer(repo: &Path, yes: bool) -> Result<bool> {
+    let plan = plan(repo)?;
+    if plan.values.is_empty() && plan.flagged.is_empty() {
+        println!("{}", output::paint("Nothing personal found", Tone::Ok));
+        return Ok(false);
+    }
+    let interactive = io::stdin().is_terminal() && io::stdout().is_terminal();
+    let mut changed = false;
+
+    let mut replace = Vec::new();
+    for (kind, value, files) in &plan.values {
+        let placeholder = placeholder(kind);
+        println!(
+            "Your {} {} is in {}",
+            kind,
+            output::paint(value, Tone::Warn),
+            files.join(", ")
+        );
+        let question = format!("Commit it as {} instead?", placeholder);
+        if decide(yes, interactive, &question)? {
+            replace.push((*kind, value.as_str()));
+        }
+    }
+    if !replace.is_empty() {
+        install_filter(repo, &replace)?;
+        changed = true;
+        println!(
+            "{}",
+            output::paint(
+                "Commits get placeholders; your files keep the real values",
+                Tone::Ok
+            )
+        );
+    }
+
+    if !plan.flagged.is_empty() {
+        println!(
+            "Possible secrets in {}",
+            output::paint(&plan.flagged.join(", "), Tone::Warn)
+        );
+        if decide(
+            yes,
+            interactive,
+            "Keep these files out of git as local overrides?",
+        )? {
+            exclude(repo, &plan.flagged)?;
+            changed = true;
+            println!(
+                "{}",
+                output::paint(
+                    "They stay deployed but are never committed (.git/info/exclude)",
+                    Tone::Ok
+                )
+            );
+        }
+    }
+
+    if !changed && !interactive && !yes {
+        println!(
+            "Nothing changed; run dothub anonymize {} --yes to make it publishable",
+            repo.display()
+        );
+    }
+    Ok(changed)
+}
+
+fn placeholder(kind: &str) -> &'static str {
+    PLACEHOLDERS
+        .iter()
+        .find(|(k, _)| *k == kind)
+        .map(|(_, p)| *p)
+        .unwrap_or_default()
+}
+
+fn plan(repo: &Path) -> Result<Plan> {
+    let leaks = crate::secrets::scan_dir(repo)?;
+    let flagged = leaks
+        .iter()
+        .map(|leak| leak.path.clone())
+        .collect::<BTreeSet<_>>();
+    let files = text_files(repo)
+        .into_iter()
+        .filter(|(rel, _)| !flagged.contains(rel))
+        .collect::<Vec<_>>();
+
+    let mut values = Vec::new();
+    for (kind, value) in personal(&files) {
+        let re = Regex::new(&pattern(kind, &value)).context("Building the anonymize pattern")?;
+        let found = files
+            .iter()
+            .filter(|(_, text)| re.is_match(text))
+            .map(|(rel, _)| rel.clone())
+            .collect::<Vec<_>>();
+        if !found.is_empty() {
+            values.push((kind, value, found));
+        }
+    }
+    Ok(Plan {
+        values,
+        flagged: flagged.into_iter().collect(),
+    })
+}
+
+/// Your name, email and hostname: from git's global config, from `[user]`
+/// sections in the repo's files (a git config being adopted is no longer
+/// where git looks), and from `uname -n`.
+fn personal(files: &[(String, String)]) -> Vec<(&'static str, String)> {
+    let mut found = Vec::new();
+    for kind in ["email", "name"] {
+        let global = Command::new("git")
+            .args(["config", "--global", "--get", &format!("user.{}", kind)])
+            .output()
+            .ok()
+            .filter(|out| out.status.success())
+            .map(|out| String::from_utf8_lossy(&out.stdout).trim().to_string());
+        let values = global
+            .into_iter()
+            .chain(files.iter().flat_map(|(_, text)| user_section(text, kind)));
+        for value in values {
+            if !value.is_empty() && value != placeholder(kind) {
+                found.push((kind, value));
+            }
+        }
+    }
+    if let Some(host) = hostname() {
+        found.push(("hostname", host));
+    }
+    let mut seen = BTreeSet::new();
+    found.retain(|(_, value)| seen.insert(value.clone()));
+    found
+}
+
+/// Values of `key` in the `[user]` sections of a git config.
+fn user_section(text: &str, key: &str) -> Vec<String> {
+    let mut in_user = false;
+    let mut values = Vec::new();
+    for line in text.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_user = line.eq_ignore_ascii_case("[user]");
+        } else if in_user {
+            if let Some((k, v)) = line.split_once('=') {
+                if k.trim().eq_ignore_ascii_case(key) {
+                    values.push(v.trim().trim_matches('"').to_string());
+                }
+            }
+        }
+    }
+    values
+}
+
+/// The machine's short hostname, unless it is too short or common to
+/// replace without mangling other words.
+fn hostname() -> Option<String> {
+    let out = Command::new("uname").arg("-n").output().ok()?;
+    let name = String::from_utf8_lossy(&out.stdout);
+    let short = name.trim().split('.').next()?.to_string();
+    (short.len() >= 3 && short != "localhost").then_some(short)
+}
+
+/// How `value` of `kind` is found; hostnames only as whole words.
+fn pattern(kind: &str, value: &str) -> String {
+    let escaped = regex::escape(value);
+    if kind == "hostname" {
+        format!(r"\b{}\b", escaped)
+    } else {
+        escaped
+    }
+}
+
+/// Text files under `repo` outside .git, by relative path.
+fn text_files(repo: &Path) -> Vec<(String, String)> {
+    let mut files = Vec::new();
+    let mut pending = vec![repo.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Ok(kind) = entry.file_type() else {
+                continue;
+            };
+            if kind.is_dir() && entry.file_name() != ".git" {
+                pending.push(path);
+            } else if kind.is_file() {
+                let Ok(bytes) = fs::read(&path) else {
+                    continue;
+                };
+                if bytes.contains(&0) {
+                    continue;
+                }
+                if let Ok(rel) = path.strip_prefix(repo) {
+                    files.push((
+                        rel.to_string_lossy().to_string(),
+                        String::from_utf8_lossy(&bytes).to_string(),
+                    ));
+                }
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+fn decide(yes: bool, interactive: bool, question: &str) -> Result<bool> {
+    if yes {
+        return Ok(true);
+    }
+    if !interactive {
+        return Ok(false);
+    }
+    print!("{} [y/N] ", question);
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// How git runs dothub for the filter: by name when it is on PATH, so
+/// upgrading or moving it doesn't break commits, or else by this executable's
+/// path, quoted for the shell git runs filters with.
+fn filter_program() -> Result<String> {
+    if which::which("dothub").is_ok() {
+        return Ok("dothub".to_string());
+    }
+    let exe = std::env::current_exe().context("Finding the dothub executable")?;
+    let msg = format!(
+        "dothub is not on PATH, so git runs {} for the filter; run dothub anonymize again after moving it",
+        exe.display()
+    );
+    println!("{}", output::paint(&msg, Tone::Warn));
+    Ok(format!(
+        "'{}'",
+        exe.to_string_lossy().replace('\'', "'\\''")
+    ))
+}
+
+/// Set up the filter in the clone at `repo` for `values`.
+fn install_filter(repo: &Path, values: &[(&str, &str)]) -> Result<()> {
+    let program = filter_program()?;
+    for direction in ["clean", "smudge"] {
+        git::output(
+            repo,
+            &[
+                "config",
+                &format!("filter.{}.{}", FILTER, direction),
+                &format!("{} anonymize-filter {}", program, direction),
+            ],
+        )?;
+    }
+    // Without dothub the filter would let the real values through, so a
+    // missing dothub fails the commit instead
+    git::output(
+        repo,
+        &["config", &format!("filter.{}.required", FILTER), "true"],
+    )?;
+    for (kind, _) in PLACEHOLDERS {
+        let key = format!("dothub.anonymize.{}", kind);
+        let _ = git::output(repo, &["config", "--unset-all", &key]);
+        for (_, value) in values.iter().filter(|(k, _)| k == kind) {
+            git::output(repo, &["config", "--add", &key, value])?;
+        }
+    }
+    let attributes = repo.join(git::output(
+        repo,
+        &["rev-parse", "--git-path", "info/attributes"],
+    )?);
+    let line = format!("* filter={}", FILTER);
+    append_once(&attributes, &[line.as_str()])
+}
+
+/// Keep `files` out of the clone at `repo`, untracking them if they were.
+fn exclude(repo: &Path, files: &[String]) -> Result<()> {
+    let path = repo.join(git::output(
+        repo,
+        &["rev-parse", "--git-path", "info/exclude"],
+    )?);
+    let mut lines = vec![OVERRIDES];
+    lines.extend(files.iter().map(|f| f.as_str()));
+    append_once(&path, &lines)?;
+    for file in files {
+        let _ = git::output(repo, &["rm", "--cached", "-q", "--", file]);
+    }
+    Ok(())
+}
+
+/// Append the `lines` that `path` doesn't have yet.
+fn append_once(path: &Path, lines: &[&str]) -> Result<()> {
+    let mut text = fs::read_to_string(path).unwrap_or_default();
+    let missing = lines
+        .iter()
+        .filter(|line| !text.lines().any(|l| l == **line))
+        .collect::<Vec<_>>();
+    if missing.is_empty() {
+        return Ok(());
+    }
+    if !text.is_empty() && !text.ends_with('\n') {
+        text.push('\n');
+    }
+    for line in missing {
+        text.push_str(line);
+        text.push('\n');
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).with_context(|| format!("Creating {}", parent.display()))?;
+    }
+    fs::write(path, text).with_context(|| format!("Writing {}", path.display()))
+}
+
+/// `dothub anonymize-filter clean|smudge`, run by git in the clone: stdin to
+/// stdout with the values swapped. Binary files pass through as they are.
+pub fn cmd_filter(direction: &str) -> Result<()> {
+    let mut bytes = Vec::new();
+    io::stdin()
+        .read_to_end(&mut bytes)
+        .context("Reading the file from git")?;
+    if !bytes.contains(&0) {
+        for (kind, placeholder) in PLACEHOLDERS {
+            let key = format!("dothub.anonymize.{}", kind);
+            let Ok(values) = git::output(Path::new("."), &["config", "--get-all", &key]) else {
+                continue;
+            };
+            // Every value is committed as the placeholder, which checks out
+            // as the first
+            let swaps = match direction {
+                "clean" => values
+                    .lines()
+                    .map(|v| (v, *placeholder))
+                    .collect::<Vec<_>>(),
+                _ => values.lines().take(1).map(|v| (*placeholder, v)).collect(),
+            };
+            for (from, to) in swaps {
+                let re = regex::bytes::Regex::new(&pattern(kind, from))
+                    .context("Building the anonymize pattern")?;
+                bytes = re
+                    .replace_all(&bytes, regex::bytes::NoExpand(to.as_bytes()))
+                    .into_owned();
+            }
+        }
+    }
+    io::stdout()
+        .write_all(&bytes)
+        .context("Writing the file to git")
+}
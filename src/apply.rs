@@ -0,0 +1,706 @@
+//! `dothub apply`: bring the machine in line with a manifest of repos and links.
+//!
+//! The manifest (`~/.config/dothub/manifest.toml` by default) names the repos
+//! to install and the targets to link, in the format of `link --from`:
+//!
+//! ```toml
+//! [repos]
+//! hygo-nvim = "https://github.com/hygo/nvim"
+//!
+//! [links]
+//! nvim = "hygo-nvim"
+//! git = "dotfiles/git"
+//! p10k = { repo = "p10k-theme", after = ["zsh"] }
+//! ```
+//!
+//! A link may name other targets it has to come `after`, e.g. when its hook
+//! expects another config in place. Links are made in dependency order, a
+//! link whose dependency failed is skipped, and cycles are refused.
+//!
+//! Repos and links can be limited to some machines with a `when` table of
+//! conditions that must all hold: `os`, `arch` and `hostname` globs, an `env`
+//! variable that is set (or `"VAR=glob"`), and a `command` found in PATH.
+//! Entries for other machines are left out of the plan as if not listed.
+//!
+//! A manifest can `include` others, e.g. a team's shared base and a file per
+//! machine role. Included files are read in order, paths relative to the
+//! including file or https:// URLs, and each layer's repos and links replace
+//! same-named ones from the layers before it; the including file's own
+//! entries come last and win. A replaced entry is replaced whole, `after` and
+//! `when` included. A downloaded manifest can only include others by URL.
+//!
+//! The manifest can also be an https:// URL, for bootstrapping a fresh
+//! machine from a cloud-init script: with `--unattended` nothing is asked,
+//! git is installed with the system package manager when missing, and the
+//! repos the manifest lists are linked without waiting for an audit review.
+//!
+//! Organizations can publish a manifest of their own, applied with `dothub
+//! org apply` as a separate layer: its targets are recorded per organization,
+//! so the personal manifest neither links nor unlinks them, and updating or
+//! removing the layer leaves personal links alone.
+//!
+//! Applying works in two stages. Planning compares the manifest with the store
+//! and state and lists what would be cloned, linked and unlinked; `--check`
+//! writes that plan to a file for review. `--commit <plan>` later carries out
+//! exactly the operations in the file, provided nothing changed in between.
+
+use crate::output::{self, Tone};
+use crate::state::{self, DeployMode, State};
+use anyhow::{bail, Context, Result};
+use comfy_table::Cell;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const PLAN_VERSION: u32 = 1;
+
+/// What a machine should have, read from the manifest file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    /// Manifests layered under this one, later ones overriding earlier ones
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// Store name to install URL or path
+    #[serde(default)]
+    pub repos: BTreeMap<String, RepoSpec>,
+    /// Target under ~/.config to "repo" or "repo/subpath"
+    #[serde(default)]
+    pub links: BTreeMap<String, LinkSpec>,
+}
+
+/// A manifest repo: its URL or path, or a table that can also say `when`.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum RepoSpec {
+    Url(String),
+    Full(RepoEntry),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RepoEntry {
+    pub url: String,
+    pub when: Option<When>,
+}
+
+impl RepoSpec {
+    pub fn url(&self) -> &str {
+        match self {
+            RepoSpec::Url(url) => url,
+            RepoSpec::Full(entry) => &entry.url,
+        }
+    }
+
+    fn when(&self) -> Option<&When> {
+        match self {
+            RepoSpec::Url(_) => None,
+            RepoSpec::Full(entry) => entry.when.as_ref(),
+        }
+    }
+}
+
+/// A manifest link: "repo[/subpath]", or a table that can also list the
+/// targets to link first and say `when`.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum LinkSpec {
+    Source(String),
+    Full(LinkEntry),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LinkEntry {
+    pub repo: String,
+    #[serde(default)]
+    pub after: Vec<String>,
+    pub when: Option<When>,
+}
+
+impl LinkSpec {
+    pub fn source(&self) -> &str {
+        match self {
+            LinkSpec::Source(s) => s,
+            LinkSpec::Full(entry) => &entry.repo,
+        }
+    }
+
+    pub fn after(&self) -> &[String] {
+        match self {
+            LinkSpec::Source(_) => &[],
+            LinkSpec::Full(entry) => &entry.after,
+        }
+    }
+
+    fn when(&self) -> Option<&When> {
+        match self {
+            LinkSpec::Source(_) => None,
+            LinkSpec::Full(entry) => entry.when.as_ref(),
+        }
+    }
+}
+
+/// Conditions on the machine, all of which must hold for an entry to apply.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct When {
+    /// Glob on the OS as Rust names it: linux, macos, windows, freebsd
+    pub os: Option<String>,
+    /// Glob on the CPU architecture: x86_64, aarch64
+    pub arch: Option<String>,
+    pub hostname: Option<String>,
+    /// "VAR" to require it set and non-empty, or "VAR=glob"
+    pub env: Option<String>,
+    /// A program that must be found in PATH
+    pub command: Option<String>,
+}
+
+impl When {
+    /// The first condition that doesn't hold on this machine, as written.
+    fn unmet(&self) -> Option<String> {
+        let glob = |pattern: &str, value: &str| {
+            glob::Pattern::new(pattern).is_ok_and(|p| p.matches(value))
+        };
+        if let Some(os) = self
+            .os
+            .as_deref()
+            .filter(|os| !glob(os, std::env::consts::OS))
+        {
+            return Some(format!("os = {}", os));
+        }
+        if let Some(arch) = self
+            .arch
+            .as_deref()
+            .filter(|a| !glob(a, std::env::consts::ARCH))
+        {
+            return Some(format!("arch = {}", arch));
+        }
+        if let Some(host) = self.hostname.as_deref().filter(|h| !glob(h, &hostname())) {
+            return Some(format!("hostname = {}", host));
+        }
+        if let Some(env) = &self.env {
+            let holds = match env.split_once('=') {
+                Some((var, pattern)) => std::env::var(var).is_ok_and(|v| glob(pattern, &v)),
+                None => std::env::var(env).is_ok_and(|v| !v.is_empty()),
+            };
+            if !holds {
+                return Some(format!("env = {}", env));
+            }
+        }
+        if let Some(command) = self.command.as_deref().filter(|c| which::which(c).is_err()) {
+            return Some(format!("command = {}", command));
+        }
+        None
+    }
+}
+
+fn hostname() -> String {
+    if let Ok(name) = fs::read_to_string("/proc/sys/kernel/hostname") {
+        return name.trim().to_string();
+    }
+    std::process::Command::new("hostname")
+        .output()
+        .map(|out| String::from_utf8_lossy(&out.stdout).trim().to_string())
+        .unwrap_or_default()
+}
+
+impl Manifest {
+    /// Put `layer` on top: its entries replace same-named ones.
+    fn overlay(&mut self, layer: Manifest) {
+        self.repos.extend(layer.repos);
+        self.links.extend(layer.links);
+    }
+
+    /// Drop the entries whose `when` doesn't hold here, and dependencies on
+    /// dropped links. Returns what was dropped and why.
+    fn for_this_machine(&mut self) -> Vec<(String, String)> {
+        let mut dropped = Vec::new();
+        self.repos
+            .retain(|name, spec| match spec.when().and_then(When::unmet) {
+                Some(why) => {
+                    dropped.push((name.clone(), why));
+                    false
+                }
+                None => true,
+            });
+        let mut gone = Vec::new();
+        self.links
+            .retain(|target, spec| match spec.when().and_then(When::unmet) {
+                Some(why) => {
+                    gone.push(target.clone());
+                    dropped.push((target.clone(), why));
+                    false
+                }
+                None => true,
+            });
+        self.forget_links(&gone);
+        dropped
+    }
+
+    /// Remove the links of `targets`, and dependencies on them.
+    fn forget_links(&mut self, targets: &[String]) {
+        self.links.retain(|target, _| !targets.contains(target));
+        for spec in self.links.values_mut() {
+            if let LinkSpec::Full(entry) = spec {
+                entry.after.retain(|dep| !targets.contains(dep));
+            }
+        }
+    }
+}
+
+/// One step of a plan.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Op {
+    Clone {
+        name: String,
+        url: String,
+    },
+    Link {
+        target_name: String,
+        name: String,
+        source: PathBuf,
+        target: PathBuf,
+        /// Targets that must be linked first
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        after: Vec<String>,
+    },
+    /// A target linked by an earlier apply that the manifest no longer lists
+    Unlink { target_name: String },
+}
+
+impl Op {
+    fn describe(&self) -> (&'static str, String, String) {
+        match self {
+            Op::Clone { name, url } => ("clone", name.clone(), url.clone()),
+            Op::Link {
+                target_name,
+                source,
+                ..
+            } => (
+                "link",
+                target_name.clone(),
+                source
+                    .strip_prefix(crate::dothub_dir())
+                    .unwrap_or(source)
+                    .display()
+                    .to_string(),
+            ),
+            Op::Unlink { target_name } => ("unlink", target_name.clone(), String::new()),
+        }
+    }
+}
+
+/// A reviewed list of operations, tied to the state it was planned against.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Plan {
+    pub version: u32,
+    pub manifest: PathBuf,
+    /// Organization whose layer this applies; the personal manifest if none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layer: Option<String>,
+    pub created_at: u64,
+    /// sha256 of the state file when planned; a plan for other state is refused
+    pub state_hash: String,
+    /// Targets the manifest links, recorded as applied once the plan ran
+    pub applied: Vec<String>,
+    pub ops: Vec<Op>,
+}
+
+pub fn default_manifest_path() -> PathBuf {
+    crate::config::config_path().with_file_name("manifest.toml")
+}
+
+/// The manifest to apply: `arg` itself, or for an https:// URL a fresh
+/// download of it kept in the cache directory.
+pub fn resolve_manifest(arg: Option<&Path>) -> Result<PathBuf> {
+    let Some(arg) = arg else {
+        return Ok(default_manifest_path());
+    };
+    let text = arg.to_string_lossy();
+    if text.starts_with("http://") {
+        bail!("Refusing to fetch a manifest over plain http; use https://");
+    }
+    if !text.starts_with("https://") {
+        return Ok(arg.to_path_buf());
+    }
+    let body = crate::http::fetch_bytes(&text)?;
+    let body = String::from_utf8(body).with_context(|| format!("{} is not text", text))?;
+    toml::from_str::<Manifest>(&body).with_context(|| format!("Parsing {}", text))?;
+    let digest = Sha256::digest(text.as_bytes());
+    let name: String = digest.iter().take(8).map(|b| format!("{:02x}", b)).collect();
+    let path = fetched_dir().join(format!("{}.toml", name));
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed creating {}", parent.display()))?;
+    }
+    fs::write(&path, body).with_context(|| format!("Writing {}", path.display()))?;
+    println!("Fetched {}", text);
+    Ok(path)
+}
+
+/// Where manifests downloaded from a URL are kept.
+fn fetched_dir() -> PathBuf {
+    crate::http::cache_dir().join("manifests")
+}
+
+/// Read the manifest at `path` with the manifests it includes merged in.
+pub fn load_manifest(path: &Path) -> Result<Manifest> {
+    load_layers(path, &mut Vec::new())
+}
+
+/// `stack` holds the files being read, to refuse an include cycle.
+fn load_layers(path: &Path, stack: &mut Vec<PathBuf>) -> Result<Manifest> {
+    let key = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    if let Some(at) = stack.iter().position(|p| *p == key) {
+        let cycle = stack[at..]
+            .iter()
+            .chain([&key])
+            .map(|p| p.display().to_string())
+            .collect::<Vec<_>>();
+        bail!("Manifests include each other in a cycle: {}", cycle.join(" -> "));
+    }
+    let text = fs::read_to_string(path).with_context(|| format!("Reading {}", path.display()))?;
+    let mut own: Manifest =
+        toml::from_str(&text).with_context(|| format!("Parsing {}", path.display()))?;
+
+    stack.push(key);
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let mut merged = Manifest::default();
+    for include in std::mem::take(&mut own.include) {
+        let included = if include.contains("://") {
+            resolve_manifest(Some(Path::new(&include)))?
+        } else if dir == fetched_dir() {
+            // Next to a downloaded manifest is only the cache
+            bail!(
+                "{} was downloaded, so it can't include '{}' by a relative path; give its full https:// URL",
+                path.display(),
+                include
+            );
+        } else {
+            dir.join(&include)
+        };
+        let layer = load_layers(&included, stack)
+            .with_context(|| format!("Included from {}", path.display()))?;
+        merged.overlay(layer);
+    }
+    stack.pop();
+    merged.overlay(own);
+    Ok(merged)
+}
+
+fn state_hash() -> String {
+    state::hash_file(&state::state_path()).unwrap_or_default()
+}
+
+/// Compare the manifest at `path` with the store and state, as the layer of
+/// organization `layer` or as the personal manifest.
+pub fn plan(path: &Path, layer: Option<&str>) -> Result<Plan> {
+    let mut manifest = load_manifest(path)?;
+    for (entry, why) in manifest.for_this_machine() {
+        println!(
+            "{}",
+            output::paint(
+                &format!("Not for this machine: {} ({})", entry, why),
+                Tone::Muted
+            )
+        );
+    }
+    let st = State::load()?;
+    let owners = owners(&st, layer);
+    let taken = manifest
+        .links
+        .keys()
+        .filter(|target| owners.contains_key(target.as_str()))
+        .cloned()
+        .collect::<Vec<_>>();
+    for target in &taken {
+        println!(
+            "{}",
+            output::paint(
+                &format!("Leaving {} to org {}", target, owners[target.as_str()]),
+                Tone::Muted
+            )
+        );
+    }
+    manifest.forget_links(&taken);
+    let previous = match layer {
+        Some(org) => st.orgs.get(org).map(|o| o.applied.clone()).unwrap_or_default(),
+        None => st.applied.clone(),
+    };
+    let store = crate::dothub_dir();
+
+    let mut ops = Vec::new();
+    for (name, spec) in &manifest.repos {
+        let url = spec.url();
+        if !store.join(name).exists() {
+            ops.push(Op::Clone {
+                name: name.clone(),
+                url: url.to_string(),
+            });
+        }
+    }
+    for target_name in link_order(&manifest.links)? {
+        let spec = &manifest.links[&target_name];
+        let target_name = &target_name;
+        let (name, source) = match spec.source().split_once('/') {
+            Some((name, sub)) => (name, store.join(name).join(sub)),
+            None => (spec.source(), store.join(spec.source())),
+        };
+        if !store.join(name).exists() && !manifest.repos.contains_key(name) {
+            bail!(
+                "{} links to {}, which is neither installed nor listed under [repos]",
+                target_name,
+                name
+            );
+        }
+        let target = crate::targets::path(target_name)?;
+        crate::protect::check(&target)
+            .with_context(|| format!("The manifest links {}", target_name))?;
+        // Already in place: same source, still a symlink and nothing drifted
+        let done = st.links.get(target_name).is_some_and(|rec| {
+            rec.source == source
+                && (rec.mode == DeployMode::Symlink || crate::targets::copy_reason(&target).is_some())
+                && crate::verify::check_record(rec).is_empty()
+                && !crate::assets::pending(name)
+        });
+        if !done {
+            ops.push(Op::Link {
+                target_name: target_name.clone(),
+                name: name.to_string(),
+                source,
+                target,
+                after: spec.after().to_vec(),
+            });
+        }
+    }
+    for target_name in &previous {
+        if manifest.links.contains_key(target_name) || owners.contains_key(target_name.as_str()) {
+            continue;
+        }
+        if let Some(rec) = st.links.get(target_name) {
+            crate::protect::check(&rec.target)
+                .with_context(|| format!("The plan would unlink {}", target_name))?;
+            ops.push(Op::Unlink {
+                target_name: target_name.clone(),
+            });
+        }
+    }
+
+    Ok(Plan {
+        version: PLAN_VERSION,
+        manifest: path.to_path_buf(),
+        layer: layer.map(str::to_string),
+        created_at: state::now_unix(),
+        state_hash: state_hash(),
+        applied: manifest.links.keys().cloned().collect(),
+        ops,
+    })
+}
+
+/// Targets linked by organization layers other than `layer`, and the
+/// organization each belongs to. Their links win over the personal manifest's.
+fn owners<'a>(st: &'a State, layer: Option<&str>) -> BTreeMap<&'a str, &'a str> {
+    st.orgs
+        .iter()
+        .filter(|(org, _)| Some(org.as_str()) != layer)
+        .flat_map(|(org, o)| o.applied.iter().map(|t| (t.as_str(), org.as_str())))
+        .collect()
+}
+
+/// Targets of `links` ordered so each comes after the ones it names in
+/// `after`, otherwise alphabetically.
+fn link_order(links: &BTreeMap<String, LinkSpec>) -> Result<Vec<String>> {
+    fn visit(
+        target: &str,
+        links: &BTreeMap<String, LinkSpec>,
+        path: &mut Vec<String>,
+        order: &mut Vec<String>,
+    ) -> Result<()> {
+        if order.iter().any(|t| t == target) {
+            return Ok(());
+        }
+        if let Some(start) = path.iter().position(|t| t == target) {
+            let mut cycle = path[start..].to_vec();
+            cycle.push(target.to_string());
+            bail!("The manifest's links depend on each other in a cycle: {}", cycle.join(" -> "));
+        }
+        path.push(target.to_string());
+        for dep in links[target].after() {
+            if !links.contains_key(dep) {
+                bail!("{} is linked after {}, which the manifest doesn't link", target, dep);
+            }
+            visit(dep, links, path, order)?;
+        }
+        path.pop();
+        order.push(target.to_string());
+        Ok(())
+    }
+
+    let mut order = Vec::new();
+    for target in links.keys() {
+        visit(target, links, &mut Vec::new(), &mut order)?;
+    }
+    Ok(order)
+}
+
+pub fn print_plan(plan: &Plan) {
+    if plan.ops.is_empty() {
+        println!(
+            "{}",
+            output::paint(&crate::i18n::t("apply-nothing", &[]), Tone::Ok)
+        );
+        return;
+    }
+    let mut table = output::table(["Action", "Subject", "From"]);
+    for op in &plan.ops {
+        let (action, subject, from) = op.describe();
+        let tone = if action == "unlink" { Tone::Warn } else { Tone::Info };
+        table.add_row(vec![output::cell(action, tone), Cell::new(subject), Cell::new(from)]);
+    }
+    println!("{}", table);
+}
+
+/// `dothub apply`: plan and run, or only one of the two stages.
+pub fn cmd_apply(
+    manifest: Option<&Path>,
+    check: Option<&Path>,
+    commit: Option<&Path>,
+    unattended: bool,
+) -> Result<()> {
+    if let Some(plan_file) = commit {
+        let text = fs::read_to_string(plan_file)
+            .with_context(|| format!("Reading {}", plan_file.display()))?;
+        let plan: Plan = serde_json::from_str(&text)
+            .with_context(|| format!("Parsing {}", plan_file.display()))?;
+        if plan.version != PLAN_VERSION {
+            bail!("{} was written by another dothub version; plan again", plan_file.display());
+        }
+        if plan.state_hash != state_hash() {
+            bail!(crate::error::Error::new(
+                crate::error::Code::PlanStale,
+                format!("Links or repos changed since {} was planned", plan_file.display()),
+            )
+            .hint(format!("dothub apply --check {} and review it again", plan_file.display())));
+        }
+        return execute(&plan, unattended);
+    }
+
+    let path = resolve_manifest(manifest)?;
+    let plan = plan(&path, None)?;
+    print_plan(&plan);
+    if let Some(plan_file) = check {
+        let text = serde_json::to_string_pretty(&plan).context("Serializing plan")?;
+        fs::write(plan_file, text).with_context(|| format!("Writing {}", plan_file.display()))?;
+        println!(
+            "Wrote the plan to {}; run it with dothub apply --commit {}",
+            plan_file.display(),
+            plan_file.display()
+        );
+        return Ok(());
+    }
+    execute(&plan, unattended)
+}
+
+pub fn execute(plan: &Plan, unattended: bool) -> Result<()> {
+    if !plan.ops.is_empty() && !crate::vfs::dry_run() {
+        let st = State::load()?;
+        let targets = plan
+            .ops
+            .iter()
+            .filter_map(|op| match op {
+                Op::Clone { .. } => None,
+                Op::Link { target, .. } => Some(target.clone()),
+                Op::Unlink { target_name } => st.links.get(target_name).map(|r| r.target.clone()),
+            })
+            .collect();
+        crate::snapshot::record(crate::snapshot::Operation {
+            command: "apply".to_string(),
+            at: state::now_unix(),
+            targets,
+            snapshot: crate::snapshot::before("apply")?,
+        })?;
+    }
+    let clones = plan.ops.iter().any(|op| matches!(op, Op::Clone { .. }));
+    if clones && unattended && !crate::vfs::dry_run() && which::which("git").is_err() {
+        println!("git is missing; installing it");
+        crate::bundle::install_unattended("git")?;
+    }
+    let mut failed = 0;
+    // Targets that didn't get linked, which the links after them wait for
+    let mut missing: Vec<&str> = Vec::new();
+    for op in &plan.ops {
+        if let Op::Link {
+            target_name, after, ..
+        } = op
+        {
+            if let Some(dep) = after.iter().find(|d| missing.contains(&d.as_str())) {
+                failed += 1;
+                missing.push(target_name);
+                println!(
+                    "{}",
+                    output::paint(
+                        &format!("Skipped {}: {} was not linked", target_name, dep),
+                        Tone::Error
+                    )
+                );
+                continue;
+            }
+        }
+        if let (Op::Link { name, .. }, true) = (op, unattended) {
+            // Whoever wrote the manifest chose these repos
+            crate::audit::accept_listed(name)?;
+        }
+        let result = match op {
+            Op::Clone { name, url } => crate::install_repo(url, Some(name), false, true)
+                .map(|_| format!("Installed {}", name)),
+            Op::Link {
+                target_name,
+                name,
+                source,
+                target,
+                ..
+            } => {
+                let linked = crate::link_one(name, source, target_name, target, false);
+                if !matches!(linked, Ok(true)) {
+                    missing.push(target_name);
+                }
+                linked.map(|linked| {
+                    if linked {
+                        crate::reload::after_change(target_name, target);
+                        format!("Linked {}", target_name)
+                    } else {
+                        output::paint(&format!("Skipped {}", target_name), Tone::Warn)
+                    }
+                })
+            }
+            Op::Unlink { target_name } => State::load().and_then(|mut st| {
+                let msg = crate::unlink_target(&mut st, target_name)?;
+                st.save()?;
+                Ok(msg)
+            }),
+        };
+        match result {
+            Ok(msg) => println!("{}", msg),
+            Err(e) => {
+                failed += 1;
+                println!("{}", output::paint(&format!("{:#}", e), Tone::Error));
+            }
+        }
+    }
+
+    let mut st = State::load()?;
+    let applied = plan.applied.iter().cloned().collect();
+    match &plan.layer {
+        Some(org) => st.orgs.entry(org.clone()).or_default().applied = applied,
+        None => st.applied = applied,
+    }
+    st.save()?;
+    if failed > 0 {
+        bail!("{} of {} operation(s) failed", failed, plan.ops.len());
+    }
+    Ok(())
+}
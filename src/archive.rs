@@ -0,0 +1,135 @@
+//! `dothub archive`: old experiments out of the way, but not gone.
+//!
+//! Archiving a repo unlinks its targets and moves it to `.archive/` in the
+//! store, where update, status, list and the other commands that walk the
+//! store don't look. State keeps why and when, the targets it was linked as,
+//! and its record (tags, schedule, trust), so `dothub unarchive` puts it back
+//! as it was and says how to relink it.
+
+use crate::output::{self, Tone};
+use crate::state::{ArchivedRepo, State};
+use anyhow::{bail, Context, Result};
+use comfy_table::Cell;
+use std::fs;
+use std::path::PathBuf;
+
+/// Where archived repos live; hidden, so nothing lists it as a repo.
+pub fn dir() -> PathBuf {
+    crate::dothub_dir().join(".archive")
+}
+
+/// `dothub archive <name>`: unlink the repo and move it to the archive.
+pub fn cmd_archive(name: &str, reason: Option<&str>) -> Result<()> {
+    let path = crate::dothub_dir().join(name);
+    if !crate::vfs::fs().exists(&path) {
+        bail!(crate::error::repo_not_found(name, &path));
+    }
+    let dest = dir().join(name);
+    if crate::vfs::fs().exists(&dest) {
+        bail!(
+            "An archived repo named {} already exists; unarchive or delete {} first",
+            name,
+            dest.display()
+        );
+    }
+    let mut st = State::load()?;
+    let targets = crate::linked_targets(&st, name);
+    for target_name in &targets {
+        println!("{}", crate::unlink_target(&mut st, target_name)?);
+    }
+    // Save the unlinks before moving, so a failed move leaves state true
+    st.save()?;
+    fs::create_dir_all(dir()).with_context(|| format!("Creating {}", dir().display()))?;
+    fs::rename(&path, &dest)
+        .with_context(|| format!("Moving {} -> {}", path.display(), dest.display()))?;
+    let record = st.repos.remove(name);
+    st.archived.insert(
+        name.to_string(),
+        ArchivedRepo {
+            archived_at: crate::state::now_unix(),
+            reason: reason.map(str::to_string),
+            targets,
+            record,
+        },
+    );
+    st.save()?;
+    println!(
+        "{}",
+        output::paint(
+            &format!(
+                "Archived {}; dothub unarchive {} brings it back",
+                name, name
+            ),
+            Tone::Ok
+        )
+    );
+    Ok(())
+}
+
+/// `dothub unarchive <name>`: move the repo back into the store.
+pub fn cmd_unarchive(name: &str) -> Result<()> {
+    let path = dir().join(name);
+    if !crate::vfs::fs().exists(&path) {
+        bail!(crate::error::Error::new(
+            crate::error::Code::RepoNotFound,
+            format!("No archived repo named {}", name),
+        )
+        .hint("dothub archive lists the archived repos"));
+    }
+    let dest = crate::dothub_dir().join(name);
+    if crate::vfs::fs().exists(&dest) {
+        bail!(
+            "{} is in the store again; remove it or rename it before unarchiving",
+            dest.display()
+        );
+    }
+    fs::rename(&path, &dest)
+        .with_context(|| format!("Moving {} -> {}", path.display(), dest.display()))?;
+    let mut st = State::load()?;
+    let archived = st.archived.remove(name).unwrap_or_default();
+    if let Some(record) = archived.record {
+        st.repos.insert(name.to_string(), record);
+    }
+    st.save()?;
+    println!("{}", output::paint(&format!("Restored {}", name), Tone::Ok));
+    for target_name in &archived.targets {
+        println!("Relink it with dothub link {} {}", name, target_name);
+    }
+    Ok(())
+}
+
+/// `dothub archive` without a name: what is archived, when and why.
+pub fn cmd_list() -> Result<()> {
+    let st = State::load()?;
+    let mut names = match fs::read_dir(dir()) {
+        Ok(entries) => entries
+            .flatten()
+            .map(|e| e.file_name().to_string_lossy().to_string())
+            .collect::<Vec<_>>(),
+        Err(_) => Vec::new(),
+    };
+    names.sort();
+    if names.is_empty() {
+        println!("No archived repositories.");
+        return Ok(());
+    }
+    let now = crate::state::now_unix();
+    let mut table = output::table(["Name", "Archived", "Reason", "Was linked as"]);
+    for name in names {
+        let entry = st.archived.get(&name);
+        let when = entry.map_or("-".to_string(), |a| output::ago(a.archived_at, now));
+        let reason = entry.and_then(|a| a.reason.clone());
+        let targets = entry.map(|a| a.targets.join(", ")).unwrap_or_default();
+        table.add_row(vec![
+            Cell::new(name),
+            Cell::new(when),
+            match reason {
+                Some(reason) => Cell::new(reason),
+                None => output::cell("-", Tone::Muted),
+            },
+            Cell::new(targets),
+        ]);
+    }
+    println!("{}", table);
+    Ok(())
+}
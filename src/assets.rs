@@ -0,0 +1,216 @@
+//! Downloads a repo declares instead of committing them, such as a tmux
+//! plugin release or a themes tarball.
+//!
+//! Each `[[assets]]` entry in dothub.toml names a URL, its sha256 and where it
+//! goes in the repo. Linking, `dothub apply` and `dothub update` fetch the
+//! ones not yet in place, refuse any whose checksum differs, and extract
+//! archives. Assets land in the store, so links and copies see them like
+//! committed files; in git repos they are added to .git/info/exclude so they
+//! don't show up as changes. A download the repo adds or changes is its
+//! author's choice, so `dothub trust` decides whether it is fetched, and a
+//! dest that a symlink in the repo leads outside of it is refused.
+
+use crate::manifest::{Asset, RepoManifest};
+use crate::state::State;
+use anyhow::{bail, Context, Result};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Whether `name` declares assets that are missing or out of date.
+pub fn pending(name: &str) -> bool {
+    let repo = crate::dothub_dir().join(name);
+    let Ok(Some(manifest)) = RepoManifest::load(&repo) else {
+        return false;
+    };
+    let Ok(st) = State::load() else {
+        return true;
+    };
+    let have = st.repos.get(name).map(|r| &r.assets);
+    manifest.assets.iter().any(|asset| {
+        have.and_then(|h| h.get(&asset.dest)) != Some(&asset.sha256.to_lowercase())
+            || fs::symlink_metadata(repo.join(&asset.dest)).is_err()
+    }) || have.is_some_and(|h| {
+        h.keys()
+            .any(|d| !manifest.assets.iter().any(|a| a.dest == *d))
+    })
+}
+
+/// Put the assets of `name` in place, and remove those it no longer declares.
+/// Returns how many were downloaded.
+pub fn fetch(name: &str) -> Result<usize> {
+    if crate::vfs::dry_run() {
+        if pending(name) {
+            crate::vfs::note(format!("download the assets of {}", name));
+        }
+        return Ok(0);
+    }
+    let repo = crate::dothub_dir().join(name);
+    let manifest = RepoManifest::load(&repo)?.unwrap_or_default();
+    let mut st = State::load()?;
+    let have = st
+        .repos
+        .get(name)
+        .map(|r| r.assets.clone())
+        .unwrap_or_default();
+    if manifest.assets.is_empty() && have.is_empty() {
+        return Ok(0);
+    }
+
+    let fresh = manifest
+        .assets
+        .iter()
+        .filter(|a| have.get(&a.dest) != Some(&a.sha256.to_lowercase()))
+        .map(|a| format!("{} -> {}", a.url, a.dest))
+        .collect::<Vec<_>>();
+    let allowed = fresh.is_empty()
+        || crate::trust::allow(name, "the downloads of its [[assets]]", &fresh.join("\n"))?;
+
+    let mut fetched = 0;
+    for asset in &manifest.assets {
+        check_dest(&asset.dest)?;
+        let want = asset.sha256.to_lowercase();
+        let known = have.get(&asset.dest) == Some(&want);
+        let dest = resolve_dest(&repo, &asset.dest)?;
+        if (known && fs::symlink_metadata(&dest).is_ok()) || (!known && !allowed) {
+            continue;
+        }
+        place(&dest, asset, &want)?;
+        exclude(&repo, &asset.dest)?;
+        st.repos
+            .entry(name.to_string())
+            .or_default()
+            .assets
+            .insert(asset.dest.clone(), want);
+        st.save()?;
+        fetched += 1;
+    }
+    for dest in have.keys() {
+        if manifest.assets.iter().any(|a| a.dest == *dest) {
+            continue;
+        }
+        crate::remove_path(&resolve_dest(&repo, dest)?)?;
+        println!("Removed asset {} of {}", dest, name);
+        if let Some(rec) = st.repos.get_mut(name) {
+            rec.assets.remove(dest);
+        }
+        st.save()?;
+    }
+    Ok(fetched)
+}
+
+/// Assets go inside the repo: a relative path that doesn't climb out of it.
+fn check_dest(dest: &str) -> Result<()> {
+    let path = Path::new(dest);
+    let inside = !dest.is_empty()
+        && path
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+        && path.components().next() != Some(Component::Normal(".git".as_ref()));
+    if !inside {
+        bail!("Asset dest '{}' must be a path inside the repo", dest);
+    }
+    Ok(())
+}
+
+/// Where `dest` is under `repo`, refused when a symlink committed to the repo
+/// would lead it outside, e.g. `vendor -> ~/.ssh`.
+fn resolve_dest(repo: &Path, dest: &str) -> Result<PathBuf> {
+    let root = fs::canonicalize(repo).with_context(|| format!("Resolving {}", repo.display()))?;
+    let path = root.join(dest);
+    // The deepest directory that exists is where the rest gets created
+    let mut existing = path.parent().unwrap_or(&root);
+    while fs::symlink_metadata(existing).is_err() {
+        existing = existing.parent().unwrap_or(&root);
+    }
+    let real =
+        fs::canonicalize(existing).with_context(|| format!("Resolving {}", existing.display()))?;
+    if !real.starts_with(&root) {
+        bail!(
+            "Asset dest '{}' leads outside the repo through a symlink to {}",
+            dest,
+            real.display()
+        );
+    }
+    Ok(path)
+}
+
+/// Download `asset`, verify it and write or extract it to `dest`.
+fn place(dest: &Path, asset: &Asset, want: &str) -> Result<()> {
+    let (bytes, mirrored) = match crate::mirror::asset(want) {
+        Some(bytes) => (bytes, true),
+        None => {
+            println!("Downloading {}", asset.url);
+            (crate::http::fetch_bytes(&asset.url)?, false)
+        }
+    };
+    let actual: String = Sha256::digest(&bytes)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    if actual != want {
+        bail!(crate::error::Error::new(
+            crate::error::Code::ChecksumMismatch,
+            format!(
+                "{} has sha256 {}, but dothub.toml expects {}",
+                asset.url, actual, want
+            ),
+        )
+        .hint("ask the repo's author to fix the checksum, or update it if you changed the url"));
+    }
+    if !mirrored {
+        crate::mirror::store_asset(want, &bytes)?;
+    }
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed creating {}", parent.display()))?;
+    }
+    let path = asset.url.split(['?', '#']).next().unwrap_or(&asset.url);
+    match crate::source::archive_ext(path) {
+        Some(ext) => {
+            let dir = crate::http::cache_dir().join("assets");
+            fs::create_dir_all(&dir)
+                .with_context(|| format!("Failed creating {}", dir.display()))?;
+            let archive = dir.join(format!("{}{}", actual, ext));
+            fs::write(&archive, &bytes)
+                .with_context(|| format!("Writing {}", archive.display()))?;
+            crate::source::swap_in(dest, |staging| crate::source::extract(&archive, staging))?;
+        }
+        None => {
+            let tmp = dest.with_file_name(format!(
+                ".{}.new",
+                dest.file_name().unwrap_or_default().to_string_lossy()
+            ));
+            fs::write(&tmp, &bytes).with_context(|| format!("Writing {}", tmp.display()))?;
+            crate::remove_path(dest)?;
+            fs::rename(&tmp, dest)
+                .with_context(|| format!("Moving {} -> {}", tmp.display(), dest.display()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Keep `dest` out of `git status` in git repos.
+fn exclude(repo: &Path, dest: &str) -> Result<()> {
+    if !crate::git::is_repo(repo) {
+        return Ok(());
+    }
+    let rel = crate::git::output(repo, &["rev-parse", "--git-path", "info/exclude"])?;
+    let path = repo.join(rel.trim());
+    let line = format!("/{}", dest.trim_start_matches("./"));
+    let text = fs::read_to_string(&path).unwrap_or_default();
+    if text.lines().any(|l| l == line) {
+        return Ok(());
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed creating {}", parent.display()))?;
+    }
+    let mut text = text;
+    if !text.is_empty() && !text.ends_with('\n') {
+        text.push('\n');
+    }
+    text.push_str(&line);
+    text.push('\n');
+    fs::write(&path, text).with_context(|| format!("Writing {}", path.display()))
+}
@@ -0,0 +1,446 @@
+//! `dothub audit`: red flags in a stored repo before it is first linked.
+//!
+//! Linking someone else's shell or editor config runs their code the next
+//! time the application starts. The scan looks for what deserves a read
+//! first: executables, downloads piped into a shell, binary blobs, startup
+//! hooks that reach the network and credentials left in the files. A repo
+//! with findings is only linked for the first time after the user accepts
+//! them, either at the prompt or with `dothub audit <name> --accept`.
+
+use crate::error::{Code, Error};
+use crate::manifest::{RepoManifest, REPO_MANIFEST};
+use crate::output::{self, Tone};
+use crate::state::State;
+use anyhow::{bail, Context, Result};
+use comfy_table::Cell;
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Read, Write};
+use std::path::Path;
+
+/// Files larger than this are not read line by line.
+const MAX_TEXT_SIZE: u64 = 1024 * 1024;
+
+/// Extensions of binary files that are expected in a config repo.
+const MEDIA: &[&str] = &[
+    "png", "jpg", "jpeg", "gif", "webp", "ico", "bmp", "svg", "ttf", "otf", "woff", "woff2",
+];
+
+/// Variables that change which programs or libraries run, or run code at
+/// startup. `[env]` never exports them.
+pub const EXEC_ENV: &[&str] = &[
+    "PATH",
+    "LD_PRELOAD",
+    "LD_AUDIT",
+    "LD_LIBRARY_PATH",
+    "DYLD_INSERT_LIBRARIES",
+    "DYLD_LIBRARY_PATH",
+    "BASH_ENV",
+    "ENV",
+    "PROMPT_COMMAND",
+    "ZDOTDIR",
+    "GIT_SSH_COMMAND",
+    "GIT_EXEC_PATH",
+    "PYTHONSTARTUP",
+    "PYTHONPATH",
+    "NODE_OPTIONS",
+    "PERL5OPT",
+    "RUBYOPT",
+];
+
+/// Commands that download or open connections.
+const NETWORK: &[&str] = &[
+    "curl ",
+    "wget ",
+    "nc ",
+    "http://",
+    "https://",
+    "Invoke-WebRequest",
+];
+
+/// Lines that run code on an editor or shell event.
+const HOOKS: &[&str] = &[
+    "autocmd",
+    "nvim_create_autocmd",
+    "precmd",
+    "preexec",
+    "chpwd",
+    "PROMPT_COMMAND",
+    "add-zsh-hook",
+    "--on-event",
+    "--on-variable",
+    "trap ",
+];
+
+/// Prefixes of well-known token formats.
+const TOKEN_PREFIXES: &[&str] = &[
+    "ghp_",
+    "gho_",
+    "ghs_",
+    "github_pat_",
+    "glpat-",
+    "xoxb-",
+    "xoxp-",
+    "sk-",
+];
+
+/// Assignments whose literal value is likely a secret.
+const SECRET_NAMES: &[&str] = &["password", "passwd", "secret", "token", "api_key", "apikey"];
+
+pub struct Finding {
+    pub rule: &'static str,
+    /// Path relative to the repo
+    pub path: String,
+    pub line: Option<usize>,
+    pub detail: String,
+}
+
+/// Scan every file of the repo at `repo` except .git.
+pub fn scan(repo: &Path) -> Result<Vec<Finding>> {
+    let mut findings = Vec::new();
+    walk(repo, repo, &mut findings)?;
+    if let Some(manifest) = RepoManifest::load(repo)? {
+        let hooks = &manifest.hooks;
+        for (hook, script) in [
+            ("post_link", &hooks.post_link),
+            ("post_update", &hooks.post_update),
+        ] {
+            if let Some(script) = script {
+                findings.push(Finding {
+                    rule: "hook",
+                    path: REPO_MANIFEST.to_string(),
+                    line: None,
+                    detail: crate::hub::truncate(&format!("{}: {}", hook, script), 80),
+                });
+            }
+        }
+        for (var, value) in &manifest.env {
+            let detail = if EXEC_ENV.contains(&var.as_str()) {
+                format!("{} changes what runs and is never exported", var)
+            } else {
+                format!("{} = {}", var, value)
+            };
+            findings.push(Finding {
+                rule: "env",
+                path: REPO_MANIFEST.to_string(),
+                line: None,
+                detail: crate::hub::truncate(&detail, 80),
+            });
+        }
+        for asset in &manifest.assets {
+            findings.push(Finding {
+                rule: "asset",
+                path: REPO_MANIFEST.to_string(),
+                line: None,
+                detail: crate::hub::truncate(&format!("{} -> {}", asset.url, asset.dest), 80),
+            });
+        }
+    }
+    Ok(findings)
+}
+
+fn walk(root: &Path, dir: &Path, findings: &mut Vec<Finding>) -> Result<()> {
+    let mut entries: Vec<_> = fs::read_dir(dir)
+        .with_context(|| format!("Reading {}", dir.display()))?
+        .collect::<io::Result<_>>()?;
+    entries.sort_by_key(|e| e.file_name());
+    for entry in entries {
+        let path = entry.path();
+        if entry.file_name() == ".git" {
+            continue;
+        }
+        // Symlinks are not followed: they may point anywhere on this machine
+        let meta = fs::symlink_metadata(&path)?;
+        if meta.is_dir() {
+            walk(root, &path, findings)?;
+        } else if meta.is_file() {
+            let rel = path
+                .strip_prefix(root)
+                .unwrap_or(&path)
+                .display()
+                .to_string();
+            scan_file(&path, &rel, &meta, findings)?;
+        }
+    }
+    Ok(())
+}
+
+fn scan_file(
+    path: &Path,
+    rel: &str,
+    meta: &fs::Metadata,
+    findings: &mut Vec<Finding>,
+) -> Result<()> {
+    let mut head = Vec::new();
+    fs::File::open(path)
+        .with_context(|| format!("Reading {}", path.display()))?
+        .take(8192)
+        .read_to_end(&mut head)?;
+    let binary = head.contains(&0);
+    let ext = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if meta.permissions().mode() & 0o111 != 0 {
+            let kind = if head.starts_with(b"#!") {
+                String::from_utf8_lossy(head.split(|b| *b == b'\n').next().unwrap_or_default())
+                    .to_string()
+            } else if binary {
+                "compiled program".to_string()
+            } else {
+                "executable file".to_string()
+            };
+            findings.push(Finding {
+                rule: "executable",
+                path: rel.to_string(),
+                line: None,
+                detail: kind,
+            });
+        }
+    }
+    if binary {
+        if !MEDIA.contains(&ext.as_str()) {
+            findings.push(Finding {
+                rule: "binary",
+                path: rel.to_string(),
+                line: None,
+                detail: format!("{} of binary data", crate::conflict::human_size(meta.len())),
+            });
+        }
+        return Ok(());
+    }
+    if meta.len() > MAX_TEXT_SIZE {
+        return Ok(());
+    }
+    let text = fs::read(path).with_context(|| format!("Reading {}", path.display()))?;
+    for (idx, line) in String::from_utf8_lossy(&text).lines().enumerate() {
+        if let Some((rule, detail)) = check_line(line) {
+            findings.push(Finding {
+                rule,
+                path: rel.to_string(),
+                line: Some(idx + 1),
+                detail,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// The red flag on one line of text, if any.
+fn check_line(line: &str) -> Option<(&'static str, String)> {
+    let trimmed = line.trim();
+    if pipes_to_shell(trimmed) {
+        return Some(("pipe-to-shell", crate::hub::truncate(trimmed, 80)));
+    }
+    if HOOKS.iter().any(|h| trimmed.contains(h)) && NETWORK.iter().any(|n| trimmed.contains(n)) {
+        return Some(("network-hook", crate::hub::truncate(trimmed, 80)));
+    }
+    if let Some(kind) = credential(trimmed) {
+        return Some(("credential", kind));
+    }
+    None
+}
+
+/// `curl ... | sh`, `wget -O- ... | sudo bash` and the like.
+fn pipes_to_shell(line: &str) -> bool {
+    let Some(start) = ["curl", "wget"].iter().filter_map(|c| line.find(c)).min() else {
+        return false;
+    };
+    line[start..].split('|').skip(1).any(|stage| {
+        let stage = stage.trim_start();
+        let stage = stage.strip_prefix("sudo ").unwrap_or(stage).trim_start();
+        ["sh", "bash", "zsh", "fish", "python", "perl"]
+            .iter()
+            .any(|sh| stage == *sh || stage.starts_with(&format!("{} ", sh)))
+    })
+}
+
+/// What kind of credential the line seems to hold; the value itself is never
+/// shown.
+fn credential(line: &str) -> Option<String> {
+    if line.contains("PRIVATE KEY-----") {
+        return Some("private key".to_string());
+    }
+    for word in line.split(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-')) {
+        if word.len() == 20
+            && word.starts_with("AKIA")
+            && word
+                .chars()
+                .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+        {
+            return Some("AWS access key".to_string());
+        }
+        if let Some(prefix) = TOKEN_PREFIXES.iter().find(|p| word.starts_with(*p)) {
+            if word.len() >= prefix.len() + 20 {
+                return Some(format!("{}… token", prefix));
+            }
+        }
+    }
+    let lower = line.to_lowercase();
+    let (key, value) = lower.split_once('=').or_else(|| lower.split_once(':'))?;
+    let key = key
+        .trim()
+        .trim_start_matches("export ")
+        .trim_matches(['"', '\'']);
+    let name = SECRET_NAMES.iter().find(|n| key.ends_with(*n))?;
+    let value = value.trim().trim_end_matches([',', ';']);
+    let quoted = value.len() >= 10
+        && (value.starts_with('"') && value.ends_with('"')
+            || value.starts_with('\'') && value.ends_with('\''));
+    // Values read from the environment or a password manager are fine
+    let indirect = ["$", "getenv", "env.", "pass ", "op read", "secret-tool"]
+        .iter()
+        .any(|s| value.contains(s));
+    (quoted && !indirect).then(|| format!("literal {}", name))
+}
+
+fn print_findings(findings: &[Finding]) {
+    let mut table = output::table(["Rule", "File", "Line", "Detail"]);
+    for f in findings {
+        let tone = match f.rule {
+            "credential" | "pipe-to-shell" | "network-hook" => Tone::Error,
+            _ => Tone::Warn,
+        };
+        table.add_row(vec![
+            output::cell(f.rule, tone),
+            Cell::new(&f.path),
+            Cell::new(f.line.map_or("-".to_string(), |l| l.to_string())),
+            Cell::new(&f.detail),
+        ]);
+    }
+    println!("{}", table);
+}
+
+/// Record that the user accepted the findings for `name` at its current commit.
+fn accept(name: &str) -> Result<()> {
+    let path = crate::dothub_dir().join(name);
+    let rev = crate::git::output(&path, &["rev-parse", "HEAD"]).unwrap_or_default();
+    let mut st = State::load()?;
+    st.repos.entry(name.to_string()).or_default().audit_accepted = Some(rev);
+    st.save()
+}
+
+/// Accept `name` without asking, as `dothub apply --unattended` does for the
+/// repos its manifest lists. The findings are still printed for the log.
+pub fn accept_listed(name: &str) -> Result<()> {
+    let st = State::load()?;
+    let linked_before = st.links.values().any(|l| l.name == name);
+    if linked_before
+        || st
+            .repos
+            .get(name)
+            .is_some_and(|r| r.audit_accepted.is_some())
+    {
+        return Ok(());
+    }
+    let path = crate::dothub_dir().join(name);
+    if crate::vfs::dry_run() && !path.exists() {
+        // Not cloned in a dry run; check_first_link says it would be audited
+        return Ok(());
+    }
+    let findings = scan(&path)?;
+    if !findings.is_empty() {
+        println!(
+            "{}",
+            output::paint(
+                &format!("Accepting {} unattended; the audit found:", name),
+                Tone::Warn
+            )
+        );
+        print_findings(&findings);
+    }
+    accept(name)
+}
+
+/// Before `name` is linked for the first time, make sure its findings were
+/// accepted. With `interactive`, the user is asked on the terminal.
+pub fn check_first_link(name: &str, interactive: bool) -> Result<()> {
+    let st = State::load()?;
+    let linked_before = st.links.values().any(|l| l.name == name);
+    let accepted = st
+        .repos
+        .get(name)
+        .is_some_and(|r| r.audit_accepted.is_some());
+    if linked_before || accepted {
+        return Ok(());
+    }
+    let path = crate::dothub_dir().join(name);
+    if crate::vfs::dry_run() && !path.exists() {
+        crate::vfs::note(format!("audit {} before linking it", name));
+        return Ok(());
+    }
+    let findings = scan(&path)?;
+    if findings.is_empty() {
+        return Ok(());
+    }
+    let flagged = || {
+        Error::new(
+            Code::AuditFlagged,
+            format!(
+                "{} has {} red flag(s) and has not been reviewed",
+                name,
+                findings.len()
+            ),
+        )
+        .hint(format!(
+            "dothub audit {} shows them; accept them with dothub audit {} --accept",
+            name, name
+        ))
+    };
+    if !interactive || !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        bail!(flagged());
+    }
+    println!(
+        "{}",
+        output::paint(
+            &format!("{} is linked for the first time; the audit found:", name),
+            Tone::Warn
+        )
+    );
+    print_findings(&findings);
+    print!("Link {} anyway? [y/N] ", name);
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    if !matches!(answer.trim(), "y" | "Y" | "yes") {
+        bail!(flagged());
+    }
+    accept(name)
+}
+
+/// `dothub audit`: show the findings for a stored repo, and with `--accept`
+/// allow it to be linked.
+pub fn cmd_audit(name: &str, accept_findings: bool) -> Result<()> {
+    let path = crate::dothub_dir().join(name);
+    if !path.exists() {
+        bail!(crate::error::repo_not_found(name, &path));
+    }
+    let findings = scan(&path)?;
+    if findings.is_empty() {
+        println!(
+            "{}",
+            output::paint(&format!("No red flags in {}.", name), Tone::Ok)
+        );
+    } else {
+        print_findings(&findings);
+    }
+    if accept_findings {
+        accept(name)?;
+        println!("Accepted {} as reviewed; it can be linked now.", name);
+        return Ok(());
+    }
+    let st = State::load()?;
+    match st.repos.get(name).and_then(|r| r.audit_accepted.as_deref()) {
+        Some("") => println!("Accepted earlier."),
+        Some(rev) => println!("Accepted earlier at commit {}.", &rev[..rev.len().min(10)]),
+        None if !findings.is_empty() => println!(
+            "Read the flagged files, then run dothub audit {} --accept before linking it.",
+            name
+        ),
+        None => {}
+    }
+    Ok(())
+}
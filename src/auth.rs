@@ -0,0 +1,284 @@
+//! `dothub auth`: forge tokens kept in the OS keychain.
+//!
+//! `dothub auth login github` asks for a personal access token without
+//! echoing it, checks it with the forge and stores it in the keychain
+//! (Keychain on macOS, Credential Manager on Windows, the Secret Service on
+//! Linux), so it stays out of shell history and the environment. API calls
+//! use it whenever the forge's token variable (GITHUB_TOKEN, ...) is unset,
+//! and git asks dothub for it as a credential helper when cloning or pulling
+//! private repos over HTTPS. DOTHUB_NO_KEYRING keeps dothub away from the
+//! keychain altogether, for tests and CI.
+
+use crate::forge::ForgeKind;
+use crate::output::{self, Tone};
+use anyhow::{bail, Context, Result};
+use comfy_table::Cell;
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::terminal;
+use std::collections::HashMap;
+use std::io::{self, BufRead, IsTerminal, Read, Write};
+use std::sync::Mutex;
+
+const SERVICE: &str = "dothub";
+
+const FORGES: [ForgeKind; 4] = [
+    ForgeKind::GitHub,
+    ForgeKind::GitLab,
+    ForgeKind::Gitea,
+    ForgeKind::SourceHut,
+];
+
+fn entry(kind: ForgeKind) -> Result<keyring::Entry> {
+    if std::env::var_os("DOTHUB_NO_KEYRING").is_some_and(|v| !v.is_empty()) {
+        bail!("The keychain is turned off by DOTHUB_NO_KEYRING");
+    }
+    keyring::Entry::new(SERVICE, kind.name()).context("Opening the keychain")
+}
+
+/// Token saved by `dothub auth login`, looked up once per run.
+pub fn stored_token(kind: ForgeKind) -> Option<String> {
+    static TOKENS: Mutex<Option<HashMap<ForgeKind, Option<String>>>> = Mutex::new(None);
+    let mut tokens = TOKENS.lock().ok()?;
+    tokens
+        .get_or_insert_with(HashMap::new)
+        .entry(kind)
+        .or_insert_with(|| entry(kind).ok()?.get_password().ok())
+        .clone()
+}
+
+/// `dothub auth login`: store a token for `kind`, read from stdin with
+/// `with_token` or asked for without echo.
+pub fn cmd_auth_login(kind: ForgeKind, with_token: bool) -> Result<()> {
+    let token = if with_token || !io::stdin().is_terminal() {
+        let mut text = String::new();
+        io::stdin().read_to_string(&mut text)?;
+        text.trim().to_string()
+    } else {
+        println!("Paste a {} access token (input is hidden):", kind.name());
+        read_hidden()?
+    };
+    if token.is_empty() {
+        bail!("No token given");
+    }
+    if !crate::http::is_offline() {
+        match whoami(kind, &token) {
+            Ok(account) => println!("Token belongs to {}", account),
+            Err(e) if e.is::<Rejected>() => bail!(e),
+            Err(e) => {
+                let msg = format!("Couldn't check the token ({:#}); storing it anyway", e);
+                println!("{}", output::paint(&msg, Tone::Warn));
+            }
+        }
+    }
+    entry(kind)?
+        .set_password(&token)
+        .map_err(|e| anyhow::anyhow!("{}", e))
+        .with_context(|| {
+            format!(
+                "No keychain to store the token in; export {} instead",
+                kind.token_var()
+            )
+        })?;
+    let msg = format!("Logged in to {}", kind.name());
+    println!("{}", output::paint(&msg, Tone::Ok));
+    if std::env::var(kind.token_var()).is_ok_and(|t| !t.is_empty()) {
+        let msg = format!(
+            "{} is set and is used before the stored token",
+            kind.token_var()
+        );
+        println!("{}", output::paint(&msg, Tone::Warn));
+    }
+    Ok(())
+}
+
+/// A line from the terminal, not echoed.
+fn read_hidden() -> Result<String> {
+    terminal::enable_raw_mode().context("Enabling raw terminal mode")?;
+    let mut text = String::new();
+    let result = loop {
+        match event::read() {
+            Ok(Event::Key(key)) if key.kind != KeyEventKind::Release => match key.code {
+                KeyCode::Enter => break Ok(()),
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    break Err(anyhow::anyhow!("Cancelled"));
+                }
+                KeyCode::Char(c) => text.push(c),
+                KeyCode::Backspace => {
+                    text.pop();
+                }
+                _ => {}
+            },
+            // Terminals deliver a paste as key presses unless bracketed paste is on
+            Ok(Event::Paste(pasted)) => text.push_str(&pasted),
+            Ok(_) => {}
+            Err(e) => break Err(e.into()),
+        }
+    };
+    terminal::disable_raw_mode().context("Restoring the terminal")?;
+    println!();
+    result.map(|()| text.trim().to_string())
+}
+
+/// The forge turned the token down.
+#[derive(Debug)]
+struct Rejected(String);
+
+impl std::fmt::Display for Rejected {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Rejected {}
+
+/// Account name the token belongs to, as the forge's public instance sees it.
+pub fn whoami(kind: ForgeKind, token: &str) -> Result<String> {
+    let client = crate::http::client()?;
+    let (url, req, pointer) = match kind {
+        ForgeKind::GitHub => {
+            let url = "https://api.github.com/user";
+            (url, client.get(url).bearer_auth(token), "/login")
+        }
+        ForgeKind::GitLab => {
+            let url = "https://gitlab.com/api/v4/user";
+            (
+                url,
+                client.get(url).header("PRIVATE-TOKEN", token),
+                "/username",
+            )
+        }
+        ForgeKind::Gitea => {
+            let url = "https://codeberg.org/api/v1/user";
+            let req = client
+                .get(url)
+                .header("Authorization", format!("token {}", token));
+            (url, req, "/login")
+        }
+        ForgeKind::SourceHut => {
+            let url = "https://meta.sr.ht/query";
+            let req = client
+                .post(url)
+                .bearer_auth(token)
+                .json(&serde_json::json!({ "query": "{ me { canonicalName } }" }));
+            (url, req, "/data/me/canonicalName")
+        }
+    };
+    let resp = req.send().with_context(|| format!("Asking {}", url))?;
+    let status = resp.status();
+    if status == reqwest::StatusCode::UNAUTHORIZED || status == reqwest::StatusCode::FORBIDDEN {
+        bail!(Rejected(format!(
+            "{} rejected the token (HTTP {})",
+            url, status
+        )));
+    }
+    if !status.is_success() {
+        bail!("HTTP {} for {}", status, url);
+    }
+    let v: serde_json::Value = resp
+        .json()
+        .with_context(|| format!("parsing json from {}", url))?;
+    v.pointer(pointer)
+        .and_then(|n| n.as_str())
+        .map(|n| n.to_string())
+        .with_context(|| format!("{} didn't say who the token belongs to", url))
+}
+
+/// `dothub auth status`: where each forge's token comes from.
+pub fn cmd_auth_status() -> Result<()> {
+    let mut table = output::table(["Forge", "Token", "Account"]);
+    for kind in FORGES {
+        let from_env = std::env::var(kind.token_var()).is_ok_and(|t| !t.is_empty());
+        let (source, tone) = match (from_env, stored_token(kind).is_some()) {
+            (true, _) => (format!("${}", kind.token_var()), Tone::Ok),
+            (false, true) => ("keychain".to_string(), Tone::Ok),
+            (false, false) => ("none".to_string(), Tone::Muted),
+        };
+        let account = match kind.token() {
+            Some(_) if crate::http::is_offline() => {
+                output::cell("not checked offline", Tone::Muted)
+            }
+            Some(token) => match whoami(kind, &token) {
+                Ok(account) => Cell::new(account),
+                Err(e) => output::cell(format!("{:#}", e), Tone::Error),
+            },
+            None => Cell::new(""),
+        };
+        table.add_row(vec![
+            Cell::new(kind.name()),
+            output::cell(source, tone),
+            account,
+        ]);
+    }
+    println!("{}", table);
+    Ok(())
+}
+
+/// `dothub auth logout`: remove the stored token for `kind`.
+pub fn cmd_auth_logout(kind: ForgeKind) -> Result<()> {
+    match entry(kind)?.delete_credential() {
+        Ok(()) => println!("Removed the {} token from the keychain", kind.name()),
+        Err(keyring::Error::NoEntry) => println!("No {} token is stored", kind.name()),
+        Err(e) => bail!(
+            "Removing the {} token from the keychain: {}",
+            kind.name(),
+            e
+        ),
+    }
+    if std::env::var(kind.token_var()).is_ok_and(|t| !t.is_empty()) {
+        let msg = format!("{} is still set in the environment", kind.token_var());
+        println!("{}", output::paint(&msg, Tone::Warn));
+    }
+    Ok(())
+}
+
+/// `-c` options making dothub a credential helper for git, after the user's
+/// own helpers.
+pub fn git_config() -> Vec<String> {
+    let Ok(exe) = std::env::current_exe() else {
+        return Vec::new();
+    };
+    let exe = exe.to_string_lossy().replace('\'', "'\\''");
+    vec![
+        "-c".to_string(),
+        format!("credential.helper=!'{}' auth git-credential", exe),
+    ]
+}
+
+/// `dothub auth git-credential get`: answer git's credential request for a
+/// forge host with its token. Other operations and hosts are left to git.
+pub fn cmd_git_credential(operation: &str) -> Result<()> {
+    let mut fields = HashMap::new();
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+        if line.is_empty() {
+            break;
+        }
+        if let Some((key, value)) = line.split_once('=') {
+            fields.insert(key.to_string(), value.to_string());
+        }
+    }
+    if operation != "get" || fields.get("protocol").map(String::as_str) != Some("https") {
+        return Ok(());
+    }
+    let Some(host) = fields.get("host") else {
+        return Ok(());
+    };
+    let Some(kind) = ForgeKind::from_host(host) else {
+        return Ok(());
+    };
+    // Never a token to a host that only looks like the forge's
+    let Some(token) = kind.token_for(host) else {
+        return Ok(());
+    };
+    // What each forge expects next to a token in HTTP basic auth
+    let (username, password) = match kind {
+        ForgeKind::GitHub => ("x-access-token", token.as_str()),
+        ForgeKind::GitLab => ("oauth2", token.as_str()),
+        ForgeKind::Gitea => (token.as_str(), "x-oauth-basic"),
+        ForgeKind::SourceHut => return Ok(()),
+    };
+    let mut out = io::stdout().lock();
+    writeln!(out, "username={}", username)?;
+    writeln!(out, "password={}", password)?;
+    Ok(())
+}
@@ -0,0 +1,289 @@
+//! Backups of targets that were replaced, and how long they are kept.
+//!
+//! A backup is the old target renamed beside it, as
+//! `<target>.dothub-backup-<time>`, so finding them means looking next to
+//! targets: in ~/.config, the home directory, and wherever a recorded
+//! deployment lives. `keep` and `max_age_days` under `[backup]` in the config
+//! bound them per target; each new backup prunes the older ones of its target
+//! past either limit, and `dothub backups prune` applies them everywhere.
+//! Without either, backups are kept until pruned by hand.
+
+use crate::output::{self, Tone};
+use crate::state::{self, State};
+use crate::vfs;
+use anyhow::{bail, Context, Result};
+use comfy_table::Cell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+
+const MARKER: &str = ".dothub-backup-";
+const DAY: u64 = 24 * 60 * 60;
+
+pub struct Backup {
+    /// The target it was taken of
+    pub target: PathBuf,
+    pub path: PathBuf,
+    pub made_at: u64,
+}
+
+/// Limits on the backups kept of each target.
+#[derive(Clone, Copy, Default)]
+pub struct Retention {
+    pub keep: Option<NonZeroUsize>,
+    pub max_age_days: Option<u64>,
+}
+
+impl Retention {
+    /// The limits from `[backup]` in the config.
+    pub fn configured() -> Retention {
+        let config = crate::config::Config::load()
+            .map(|c| c.backup)
+            .unwrap_or_default();
+        Retention {
+            keep: config.keep,
+            max_age_days: config.max_age_days,
+        }
+    }
+
+    fn is_set(self) -> bool {
+        self.keep.is_some() || self.max_age_days.is_some()
+    }
+
+    /// Backups past a limit, given one target's backups newest first.
+    fn expired<'a>(self, newest_first: &[&'a Backup], now: u64) -> Vec<&'a Backup> {
+        newest_first
+            .iter()
+            .enumerate()
+            .filter(|(i, b)| {
+                self.keep.is_some_and(|keep| *i >= keep.get())
+                    || self
+                        .max_age_days
+                        .is_some_and(|days| now.saturating_sub(b.made_at) > days * DAY)
+            })
+            .map(|(_, b)| *b)
+            .collect()
+    }
+}
+
+/// The backup to move `target` to.
+pub fn path_for(target: &Path) -> PathBuf {
+    let file_name = target
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    target.with_file_name(format!("{}{}{}", file_name, MARKER, state::now_unix()))
+}
+
+/// `path` as a backup, if it is named like one.
+fn parse(path: &Path) -> Option<Backup> {
+    let name = path.file_name()?.to_str()?;
+    let (target, time) = name.rsplit_once(MARKER)?;
+    if target.is_empty() {
+        return None;
+    }
+    Some(Backup {
+        target: path.with_file_name(target),
+        path: path.to_path_buf(),
+        made_at: time.parse().ok()?,
+    })
+}
+
+/// Every backup dothub can find, oldest first.
+pub fn find() -> Result<Vec<Backup>> {
+    let st = State::load()?;
+    let mut dirs = BTreeSet::new();
+    if let Ok(config) = crate::owner::config_root() {
+        dirs.insert(config);
+    }
+    if let Some(home) = dirs::home_dir() {
+        dirs.insert(home);
+    }
+    for record in st.links.values() {
+        if let Some(parent) = record.target.parent() {
+            dirs.insert(parent.to_path_buf());
+        }
+    }
+    let mut found = Vec::new();
+    for dir in dirs {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        found.extend(entries.flatten().filter_map(|e| parse(&e.path())));
+    }
+    found.sort_by(|a, b| a.made_at.cmp(&b.made_at).then(a.path.cmp(&b.path)));
+    Ok(found)
+}
+
+/// Backups by target, newest first.
+fn by_target(backups: &[Backup]) -> BTreeMap<&Path, Vec<&Backup>> {
+    let mut grouped: BTreeMap<&Path, Vec<&Backup>> = BTreeMap::new();
+    for backup in backups.iter().rev() {
+        grouped.entry(&backup.target).or_default().push(backup);
+    }
+    grouped
+}
+
+/// Prune the backups of the target `taken` is a backup of past the
+/// configured limits, after taking it. Returns how many were removed.
+pub fn enforce(taken: &Path) -> Result<usize> {
+    let retention = Retention::configured();
+    let Some(taken) = parse(taken).filter(|_| retention.is_set()) else {
+        return Ok(0);
+    };
+    let target = taken.target.clone();
+    let mut backups = find()?;
+    // A dry run only moved it in memory
+    if !backups.iter().any(|b| b.path == taken.path) {
+        backups.push(taken);
+    }
+    let grouped = by_target(&backups);
+    let Some(newest_first) = grouped.get(target.as_path()) else {
+        return Ok(0);
+    };
+    let expired = retention.expired(newest_first, state::now_unix());
+    for backup in &expired {
+        crate::remove_path(&backup.path)?;
+    }
+    Ok(expired.len())
+}
+
+/// Whether `backup` was taken of the target named `target`: a target name,
+/// or the path of one.
+fn of(backup: &Backup, target: &str) -> Result<bool> {
+    let path = if target.contains('/') {
+        crate::source::expand_tilde(target)
+    } else {
+        crate::targets::path(target)?
+    };
+    Ok(backup.target == path)
+}
+
+/// `dothub backups list [target]`.
+pub fn cmd_list(target: Option<&str>) -> Result<()> {
+    let mut backups = find()?;
+    if let Some(target) = target {
+        backups.retain(|b| of(b, target).unwrap_or(false));
+    }
+    if backups.is_empty() {
+        println!("No backups found.");
+        return Ok(());
+    }
+    let now = state::now_unix();
+    let retention = Retention::configured();
+    let grouped = by_target(&backups);
+    let expired = grouped
+        .values()
+        .flat_map(|newest_first| retention.expired(newest_first, now))
+        .map(|b| b.path.clone())
+        .collect::<BTreeSet<_>>();
+    let mut table = output::table(["Target", "Backup", "Taken", "Size"]);
+    for backup in &backups {
+        let taken = output::ago(backup.made_at, now);
+        table.add_row(vec![
+            Cell::new(backup.target.display()),
+            Cell::new(
+                backup
+                    .path
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy(),
+            ),
+            if expired.contains(&backup.path) {
+                output::cell(format!("{} (expired)", taken), Tone::Warn)
+            } else {
+                Cell::new(taken)
+            },
+            Cell::new(crate::conflict::inventory(&backup.path)),
+        ]);
+    }
+    println!("{}", table);
+    Ok(())
+}
+
+/// `dothub backups prune`: remove backups past the limits, those given
+/// replacing the configured ones.
+pub fn cmd_prune(keep: Option<NonZeroUsize>, max_age_days: Option<u64>) -> Result<()> {
+    // Loaded here so an invalid [backup] is reported rather than ignored
+    let configured = crate::config::Config::load()?.backup;
+    let retention = Retention {
+        keep: keep.or(configured.keep),
+        max_age_days: max_age_days.or(configured.max_age_days),
+    };
+    if !retention.is_set() {
+        bail!(crate::error::Error::new(
+            crate::error::Code::ConfigInvalid,
+            "No retention limits to prune by"
+        )
+        .hint("pass --keep N or --max-age-days D, or set them under [backup] in the config"));
+    }
+    let backups = find()?;
+    let now = state::now_unix();
+    let mut removed = 0usize;
+    for newest_first in by_target(&backups).values() {
+        for backup in retention.expired(newest_first, now) {
+            crate::remove_path(&backup.path)?;
+            println!("  removed {}", backup.path.display());
+            removed += 1;
+        }
+    }
+    println!("Pruned {} of {} backup(s).", removed, backups.len());
+    Ok(())
+}
+
+/// `dothub backups restore <target> [--at time]`: put the newest backup of
+/// `target` (or the one taken at `at`) back in place. A deployment there is
+/// unlinked first; anything else there is left alone.
+pub fn cmd_restore(target: &str, at: Option<u64>) -> Result<()> {
+    let backups = find()?;
+    let mut candidates = Vec::new();
+    for backup in &backups {
+        if of(backup, target)? && at.is_none_or(|at| backup.made_at == at) {
+            candidates.push(backup);
+        }
+    }
+    let Some(backup) = candidates.last() else {
+        bail!(
+            "No backup of {} found{}; dothub backups list shows them",
+            target,
+            at.map(|at| format!(" taken at {}", at)).unwrap_or_default()
+        );
+    };
+
+    let mut st = State::load()?;
+    let deployed = st
+        .links
+        .iter()
+        .find(|(_, rec)| rec.target == backup.target)
+        .map(|(name, _)| name.clone());
+    if let Some(name) = deployed {
+        println!("{}", crate::unlink_target(&mut st, &name)?);
+        st.save()?;
+    }
+    if vfs::fs().exists(&backup.target) {
+        bail!(crate::error::Error::new(
+            crate::error::Code::UnmanagedTarget,
+            format!(
+                "{} exists and was not deployed by dothub",
+                backup.target.display()
+            )
+        )
+        .hint("move it aside, then restore again"));
+    }
+    vfs::fs()
+        .rename(&backup.path, &backup.target)
+        .with_context(|| format!("Restoring {}", backup.path.display()))?;
+    println!(
+        "{}",
+        output::paint(
+            &format!(
+                "Restored {} from the backup taken {}",
+                backup.target.display(),
+                output::ago(backup.made_at, state::now_unix())
+            ),
+            Tone::Ok
+        )
+    );
+    Ok(())
+}
@@ -0,0 +1,193 @@
+//! `dothub bench`: synthetic stores to time the hot paths against.
+//!
+//! `dothub bench generate DIR --repos N` builds a store of N small git repos
+//! (a quarter of them with uncommitted changes), a manifest linking each of
+//! them and a hub index listing them, all under DIR. `dothub bench run TARGET
+//! --dir DIR` then times status scanning, plan generation or hub parsing on
+//! it, with every dothub path pointed inside DIR so the real store, state and
+//! caches are never touched. The criterion suite in benches/ drives both.
+
+use crate::output::{self, Tone};
+use anyhow::{bail, Context, Result};
+use comfy_table::Cell;
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// What `dothub bench run` times.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Target {
+    /// `dothub status` with the status cache warm
+    Status,
+    /// `dothub status --refresh`, running git in every repo
+    StatusCold,
+    /// Planning `dothub apply` for the manifest
+    Plan,
+    /// Parsing the hub index
+    Hub,
+}
+
+impl Target {
+    fn name(self) -> &'static str {
+        match self {
+            Target::Status => "status",
+            Target::StatusCold => "status-cold",
+            Target::Plan => "plan",
+            Target::Hub => "hub",
+        }
+    }
+}
+
+/// Types the synthetic hub index spreads its entries over.
+const TYPES: [&str; 6] = ["nvim", "tmux", "zsh", "fish", "kitty", "alacritty"];
+
+fn store(dir: &Path) -> PathBuf {
+    dir.join("store")
+}
+
+/// `dothub bench generate`: a store of `repos` git repos with `files` files
+/// each, plus manifest.toml and hub.yml, under `dir`.
+pub fn cmd_bench_generate(dir: &Path, repos: usize, files: usize) -> Result<()> {
+    if dir.exists() && fs::read_dir(dir)?.next().is_some() {
+        bail!("{} is not empty; pick a new directory", dir.display());
+    }
+    let store = store(dir);
+    fs::create_dir_all(&store).with_context(|| format!("Failed creating {}", store.display()))?;
+    fs::create_dir_all(dir.join("home/.config"))?;
+
+    let mut manifest = String::from("[repos]\n");
+    let mut links = String::from("\n[links]\n");
+    let mut hub: BTreeMap<&str, String> = BTreeMap::new();
+    for i in 0..repos {
+        let name = format!("repo-{:04}", i);
+        let repo = store.join(&name);
+        for f in 0..files {
+            let path = repo
+                .join(format!("dir-{}", f % 4))
+                .join(format!("file-{}.conf", f));
+            fs::create_dir_all(path.parent().unwrap_or(&repo))?;
+            fs::write(
+                &path,
+                format!("# {} file {}\nkey = {}\n", name, f, i * files + f),
+            )
+            .with_context(|| format!("Writing {}", path.display()))?;
+        }
+        let identity = [
+            "-c",
+            "user.name=dothub",
+            "-c",
+            "user.email=bench@dothub.invalid",
+        ];
+        crate::git::output(&repo, &["init", "-q"])?;
+        crate::git::output(&repo, &["add", "-A"])?;
+        crate::git::output(
+            &repo,
+            &[&identity[..], &["commit", "-q", "-m", "Initial"]].concat(),
+        )?;
+        if i % 4 == 0 {
+            fs::write(repo.join("dir-0/file-0.conf"), "# edited\n")?;
+        }
+
+        let url = format!("https://github.com/bench/{}", name);
+        writeln!(manifest, "{} = \"{}\"", name, url)?;
+        writeln!(links, "{} = \"{}\"", name, name)?;
+        let entries = hub.entry(TYPES[i % TYPES.len()]).or_default();
+        if i % 2 == 0 {
+            writeln!(entries, "  - {}", url)?;
+        } else {
+            writeln!(entries, "  - url: {}", url)?;
+            writeln!(entries, "    description: Synthetic config {}", i)?;
+            writeln!(entries, "    min_version: \"0.{}\"", i % 10)?;
+            writeln!(entries, "    os: [linux, macos]")?;
+        }
+    }
+    manifest.push_str(&links);
+    fs::write(dir.join("manifest.toml"), manifest)?;
+    let hub: String = hub
+        .into_iter()
+        .map(|(ty, entries)| format!("{}:\n{}", ty, entries))
+        .collect();
+    fs::write(dir.join("hub.yml"), hub)?;
+    println!(
+        "{}",
+        output::paint(
+            &format!("Generated {} repos under {}", repos, dir.display()),
+            Tone::Ok
+        )
+    );
+    Ok(())
+}
+
+/// `dothub bench run`: time `target` `iterations` times on the store in `dir`.
+/// With `json`, print the timings in nanoseconds for the criterion suite.
+pub fn cmd_bench_run(target: Target, dir: &Path, iterations: u32, json: bool) -> Result<()> {
+    if !store(dir).is_dir() {
+        bail!(
+            "{} holds no synthetic store; run dothub bench generate first",
+            dir.display()
+        );
+    }
+    let dir = fs::canonicalize(dir)?;
+    // Nothing outside `dir` is read or written; set before any thread starts
+    env::set_var("HOME", dir.join("home"));
+    env::set_var("DOTHUB_DIR", store(&dir));
+    env::set_var("DOTHUB_STATE", dir.join("state/state.json"));
+    env::set_var("DOTHUB_CONFIG", dir.join("config.toml"));
+    env::set_var("DOTHUB_CACHE", dir.join("cache"));
+    env::set_var("DOTHUB_SOCKET", dir.join("daemon.sock"));
+    env::set_var("DOTHUB_OFFLINE", "1");
+
+    let manifest = dir.join("manifest.toml");
+    let hub = fs::read_to_string(dir.join("hub.yml"))?;
+    let once = || -> Result<Duration> {
+        let start = Instant::now();
+        match target {
+            Target::Status | Target::StatusCold => {
+                crate::status::set_refresh(target == Target::StatusCold);
+                crate::status::scan()?;
+            }
+            Target::Plan => {
+                crate::apply::plan(&manifest, None)?;
+            }
+            Target::Hub => {
+                crate::hub::parse_index(&hub, &[])?;
+            }
+        }
+        Ok(start.elapsed())
+    };
+    // Fills the status cache, so `status` measures the warm path
+    once()?;
+    let mut times = (0..iterations.max(1))
+        .map(|_| once())
+        .collect::<Result<Vec<_>>>()?;
+    times.sort();
+    let total: Duration = times.iter().sum();
+    let (min, median) = (times[0], times[times.len() / 2]);
+
+    if json {
+        println!(
+            "{}",
+            serde_json::json!({
+                "target": target.name(),
+                "iterations": times.len(),
+                "total_ns": total.as_nanos() as u64,
+                "min_ns": min.as_nanos() as u64,
+                "median_ns": median.as_nanos() as u64,
+            })
+        );
+        return Ok(());
+    }
+    let mut table = output::table(["Target", "Runs", "Min", "Median", "Mean"]);
+    table.add_row(vec![
+        Cell::new(target.name()),
+        Cell::new(times.len()),
+        Cell::new(format!("{:.2?}", min)),
+        Cell::new(format!("{:.2?}", median)),
+        Cell::new(format!("{:.2?}", total / times.len() as u32)),
+    ]);
+    println!("{}", table);
+    Ok(())
+}
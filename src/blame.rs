@@ -0,0 +1,161 @@
+//! `dothub blame`: which commit put a line of a deployed config there.
+//!
+//! The file is looked up through the deployment record, from the target to
+//! the repo it was linked or copied from, and `git blame` runs on the stored
+//! file. Copies may have been edited since; those are flagged, since the line
+//! numbers then refer to the repo's version.
+
+use crate::git;
+use crate::output::{self, Tone};
+use crate::state::{DeployMode, State};
+use anyhow::{bail, Context, Result};
+use std::path::Path;
+
+pub fn cmd_blame(target_name: &str, spec: &str) -> Result<()> {
+    let st = State::load()?;
+    let Some(record) = st.links.get(target_name) else {
+        bail!(crate::error::Error::new(
+            crate::error::Code::NotManaged,
+            format!("{} is not a target deployed by dothub", target_name),
+        )
+        .hint("dothub active"));
+    };
+    let (file, line) = match spec.rsplit_once(':') {
+        Some((file, line)) => (
+            file,
+            line.parse::<u32>()
+                .ok()
+                .filter(|n| *n > 0)
+                .with_context(|| format!("Invalid line number '{}'", line))?,
+        ),
+        None => bail!("Expected <file>:<line>, e.g. init.lua:12"),
+    };
+    // A single-file target has nothing to name inside it
+    let rel = if record.source.is_file() { "" } else { file };
+    let stored = if rel.is_empty() {
+        record.source.clone()
+    } else {
+        record.source.join(rel)
+    };
+    if !stored.is_file() {
+        bail!("{} has no file {}", target_name, file);
+    }
+
+    let repo = crate::dothub_dir().join(&record.name);
+    if !git::is_repo(&repo) {
+        bail!(
+            "{} is not a git repository, so it has no history to blame",
+            record.name
+        );
+    }
+    if record.mode == DeployMode::Copy && copy_changed(&record.files, rel, &record.target) {
+        println!(
+            "{}",
+            output::paint(
+                "The deployed copy was edited since it was made; line numbers are the repo's",
+                Tone::Warn
+            )
+        );
+    }
+
+    let in_repo = stored.strip_prefix(&repo).unwrap_or(&stored);
+    let path_arg = in_repo.to_string_lossy();
+    let range = format!("{},{}", line, line);
+    let out = git::output(
+        &repo,
+        &[
+            "blame",
+            "--porcelain",
+            "-L",
+            &range,
+            "--",
+            path_arg.as_ref(),
+        ],
+    )?;
+    let blame = parse_porcelain(&out).context("Unexpected git blame output")?;
+
+    let local = blame.commit.chars().all(|c| c == '0');
+    let upstream = !local
+        && git::output(
+            &repo,
+            &["merge-base", "--is-ancestor", &blame.commit, "@{upstream}"],
+        )
+        .is_ok();
+    println!("{}:{}  {}", in_repo.display(), line, blame.text);
+    if local {
+        println!("Not committed yet (changed in the store)");
+        return Ok(());
+    }
+    println!(
+        "{}  {} <{}>, {}",
+        &blame.commit[..blame.commit.len().min(10)],
+        blame.author,
+        blame.email,
+        output::ago(blame.time, crate::state::now_unix())
+    );
+    println!("    {}", blame.summary);
+    if !git::has_upstream(&repo) {
+        return Ok(());
+    }
+    let origin = if upstream {
+        output::paint("from upstream", Tone::Info)
+    } else {
+        output::paint("a local commit, not upstream", Tone::Warn)
+    };
+    println!("{}", origin);
+    Ok(())
+}
+
+/// Whether the deployed copy of `rel` no longer matches its recorded checksum.
+fn copy_changed(
+    files: &std::collections::BTreeMap<String, String>,
+    rel: &str,
+    target: &Path,
+) -> bool {
+    let deployed = if rel.is_empty() {
+        target.to_path_buf()
+    } else {
+        target.join(rel)
+    };
+    match (files.get(rel), crate::state::hash_file(&deployed)) {
+        (Some(recorded), Ok(actual)) => *recorded != actual,
+        _ => false,
+    }
+}
+
+struct Blame {
+    commit: String,
+    author: String,
+    email: String,
+    time: u64,
+    summary: String,
+    text: String,
+}
+
+/// The single line of `git blame --porcelain -L n,n` output.
+fn parse_porcelain(out: &str) -> Option<Blame> {
+    let mut lines = out.lines();
+    let commit = lines.next()?.split_whitespace().next()?.to_string();
+    let mut blame = Blame {
+        commit,
+        author: String::new(),
+        email: String::new(),
+        time: 0,
+        summary: String::new(),
+        text: String::new(),
+    };
+    for line in lines {
+        if let Some(text) = line.strip_prefix('\t') {
+            blame.text = text.trim().to_string();
+        } else if let Some(v) = line.strip_prefix("author-mail ") {
+            blame.email = v.trim_matches(['<', '>']).to_string();
+        } else if let Some(v) = line.strip_prefix("author-time ") {
+            blame.time = v.parse().unwrap_or(0);
+        } else if let Some(v) = line.strip_prefix("author ") {
+            blame.author = v.to_string();
+        } else if let Some(v) = line.strip_prefix("summary ") {
+            blame.summary = v.to_string();
+        }
+    }
+    Some(blame)
+}
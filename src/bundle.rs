@@ -0,0 +1,267 @@
+//! `dothub bundle install`: an application and a config for it in one step.
+//!
+//! The steps run in the order that is cheapest to undo: the config repo is
+//! fetched first, then the application is installed with the system package
+//! manager, and only then is the config linked. A failed package install
+//! removes the repo fetched for it, so nothing is left half set up.
+
+use crate::manifest::{PackageSpec, RepoManifest};
+use crate::output::{self, Tone};
+use crate::{doctor, forge, hub, source};
+use anyhow::{bail, Context, Result};
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::process::Command;
+
+/// A system package manager and how to install with it.
+struct Manager {
+    name: &'static str,
+    install: &'static [&'static str],
+    /// Flags that answer its prompts, for runs without a terminal
+    yes: &'static [&'static str],
+    /// Needs root; run through sudo unless we already are root
+    root: bool,
+}
+
+/// Checked in this order; the first one found in PATH is used.
+const MANAGERS: &[Manager] = &[
+    Manager {
+        name: "brew",
+        install: &["install"],
+        yes: &[],
+        root: false,
+    },
+    // Termux and FreeBSD
+    Manager {
+        name: "pkg",
+        install: &["install"],
+        yes: &["-y"],
+        root: true,
+    },
+    Manager {
+        name: "paru",
+        install: &["-S", "--needed"],
+        yes: &["--noconfirm"],
+        root: false,
+    },
+    Manager {
+        name: "yay",
+        install: &["-S", "--needed"],
+        yes: &["--noconfirm"],
+        root: false,
+    },
+    Manager {
+        name: "pacman",
+        install: &["-S", "--needed"],
+        yes: &["--noconfirm"],
+        root: true,
+    },
+    Manager {
+        name: "apt-get",
+        install: &["install"],
+        yes: &["-y"],
+        root: true,
+    },
+    Manager {
+        name: "dnf",
+        install: &["install"],
+        yes: &["-y"],
+        root: true,
+    },
+    Manager {
+        name: "zypper",
+        install: &["install"],
+        yes: &["-y"],
+        root: true,
+    },
+    Manager {
+        name: "apk",
+        install: &["add"],
+        yes: &[],
+        root: true,
+    },
+];
+
+/// Package name for a config type when its repo does not declare one.
+fn default_package(config_type: &str) -> &str {
+    match config_type {
+        "nvim" => "neovim",
+        "hypr" => "hyprland",
+        other => other,
+    }
+}
+
+pub fn cmd_bundle_install(
+    config_type: &str,
+    repo: Option<&str>,
+    manager: Option<&str>,
+    hub_url: Option<&str>,
+) -> Result<()> {
+    let manager = pick_manager(manager)?;
+    let repo = match repo {
+        Some(r) => r.to_string(),
+        None => choose_from_hub(config_type, hub_url)?,
+    };
+
+    // 1. Config: fetch it, but don't deploy anything yet
+    let name = source::derive_name(&source::detect(&repo, false)?);
+    let fetched = crate::install_repo(&repo, Some(&name), false, true)?.is_some();
+    if fetched {
+        println!("Installed {}", name);
+    }
+    let manifest = RepoManifest::load(&crate::dothub_dir().join(&name))?;
+
+    // 2. Application
+    let binary = doctor::app_binary(config_type);
+    if which::which(binary).is_ok() {
+        println!("{} is already installed; skipping the package", binary);
+    } else {
+        let package = match manifest.as_ref().and_then(|m| m.package.as_ref()) {
+            Some(PackageSpec::Name(p)) => Some(p.as_str()),
+            Some(PackageSpec::PerManager(map)) => map.get(manager.name).map(|p| p.as_str()),
+            None => None,
+        }
+        .unwrap_or(default_package(config_type));
+        if let Err(e) = install_package(manager, package, false) {
+            if fetched {
+                crate::remove_path(&crate::dothub_dir().join(&name))?;
+                let mut st = crate::state::State::load()?;
+                st.repos.remove(&name);
+                st.save()?;
+                println!("Removed {} again since the application is missing", name);
+            }
+            return Err(e);
+        }
+    }
+
+    // 3. Link, with the usual conflict handling
+    let target = manifest
+        .as_ref()
+        .and_then(|m| m.default_target())
+        .unwrap_or(config_type)
+        .to_string();
+    crate::cmd_link(&name, Some(&target), false).with_context(|| {
+        format!(
+            "{} is installed but its config was not linked; retry with `dothub link {} {}`",
+            binary, name, target
+        )
+    })?;
+    println!(
+        "{}",
+        output::paint(&format!("{} is ready with {}", config_type, name), Tone::Ok)
+    );
+    Ok(())
+}
+
+fn pick_manager(wanted: Option<&str>) -> Result<&'static Manager> {
+    if let Some(name) = wanted {
+        let Some(m) = MANAGERS.iter().find(|m| m.name == name) else {
+            let names: Vec<&str> = MANAGERS.iter().map(|m| m.name).collect();
+            bail!(
+                "Unknown package manager '{}'; expected one of {}",
+                name,
+                names.join(", ")
+            );
+        };
+        if which::which(m.name).is_err() {
+            bail!("{} is not installed or not found in PATH", m.name);
+        }
+        return Ok(m);
+    }
+    let found = MANAGERS.iter().find(|m| which::which(m.name).is_ok());
+    found.context("No supported package manager found; pass --manager or install the app yourself")
+}
+
+/// Install `package` with the first package manager found, answering its
+/// prompts; sudo fails rather than asking for a password.
+pub fn install_unattended(package: &str) -> Result<()> {
+    install_package(pick_manager(None)?, package, true)
+}
+
+fn install_package(manager: &Manager, package: &str, unattended: bool) -> Result<()> {
+    // Termux's apt and pkg work on $PREFIX, which the app itself owns
+    let as_root = manager.root && !is_root() && !crate::targets::is_termux();
+    let mut cmd = if as_root {
+        let mut c = Command::new("sudo");
+        if unattended {
+            c.arg("-n");
+        }
+        c.arg(manager.name);
+        c
+    } else {
+        Command::new(manager.name)
+    };
+    cmd.args(manager.install);
+    if unattended {
+        cmd.args(manager.yes);
+    }
+    cmd.arg(package);
+    println!(
+        "Installing {} with {}{}",
+        package,
+        if as_root { "sudo " } else { "" },
+        manager.name
+    );
+    let status = cmd
+        .status()
+        .with_context(|| format!("Failed to spawn {}", manager.name))?;
+    if !status.success() {
+        bail!("{} could not install {}", manager.name, package);
+    }
+    Ok(())
+}
+
+fn is_root() -> bool {
+    Command::new("id")
+        .arg("-u")
+        .output()
+        .is_ok_and(|out| String::from_utf8_lossy(&out.stdout).trim() == "0")
+}
+
+/// The hub entry to use for `config_type`: the only one, or the user's pick
+/// from a list ranked by stars.
+fn choose_from_hub(config_type: &str, hub_url: Option<&str>) -> Result<String> {
+    let yaml = hub::fetch_index(hub_url)?;
+    let items = hub::parse_index(&yaml, &[config_type.to_string()])?;
+    if items.is_empty() {
+        bail!(
+            "The hub has no {} configs; pass --repo to choose one yourself",
+            config_type
+        );
+    }
+    let links: Vec<String> = items.iter().map(|i| i.link.clone()).collect();
+    let entries = hub::rank(items, &forge::fetch_all(&links)?);
+    if entries.len() == 1 {
+        return Ok(entries[0].link.clone());
+    }
+    if !io::stdin().is_terminal() {
+        let links: Vec<&str> = entries.iter().map(|e| e.link.as_str()).collect();
+        bail!(
+            "The hub has several {} configs; pass --repo with one of: {}",
+            config_type,
+            links.join(", ")
+        );
+    }
+    for (i, e) in entries.iter().enumerate() {
+        let description = e.description().unwrap_or("");
+        println!(
+            "{:>2}. {} ({} stars) {}",
+            i + 1,
+            e.link,
+            e.meta.stars,
+            hub::truncate(description, 50)
+        );
+    }
+    let stdin = io::stdin();
+    loop {
+        print!("Config to install [1-{}]: ", entries.len());
+        io::stdout().flush()?;
+        let mut answer = String::new();
+        if stdin.lock().read_line(&mut answer)? == 0 {
+            bail!("No config chosen");
+        }
+        match answer.trim().parse::<usize>() {
+            Ok(n) if (1..=entries.len()).contains(&n) => return Ok(entries[n - 1].link.clone()),
+            _ => println!("Enter a number between 1 and {}", entries.len()),
+        }
+    }
+}
@@ -0,0 +1,111 @@
+//! What an update pulled in, shown after `dothub update` so you can tell
+//! whether an application needs restarting.
+
+use crate::git;
+use crate::output::{self, Tone};
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::process::Command;
+
+/// Commit subjects shown per repo before the rest are counted.
+const MAX_SUBJECTS: usize = 10;
+
+/// Lockfiles whose changes mean plugins moved to other versions.
+const LOCKFILES: &[&str] = &["lazy-lock.json", "packer_compiled.lua", "plugin.lock", "Cargo.lock"];
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum Changelog {
+    /// Commit subjects, changed directories and risky files
+    Summary,
+    /// The full log with patches, in git's pager
+    Full,
+    None,
+}
+
+/// Print what changed in `repo` between two commits, after a blank line.
+pub fn show(mode: Changelog, name: &str, repo: &Path, before: &str, after: &str) -> Result<()> {
+    if mode == Changelog::None {
+        return Ok(());
+    }
+    println!();
+    let range = format!("{}..{}", before, after);
+    match mode {
+        Changelog::None => Ok(()),
+        Changelog::Full => {
+            Command::new("git")
+                .arg("-C")
+                .arg(repo)
+                .args(["log", "--stat", "-p", &range])
+                .status()
+                .context("Running git log")?;
+            Ok(())
+        }
+        Changelog::Summary => {
+            for line in summary(name, repo, &range)? {
+                println!("{}", line);
+            }
+            Ok(())
+        }
+    }
+}
+
+fn summary(name: &str, repo: &Path, range: &str) -> Result<Vec<String>> {
+    let log = git::output(repo, &["log", "--format=%h %s", range])?;
+    let subjects: Vec<&str> = log.lines().collect();
+    let files = git::output(repo, &["diff", "--name-only", range])?;
+    let files: Vec<&str> = files.lines().collect();
+
+    let mut lines = vec![output::paint(
+        &format!(
+            "{}: {} commit(s), {} file(s) changed",
+            name,
+            subjects.len(),
+            files.len()
+        ),
+        Tone::Info,
+    )];
+    for subject in subjects.iter().take(MAX_SUBJECTS) {
+        lines.push(format!("  {}", subject));
+    }
+    if subjects.len() > MAX_SUBJECTS {
+        lines.push(format!("  ... and {} more", subjects.len() - MAX_SUBJECTS));
+    }
+
+    let mut dirs: BTreeMap<&str, usize> = BTreeMap::new();
+    for file in &files {
+        let dir = file.rsplit_once('/').map(|(d, _)| d).unwrap_or(".");
+        *dirs.entry(dir).or_default() += 1;
+    }
+    if !dirs.is_empty() {
+        let dirs: Vec<String> = dirs
+            .iter()
+            .map(|(dir, n)| format!("{} ({})", dir, n))
+            .collect();
+        lines.push(format!("  files: {}", dirs.join(", ")));
+    }
+
+    for file in &files {
+        if let Some(why) = risk(file) {
+            lines.push(output::paint(&format!("  ! {} {}", file, why), Tone::Warn));
+        }
+    }
+    Ok(lines)
+}
+
+/// Why a changed file is worth a closer look, if it is.
+fn risk(file: &str) -> Option<&'static str> {
+    let base = file.rsplit('/').next().unwrap_or(file);
+    if LOCKFILES.contains(&base) {
+        return Some("changed: plugin versions moved");
+    }
+    if base == crate::manifest::REPO_MANIFEST {
+        return Some("changed: the target or package may be different");
+    }
+    // Only entry files at the repo root are loaded at startup
+    if !file.contains('/') && crate::edit::is_entry_file(base) {
+        return Some("changed: restart the application to pick it up");
+    }
+    None
+}
@@ -0,0 +1,296 @@
+//! `dothub compare`: two configs for the same application, side by side.
+//!
+//! Both are started headless the same way `dothub verify-repo` starts one,
+//! taking turns so that whatever else the machine is doing slows both alike,
+//! after an untimed first start each in which plugin managers install what
+//! they need. They share a home in the cache directory that persists between
+//! runs: applications that take their config directory's name from the
+//! environment (nvim's `NVIM_APPNAME`) get one name per config, and the
+//! others have `$XDG_CONFIG_HOME/<type>` switched to each config in turn.
+//! nvim's own `--startuptime` report is read as well.
+
+use crate::manifest::RepoManifest;
+use crate::output::{self, Tone};
+use crate::verify_repo;
+use anyhow::{bail, Context, Result};
+use comfy_table::Cell;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Types that read their config from `$XDG_CONFIG_HOME/$<variable>`.
+const APP_NAMES: &[(&str, &str)] = &[("nvim", "NVIM_APPNAME")];
+
+/// Types that report their own startup time, and the command that starts
+/// them writing that report to `$STARTUPTIME`.
+const PROFILED: &[(&str, &str)] = &[(
+    "nvim",
+    "nvim --headless -i NONE --startuptime \"$STARTUPTIME\" +qa!",
+)];
+
+/// One of the two configs and what its runs measured.
+struct Side {
+    name: String,
+    dir: PathBuf,
+    /// `$XDG_CONFIG_HOME` entry the config is linked as
+    app: String,
+    times: Vec<Duration>,
+    failed: usize,
+    first_error: Option<String>,
+    /// Startup times the application reported itself, in milliseconds
+    reported: Vec<f64>,
+    sourced: Option<usize>,
+}
+
+/// `dothub compare <target> <a> <b>`: `target` is the config type, and `a`
+/// and `b` are stored repos or paths to checkouts.
+pub fn cmd_compare(config_type: &str, a: &str, b: &str, runs: u32) -> Result<()> {
+    let Some((_, program, command)) = verify_repo::STARTUP
+        .iter()
+        .find(|(t, _, _)| *t == config_type)
+    else {
+        let known = verify_repo::STARTUP
+            .iter()
+            .map(|(t, _, _)| *t)
+            .collect::<Vec<_>>();
+        bail!(
+            "No way to start {} headless to time it; dothub compare knows {}",
+            config_type,
+            known.join(", ")
+        );
+    };
+    if which::which(program).is_err() {
+        bail!("{} is not installed or not found in PATH", program);
+    }
+    let command = PROFILED
+        .iter()
+        .find(|(t, _)| *t == config_type)
+        .map_or(*command, |(_, profiled)| *profiled);
+    let app_name = APP_NAMES
+        .iter()
+        .find(|(t, _)| *t == config_type)
+        .map(|(_, variable)| *variable);
+
+    let mut sides = [side(a, config_type)?, side(b, config_type)?];
+    if sides[0].dir == sides[1].dir {
+        bail!("{} and {} are the same config", a, b);
+    }
+    if app_name.is_some() {
+        for (side, suffix) in sides.iter_mut().zip(["a", "b"]) {
+            side.app = format!("dothub-compare-{}-{}", suffix, side.name);
+        }
+    }
+
+    let home = crate::http::cache_dir().join("compare");
+    let config = home.join(".config");
+    fs::create_dir_all(&config).with_context(|| format!("Creating {}", config.display()))?;
+    let report = home.join("startuptime.log");
+    let start = |side: &mut Side, timed: bool| -> Result<()> {
+        // Switch the one config location, or point the app's own name at it
+        let link = config.join(&side.app);
+        if fs::symlink_metadata(&link).is_ok() {
+            fs::remove_file(&link).with_context(|| format!("Removing {}", link.display()))?;
+        }
+        crate::vfs::fs().symlink(&side.dir, &link)?;
+        let _ = fs::remove_file(&report);
+        let mut envs = vec![("STARTUPTIME", report.as_os_str())];
+        if let Some(variable) = app_name {
+            envs.push((variable, OsStr::new(&side.app)));
+        }
+        let started = Instant::now();
+        let (ok, output) = verify_repo::run(&side.dir, Some(&home), command, &envs)?;
+        let elapsed = started.elapsed();
+        if !timed {
+            return Ok(());
+        }
+        if !ok {
+            side.failed += 1;
+            side.first_error.get_or_insert_with(|| {
+                output
+                    .lines()
+                    .next()
+                    .unwrap_or("exited with an error")
+                    .to_string()
+            });
+            return Ok(());
+        }
+        side.times.push(elapsed);
+        if let Ok(text) = fs::read_to_string(&report) {
+            if let Some(ms) = reported_ms(&text) {
+                side.reported.push(ms);
+            }
+            side.sourced = Some(text.lines().filter(|l| l.contains(": sourcing ")).count());
+        }
+        Ok(())
+    };
+
+    let runs = runs.max(1);
+    eprintln!(
+        "Starting {} and {} {} times each…",
+        sides[0].name, sides[1].name, runs
+    );
+    for side in sides.iter_mut() {
+        start(side, false)?;
+    }
+    for _ in 0..runs {
+        for side in sides.iter_mut() {
+            start(side, true)?;
+        }
+    }
+    print(&sides, runs);
+    Ok(())
+}
+
+/// The config of `config_type` in the repo `name`.
+fn side(name: &str, config_type: &str) -> Result<Side> {
+    let repo = verify_repo::repo_dir(name)?;
+    let provided = verify_repo::types(&repo)
+        .into_iter()
+        .find(|(t, _)| t == config_type)
+        .map(|(_, dir)| dir);
+    let all_in_one = RepoManifest::load(&repo)?.is_some_and(|m| !m.provides.is_empty());
+    let dir = match provided {
+        Some(dir) => dir,
+        None if all_in_one => bail!("{} doesn't provide a {} config", name, config_type),
+        None => repo.clone(),
+    };
+    let name = repo
+        .file_name()
+        .map_or(name.to_string(), |n| n.to_string_lossy().to_string());
+    Ok(Side {
+        name,
+        dir: fs::canonicalize(&dir).unwrap_or(dir),
+        app: config_type.to_string(),
+        times: Vec::new(),
+        failed: 0,
+        first_error: None,
+        reported: Vec::new(),
+        sourced: None,
+    })
+}
+
+/// Total startup time in an nvim `--startuptime` report: the first column of
+/// its last line.
+pub fn reported_ms(text: &str) -> Option<f64> {
+    let last = text.lines().rfind(|l| l.contains("NVIM STARTED"))?;
+    last.split_whitespace().next()?.parse().ok()
+}
+
+fn median<T: Copy + PartialOrd>(values: &[T]) -> Option<T> {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    sorted.get(sorted.len() / 2).copied()
+}
+
+/// Size of the config: files and lines, leaving out .git.
+fn size(dir: &Path) -> (usize, usize) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return (0, 0);
+    };
+    let (mut files, mut lines) = (0, 0);
+    for entry in entries.flatten() {
+        let Ok(kind) = entry.file_type() else {
+            continue;
+        };
+        if kind.is_dir() && entry.file_name() != ".git" {
+            let (f, l) = size(&entry.path());
+            files += f;
+            lines += l;
+        } else if kind.is_file() {
+            files += 1;
+            if let Ok(text) = fs::read_to_string(entry.path()) {
+                lines += text.lines().count();
+            }
+        }
+    }
+    (files, lines)
+}
+
+/// The comparison table, with the better value of each row highlighted, and
+/// which config started faster.
+fn print(sides: &[Side; 2], runs: u32) {
+    let mut table = output::table(["", sides[0].name.as_str(), sides[1].name.as_str()]);
+    let mut row = |label: &str, values: [Option<f64>; 2], show: &dyn Fn(f64) -> String| {
+        let better = match values {
+            [Some(a), Some(b)] if a < b => Some(0),
+            [Some(a), Some(b)] if b < a => Some(1),
+            _ => None,
+        };
+        let mut cells = vec![Cell::new(label)];
+        for (i, value) in values.iter().enumerate() {
+            cells.push(match value {
+                Some(v) if better == Some(i) => output::cell(show(*v), Tone::Ok),
+                Some(v) => Cell::new(show(*v)),
+                None => output::cell("-", Tone::Muted),
+            });
+        }
+        table.add_row(cells);
+    };
+    let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+    let time = |v: f64| format!("{:.1}ms", v);
+    let count = |v: f64| format!("{}", v as usize);
+    let medians = sides.each_ref().map(|s| median(&s.times).map(ms));
+    row("Startup (median)", medians, &time);
+    row(
+        "Fastest",
+        sides
+            .each_ref()
+            .map(|s| s.times.iter().min().copied().map(ms)),
+        &time,
+    );
+    row(
+        "Slowest",
+        sides
+            .each_ref()
+            .map(|s| s.times.iter().max().copied().map(ms)),
+        &time,
+    );
+    if sides.iter().any(|s| !s.reported.is_empty()) {
+        row(
+            "Reported (median)",
+            sides.each_ref().map(|s| median(&s.reported)),
+            &time,
+        );
+        row(
+            "Scripts sourced",
+            sides.each_ref().map(|s| s.sourced.map(|n| n as f64)),
+            &count,
+        );
+    }
+    row(
+        "Failed runs",
+        sides.each_ref().map(|s| Some(s.failed as f64)),
+        &|v| format!("{} of {}", v as usize, runs),
+    );
+    let sizes = sides.each_ref().map(|s| size(&s.dir));
+    row("Files", sizes.map(|(f, _)| Some(f as f64)), &count);
+    row("Lines", sizes.map(|(_, l)| Some(l as f64)), &count);
+    println!("{}", table);
+
+    for side in sides.iter().filter(|s| s.failed > 0) {
+        println!(
+            "{}",
+            output::paint(
+                &format!(
+                    "{} failed to start: {}; dothub verify-repo {} shows why",
+                    side.name,
+                    side.first_error.as_deref().unwrap_or(""),
+                    side.dir.display()
+                ),
+                Tone::Warn
+            )
+        );
+    }
+    if let [Some(a), Some(b)] = medians {
+        let (fast, slow, ratio) = if a <= b {
+            (&sides[0], &sides[1], b / a.max(0.001))
+        } else {
+            (&sides[1], &sides[0], a / b.max(0.001))
+        };
+        println!(
+            "{} started {:.1}x as fast as {}",
+            fast.name, ratio, slow.name
+        );
+    }
+}
@@ -0,0 +1,107 @@
+//! Whether a hub entry fits this machine.
+//!
+//! Entries may state the oldest application version they work with and the
+//! operating systems they support (see `EntryInfo`). The OS is known at build
+//! time; the application version is probed by running its binary with
+//! `--version`. Anything that cannot be probed counts as compatible, so a
+//! missing application never hides an entry.
+
+use crate::hub::EntryInfo;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::env;
+use std::process::Command;
+use std::sync::{Mutex, OnceLock};
+
+/// Probed versions by binary, so a hub with many nvim entries runs nvim once.
+static VERSIONS: OnceLock<Mutex<HashMap<String, Option<String>>>> = OnceLock::new();
+
+/// This system's name as written in hub.yml: "linux", "macos", "windows", ...
+pub fn os_name() -> &'static str {
+    env::consts::OS
+}
+
+/// The installed version of the application for `config_type`, e.g. "0.9.5".
+pub fn app_version(config_type: &str) -> Option<String> {
+    let bin = crate::doctor::app_binary(config_type);
+    let cache = VERSIONS.get_or_init(Default::default);
+    if let Some(v) = cache.lock().ok()?.get(bin) {
+        return v.clone();
+    }
+    let version = probe(bin);
+    cache.lock().ok()?.insert(bin.to_string(), version.clone());
+    version
+}
+
+fn probe(bin: &str) -> Option<String> {
+    // tmux only understands -V
+    let flag = if bin == "tmux" { "-V" } else { "--version" };
+    let out = Command::new(bin).arg(flag).output().ok()?;
+    let text = String::from_utf8_lossy(&out.stdout);
+    text.lines().next().and_then(parse_version)
+}
+
+/// The first dotted number in `line`: "NVIM v0.10.1" gives "0.10.1",
+/// "tmux 3.3a" gives "3.3".
+fn parse_version(line: &str) -> Option<String> {
+    line.split(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .map(|s| s.trim_matches('.'))
+        .find(|s| s.contains('.') && s.starts_with(|c: char| c.is_ascii_digit()))
+        .map(str::to_string)
+}
+
+/// Compare dotted versions number by number; missing parts count as 0.
+fn compare(a: &str, b: &str) -> Ordering {
+    let parts = |v: &str| -> Vec<u64> {
+        v.split('.')
+            .map(|p| p.trim_start_matches(|c: char| !c.is_ascii_digit()))
+            .map(|p| p.parse().unwrap_or(0))
+            .collect()
+    };
+    let (a, b) = (parts(a), parts(b));
+    for i in 0..a.len().max(b.len()) {
+        let (x, y) = (a.get(i).unwrap_or(&0), b.get(i).unwrap_or(&0));
+        if x != y {
+            return x.cmp(y);
+        }
+    }
+    Ordering::Equal
+}
+
+/// Why the entry does not fit this system, or None when it does (or when we
+/// cannot tell).
+pub fn problem(config_type: &str, info: &EntryInfo) -> Option<String> {
+    let os = os_name();
+    if !info.os.is_empty() && !info.os.iter().any(|o| o.eq_ignore_ascii_case(os)) {
+        return Some(format!("{} only, this is {}", info.os.join(", "), os));
+    }
+    let wanted = info.min_version.as_deref()?;
+    let found = app_version(config_type)?;
+    (compare(&found, wanted) == Ordering::Less).then(|| {
+        format!(
+            "needs {} >= {}, found {}",
+            crate::doctor::app_binary(config_type),
+            wanted,
+            found
+        )
+    })
+}
+
+/// "nvim 0.9.5 on linux": what the entries were checked against.
+pub fn describe_system(config_types: &[&str]) -> String {
+    let mut apps: Vec<String> = Vec::new();
+    for ty in config_types {
+        let bin = crate::doctor::app_binary(ty);
+        if let Some(v) = app_version(ty) {
+            let app = format!("{} {}", bin, v);
+            if !apps.contains(&app) {
+                apps.push(app);
+            }
+        }
+    }
+    if apps.is_empty() {
+        os_name().to_string()
+    } else {
+        format!("{} on {}", apps.join(", "), os_name())
+    }
+}
@@ -0,0 +1,216 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+
+/// User configuration read from config.toml. Every section is optional.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub store: StoreConfig,
+    pub backup: BackupConfig,
+    pub vendor: VendorConfig,
+    pub link: LinkConfig,
+    pub apply: ApplyConfig,
+    pub cache: CacheConfig,
+    pub network: NetworkConfig,
+    /// Reload actions by target name, replacing the built-in ones
+    pub reload: BTreeMap<String, ReloadConfig>,
+    pub lint: LintConfig,
+    pub tui: TuiConfig,
+    pub hub: HubConfig,
+    pub usage: UsageConfig,
+    pub auth: AuthConfig,
+    pub perf: PerfConfig,
+    /// Plain, line-oriented output without color or tables, as if `--plain` was always given
+    pub plain: bool,
+    /// Language for messages, e.g. "es"; LC_ALL, LC_MESSAGES or LANG when unset
+    pub language: Option<String>,
+    /// Paths never linked over, copied into or removed; ~/.ssh and ~/.gnupg when unset
+    pub protected_paths: Option<Vec<String>>,
+}
+
+/// How to reload the application reading a target after it changed.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReloadConfig {
+    /// Only run `command` while a process with exactly this name is running
+    pub process: Option<String>,
+    /// Shell command; {target} is replaced by the target path. Empty turns reloading off
+    pub command: Option<String>,
+    /// systemd user unit to restart when it is active
+    pub unit: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LintConfig {
+    /// Lint before `dothub edit` commits, and ask before committing what fails
+    pub before_commit: bool,
+    /// Shell commands by config type, run in the stored directory, replacing
+    /// the built-in linter. Empty turns linting of that type off
+    pub commands: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    /// Self-hosted forges by host, e.g. "git.corp.example" = "gitlab", which
+    /// are sent that forge's token; only the public instances are otherwise
+    pub hosts: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UsageConfig {
+    /// Record locally when shells and nvim load their configs, for `dothub stats`
+    pub track: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PerfConfig {
+    /// Percent by which startup may slow down after an update before
+    /// `dothub update` and `dothub perf` warn; 20 when unset
+    pub regression: Option<u32>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LinkConfig {
+    /// Create relative symlinks, as if `--relative` was always given
+    pub relative: bool,
+    /// Make every deployment read-only, as if `--read-only` was always given
+    pub read_only: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ApplyConfig {
+    /// Snapshot the home subvolume or dataset (Btrfs, ZFS) before applying, for `dothub undo`
+    pub snapshot: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheConfig {
+    /// Keep mirrors of cloned repos and downloaded assets, and clone from them
+    pub mirror: bool,
+    /// Where the mirrors live, e.g. a network share several machines use;
+    /// setting it turns mirroring on. Defaults to the cache directory
+    pub dir: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NetworkConfig {
+    /// How many more times to try a clone, fetch or request that failed in a
+    /// way that may pass; 3 when unset
+    pub retries: Option<u32>,
+    /// Bytes per second downloads may use, e.g. "500k" or "2m"; git is held
+    /// to it through trickle
+    pub bandwidth: Option<String>,
+    /// Proxy for every host, e.g. "http://proxy.corp:3128"; HTTPS_PROXY,
+    /// HTTP_PROXY or ALL_PROXY when unset
+    pub proxy: Option<String>,
+    /// Proxies by host or host pattern, e.g. "*.corp.example"; "direct" goes
+    /// without one
+    pub proxies: BTreeMap<String, String>,
+    /// Hosts reached without a proxy, like NO_PROXY, which is used when unset
+    pub no_proxy: Option<Vec<String>>,
+    /// PEM file with extra CA certificates to trust, e.g. a corporate proxy's
+    pub ca_bundle: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StoreConfig {
+    /// A read-only store kept by an admin, e.g. "/opt/dothub"; repos linked
+    /// from it needn't be installed. DOTHUB_SHARED overrides it
+    pub shared: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BackupConfig {
+    /// Backups kept of each target; taking one removes the oldest past it.
+    /// At least 1, so the backup just taken survives
+    pub keep: Option<NonZeroUsize>,
+    /// Days a backup is kept before the next backup or prune removes it
+    pub max_age_days: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HubConfig {
+    /// minisign public key the hub index must be signed with: the key itself
+    /// ("RW...") or a path to a .pub file. Replaces the built-in key
+    pub public_key: Option<String>,
+    /// Refuse an index that has no signature instead of warning
+    pub require_signature: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VendorConfig {
+    /// Mirror URL for `dothub vendor`, e.g. "git@github.com:me/{name}.git".
+    /// Placeholders: {name} (store name), {owner} and {repo} (from the upstream URL).
+    pub url_template: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TuiConfig {
+    /// Built-in palette to start from
+    pub theme: ThemeMode,
+    /// Color overrides by role, e.g. heading = "magenta" or accent = "#50fa7b"
+    pub colors: BTreeMap<String, String>,
+    /// Keys per action, replacing that action's defaults, e.g. down = ["j", "ctrl-n"]
+    pub keys: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ThemeMode {
+    /// Pick dark or light from the terminal background (COLORFGBG)
+    #[default]
+    Auto,
+    Dark,
+    Light,
+}
+
+pub fn config_path() -> PathBuf {
+    if let Ok(p) = env::var("DOTHUB_CONFIG") {
+        return PathBuf::from(p);
+    }
+    if let Some(mut dir) = dirs::config_dir() {
+        dir.push("dothub");
+        dir.push("config.toml");
+        return dir;
+    }
+    crate::dothub_dir().join("config.toml")
+}
+
+impl Config {
+    pub fn load() -> Result<Self> {
+        let path = config_path();
+        let text = match fs::read_to_string(&path) {
+            Ok(t) => t,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Config::default()),
+            Err(e) => return Err(e).with_context(|| format!("Reading {}", path.display())),
+        };
+        toml::from_str(&text).map_err(|e| {
+            anyhow::Error::new(
+                crate::error::Error::new(
+                    crate::error::Code::ConfigInvalid,
+                    format!("Parsing {}: {}", path.display(), e),
+                )
+                .hint(format!("fix or move aside {}; dothub runs with defaults without it", path.display())),
+            )
+        })
+    }
+}
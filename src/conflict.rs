@@ -0,0 +1,379 @@
+//! Link targets that already exist but were not deployed by dothub.
+//!
+//! Instead of deleting such a target, `link` and the dashboard ask what to do
+//! with it: move it aside as a backup, adopt it into the store as a repo of
+//! its own, or leave it alone. The existing files can be compared with the
+//! repo before deciding. Targets replaced without a backup go to the trash
+//! rather than being deleted, unless `--permanent` was given.
+
+use crate::git;
+use crate::manifest::RepoManifest;
+use crate::state::{self, DeployMode, State};
+use crate::vfs::{self, Kind};
+use anyhow::{bail, Context, Result};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+/// What `link`, `apply` and `import` do with unmanaged targets, chosen once
+/// per run with `--backup`, `--force` or `--no-clobber`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Clobber {
+    /// Ask on the terminal; without one, fail and leave the target alone
+    Ask = 0,
+    /// Back up and replace
+    Backup = 1,
+    /// Replace without a backup
+    Force = 2,
+    /// Skip with a warning
+    Keep = 3,
+}
+
+static CLOBBER: AtomicU8 = AtomicU8::new(Clobber::Ask as u8);
+static PERMANENT: AtomicBool = AtomicBool::new(false);
+
+pub fn set_clobber(mode: Clobber) {
+    CLOBBER.store(mode as u8, Ordering::SeqCst);
+}
+
+/// Delete replaced targets instead of moving them to the trash (`--permanent`).
+pub fn set_permanent(permanent: bool) {
+    PERMANENT.store(permanent, Ordering::SeqCst);
+}
+
+fn clobber() -> Clobber {
+    match CLOBBER.load(Ordering::SeqCst) {
+        1 => Clobber::Backup,
+        2 => Clobber::Force,
+        3 => Clobber::Keep,
+        _ => Clobber::Ask,
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Resolution {
+    /// Rename the target to a timestamped backup, then deploy
+    Backup,
+    /// Move the target into the store as a new repo, then deploy
+    Adopt,
+    /// Delete the target without a backup, then deploy (`--force`)
+    Replace,
+    /// Leave the target alone and do not deploy
+    Skip,
+}
+
+/// True when `target` exists and is not a deployment dothub made. Symlinks
+/// into the store count as ours even without a state record.
+pub fn is_unmanaged(target_name: &str, target: &Path) -> Result<bool> {
+    let fs = vfs::fs();
+    let Some(kind) = fs.kind(target) else {
+        return Ok(false);
+    };
+    let is_symlink = kind == Kind::Symlink;
+    if is_symlink {
+        if let Ok(dest) = fs.read_link(target) {
+            // Relative links (`link --relative`) resolve from the link's directory
+            let dest = match target.parent() {
+                Some(dir) if dest.is_relative() => dir.join(dest),
+                _ => dest,
+            };
+            let dest = dest.canonicalize().unwrap_or(dest);
+            let store = crate::dothub_dir();
+            if dest.starts_with(store.canonicalize().unwrap_or(store)) {
+                return Ok(false);
+            }
+        }
+    }
+    let state = State::load()?;
+    let managed = state.links.get(target_name).is_some_and(|rec| {
+        rec.target == target
+            && match rec.mode {
+                DeployMode::Symlink => is_symlink,
+                DeployMode::Copy => !is_symlink,
+            }
+    });
+    Ok(!managed)
+}
+
+/// Apply a resolution other than Skip so the target can be deployed over.
+/// Returns a line describing what happened to the old target.
+pub fn resolve(resolution: Resolution, target_name: &str, target: &Path) -> Result<String> {
+    crate::protect::check(target)?;
+    match resolution {
+        Resolution::Backup => {
+            let backup = crate::backups::path_for(target);
+            vfs::fs()
+                .rename(target, &backup)
+                .with_context(|| format!("Moving {} to {}", target.display(), backup.display()))?;
+            let mut line = crate::i18n::t(
+                "backed-up",
+                &[("target", &target.display()), ("backup", &backup.display())],
+            );
+            let pruned = crate::backups::enforce(&backup)?;
+            if pruned > 0 {
+                line.push_str(&format!(" (removed {} older backup(s) past [backup] limits)", pruned));
+            }
+            Ok(line)
+        }
+        Resolution::Adopt => {
+            let name = adopt(target_name, target)?;
+            Ok(format!(
+                "Adopted {} into the store as {}; link it again with `dothub link {}`",
+                target.display(),
+                name,
+                name
+            ))
+        }
+        Resolution::Replace => {
+            crate::readonly::set_writable(target, true)?;
+            if PERMANENT.load(Ordering::SeqCst) {
+                crate::remove_path(target)?;
+                return Ok(crate::i18n::t("removed-force", &[("target", &target.display())]));
+            }
+            vfs::fs().trash(target).with_context(|| {
+                format!(
+                    "Moving {} to the trash (--permanent deletes it instead)",
+                    target.display()
+                )
+            })?;
+            Ok(crate::i18n::t("trashed", &[("target", &target.display())]))
+        }
+        Resolution::Skip => bail!("Skip leaves {} in place", target.display()),
+    }
+}
+
+/// Move an existing config directory into the store as `<target>-local`,
+/// commit it and give it a manifest pointing back at the same target.
+fn adopt(target_name: &str, target: &Path) -> Result<String> {
+    let fs = vfs::fs();
+    if fs.kind(target) != Some(Kind::Dir) {
+        bail!(
+            "Only directories can be adopted; {} is not one",
+            target.display()
+        );
+    }
+    crate::ensure_store_dir()?;
+    let base = format!("{}-local", target_name);
+    let mut name = base.clone();
+    let mut n = 2;
+    while fs.exists(&crate::dothub_dir().join(&name)) {
+        name = format!("{}-{}", base, n);
+        n += 1;
+    }
+    let dest = crate::dothub_dir().join(&name);
+    if fs.rename(target, &dest).is_err() {
+        // The store may be on another filesystem: copy it, history included,
+        // and only remove the original once the copy is known to be whole
+        fs.copy_all(target, &dest)
+            .with_context(|| format!("Copying {} -> {}", target.display(), dest.display()))?;
+        if !vfs::dry_run() && !same_tree(target, &dest) {
+            bail!(
+                "The copy of {} at {} differs from it, so it was left in place; \
+                 remove {} and try again",
+                target.display(),
+                dest.display(),
+                dest.display()
+            );
+        }
+        crate::remove_path(target)?;
+    }
+    if vfs::dry_run() {
+        vfs::note(format!("commit {} as a new repo", dest.display()));
+        return Ok(name);
+    }
+
+    if RepoManifest::load(&dest)?.is_none() {
+        RepoManifest {
+            config_type: None,
+            target: Some(target_name.to_string()),
+            description: Some(format!("Adopted from {}", target.display())),
+            package: None,
+            tags: Vec::new(),
+            units: Vec::new(),
+            hooks: Default::default(),
+            modes: crate::modes::unusual(&dest)?,
+            env: Default::default(),
+            assets: Vec::new(),
+            provides: Default::default(),
+        }
+        .save(&dest)?;
+    }
+    if git::ensure_git().is_ok() && !git::is_repo(&dest) {
+        git::output(&dest, &["init", "-q"])?;
+        crate::anonymize::offer(&dest, false)?;
+        git::output(&dest, &["add", "-A"])?;
+        let msg = format!("Adopt existing {} config", target_name);
+        // Adopting goes on without a commit when something looks like a
+        // secret, or committing fails (e.g. signing), and says so
+        let leaks = crate::secrets::scan_staged(&dest)?;
+        if !leaks.is_empty() {
+            crate::secrets::print(&leaks);
+            println!(
+                "Adopted without a commit: {} possible secret(s) in {}",
+                leaks.len(),
+                dest.display()
+            );
+        } else if let Err(e) = git::commit(&dest, &msg) {
+            println!("Adopted without a commit: {}", e);
+        }
+    }
+
+    let mut st = State::load()?;
+    st.repos.entry(name.clone()).or_default().installed_at = state::now_unix();
+    st.save()?;
+    Ok(name)
+}
+
+/// Whether `copy` holds everything `original` does, byte for byte, symlinks
+/// pointing where they did.
+fn same_tree(original: &Path, copy: &Path) -> bool {
+    let (Ok(a), Ok(b)) = (fs::symlink_metadata(original), fs::symlink_metadata(copy)) else {
+        return false;
+    };
+    if a.file_type().is_symlink() {
+        return b.file_type().is_symlink()
+            && fs::read_link(original).ok() == fs::read_link(copy).ok();
+    }
+    if a.is_dir() {
+        let Ok(entries) = fs::read_dir(original) else {
+            return false;
+        };
+        return b.is_dir()
+            && entries.flatten().all(|entry| {
+                same_tree(&entry.path(), &copy.join(entry.file_name()))
+            });
+    }
+    b.is_file()
+        && a.len() == b.len()
+        && matches!((fs::read(original), fs::read(copy)), (Ok(x), Ok(y)) if x == y)
+}
+
+/// File-level comparison of an existing target with the repo about to replace it.
+pub fn describe(target: &Path, source: &Path) -> Result<Vec<String>> {
+    let existing = state::hash_tree(target)?;
+    let incoming = state::hash_tree(source)?;
+    let incoming: BTreeMap<_, _> = incoming
+        .into_iter()
+        .filter(|(path, _)| !path.starts_with(".git/"))
+        .collect();
+    let paths: BTreeSet<&String> = existing.keys().chain(incoming.keys()).collect();
+
+    let mut lines = Vec::new();
+    let mut same = 0;
+    for path in paths {
+        let shown = if path.is_empty() {
+            target.display().to_string()
+        } else {
+            path.clone()
+        };
+        match (existing.get(path), incoming.get(path)) {
+            (Some(a), Some(b)) if a == b => same += 1,
+            (Some(_), Some(_)) => lines.push(format!("~ {}  (differs)", shown)),
+            (Some(_), None) => lines.push(format!("- {}  (only in existing target)", shown)),
+            (None, Some(_)) => lines.push(format!("+ {}  (only in repo)", shown)),
+            (None, None) => {}
+        }
+    }
+    if same > 0 {
+        lines.push(format!("  {} identical file(s)", same));
+    }
+    if lines.is_empty() {
+        lines.push("  (both are empty)".to_string());
+    }
+    Ok(lines)
+}
+
+/// Size, file count and git-ness of an existing target, e.g.
+/// "12 files, 48.2 KiB, a git repository".
+pub fn inventory(target: &Path) -> String {
+    let (mut files, mut bytes) = (0u64, 0u64);
+    let mut pending = vec![target.to_path_buf()];
+    while let Some(path) = pending.pop() {
+        let Ok(md) = fs::symlink_metadata(&path) else {
+            continue;
+        };
+        if md.is_dir() {
+            if let Ok(entries) = fs::read_dir(&path) {
+                pending.extend(entries.flatten().map(|e| e.path()));
+            }
+        } else {
+            files += 1;
+            bytes += md.len();
+        }
+    }
+    let mut parts = vec![
+        format!("{} file{}", files, if files == 1 { "" } else { "s" }),
+        human_size(bytes),
+    ];
+    if target.join(".git").exists() {
+        parts.push("a git repository".to_string());
+    }
+    parts.join(", ")
+}
+
+pub fn human_size(bytes: u64) -> String {
+    const UNITS: &[&str] = &["KiB", "MiB", "GiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// Decide what to do with an unmanaged target: the `--backup`, `--force`
+/// or `--no-clobber` choice if one was made, otherwise ask on the terminal
+/// after showing what it holds. Without a terminal to ask on, nothing is
+/// touched.
+pub fn prompt(target: &Path, source: &Path) -> Result<Resolution> {
+    match clobber() {
+        Clobber::Backup => return Ok(Resolution::Backup),
+        Clobber::Force => return Ok(Resolution::Replace),
+        Clobber::Keep => return Ok(Resolution::Skip),
+        Clobber::Ask => {}
+    }
+    if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        bail!(crate::error::Error::new(
+            crate::error::Code::UnmanagedTarget,
+            format!(
+                "{} already exists and was not deployed by dothub ({})",
+                target.display(),
+                inventory(target)
+            ),
+        )
+        .hint("pass --backup to back it up and replace it, --force to replace it or --no-clobber to skip it"));
+    }
+    println!(
+        "{}",
+        crate::i18n::t(
+            "conflict-exists",
+            &[("target", &target.display()), ("inventory", &inventory(target))]
+        )
+    );
+    let stdin = io::stdin();
+    loop {
+        print!("{}", crate::i18n::t("conflict-question", &[]));
+        io::stdout().flush()?;
+        let mut answer = String::new();
+        if stdin.lock().read_line(&mut answer)? == 0 {
+            return Ok(Resolution::Skip);
+        }
+        match answer.trim().to_lowercase().as_str() {
+            "b" | "backup" => return Ok(Resolution::Backup),
+            "a" | "adopt" => return Ok(Resolution::Adopt),
+            "s" | "skip" | "" => return Ok(Resolution::Skip),
+            "v" | "view" => {
+                for line in describe(target, source)? {
+                    println!("  {}", line);
+                }
+            }
+            other => println!("{}", crate::i18n::t("unknown-choice", &[("choice", &other)])),
+        }
+    }
+}
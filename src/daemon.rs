@@ -0,0 +1,277 @@
+//! `dothub daemon`: keeps the status of every stored repo in memory and serves
+//! it over a unix socket, so `status`, `prompt` and the watch view don't have
+//! to run git in every repo themselves.
+//!
+//! The status is recomputed when files in the store or the state file change,
+//! and after fetching every repo with an upstream at a fixed interval.
+//!
+//! The protocol is one JSON request per connection, answered with one line of
+//! JSON: `{"cmd":"status"}` returns the cached status, `{"cmd":"refresh"}`
+//! recomputes it first and `{"cmd":"ping"}` returns the time of the last check.
+
+use crate::status::StatusRow;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::path::PathBuf;
+
+const SOCKET_FILE: &str = "dothub.sock";
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "cmd", rename_all = "lowercase")]
+enum Request {
+    Ping,
+    Status,
+    Refresh,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct Reply {
+    /// Unix timestamp of the check the repos come from
+    checked_at: u64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    repos: Vec<StatusRow>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Where the daemon listens: $DOTHUB_SOCKET, else the XDG runtime dir, else
+/// the cache dir.
+pub fn socket_path() -> PathBuf {
+    if let Ok(p) = env::var("DOTHUB_SOCKET") {
+        return PathBuf::from(p);
+    }
+    match dirs::runtime_dir() {
+        Some(dir) => dir.join(SOCKET_FILE),
+        None => crate::http::cache_dir().join(SOCKET_FILE),
+    }
+}
+
+/// Status rows from a running daemon, or None when there is none to ask.
+pub fn cached_status() -> Option<Vec<StatusRow>> {
+    let reply = unix::request(&Request::Status).ok()?;
+    reply.error.is_none().then_some(reply.repos)
+}
+
+pub fn cmd_daemon(fetch_interval: u64) -> anyhow::Result<()> {
+    unix::serve(fetch_interval)
+}
+
+#[cfg(not(unix))]
+mod unix {
+    use super::{Reply, Request};
+    use anyhow::{bail, Result};
+
+    pub fn request(_req: &Request) -> Result<Reply> {
+        bail!("The status daemon needs unix sockets")
+    }
+
+    pub fn serve(_fetch_interval: u64) -> Result<()> {
+        bail!("The status daemon needs unix sockets")
+    }
+}
+
+#[cfg(unix)]
+mod unix {
+    use super::{socket_path, Reply, Request};
+    use crate::output::{self, Tone};
+    use crate::status::{self, StatusRow};
+    use crate::{git, http, state};
+    use anyhow::{bail, Context, Result};
+    use notify::{RecursiveMode, Watcher};
+    use std::fs;
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::path::Path;
+    use std::sync::mpsc::{self, RecvTimeoutError};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    /// Clients fall back to computing the status themselves, so never wait long.
+    const CLIENT_TIMEOUT: Duration = Duration::from_secs(2);
+    /// Quiet period after a file change before rescanning, so a checkout or
+    /// pull triggers one scan instead of hundreds.
+    const DEBOUNCE: Duration = Duration::from_millis(300);
+
+    struct Snapshot {
+        checked_at: u64,
+        repos: Vec<StatusRow>,
+    }
+
+    type Shared = Arc<Mutex<Snapshot>>;
+
+    pub fn request(req: &Request) -> Result<Reply> {
+        let mut stream = UnixStream::connect(socket_path())?;
+        stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+        stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+        let mut line = serde_json::to_string(req)?;
+        line.push('\n');
+        stream.write_all(line.as_bytes())?;
+        let mut answer = String::new();
+        BufReader::new(stream).read_line(&mut answer)?;
+        Ok(serde_json::from_str(&answer)?)
+    }
+
+    pub fn serve(fetch_interval: u64) -> Result<()> {
+        crate::ensure_store_dir()?;
+        git::ensure_git()?;
+        let path = socket_path();
+        if UnixStream::connect(&path).is_ok() {
+            bail!("A dothub daemon is already listening on {}", path.display());
+        }
+        // A socket left behind by a daemon that did not shut down cleanly
+        let _ = fs::remove_file(&path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed creating {}", parent.display()))?;
+        }
+        let listener = UnixListener::bind(&path)
+            .with_context(|| format!("Listening on {}", path.display()))?;
+
+        let shared: Shared = Arc::new(Mutex::new(Snapshot {
+            checked_at: state::now_unix(),
+            repos: status::scan()?,
+        }));
+
+        let (tx, rx) = mpsc::channel();
+        let store = crate::dothub_dir();
+        let state_file = state::state_path();
+        let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+            let Ok(event) = res else { return };
+            if !event.kind.is_access() && event.paths.iter().any(|p| relevant(p)) {
+                let _ = tx.send(());
+            }
+        })
+        .context("Starting the file watcher")?;
+        watcher
+            .watch(&store, RecursiveMode::Recursive)
+            .with_context(|| format!("Watching {}", store.display()))?;
+        // The state file records links, shown next to each repo
+        if let Some(dir) = state_file.parent().filter(|d| d.exists()) {
+            if !dir.starts_with(&store) {
+                watcher.watch(dir, RecursiveMode::NonRecursive)?;
+            }
+        }
+
+        {
+            let shared = Arc::clone(&shared);
+            thread::spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    let shared = Arc::clone(&shared);
+                    thread::spawn(move || {
+                        let _ = handle(stream, &shared);
+                    });
+                }
+            });
+        }
+
+        println!(
+            "{}",
+            output::paint(
+                &format!("dothub daemon listening on {}", path.display()),
+                Tone::Info
+            )
+        );
+        let fetch_every = Duration::from_secs(fetch_interval.max(60));
+        let mut last_fetch = Instant::now();
+        loop {
+            let mut changed = match rx.recv_timeout(Duration::from_secs(1)) {
+                Ok(()) => true,
+                Err(RecvTimeoutError::Timeout) => false,
+                Err(RecvTimeoutError::Disconnected) => bail!("The file watcher stopped"),
+            };
+            if changed {
+                while rx.recv_timeout(DEBOUNCE).is_ok() {}
+            }
+            if last_fetch.elapsed() >= fetch_every {
+                last_fetch = Instant::now();
+                if !http::is_offline() {
+                    fetch_all();
+                    changed = true;
+                }
+            }
+            if changed {
+                // Keep serving the last good status; the next change retries
+                if let Err(e) = refresh(&shared) {
+                    output::error(&format!("{:#}", e));
+                }
+                // Drop the events our own fetch or scan caused
+                while rx.try_recv().is_ok() {}
+            }
+        }
+    }
+
+    /// Changes under .git that only git's own bookkeeping makes are not worth a rescan.
+    fn relevant(path: &Path) -> bool {
+        if !path.components().any(|c| c.as_os_str() == ".git") {
+            return true;
+        }
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy())
+            .unwrap_or_default();
+        !(name.ends_with(".lock") || name == "index" || name == "FETCH_HEAD")
+    }
+
+    fn refresh(shared: &Shared) -> Result<()> {
+        let repos = status::scan()?;
+        let mut snap = shared.lock().unwrap_or_else(|e| e.into_inner());
+        snap.repos = repos;
+        snap.checked_at = state::now_unix();
+        Ok(())
+    }
+
+    fn fetch_all() {
+        let Ok(repos) = crate::installed_repos() else {
+            return;
+        };
+        // Repos not due for an update would only show news that update won't pull
+        let st = state::State::load().unwrap_or_default();
+        let now = state::now_unix();
+        for (name, path) in repos {
+            let due = st.repos.get(&name).map_or(Some(0), |r| r.due_in(now)) == Some(0);
+            if due && git::is_repo(&path) && git::has_upstream(&path) {
+                // A failed fetch leaves the last known upstream in place
+                let _ = git::network(&path, &["fetch", "-q"], false);
+            }
+        }
+    }
+
+    fn handle(stream: UnixStream, shared: &Shared) -> Result<()> {
+        stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+        let mut line = String::new();
+        BufReader::new(&stream).read_line(&mut line)?;
+        let reply = match serde_json::from_str::<Request>(&line) {
+            Ok(req) => answer(req, shared),
+            Err(e) => Reply {
+                error: Some(format!("Invalid request: {}", e)),
+                ..Reply::default()
+            },
+        };
+        let mut text = serde_json::to_string(&reply)?;
+        text.push('\n');
+        (&stream).write_all(text.as_bytes())?;
+        Ok(())
+    }
+
+    fn answer(req: Request, shared: &Shared) -> Reply {
+        if let Request::Refresh = req {
+            if let Err(e) = refresh(shared) {
+                return Reply {
+                    error: Some(e.to_string()),
+                    ..Reply::default()
+                };
+            }
+        }
+        let snap = shared.lock().unwrap_or_else(|e| e.into_inner());
+        let repos = match req {
+            Request::Ping => Vec::new(),
+            Request::Status | Request::Refresh => snap.repos.clone(),
+        };
+        Reply {
+            checked_at: snap.checked_at,
+            repos,
+            error: None,
+        }
+    }
+}
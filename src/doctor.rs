@@ -0,0 +1,217 @@
+use crate::output::{self, Tone};
+use crate::state::{self, DeployMode, State};
+use crate::{git, selinux, stats, verify};
+use anyhow::{Context, Result};
+use comfy_table::Cell;
+use std::fs;
+use std::path::Path;
+
+const DAY: u64 = 24 * 60 * 60;
+
+/// Executable that reads ~/.config/<target>, for targets whose binary is named differently.
+pub fn app_binary(target: &str) -> &str {
+    match target {
+        "helix" => "hx",
+        "hypr" => "Hyprland",
+        "nvim" | "neovim" => "nvim",
+        "Code" | "vscode" => "code",
+        other => other,
+    }
+}
+
+struct Finding {
+    check: &'static str,
+    tone: Tone,
+    subject: String,
+    detail: String,
+    suggestion: Option<String>,
+}
+
+pub fn cmd_doctor(stale_days: u64, relativize: bool) -> Result<()> {
+    let mut findings = Vec::new();
+
+    if git::ensure_git().is_err() {
+        findings.push(Finding {
+            check: "git",
+            tone: Tone::Error,
+            subject: "git".to_string(),
+            detail: "git is not installed or not found in PATH".to_string(),
+            suggestion: None,
+        });
+    }
+
+    let store = crate::dothub_dir();
+    if !store.exists() {
+        findings.push(Finding {
+            check: "store",
+            tone: Tone::Warn,
+            subject: store.display().to_string(),
+            detail: "store directory does not exist yet".to_string(),
+            suggestion: Some("dothub install <repo>".to_string()),
+        });
+    }
+
+    let st = State::load()?;
+    let now = state::now_unix();
+
+    // Repos whose upstream was deleted or renamed, as the last update found
+    for (name, rec) in &st.repos {
+        let Some(found) = rec.upstream_gone else {
+            continue;
+        };
+        findings.push(Finding {
+            check: "upstream gone",
+            tone: Tone::Error,
+            subject: name.clone(),
+            detail: format!(
+                "upstream deleted or renamed, found {}",
+                output::ago(found, now)
+            ),
+            suggestion: Some(format!(
+                "keep it frozen: dothub schedule {n} manual; switch remote: dothub remote set {n} <url>; \
+                 or mirror it: dothub vendor {n}",
+                n = name
+            )),
+        });
+    }
+
+    // Repos whose upstream has gone quiet
+    if store.exists() {
+        for (name, path) in crate::installed_repos()? {
+            if !git::is_repo(&path) {
+                continue;
+            }
+            let Some(last) = stats::last_upstream_commit(&path) else {
+                continue;
+            };
+            let idle = now.saturating_sub(last);
+            if idle >= stale_days * DAY {
+                let linked = crate::linked_targets(&st, &name);
+                let suggestion = if linked.is_empty() {
+                    format!("dothub remove {}", name)
+                } else {
+                    format!("dothub unlink {} && dothub remove {}", linked.join(" "), name)
+                };
+                findings.push(Finding {
+                    check: "stale",
+                    tone: Tone::Warn,
+                    subject: name,
+                    detail: format!("no upstream commit for {}", output::duration(idle)),
+                    suggestion: Some(suggestion),
+                });
+            }
+        }
+    }
+
+    for (target, record) in &st.links {
+        // Deployments changed outside dothub
+        let problems = verify::check_record(record);
+        if let Some(first) = problems.first() {
+            findings.push(Finding {
+                check: "deployment",
+                tone: Tone::Error,
+                subject: target.clone(),
+                detail: first.describe(),
+                suggestion: Some("dothub verify".to_string()),
+            });
+        } else if record.mode == DeployMode::Symlink
+            && fs::read_link(&record.target).is_ok_and(|p| p.is_absolute())
+        {
+            // Absolute links break when the home directory or store moves
+            if relativize {
+                make_relative(&record.target, &record.source)?;
+                println!("Relativized {}", record.target.display());
+            } else {
+                findings.push(Finding {
+                    check: "absolute link",
+                    tone: Tone::Info,
+                    subject: target.clone(),
+                    detail: "breaks if home or the store moves".to_string(),
+                    suggestion: Some("dothub doctor --relativize".to_string()),
+                });
+            }
+        }
+
+        // Labels a confined application would be denied by
+        let labels = selinux::mismatches(&record.target);
+        if let Some((path, current, expected)) = labels.first() {
+            findings.push(Finding {
+                check: "selinux",
+                tone: Tone::Warn,
+                subject: target.clone(),
+                detail: format!(
+                    "{} labeled {}, policy expects {}{}",
+                    path,
+                    current,
+                    expected,
+                    if labels.len() > 1 {
+                        format!(" (and {} more)", labels.len() - 1)
+                    } else {
+                        String::new()
+                    }
+                ),
+                suggestion: Some(format!("restorecon -R {}", record.target.display())),
+            });
+        }
+
+        // Configs for applications that are no longer installed; only ~/.config
+        // targets are named after an application
+        let in_config = record
+            .target
+            .parent()
+            .is_some_and(|p| p.ends_with(".config"));
+        let bin = app_binary(target);
+        if in_config && which::which(bin).is_err() {
+            findings.push(Finding {
+                check: "app missing",
+                tone: Tone::Warn,
+                subject: target.clone(),
+                detail: format!("{} not found in PATH", bin),
+                suggestion: Some(format!("dothub unlink {}", target)),
+            });
+        }
+    }
+
+    if findings.is_empty() {
+        println!("{}", output::paint("No problems found.", Tone::Ok));
+        return Ok(());
+    }
+
+    let mut table = output::table(["Check", "Subject", "Detail", "Suggestion"]);
+    for f in &findings {
+        table.add_row(vec![
+            output::cell(f.check, f.tone),
+            Cell::new(&f.subject),
+            Cell::new(&f.detail),
+            Cell::new(f.suggestion.as_deref().unwrap_or("")),
+        ]);
+    }
+    println!("{}", table);
+    println!("{} finding(s).", findings.len());
+    Ok(())
+}
+
+/// Replace the symlink at `target` with a relative one to `source`. The new
+/// link is created beside it and renamed over, so the target never goes missing.
+fn make_relative(target: &Path, source: &Path) -> Result<()> {
+    let dir = target.parent().context("Target has no parent directory")?;
+    let rel = crate::relative_path(dir, source)?;
+    let mut tmp_name = target.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".dothub-tmp");
+    let tmp = dir.join(tmp_name);
+    if crate::symlink_exists(&tmp) {
+        fs::remove_file(&tmp)?;
+    }
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(&rel, &tmp)
+        .with_context(|| format!("Failed creating symlink {}", tmp.display()))?;
+    #[cfg(windows)]
+    if source.is_dir() {
+        std::os::windows::fs::symlink_dir(&rel, &tmp)
+            .with_context(|| format!("Failed creating symlink {}", tmp.display()))?;
+    } else {
+        std::os::windows::fs::symlink_file(&rel, &tmp)
+            .with_context(|| format!("Failed creating symlink {}", tmp.display()))?;
+    }
+    fs::rename(&tmp, target).with_context(|| format!("Replacing {}", target.display()))
+}
@@ -0,0 +1,232 @@
+//! `dothub edit`: change a deployed config where it is stored.
+//!
+//! The target is resolved to the stored repo behind it and the editor opens on
+//! the file that config type starts from (e.g. `init.lua` for nvim), in the
+//! repo's directory. Afterwards the changes are shown with an offer to commit
+//! and push them. Read-only deployments are unlocked for the editing session
+//! and locked again afterwards, at the new commit when one was made.
+
+use crate::manifest::RepoManifest;
+use crate::output::{self, Tone};
+use crate::state::{DeployMode, State};
+use crate::{git, readonly};
+use anyhow::{bail, Context, Result};
+use std::env;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::Ordering;
+
+/// Files a config type is usually started from, most likely first.
+const ENTRY_FILES: &[(&str, &[&str])] = &[
+    ("nvim", &["init.lua", "init.vim"]),
+    ("vim", &["vimrc", ".vimrc"]),
+    ("tmux", &["tmux.conf", ".tmux.conf"]),
+    ("zsh", &[".zshrc", "zshrc"]),
+    ("bash", &[".bashrc", "bashrc"]),
+    ("fish", &["config.fish"]),
+    ("kitty", &["kitty.conf"]),
+    ("alacritty", &["alacritty.toml", "alacritty.yml"]),
+    ("wezterm", &["wezterm.lua"]),
+    ("ghostty", &["config"]),
+    ("hypr", &["hyprland.conf"]),
+    ("sway", &["config"]),
+    ("i3", &["config"]),
+    ("helix", &["config.toml"]),
+    ("starship", &["starship.toml"]),
+    ("git", &["config"]),
+];
+
+/// True when `file` is the entry file of some config type.
+pub fn is_entry_file(file: &str) -> bool {
+    ENTRY_FILES.iter().any(|(_, files)| files.contains(&file))
+}
+
+pub fn cmd_edit(target_name: &str) -> Result<()> {
+    let mut st = State::load()?;
+    // Targets first; a repo name opens the repo itself
+    let (record, name, source) = match st.links.get(target_name).cloned() {
+        Some(rec) => {
+            let (name, source) = (rec.name.clone(), rec.source.clone());
+            (Some(rec), name, source)
+        }
+        None if crate::dothub_dir().join(target_name).is_dir() => (
+            None,
+            target_name.to_string(),
+            crate::dothub_dir().join(target_name),
+        ),
+        None => bail!(
+            "{} is neither a deployed target nor a stored repo",
+            target_name
+        ),
+    };
+    let repo = crate::dothub_dir().join(&name);
+    let read_only = record.as_ref().is_some_and(|r| r.read_only);
+    if read_only {
+        readonly::unlock_repo(&mut st, &name)?;
+        st.save()?;
+        println!("Unlocked {} for editing", name);
+    }
+
+    let edited = open_editor(&source, entry_file(&source, target_name, &repo).as_deref());
+    let committed = match &edited {
+        Ok(()) if git::is_repo(&repo) => review(&repo, target_name),
+        _ => Ok(()),
+    };
+
+    // Lock again even when editing failed, so the deployment never stays writable
+    match &record {
+        Some(rec) if rec.read_only => {
+            if rec.mode == DeployMode::Copy {
+                crate::READ_ONLY.store(true, Ordering::SeqCst);
+                println!(
+                    "{}",
+                    crate::deploy(&name, &rec.source, target_name, &rec.target, true)?
+                );
+            }
+            let mut st = State::load()?;
+            readonly::lock_repo(&mut st, &name)?;
+            st.save()?;
+            println!("Locked {} again", name);
+        }
+        Some(rec) if rec.mode == DeployMode::Copy => println!(
+            "{} is a copy; deploy the changes with `dothub link --copy {} {}`",
+            rec.target.display(),
+            name,
+            target_name
+        ),
+        _ => {}
+    }
+    edited.and(committed)
+}
+
+/// The file to open in `source`: the entry file for the repo's config type,
+/// or None to open the directory.
+fn entry_file(source: &Path, target_name: &str, repo: &Path) -> Option<PathBuf> {
+    let manifest = RepoManifest::load(repo).ok().flatten();
+    let config_type = manifest
+        .as_ref()
+        .and_then(|m| m.config_type.as_deref())
+        .unwrap_or(target_name);
+    let (_, files) = ENTRY_FILES.iter().find(|(t, _)| *t == config_type)?;
+    files
+        .iter()
+        .map(|f| source.join(f))
+        .find(|p| p.is_file())
+}
+
+/// Open `$EDITOR` (vi when unset) in `dir`, on `file` when given, and wait for
+/// it to exit.
+fn open_editor(dir: &Path, file: Option<&Path>) -> Result<()> {
+    let editor = env::var("EDITOR")
+        .ok()
+        .filter(|e| !e.trim().is_empty())
+        .unwrap_or_else(|| "vi".to_string());
+    // EDITOR may carry arguments, e.g. "code --wait"
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or("vi");
+    let status = Command::new(program)
+        .args(words)
+        .arg(file.unwrap_or(dir))
+        .current_dir(if dir.is_dir() { dir } else { Path::new(".") })
+        .status()
+        .with_context(|| format!("Failed to start {}", program))?;
+    if !status.success() {
+        bail!("{} exited with {}", program, status);
+    }
+    Ok(())
+}
+
+/// Show what the editing session changed in `repo` and offer to commit and
+/// push it.
+fn review(repo: &Path, target_name: &str) -> Result<()> {
+    let changes = git::output(repo, &["status", "--porcelain"])?;
+    if changes.is_empty() {
+        println!("No changes.");
+        return Ok(());
+    }
+    show_diff(repo)?;
+    if !io::stdin().is_terminal() {
+        println!("Left uncommitted changes in {}", repo.display());
+        return Ok(());
+    }
+    if !ask("Commit the changes?")? {
+        println!("Left uncommitted changes in {}", repo.display());
+        return Ok(());
+    }
+    let default = format!("Edit {} config", target_name);
+    print!("Message [{}]: ", default);
+    io::stdout().flush()?;
+    let mut message = String::new();
+    io::stdin().lock().read_line(&mut message)?;
+    let message = Some(message.trim()).filter(|m| !m.is_empty()).unwrap_or(&default);
+    if crate::config::Config::load()?.lint.before_commit {
+        let config_type = crate::lint::config_type(repo, target_name);
+        if !crate::lint::check(repo, &config_type)? && !ask("Lint failed. Commit anyway?")? {
+            println!("Left uncommitted changes in {}", repo.display());
+            return Ok(());
+        }
+    }
+    git::output(repo, &["add", "-A"])?;
+    let leaks = crate::secrets::scan_staged(repo)?;
+    if !leaks.is_empty() {
+        crate::secrets::print(&leaks);
+        if !ask("These look like secrets. Commit anyway?")? {
+            unstage(repo)?;
+            println!("Left uncommitted changes in {}", repo.display());
+            return Ok(());
+        }
+    }
+    git::commit(repo, message)?;
+    let head = git::head(repo);
+    println!("Committed {}", crate::short_sha(head.as_deref()));
+
+    if !git::has_upstream(repo) {
+        return Ok(());
+    }
+    let upstream = git::output(repo, &["rev-parse", "--abbrev-ref", "@{upstream}"])?;
+    if ask(&format!("Push to {}?", upstream))? {
+        git::network(repo, &["push", "-q"], false)?;
+        println!("{}", output::paint(&format!("Pushed to {}", upstream), Tone::Ok));
+    }
+    Ok(())
+}
+
+/// Undo `git add -A`, even before the first commit.
+fn unstage(repo: &Path) -> Result<()> {
+    if git::head(repo).is_some() {
+        git::output(repo, &["reset", "-q"])?;
+    } else {
+        git::output(repo, &["rm", "-r", "-q", "--cached", "."])?;
+    }
+    Ok(())
+}
+
+/// Print the changed paths and the diff of tracked files, in git's colors.
+fn show_diff(repo: &Path) -> Result<()> {
+    let color = if output::is_tty() { "always" } else { "never" };
+    let mut steps = vec![&["status", "--short"][..]];
+    // A repo without commits has nothing to diff against yet
+    if git::head(repo).is_some() {
+        steps.push(&["diff", "HEAD"][..]);
+    }
+    for args in steps {
+        Command::new("git")
+            .arg("--no-pager")
+            .args(["-c", &format!("color.ui={}", color)])
+            .arg("-C")
+            .arg(repo)
+            .args(args)
+            .status()
+            .context("Running git")?;
+    }
+    Ok(())
+}
+
+fn ask(question: &str) -> Result<bool> {
+    print!("{} [y/N] ", question);
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
@@ -0,0 +1,205 @@
+//! Errors with a stable code and a "try this" hint.
+//!
+//! Most failures are plain anyhow errors. The ones a user can fix themselves
+//! are raised as [`Error`], which carries a code such as `E012
+//! STORE_PERMISSION` and the command to run. It travels inside anyhow like any
+//! other error; `report` finds it in the chain and renders it as text or, with
+//! `--error-format json`, as a JSON object on stderr.
+
+use clap::ValueEnum;
+use serde::Serialize;
+use std::fmt;
+use std::path::Path;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Code {
+    RepoNotFound,
+    NotManaged,
+    GitMissing,
+    UnmanagedTarget,
+    ProtectedPath,
+    RateLimited,
+    ConfigInvalid,
+    StateInvalid,
+    PlanStale,
+    NoTarget,
+    RepoExists,
+    StorePermission,
+    Incompatible,
+    SignatureInvalid,
+    AuditFlagged,
+    SnapshotFailed,
+    ChecksumMismatch,
+    ToolMissing,
+    SigningFailed,
+    SecretsFound,
+    TokenMissing,
+}
+
+impl Code {
+    /// The stable identifier, e.g. "E012". Codes are never renumbered.
+    pub fn id(self) -> &'static str {
+        match self {
+            Code::RepoNotFound => "E001",
+            Code::NotManaged => "E002",
+            Code::GitMissing => "E003",
+            Code::UnmanagedTarget => "E004",
+            Code::ProtectedPath => "E005",
+            Code::RateLimited => "E006",
+            Code::ConfigInvalid => "E007",
+            Code::StateInvalid => "E008",
+            Code::PlanStale => "E009",
+            Code::NoTarget => "E010",
+            Code::RepoExists => "E011",
+            Code::StorePermission => "E012",
+            Code::Incompatible => "E013",
+            Code::SignatureInvalid => "E014",
+            Code::AuditFlagged => "E015",
+            Code::SnapshotFailed => "E016",
+            Code::ChecksumMismatch => "E017",
+            Code::ToolMissing => "E018",
+            Code::SigningFailed => "E019",
+            Code::SecretsFound => "E020",
+            Code::TokenMissing => "E021",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Code::RepoNotFound => "REPO_NOT_FOUND",
+            Code::NotManaged => "NOT_MANAGED",
+            Code::GitMissing => "GIT_MISSING",
+            Code::UnmanagedTarget => "UNMANAGED_TARGET",
+            Code::ProtectedPath => "PROTECTED_PATH",
+            Code::RateLimited => "RATE_LIMITED",
+            Code::ConfigInvalid => "CONFIG_INVALID",
+            Code::StateInvalid => "STATE_INVALID",
+            Code::PlanStale => "PLAN_STALE",
+            Code::NoTarget => "NO_TARGET",
+            Code::RepoExists => "REPO_EXISTS",
+            Code::StorePermission => "STORE_PERMISSION",
+            Code::Incompatible => "INCOMPATIBLE",
+            Code::SignatureInvalid => "SIGNATURE_INVALID",
+            Code::AuditFlagged => "AUDIT_FLAGGED",
+            Code::SnapshotFailed => "SNAPSHOT_FAILED",
+            Code::ChecksumMismatch => "CHECKSUM_MISMATCH",
+            Code::ToolMissing => "TOOL_MISSING",
+            Code::SigningFailed => "SIGNING_FAILED",
+            Code::SecretsFound => "SECRETS_FOUND",
+            Code::TokenMissing => "TOKEN_MISSING",
+        }
+    }
+}
+
+/// A failure the user can act on.
+#[derive(Debug)]
+pub struct Error {
+    pub code: Code,
+    pub message: String,
+    pub hint: Option<String>,
+}
+
+impl Error {
+    pub fn new(code: Code, message: impl Into<String>) -> Self {
+        Error {
+            code,
+            message: message.into(),
+            hint: None,
+        }
+    }
+
+    pub fn hint(mut self, hint: impl Into<String>) -> Self {
+        self.hint = Some(hint.into());
+        self
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// The code of the first coded error in `err`'s chain.
+pub fn code_of(err: &anyhow::Error) -> Option<Code> {
+    err.chain()
+        .find_map(|e| e.downcast_ref::<Error>())
+        .map(|e| e.code)
+}
+
+/// Shorthand for the most common coded error.
+pub fn repo_not_found(name: &str, path: &Path) -> Error {
+    Error::new(
+        Code::RepoNotFound,
+        format!("Repository not found: {}", path.display()),
+    )
+    .hint(format!(
+        "dothub list shows installed repos; install {} with dothub install <url> {}",
+        name, name
+    ))
+}
+
+/// Give a permission failure under the store a code and the command that
+/// fixes the ownership; other errors pass through unchanged.
+pub fn store_permission(err: std::io::Error, path: &Path) -> anyhow::Error {
+    if err.kind() != std::io::ErrorKind::PermissionDenied {
+        return anyhow::Error::new(err).context(format!("Writing {}", path.display()));
+    }
+    let store = crate::dothub_dir();
+    let user = std::env::var("USER").unwrap_or_else(|_| "$USER".to_string());
+    anyhow::Error::new(
+        Error::new(
+            Code::StorePermission,
+            format!("Permission denied writing {}", path.display()),
+        )
+        .hint(format!("sudo chown -R {} {}", user, store.display())),
+    )
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum ErrorFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+#[derive(Serialize)]
+struct JsonError<'a> {
+    code: Option<&'a str>,
+    name: Option<&'a str>,
+    message: String,
+    hint: Option<&'a str>,
+    causes: Vec<String>,
+}
+
+/// Print a failed run's error to stderr.
+pub fn report(err: &anyhow::Error, format: ErrorFormat) {
+    let coded = err.chain().find_map(|e| e.downcast_ref::<Error>());
+    match format {
+        ErrorFormat::Text => {
+            let label = crate::i18n::t("error", &[]);
+            match coded {
+                Some(c) => eprintln!("{}[{} {}]: {:?}", label, c.code.id(), c.code.name(), err),
+                None => eprintln!("{}: {:?}", label, err),
+            }
+            if let Some(hint) = coded.and_then(|c| c.hint.as_deref()) {
+                eprintln!("\n  {}: {}", crate::i18n::t("error-try", &[]), hint);
+            }
+        }
+        ErrorFormat::Json => {
+            let json = JsonError {
+                code: coded.map(|c| c.code.id()),
+                name: coded.map(|c| c.code.name()),
+                message: err.to_string(),
+                hint: coded.and_then(|c| c.hint.as_deref()),
+                causes: err.chain().skip(1).map(|e| e.to_string()).collect(),
+            };
+            match serde_json::to_string(&json) {
+                Ok(text) => eprintln!("{}", text),
+                Err(_) => eprintln!("Error: {:?}", err),
+            }
+        }
+    }
+}
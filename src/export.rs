@@ -0,0 +1,238 @@
+//! `dothub export`: write the deployed configuration out for other tools.
+//!
+//! `export stow` produces a GNU Stow directory with one package per repo. Each
+//! package mirrors the home directory, so `stow -d <dir> -t ~ <package>`
+//! recreates the same links without dothub. `export home-manager` writes a
+//! Nix module deploying the same files through home-manager.
+
+use crate::output::{self, Tone};
+use crate::state::{DeployMode, RepoSource, State};
+use anyhow::{bail, Context, Result};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::IsTerminal;
+use std::path::Path;
+
+pub fn cmd_export_stow(dir: &Path) -> Result<()> {
+    let st = State::load()?;
+    if st.links.is_empty() {
+        println!("No deployments recorded. Link something with dothub link first.");
+        return Ok(());
+    }
+    let home = dirs::home_dir().context("Unable to determine home directory")?;
+
+    let mut packages: BTreeMap<&str, usize> = BTreeMap::new();
+    let mut skipped = Vec::new();
+    for (target_name, record) in &st.links {
+        let Ok(rel) = record.target.strip_prefix(&home) else {
+            skipped.push(format!("{} (outside your home directory)", target_name));
+            continue;
+        };
+        // What is live right now: the store for links, the target itself for copies
+        let from = match record.mode {
+            DeployMode::Symlink => &record.source,
+            DeployMode::Copy => &record.target,
+        };
+        if !from.exists() {
+            skipped.push(format!("{} ({} is missing)", target_name, from.display()));
+            continue;
+        }
+        let to = dir.join(&record.name).join(rel);
+        if to.exists() || crate::symlink_exists(&to) {
+            bail!(
+                "{} already exists; export into an empty directory",
+                to.display()
+            );
+        }
+        if let Some(parent) = to.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed creating {}", parent.display()))?;
+        }
+        crate::copy_tree(from, &to)?;
+        *packages.entry(&record.name).or_default() += 1;
+    }
+
+    let mut table = output::table(["Package", "Targets"]);
+    for (name, count) in &packages {
+        table.add_row(vec![name.to_string(), count.to_string()]);
+    }
+    println!("{}", table);
+    for s in &skipped {
+        println!("{}", output::paint(&format!("Skipped {}", s), Tone::Warn));
+    }
+    if !packages.is_empty() {
+        let names: Vec<&str> = packages.keys().copied().collect();
+        println!(
+            "Wrote {} stow package(s) to {}. Deploy them with:\n  stow -d {} -t ~ {}",
+            packages.len(),
+            dir.display(),
+            dir.display(),
+            names.join(" ")
+        );
+    }
+    Ok(())
+}
+
+/// `dothub export home-manager`: the current deployments as a home-manager
+/// module. Git repos are pinned with `builtins.fetchGit` at their checked-out
+/// commit, other sources are referenced by path; targets under ~/.config
+/// become `xdg.configFile` entries and the rest `home.file`.
+pub fn cmd_export_home_manager(out: Option<&Path>) -> Result<()> {
+    let st = State::load()?;
+    if st.links.is_empty() {
+        println!("No deployments recorded. Link something with dothub link first.");
+        return Ok(());
+    }
+    let home = dirs::home_dir().context("Unable to determine home directory")?;
+    let config = home.join(".config");
+    let store = crate::dothub_dir();
+
+    let mut repos: BTreeMap<&str, String> = BTreeMap::new();
+    let mut notes = Vec::new();
+    let mut files = Vec::new();
+    for (target_name, record) in &st.links {
+        let (option, rel) = if let Ok(rel) = record.target.strip_prefix(&config) {
+            ("xdg.configFile", rel)
+        } else if let Ok(rel) = record.target.strip_prefix(&home) {
+            ("home.file", rel)
+        } else {
+            notes.push(format!("{} is outside your home directory", target_name));
+            continue;
+        };
+        if !repos.contains_key(record.name.as_str()) {
+            let (expr, note) = repo_expr(&st, &record.name, &store.join(&record.name))?;
+            repos.insert(&record.name, expr);
+            notes.extend(note);
+        }
+        let repo_root = store.join(&record.name);
+        let source = match record.source.strip_prefix(&repo_root) {
+            Ok(sub) if sub.as_os_str().is_empty() => nix_ident(&record.name),
+            Ok(sub) => format!(
+                "\"${{{}}}/{}\"",
+                nix_ident(&record.name),
+                nix_escape(&sub.to_string_lossy())
+            ),
+            Err(_) => nix_path(&record.source),
+        };
+        let recursive = if record.source.is_dir() {
+            "\n    recursive = true;"
+        } else {
+            ""
+        };
+        files.push(format!(
+            "  {}.\"{}\" = {{\n    source = {};{}\n  }};",
+            option,
+            nix_escape(&rel.to_string_lossy()),
+            source,
+            recursive
+        ));
+    }
+
+    let mut text = String::from(
+        "# Generated by `dothub export home-manager`; add it to `imports` in home.nix.\n",
+    );
+    for note in &notes {
+        text.push_str(&format!("# Note: {}\n", note));
+    }
+    text.push_str("{ ... }:\n\nlet\n");
+    for (name, expr) in &repos {
+        text.push_str(&format!("  {} = {};\n", nix_ident(name), expr));
+    }
+    text.push_str("in\n{\n");
+    text.push_str(&files.join("\n"));
+    text.push_str("\n}\n");
+
+    match out {
+        Some(path) => {
+            fs::write(path, &text).with_context(|| format!("Writing {}", path.display()))?;
+            println!(
+                "Wrote {} file(s) from {} repo(s) to {}.",
+                files.len(),
+                repos.len(),
+                path.display()
+            );
+        }
+        None => print!("{}", text),
+    }
+    // The notes are comments in the module too; repeat them unless they were just shown
+    if out.is_some() || !std::io::stdout().is_terminal() {
+        for note in &notes {
+            eprintln!("{}", output::paint(note, Tone::Warn));
+        }
+    }
+    Ok(())
+}
+
+/// Nix expression for the contents of stored repo `name`, and a note when
+/// the expression can't reproduce it exactly.
+fn repo_expr(st: &State, name: &str, path: &Path) -> Result<(String, Option<String>)> {
+    let source = st.repos.get(name).and_then(|r| r.source.clone());
+    match source {
+        Some(RepoSource::Local { path, .. }) => Ok((nix_path(&path), None)),
+        Some(RepoSource::Archive { location }) if location.contains("://") => Ok((
+            format!("builtins.fetchTarball \"{}\"", nix_escape(&location)),
+            None,
+        )),
+        Some(RepoSource::Archive { .. }) => Ok((nix_path(path), None)),
+        _ if crate::git::is_repo(path) => {
+            let url = crate::git::output(path, &["remote", "get-url", "origin"]).ok();
+            let rev = crate::git::head(path);
+            let (Some(url), Some(rev)) = (url, rev) else {
+                return Ok((
+                    nix_path(path),
+                    Some(format!(
+                        "{} has no origin or commit to fetch; referenced by path",
+                        name
+                    )),
+                ));
+            };
+            let dirty = crate::git::status(path).is_ok_and(|s| s.is_dirty());
+            let note = dirty.then(|| {
+                format!(
+                    "{} has uncommitted changes, which fetchGit leaves out",
+                    name
+                )
+            });
+            Ok((
+                format!(
+                    "builtins.fetchGit {{\n    url = \"{}\";\n    rev = \"{}\";\n  }}",
+                    nix_escape(&url),
+                    rev
+                ),
+                note,
+            ))
+        }
+        _ => Ok((nix_path(path), None)),
+    }
+}
+
+/// A repo name as a Nix identifier; names must not start with a digit.
+fn nix_ident(name: &str) -> String {
+    let ident: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if ident.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        ident
+    } else {
+        format!("repo-{}", ident)
+    }
+}
+
+/// Escape text for a double-quoted Nix string.
+fn nix_escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace("${", "\\${")
+}
+
+/// An absolute path as a Nix path value, safe for spaces.
+fn nix_path(path: &Path) -> String {
+    format!("/. + \"{}\"", nix_escape(&path.to_string_lossy()))
+}
@@ -0,0 +1,159 @@
+//! Environment variables that repos ask for, e.g. `STARSHIP_CONFIG`.
+//!
+//! A repo lists them under `[env]` in its dothub.toml, where `{target}` stands
+//! for the deployed path and a leading `~/` for the home directory:
+//!
+//! ```toml
+//! [env]
+//! RIPGREP_CONFIG_PATH = "{target}/ripgreprc"
+//! ```
+//!
+//! They end up in every new shell, so they are the repo's code as much as its
+//! hooks: `dothub trust` decides whether they are exported, and variables
+//! that change what runs (`PATH`, `LD_PRELOAD`, `PROMPT_COMMAND`, ...) never
+//! are. Linking records the expanded values with the deployment and
+//! unlinking drops them. Either way the env files next to the state file are written
+//! again from every deployment, one per shell syntax, and the `shell-init`
+//! integration sources the one for its shell, so new shells see the change.
+
+use crate::manifest::RepoManifest;
+use crate::output::{self, Tone};
+use crate::state::State;
+use crate::Shell;
+use anyhow::{bail, Context, Result};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// The env file for `shell`.
+pub fn path(shell: Shell) -> PathBuf {
+    let name = match shell {
+        Shell::Fish => "env.fish",
+        Shell::PowerShell => "env.ps1",
+        _ => "env.sh",
+    };
+    crate::state::state_path().with_file_name(name)
+}
+
+/// Record the variables `name` declares with the deployment of `target_name`
+/// and rewrite the env files.
+pub fn deploy(name: &str, target_name: &str) -> Result<()> {
+    let Some(manifest) = RepoManifest::load(&crate::dothub_dir().join(name))? else {
+        return Ok(());
+    };
+    if manifest.env.is_empty() {
+        return Ok(());
+    }
+    let mut st = State::load()?;
+    let Some(target) = st.links.get(target_name).map(|rec| rec.target.clone()) else {
+        return Ok(());
+    };
+    let home = crate::owner::home()?;
+    let mut env = BTreeMap::new();
+    for (var, value) in &manifest.env {
+        check_name(var)?;
+        if crate::audit::EXEC_ENV.contains(&var.as_str()) {
+            let msg = format!(
+                "Skipped {} from the [env] of {}: it changes what runs",
+                var, name
+            );
+            println!("{}", output::paint(&msg, Tone::Warn));
+            continue;
+        }
+        let value = value.replace("{target}", &target.to_string_lossy());
+        let value = match value.strip_prefix("~/") {
+            Some(rest) => home.join(rest).to_string_lossy().to_string(),
+            None => value,
+        };
+        env.insert(var.clone(), value);
+    }
+    let shown = env
+        .iter()
+        .map(|(var, value)| format!("export {}={}", var, sh_quote(value)))
+        .collect::<Vec<_>>();
+    if !env.is_empty() && !crate::trust::allow(name, "its [env] exports", &shown.join("\n"))? {
+        env.clear();
+    }
+    if let Some(rec) = st.links.get_mut(target_name) {
+        rec.env = env;
+    }
+    st.save()?;
+    write(&st)
+}
+
+fn check_name(var: &str) -> Result<()> {
+    let valid = var
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && var.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid {
+        bail!("'{}' in [env] is not a valid variable name", var);
+    }
+    Ok(())
+}
+
+/// Write the env files from every deployment in `st`.
+pub fn write(st: &State) -> Result<()> {
+    // Variable to value and the target that set it
+    let mut env: BTreeMap<&str, (&str, &str)> = BTreeMap::new();
+    for (target_name, rec) in &st.links {
+        for (var, value) in &rec.env {
+            if let Some((_, other)) = env.insert(var, (value, target_name.as_str())) {
+                let msg = format!(
+                    "{} is set by both {} and {}; using {}",
+                    var, other, target_name, target_name
+                );
+                println!("{}", output::paint(&msg, Tone::Warn));
+            }
+        }
+    }
+    for shell in [Shell::Bash, Shell::Fish, Shell::PowerShell] {
+        let mut text = String::from("# Written by dothub from the [env] of linked repos\n");
+        for (var, (value, _)) in &env {
+            text.push_str(&match shell {
+                Shell::Fish => format!("set -gx {} {}\n", var, fish_quote(value)),
+                Shell::PowerShell => format!("$env:{} = '{}'\n", var, value.replace('\'', "''")),
+                _ => format!("export {}={}\n", var, sh_quote(value)),
+            });
+        }
+        let path = path(shell);
+        let fs = crate::vfs::fs();
+        if let Some(dir) = path.parent() {
+            fs.create_dir_all(dir)
+                .with_context(|| format!("Failed creating {}", dir.display()))?;
+        }
+        fs.write(&path, text.as_bytes())
+            .with_context(|| format!("Writing {}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// POSIX shells can't escape inside single quotes, so a quote closes them,
+/// adds an escaped quote and reopens them.
+fn sh_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+fn fish_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+/// Source line for the integration script of `shell`.
+pub fn source_line(shell: Shell) -> String {
+    let path = path(shell);
+    let path = path.display();
+    match shell {
+        Shell::Fish => {
+            let path = fish_quote(&path.to_string());
+            format!("test -f {}; and source {}\n", path, path)
+        }
+        Shell::PowerShell => {
+            let path = path.to_string().replace('\'', "''");
+            format!("if (Test-Path '{}') {{ . '{}' }}\n", path, path)
+        }
+        _ => {
+            let path = sh_quote(&path.to_string());
+            format!("[ -f {} ] && . {}\n", path, path)
+        }
+    }
+}
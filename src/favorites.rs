@@ -0,0 +1,46 @@
+//! Local favorites among hub entries, kept in state and unrelated to stars
+//! on the forge. Favorites come first in `dothub` and the dashboard, and
+//! `dothub install --favorites` installs all of them.
+
+use crate::hub::{self, HubEntry};
+use crate::state::State;
+use anyhow::Result;
+use std::collections::BTreeSet;
+
+/// Whether `link` is among `favorites`.
+pub fn is_favorite(favorites: &BTreeSet<String>, link: &str) -> bool {
+    favorites.iter().any(|f| hub::same_link(f, link))
+}
+
+/// Move favorites to the front, keeping the order within each group.
+pub fn sort_first(favorites: &BTreeSet<String>, entries: &mut [HubEntry]) {
+    entries.sort_by_key(|e| !is_favorite(favorites, &e.link));
+}
+
+/// `dothub hub star` and `unstar`.
+pub fn cmd_star(entry: &str, url: Option<&str>, remove: bool) -> Result<()> {
+    let mut st = State::load()?;
+    if remove {
+        // Unstarring needs no index: match the stored links first
+        let before = st.favorites.len();
+        st.favorites.retain(|f| {
+            !(hub::same_link(f, entry) || crate::derive_repo_name(f).eq_ignore_ascii_case(entry))
+        });
+        if st.favorites.len() == before {
+            println!("{} is not a favorite", entry);
+            return Ok(());
+        }
+        st.save()?;
+        println!("Removed {} from your favorites", entry);
+        return Ok(());
+    }
+    let link = hub::resolve_entry(entry, url)?;
+    if is_favorite(&st.favorites, &link) {
+        println!("{} is already a favorite", link);
+        return Ok(());
+    }
+    st.favorites.insert(link.clone());
+    st.save()?;
+    println!("Added {} to your favorites", link);
+    Ok(())
+}
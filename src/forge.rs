@@ -0,0 +1,724 @@
+//! Repository metadata (stars, description, default branch) from the forge
+//! hosting a hub entry.
+//!
+//! The hub needs it for hundreds of entries at once, so `fetch_all` runs the
+//! requests on a tokio runtime's blocking pool, a bounded number at a time,
+//! and keeps the answers on disk for a while.
+
+use crate::git;
+use anyhow::{bail, Context, Result};
+use reqwest::blocking::{Client, RequestBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, clap::ValueEnum)]
+pub enum ForgeKind {
+    #[value(name = "github")]
+    GitHub,
+    #[value(name = "gitlab")]
+    GitLab,
+    /// Gitea and Forgejo instances such as Codeberg
+    #[value(alias = "codeberg")]
+    Gitea,
+    #[value(name = "sourcehut", alias = "srht")]
+    SourceHut,
+}
+
+impl ForgeKind {
+    /// The forge at `host`. `gitlab.*`, `gitea.*` and `forgejo.*` hosts are
+    /// taken for instances of those forges when calling their APIs; whether
+    /// they are sent a token is up to `token_for`.
+    pub fn from_host(host: &str) -> Option<ForgeKind> {
+        if let Some(kind) = configured_hosts().get(&host.to_lowercase()) {
+            return Some(*kind);
+        }
+        match host {
+            "github.com" => Some(ForgeKind::GitHub),
+            "gitlab.com" => Some(ForgeKind::GitLab),
+            "codeberg.org" => Some(ForgeKind::Gitea),
+            "git.sr.ht" => Some(ForgeKind::SourceHut),
+            h if h.starts_with("gitlab.") => Some(ForgeKind::GitLab),
+            h if h.starts_with("gitea.") || h.starts_with("forgejo.") => Some(ForgeKind::Gitea),
+            _ => None,
+        }
+    }
+
+    /// Environment variable holding an API token for this forge.
+    pub fn token_var(self) -> &'static str {
+        match self {
+            ForgeKind::GitHub => "GITHUB_TOKEN",
+            ForgeKind::GitLab => "GITLAB_TOKEN",
+            ForgeKind::Gitea => "CODEBERG_TOKEN",
+            ForgeKind::SourceHut => "SRHT_TOKEN",
+        }
+    }
+
+    /// Name used on the command line and in the keychain.
+    pub fn name(self) -> &'static str {
+        match self {
+            ForgeKind::GitHub => "github",
+            ForgeKind::GitLab => "gitlab",
+            ForgeKind::Gitea => "gitea",
+            ForgeKind::SourceHut => "sourcehut",
+        }
+    }
+
+    /// The host the forge's tokens are issued for.
+    pub fn canonical_host(self) -> &'static str {
+        match self {
+            ForgeKind::GitHub => "github.com",
+            ForgeKind::GitLab => "gitlab.com",
+            ForgeKind::Gitea => "codeberg.org",
+            ForgeKind::SourceHut => "git.sr.ht",
+        }
+    }
+
+    /// API token from the environment, or else from `dothub auth login`.
+    pub fn token(self) -> Option<String> {
+        env::var(self.token_var())
+            .ok()
+            .filter(|t| !t.is_empty())
+            .or_else(|| crate::auth::stored_token(self))
+    }
+
+    /// The token, if it may be sent to `host`: the forge's own host, or one
+    /// listed for it under `[auth.hosts]` in the config.
+    pub fn token_for(self, host: &str) -> Option<String> {
+        let allowed = host.eq_ignore_ascii_case(self.canonical_host())
+            || configured_hosts().get(&host.to_lowercase()) == Some(&self);
+        if allowed {
+            self.token()
+        } else {
+            None
+        }
+    }
+}
+
+/// `[auth.hosts]` from the config, read once.
+fn configured_hosts() -> &'static HashMap<String, ForgeKind> {
+    static HOSTS: OnceLock<HashMap<String, ForgeKind>> = OnceLock::new();
+    HOSTS.get_or_init(|| {
+        let Ok(config) = crate::config::Config::load() else {
+            return HashMap::new();
+        };
+        config
+            .auth
+            .hosts
+            .into_iter()
+            .filter_map(|(host, forge)| {
+                let kind = <ForgeKind as clap::ValueEnum>::from_str(&forge, true).ok()?;
+                Some((host.to_lowercase(), kind))
+            })
+            .collect()
+    })
+}
+
+/// A repository on a known forge.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RepoRef {
+    pub kind: ForgeKind,
+    pub host: String,
+    /// Owner path; GitLab owners may include subgroups ("group/sub")
+    pub owner: String,
+    pub name: String,
+}
+
+impl RepoRef {
+    /// Parse https and scp-style (git@host:owner/repo.git) URLs.
+    pub fn parse(link: &str) -> Option<RepoRef> {
+        let (host, path) = match url::Url::parse(link) {
+            Ok(parsed) if parsed.host_str().is_some() => {
+                (parsed.host_str()?.to_lowercase(), parsed.path().to_string())
+            }
+            _ => {
+                let rest = link.split_once('@').map(|(_, r)| r).unwrap_or(link);
+                let (host, path) = rest.split_once(':')?;
+                (host.to_lowercase(), path.to_string())
+            }
+        };
+        let kind = ForgeKind::from_host(&host)?;
+        let path = path.trim_matches('/').trim_end_matches(".git");
+        let segs: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+        let (owner, name) = match segs.as_slice() {
+            [] => return None,
+            // Heuristic: a bare owner URL refers to the owner/owner repository
+            [owner] => (owner.to_string(), owner.to_string()),
+            [owner, name] => (owner.to_string(), name.to_string()),
+            [rest @ .., name] if kind == ForgeKind::GitLab => (rest.join("/"), name.to_string()),
+            [owner, name, ..] => (owner.to_string(), name.to_string()),
+        };
+        Some(RepoRef {
+            kind,
+            host,
+            owner,
+            name,
+        })
+    }
+
+    /// The same repo however its link was spelled; forges ignore the case
+    /// of owners and names.
+    fn coalesced(&self) -> RepoRef {
+        RepoRef {
+            owner: self.owner.to_lowercase(),
+            name: self.name.to_lowercase(),
+            ..self.clone()
+        }
+    }
+
+    pub fn web_url(&self) -> String {
+        format!("https://{}/{}/{}", self.host, self.owner, self.name)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RepoMeta {
+    /// Stars (GitHub, GitLab, Gitea); sourcehut has no stars and reports 0
+    pub stars: u64,
+    pub description: Option<String>,
+    pub default_branch: Option<String>,
+}
+
+/// Signs of maintenance for `dothub hub reviews`. Times are unix seconds;
+/// None means the forge doesn't say.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RepoHealth {
+    /// Open issues; GitHub counts open pull requests too
+    pub open_issues: Option<u64>,
+    pub last_push: Option<u64>,
+    pub archived: bool,
+    /// Tag and publish time of the newest release
+    pub last_release: Option<(String, u64)>,
+    /// Number of discussions and when the latest one was active; GitHub only,
+    /// and only with GITHUB_TOKEN
+    pub discussions: Option<(u64, Option<u64>)>,
+}
+
+/// Metadata for every link, keyed by link. Links on unknown forges are skipped.
+pub struct MetaResults {
+    pub meta: HashMap<String, RepoMeta>,
+    /// GITHUB_TOKEN was set but the GraphQL batch failed and REST was used instead
+    pub graphql_failed: bool,
+}
+
+/// Last known metadata per link, so hub views work offline.
+#[derive(Default, Serialize, Deserialize)]
+struct MetaCache {
+    entries: HashMap<String, CachedMeta>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedMeta {
+    meta: RepoMeta,
+    fetched_at: u64,
+}
+
+fn meta_cache_path() -> PathBuf {
+    crate::http::cache_dir().join("meta.json")
+}
+
+fn load_meta_cache() -> MetaCache {
+    fs::read_to_string(meta_cache_path())
+        .ok()
+        .and_then(|t| serde_json::from_str(&t).ok())
+        .unwrap_or_default()
+}
+
+fn save_meta_cache(fresh: &HashMap<String, RepoMeta>) {
+    let mut cache = load_meta_cache();
+    let now = crate::state::now_unix();
+    for (link, meta) in fresh {
+        cache.entries.insert(
+            link.clone(),
+            CachedMeta {
+                meta: meta.clone(),
+                fetched_at: now,
+            },
+        );
+    }
+    let path = meta_cache_path();
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    if let Ok(text) = serde_json::to_string(&cache) {
+        let _ = fs::write(path, text);
+    }
+}
+
+fn cached_meta(links: &[String]) -> HashMap<String, RepoMeta> {
+    let cache = load_meta_cache();
+    let mut out = HashMap::new();
+    for link in links {
+        if let Some(c) = cache.entries.get(link) {
+            crate::http::note_cached(c.fetched_at);
+            out.insert(link.clone(), c.meta.clone());
+        }
+    }
+    out
+}
+
+/// Cached metadata younger than this is used without asking the forge.
+const META_FRESH_SECS: u64 = 15 * 60;
+
+/// Requests to forges in flight at once.
+const MAX_IN_FLIGHT: usize = 16;
+
+/// One request's worth of work for the enrichment pipeline.
+enum Job {
+    /// Up to 50 GitHub repos in a single GraphQL query
+    GraphQl(Vec<RepoRef>),
+    Rest(RepoRef),
+}
+
+enum Done {
+    Meta(Vec<(RepoRef, RepoMeta)>),
+    /// A GraphQL batch failed; its repos are fetched over REST instead
+    GraphQlFailed(Vec<RepoRef>),
+}
+
+/// Metadata for every link, from the cache when fresh and otherwise fetched
+/// concurrently. Links naming the same repo share one request.
+pub fn fetch_all(links: &[String]) -> Result<MetaResults> {
+    if crate::http::is_offline() {
+        return Ok(MetaResults {
+            meta: cached_meta(links),
+            graphql_failed: false,
+        });
+    }
+
+    let cache = load_meta_cache();
+    let now = crate::state::now_unix();
+    let mut meta = HashMap::new();
+    let mut wanted: HashMap<RepoRef, Vec<String>> = HashMap::new();
+    for link in links {
+        match cache.entries.get(link) {
+            Some(c) if now.saturating_sub(c.fetched_at) < META_FRESH_SECS => {
+                meta.insert(link.clone(), c.meta.clone());
+            }
+            _ => {
+                if let Some(r) = RepoRef::parse(link) {
+                    wanted.entry(r.coalesced()).or_default().push(link.clone());
+                }
+            }
+        }
+    }
+    if wanted.is_empty() {
+        return Ok(MetaResults {
+            meta,
+            graphql_failed: false,
+        });
+    }
+
+    let (github, others): (Vec<RepoRef>, Vec<RepoRef>) = wanted
+        .keys()
+        .cloned()
+        .partition(|r| r.kind == ForgeKind::GitHub);
+    let mut jobs: Vec<Job> = others.into_iter().map(Job::Rest).collect();
+    match ForgeKind::GitHub.token() {
+        Some(_) => jobs.extend(github.chunks(50).map(|c| Job::GraphQl(c.to_vec()))),
+        None => jobs.extend(github.into_iter().map(Job::Rest)),
+    }
+
+    let client = crate::http::client()?;
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .context("Starting the hub metadata pipeline")?;
+    let mut graphql_failed = false;
+    let mut fetched: HashMap<RepoRef, RepoMeta> = HashMap::new();
+    while !jobs.is_empty() {
+        let mut retry = Vec::new();
+        for done in runtime.block_on(run_jobs(&client, std::mem::take(&mut jobs))) {
+            match done {
+                Done::Meta(found) => fetched.extend(found),
+                Done::GraphQlFailed(repos) => {
+                    graphql_failed = true;
+                    retry.extend(repos.into_iter().map(Job::Rest));
+                }
+            }
+        }
+        jobs = retry;
+    }
+
+    let mut fresh = HashMap::new();
+    for (r, m) in fetched {
+        for link in wanted.remove(&r).unwrap_or_default() {
+            fresh.insert(link, m.clone());
+        }
+    }
+    save_meta_cache(&fresh);
+    meta.extend(fresh);
+    // Whatever couldn't be fetched falls back to what was last known
+    let missing: Vec<String> = wanted.into_values().flatten().collect();
+    meta.extend(cached_meta(&missing));
+    Ok(MetaResults {
+        meta,
+        graphql_failed,
+    })
+}
+
+/// Run `jobs` on blocking threads, at most MAX_IN_FLIGHT at a time.
+async fn run_jobs(client: &Client, jobs: Vec<Job>) -> Vec<Done> {
+    let permits = Arc::new(Semaphore::new(MAX_IN_FLIGHT));
+    let mut tasks = JoinSet::new();
+    for job in jobs {
+        let Ok(permit) = permits.clone().acquire_owned().await else {
+            break;
+        };
+        let client = client.clone();
+        tasks.spawn_blocking(move || {
+            let _permit = permit;
+            match job {
+                Job::GraphQl(repos) => {
+                    let token = ForgeKind::GitHub.token().unwrap_or_default();
+                    match github_graphql(&client, &repos, &token) {
+                        Ok(found) => Done::Meta(found),
+                        Err(_) => Done::GraphQlFailed(repos),
+                    }
+                }
+                Job::Rest(r) => {
+                    let found = fetch_meta(&client, &r).ok().map(|m| (r, m));
+                    Done::Meta(found.into_iter().collect())
+                }
+            }
+        });
+    }
+    let mut out = Vec::new();
+    while let Some(done) = tasks.join_next().await {
+        if let Ok(done) = done {
+            out.push(done);
+        }
+    }
+    out
+}
+
+pub fn fetch_meta(client: &Client, r: &RepoRef) -> Result<RepoMeta> {
+    match r.kind {
+        ForgeKind::GitHub => {
+            let api = format!("https://api.github.com/repos/{}/{}", r.owner, r.name);
+            let v = get_json(authorize(client.get(&api), r.kind), &api)?;
+            Ok(RepoMeta {
+                stars: v.get("stargazers_count").and_then(|n| n.as_u64()).unwrap_or(0),
+                description: json_str(&v, "description"),
+                default_branch: json_str(&v, "default_branch"),
+            })
+        }
+        ForgeKind::GitLab => {
+            let id = format!("{}/{}", r.owner, r.name).replace('/', "%2F");
+            let api = format!("https://{}/api/v4/projects/{}", r.host, id);
+            let req = match r.kind.token_for(&r.host) {
+                Some(t) => client.get(&api).header("PRIVATE-TOKEN", t),
+                None => client.get(&api),
+            };
+            let v = get_json(req, &api)?;
+            Ok(RepoMeta {
+                stars: v.get("star_count").and_then(|n| n.as_u64()).unwrap_or(0),
+                description: json_str(&v, "description"),
+                default_branch: json_str(&v, "default_branch"),
+            })
+        }
+        ForgeKind::Gitea => {
+            let api = format!("https://{}/api/v1/repos/{}/{}", r.host, r.owner, r.name);
+            let req = match r.kind.token_for(&r.host) {
+                Some(t) => client.get(&api).header("Authorization", format!("token {}", t)),
+                None => client.get(&api),
+            };
+            let v = get_json(req, &api)?;
+            Ok(RepoMeta {
+                stars: v.get("stars_count").and_then(|n| n.as_u64()).unwrap_or(0),
+                description: json_str(&v, "description"),
+                default_branch: json_str(&v, "default_branch"),
+            })
+        }
+        ForgeKind::SourceHut => sourcehut_meta(client, r),
+    }
+}
+
+/// Maintenance signals for the repo behind `link`.
+pub fn fetch_health(client: &Client, link: &str) -> Result<RepoHealth> {
+    let r = RepoRef::parse(link).with_context(|| format!("Unsupported forge: {}", link))?;
+    let mut out = RepoHealth::default();
+    match r.kind {
+        ForgeKind::GitHub => {
+            let api = format!("https://api.github.com/repos/{}/{}", r.owner, r.name);
+            let v = get_json(authorize(client.get(&api), r.kind), &api)?;
+            out.open_issues = v.get("open_issues_count").and_then(|n| n.as_u64());
+            out.last_push = json_time(&v, "pushed_at");
+            out.archived = v.get("archived").and_then(|a| a.as_bool()).unwrap_or(false);
+            let releases = format!("{}/releases?per_page=1", api);
+            // A repo without releases answers with an empty list
+            if let Ok(v) = get_json(authorize(client.get(&releases), r.kind), &releases) {
+                out.last_release = latest_release(&v, "tag_name", "published_at");
+            }
+            if let Some(token) = r.kind.token() {
+                out.discussions = github_discussions(client, &r, &token).ok().flatten();
+            }
+        }
+        ForgeKind::GitLab => {
+            let id = format!("{}/{}", r.owner, r.name).replace('/', "%2F");
+            let api = format!("https://{}/api/v4/projects/{}", r.host, id);
+            let v = get_json(authorize_raw(client.get(&api), &r), &api)?;
+            out.open_issues = v.get("open_issues_count").and_then(|n| n.as_u64());
+            out.last_push = json_time(&v, "last_activity_at");
+            out.archived = v.get("archived").and_then(|a| a.as_bool()).unwrap_or(false);
+            let releases = format!("{}/releases?per_page=1", api);
+            if let Ok(v) = get_json(authorize_raw(client.get(&releases), &r), &releases) {
+                out.last_release = latest_release(&v, "tag_name", "released_at");
+            }
+        }
+        ForgeKind::Gitea => {
+            let api = format!("https://{}/api/v1/repos/{}/{}", r.host, r.owner, r.name);
+            let v = get_json(authorize_raw(client.get(&api), &r), &api)?;
+            out.open_issues = v.get("open_issues_count").and_then(|n| n.as_u64());
+            out.last_push = json_time(&v, "updated_at");
+            out.archived = v.get("archived").and_then(|a| a.as_bool()).unwrap_or(false);
+            let releases = format!("{}/releases?limit=1", api);
+            if let Ok(v) = get_json(authorize_raw(client.get(&releases), &r), &releases) {
+                out.last_release = latest_release(&v, "tag_name", "published_at");
+            }
+        }
+        ForgeKind::SourceHut => bail!("sourcehut has no issue or release API dothub can read"),
+    }
+    Ok(out)
+}
+
+fn latest_release(v: &serde_json::Value, tag: &str, date: &str) -> Option<(String, u64)> {
+    let first = v.as_array()?.first()?;
+    Some((json_str(first, tag)?, json_time(first, date)?))
+}
+
+fn github_discussions(client: &Client, r: &RepoRef, token: &str) -> Result<Option<(u64, Option<u64>)>> {
+    let query = format!(
+        "query {{ repository(owner: \"{}\", name: \"{}\") {{ hasDiscussionsEnabled \
+         discussions(first: 1, orderBy: {{field: UPDATED_AT, direction: DESC}}) {{ totalCount nodes {{ updatedAt }} }} }} }}",
+        gql_escape(&r.owner),
+        gql_escape(&r.name)
+    );
+    let api = "https://api.github.com/graphql";
+    let req = client
+        .post(api)
+        .bearer_auth(token)
+        .json(&serde_json::json!({ "query": query }));
+    let v = post_json(req, api)?;
+    let Some(repo) = v.pointer("/data/repository") else {
+        return Ok(None);
+    };
+    if repo.get("hasDiscussionsEnabled").and_then(|d| d.as_bool()) != Some(true) {
+        return Ok(None);
+    }
+    let total = repo
+        .pointer("/discussions/totalCount")
+        .and_then(|n| n.as_u64())
+        .unwrap_or(0);
+    let last = repo
+        .pointer("/discussions/nodes/0")
+        .and_then(|n| json_time(n, "updatedAt"));
+    Ok(Some((total, last)))
+}
+
+/// sourcehut's API always requires a token; without one only the default
+/// branch can be resolved (via git itself).
+fn sourcehut_meta(client: &Client, r: &RepoRef) -> Result<RepoMeta> {
+    let mut out = RepoMeta::default();
+    if let Some(token) = ForgeKind::SourceHut.token() {
+        let user = r.owner.trim_start_matches('~');
+        let query = format!(
+            "query {{ user(username: \"{}\") {{ repository(name: \"{}\") {{ description HEAD {{ name }} }} }} }}",
+            gql_escape(user),
+            gql_escape(&r.name)
+        );
+        let api = "https://git.sr.ht/query";
+        let req = client
+            .post(api)
+            .bearer_auth(token)
+            .json(&serde_json::json!({ "query": query }));
+        let v = post_json(req, api)?;
+        let repo = v.pointer("/data/user/repository");
+        out.description = repo.and_then(|r| json_str(r, "description"));
+        out.default_branch = repo
+            .and_then(|r| r.pointer("/HEAD/name"))
+            .and_then(|n| n.as_str())
+            .map(|n| n.trim_start_matches("refs/heads/").to_string());
+    }
+    if out.default_branch.is_none() {
+        out.default_branch = remote_default_branch(&r.web_url());
+    }
+    Ok(out)
+}
+
+/// Ask the remote which branch HEAD points at. Works on any git host.
+pub fn remote_default_branch(url: &str) -> Option<String> {
+    if crate::http::is_offline() {
+        return None;
+    }
+    let out = git::network(Path::new("."), &["ls-remote", "--symref", url, "HEAD"], false).ok()?;
+    out.lines()
+        .find_map(|l| l.strip_prefix("ref: refs/heads/"))
+        .and_then(|l| l.split_whitespace().next())
+        .map(|b| b.to_string())
+}
+
+/// README file names tried in order when fetching a repo's README.
+const README_NAMES: &[&str] = &["README.md", "readme.md", "Readme.md", "README"];
+
+/// Fetch the README of a repository through the HTTP cache. `branch` is the
+/// default branch from the forge metadata, if known.
+pub fn fetch_readme(link: &str, branch: Option<&str>) -> Result<String> {
+    let r = RepoRef::parse(link).with_context(|| format!("Unsupported forge: {}", link))?;
+    let branch = match branch {
+        Some(b) => b.to_string(),
+        None => remote_default_branch(&r.web_url()).unwrap_or_else(|| "HEAD".to_string()),
+    };
+    let client = crate::http::client()?;
+    let mut last_err = None;
+    for file in README_NAMES {
+        let url = raw_url(&r, &branch, file);
+        match crate::http::get_cached(authorize_raw(client.get(&url), &r), &url) {
+            Ok(text) => return Ok(text),
+            Err(e) => last_err = Some(e),
+        }
+    }
+    Err(last_err.unwrap()).with_context(|| format!("No README found for {}", link))
+}
+
+fn raw_url(r: &RepoRef, branch: &str, file: &str) -> String {
+    match r.kind {
+        ForgeKind::GitHub => format!(
+            "https://raw.githubusercontent.com/{}/{}/{}/{}",
+            r.owner, r.name, branch, file
+        ),
+        ForgeKind::GitLab => format!("{}/-/raw/{}/{}", r.web_url(), branch, file),
+        ForgeKind::Gitea => format!("{}/raw/branch/{}/{}", r.web_url(), branch, file),
+        ForgeKind::SourceHut => format!("{}/blob/{}/{}", r.web_url(), branch, file),
+    }
+}
+
+/// Send the forge token with raw file requests so private READMEs can be read.
+fn authorize_raw(req: RequestBuilder, r: &RepoRef) -> RequestBuilder {
+    match r.kind {
+        ForgeKind::GitHub => authorize(req, r.kind),
+        ForgeKind::GitLab => match r.kind.token_for(&r.host) {
+            Some(t) => req.header("PRIVATE-TOKEN", t),
+            None => req,
+        },
+        ForgeKind::Gitea => match r.kind.token_for(&r.host) {
+            Some(t) => req.header("Authorization", format!("token {}", t)),
+            None => req,
+        },
+        ForgeKind::SourceHut => req,
+    }
+}
+
+/// Stars, description and default branch of up to 50 GitHub repos in one query.
+fn github_graphql(
+    client: &Client,
+    repos: &[RepoRef],
+    token: &str,
+) -> Result<Vec<(RepoRef, RepoMeta)>> {
+    let mut q = String::from("query { ");
+    for (i, r) in repos.iter().enumerate() {
+        q.push_str(&format!(
+            "r{}: repository(owner:\"{}\", name:\"{}\") {{ stargazerCount description defaultBranchRef {{ name }} }} ",
+            i,
+            gql_escape(&r.owner),
+            gql_escape(&r.name)
+        ));
+    }
+    q.push('}');
+
+    let _span = crate::perf::span(crate::perf::Phase::Network, || "api.github.com".to_string());
+    let resp = client
+        .post("https://api.github.com/graphql")
+        .json(&serde_json::json!({ "query": q }))
+        .header("Authorization", format!("Bearer {}", token))
+        .send()
+        .context("graphql request failed")?;
+    if !resp.status().is_success() {
+        bail!("graphql status {}", resp.status());
+    }
+    let v: serde_json::Value = resp.json().context("parse graphql json")?;
+    let mut out = Vec::new();
+    if let Some(data) = v.get("data").and_then(|d| d.as_object()) {
+        for (i, r) in repos.iter().enumerate() {
+            let Some(repo) = data.get(&format!("r{}", i)) else {
+                continue;
+            };
+            out.push((
+                r.clone(),
+                RepoMeta {
+                    stars: repo.get("stargazerCount").and_then(|n| n.as_u64()).unwrap_or(0),
+                    description: json_str(repo, "description"),
+                    default_branch: repo
+                        .pointer("/defaultBranchRef/name")
+                        .and_then(|n| n.as_str())
+                        .map(|s| s.to_string()),
+                },
+            ));
+        }
+    }
+    Ok(out)
+}
+
+fn authorize(req: RequestBuilder, kind: ForgeKind) -> RequestBuilder {
+    match kind.token() {
+        Some(t) => req.bearer_auth(t),
+        None => req,
+    }
+}
+
+fn get_json(req: RequestBuilder, url: &str) -> Result<serde_json::Value> {
+    let body = crate::http::get_cached(req, url)?;
+    serde_json::from_str(&body).with_context(|| format!("parsing json from {}", url))
+}
+
+fn post_json(req: RequestBuilder, url: &str) -> Result<serde_json::Value> {
+    let _span = crate::perf::span(crate::perf::Phase::Network, || crate::perf::host_subject(url));
+    let resp = req.send().with_context(|| format!("POST {}", url))?;
+    if !resp.status().is_success() {
+        bail!("HTTP {} for {}", resp.status(), url);
+    }
+    resp.json().with_context(|| format!("parsing json from {}", url))
+}
+
+fn json_str(v: &serde_json::Value, key: &str) -> Option<String> {
+    v.get(key)
+        .and_then(|s| s.as_str())
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+}
+
+/// An RFC 3339 timestamp field ("2024-05-01T12:00:00Z") as unix seconds.
+fn json_time(v: &serde_json::Value, key: &str) -> Option<u64> {
+    let dt: toml::value::Datetime = v.get(key)?.as_str()?.parse().ok()?;
+    let date = dt.date?;
+    let time = dt.time.unwrap_or(toml::value::Time {
+        hour: 0,
+        minute: 0,
+        second: 0,
+        nanosecond: 0,
+    });
+    // Days since the epoch for a proleptic Gregorian date (Howard Hinnant's algorithm)
+    let (y, m, d) = (date.year as i64, date.month as i64, date.day as i64);
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+    let offset = match dt.offset {
+        Some(toml::value::Offset::Custom { minutes }) => minutes as i64 * 60,
+        _ => 0,
+    };
+    let secs = days * 86400 + time.hour as i64 * 3600 + time.minute as i64 * 60 + time.second as i64
+        - offset;
+    u64::try_from(secs).ok()
+}
+
+fn gql_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -0,0 +1,233 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::io::{IsTerminal, Read, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static NO_SIGN: AtomicBool = AtomicBool::new(false);
+
+/// Commit unsigned for the rest of the run (`--no-sign`), whatever
+/// commit.gpgsign says.
+pub fn disable_signing() {
+    NO_SIGN.store(true, Ordering::SeqCst);
+}
+
+pub fn ensure_git() -> Result<()> {
+    if which::which("git").is_err() {
+        bail!(crate::error::Error::new(
+            crate::error::Code::GitMissing,
+            "git is not installed or not found in PATH"
+        )
+        .hint("install git with your package manager, e.g. sudo apt-get install git or brew install git"));
+    }
+    Ok(())
+}
+
+/// Run git in `repo` and return trimmed stdout, failing on a non-zero exit.
+pub fn output(repo: &Path, args: &[&str]) -> Result<String> {
+    let _span = crate::perf::span(crate::perf::Phase::Git, || match args {
+        // Clones run outside the repo; name them after the destination
+        ["clone", .., dest] => crate::perf::repo_subject(Path::new(dest)),
+        _ => crate::perf::repo_subject(repo),
+    });
+    let out = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(args)
+        .output()
+        .with_context(|| format!("Running git {} in {}", args.join(" "), repo.display()))?;
+    if !out.status.success() {
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        bail!(
+            "git {} failed in {}: {}",
+            args.join(" "),
+            repo.display(),
+            stderr.trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).trim().to_string())
+}
+
+/// Run a git command that talks to a remote in `repo`, retrying transient
+/// failures and keeping to the bandwidth cap. With `progress`, git's progress
+/// goes to the terminal as it runs.
+pub fn network(repo: &Path, args: &[&str], progress: bool) -> Result<String> {
+    let _span = crate::perf::span(crate::perf::Phase::Git, || match args {
+        ["clone", .., dest] => crate::perf::repo_subject(Path::new(dest)),
+        _ => crate::perf::repo_subject(repo),
+    });
+    let what = format!("git {}", args.first().unwrap_or(&""));
+    crate::network::retry(&what, || {
+        let (program, prefix) = crate::network::git_program();
+        let (config, no_proxy) = crate::network::git_config()?;
+        let mut cmd = Command::new(&program);
+        cmd.args(&prefix)
+            .args(&config)
+            .args(crate::auth::git_config())
+            .arg("-C")
+            .arg(repo);
+        if let Some(no_proxy) = no_proxy {
+            cmd.env("NO_PROXY", &no_proxy).env("no_proxy", &no_proxy);
+        }
+        match args.split_first() {
+            Some((sub, rest)) if progress && std::io::stderr().is_terminal() => {
+                cmd.arg(sub).arg("--progress").args(rest);
+            }
+            _ => {
+                cmd.args(args);
+            }
+        }
+        let (status, stdout, stderr) = if progress {
+            let mut child = cmd
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .spawn()
+                .with_context(|| format!("Running git {} in {}", args.join(" "), repo.display()))?;
+            // Show progress while keeping it to tell what went wrong
+            let mut pipe = child.stderr.take().context("git stderr")?;
+            let tee = std::thread::spawn(move || {
+                let mut seen = Vec::new();
+                let mut buf = [0; 4096];
+                while let Ok(n) = pipe.read(&mut buf) {
+                    if n == 0 {
+                        break;
+                    }
+                    let _ = std::io::stderr().write_all(&buf[..n]);
+                    seen.extend_from_slice(&buf[..n]);
+                }
+                seen
+            });
+            let out = child.wait_with_output().context("Waiting for git")?;
+            (out.status, out.stdout, tee.join().unwrap_or_default())
+        } else {
+            let out = cmd
+                .output()
+                .with_context(|| format!("Running git {} in {}", args.join(" "), repo.display()))?;
+            (out.status, out.stdout, out.stderr)
+        };
+        if !status.success() {
+            let stderr = String::from_utf8_lossy(&stderr);
+            // With progress the output was already shown; repeat only its last line
+            let detail = match progress {
+                true => stderr.lines().rfind(|l| !l.trim().is_empty()).unwrap_or(""),
+                false => stderr.trim(),
+            };
+            let msg = format!(
+                "git {} failed in {}: {}",
+                args.join(" "),
+                repo.display(),
+                detail.trim()
+            );
+            if crate::network::git_transient(&stderr) {
+                bail!(crate::network::Transient(msg));
+            }
+            bail!(msg);
+        }
+        Ok(String::from_utf8_lossy(&stdout).trim().to_string())
+    })
+}
+
+pub fn is_repo(path: &Path) -> bool {
+    path.join(".git").exists()
+}
+
+/// True when the current branch tracks a remote branch.
+pub fn has_upstream(repo: &Path) -> bool {
+    output(repo, &["rev-parse", "--abbrev-ref", "@{upstream}"]).is_ok()
+}
+
+/// Commit what is staged in `repo`. git signs it when the user's config says
+/// to (commit.gpgsign, with a GPG or SSH key per gpg.format), unless signing
+/// was disabled; a key that can't be used fails with E019 rather than a bare
+/// git error.
+pub fn commit(repo: &Path, message: &str) -> Result<()> {
+    let mut args = vec!["commit", "-q", "-m", message];
+    if NO_SIGN.load(Ordering::SeqCst) {
+        args.push("--no-gpg-sign");
+    }
+    let Err(e) = output(repo, &args) else {
+        return Ok(());
+    };
+    if !signing_failed(&e.to_string()) {
+        return Err(e);
+    }
+    let hint = match output(repo, &["config", "gpg.format"]).as_deref() {
+        Ok("ssh") => "load the key with ssh-add (or check user.signingkey), or pass --no-sign to commit unsigned",
+        _ => "unlock the key in gpg-agent (or check user.signingkey), or pass --no-sign to commit unsigned",
+    };
+    bail!(crate::error::Error::new(
+        crate::error::Code::SigningFailed,
+        format!(
+            "git is set to sign commits in {}, but the signing key could not be used",
+            repo.display()
+        )
+    )
+    .hint(hint))
+}
+
+/// Whether git's stderr says a commit failed because it couldn't be signed.
+fn signing_failed(stderr: &str) -> bool {
+    const SIGNS: &[&str] = &[
+        "gpg failed to sign",
+        "failed to sign the data",
+        "no secret key",
+        "error: load key",
+        "couldn't load public key",
+        "ssh-keygen",
+        "signing failed",
+    ];
+    let stderr = stderr.to_lowercase();
+    SIGNS.iter().any(|s| stderr.contains(s))
+}
+
+pub fn head(repo: &Path) -> Option<String> {
+    output(repo, &["rev-parse", "HEAD"]).ok()
+}
+
+/// Working tree summary as reported by `git status --porcelain=v2 --branch`.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct RepoStatus {
+    pub branch: String,
+    /// Number of changed or untracked paths
+    pub changes: usize,
+    /// Commits ahead/behind the upstream, None when no upstream is configured
+    pub ahead_behind: Option<(u64, u64)>,
+    /// Files stored with Git LFS that are still pointer files
+    #[serde(default)]
+    pub lfs_pointers: usize,
+}
+
+impl RepoStatus {
+    pub fn is_dirty(&self) -> bool {
+        self.changes > 0
+    }
+}
+
+pub fn status(repo: &Path) -> Result<RepoStatus> {
+    // Without optional locks git does not rewrite the index, which would wake the daemon's watcher
+    let text = output(
+        repo,
+        &["--no-optional-locks", "status", "--porcelain=v2", "--branch"],
+    )?;
+    let mut st = RepoStatus::default();
+    for line in text.lines() {
+        if let Some(head) = line.strip_prefix("# branch.head ") {
+            st.branch = head.to_string();
+        } else if let Some(ab) = line.strip_prefix("# branch.ab ") {
+            let mut parts = ab.split_whitespace();
+            let ahead = parts
+                .next()
+                .and_then(|a| a.trim_start_matches('+').parse().ok())
+                .unwrap_or(0);
+            let behind = parts
+                .next()
+                .and_then(|b| b.trim_start_matches('-').parse().ok())
+                .unwrap_or(0);
+            st.ahead_behind = Some((ahead, behind));
+        } else if !line.starts_with('#') && !line.is_empty() {
+            st.changes += 1;
+        }
+    }
+    Ok(st)
+}
@@ -0,0 +1,121 @@
+//! `dothub grep`: search every stored repo at once.
+//!
+//! Matching runs through `git grep`, so files ignored by a repo's .gitignore
+//! are skipped, in git repos and plain directories alike. Each match names
+//! the target it is deployed as, if any, so a setting found in an unlinked
+//! repo is not mistaken for the one in use. The dashboard's search uses the
+//! same function.
+
+use crate::output::{self, Tone};
+use crate::state::State;
+use anyhow::{bail, Context, Result};
+use comfy_table::Cell;
+use std::path::Path;
+use std::process::Command;
+
+pub struct Match {
+    pub repo: String,
+    /// Path relative to the repo
+    pub file: String,
+    pub line: u64,
+    pub text: String,
+    /// Target the file is deployed through, if it is
+    pub target: Option<String>,
+}
+
+/// Lines matching the extended regex `pattern` in the repos with one of
+/// `tags` (all repos when empty).
+pub fn search(pattern: &str, ignore_case: bool, tags: &[String]) -> Result<Vec<Match>> {
+    let st = State::load()?;
+    let repos = crate::tags::filter(&st, crate::installed_repos()?, tags);
+    let mut matches = Vec::new();
+    for (name, path) in repos {
+        for (file, line, text) in grep_repo(&path, pattern, ignore_case)? {
+            let full = path.join(&file);
+            let target = st
+                .links
+                .iter()
+                .find(|(_, rec)| rec.name == name && full.starts_with(&rec.source))
+                .map(|(target, _)| target.clone());
+            matches.push(Match {
+                repo: name.clone(),
+                file,
+                line,
+                text,
+                target,
+            });
+        }
+    }
+    Ok(matches)
+}
+
+/// (file, line, text) of each match in one repo.
+fn grep_repo(path: &Path, pattern: &str, ignore_case: bool) -> Result<Vec<(String, u64, String)>> {
+    let mut cmd = Command::new("git");
+    cmd.arg("-C").arg(path).arg("grep");
+    if crate::git::is_repo(path) {
+        cmd.arg("--untracked");
+    } else {
+        cmd.arg("--no-index");
+    }
+    cmd.args(["--exclude-standard", "-n", "-I", "-z", "-E"]);
+    if ignore_case {
+        cmd.arg("-i");
+    }
+    let out = cmd
+        .arg("-e")
+        .arg(pattern)
+        .output()
+        .context("Failed to spawn git grep")?;
+    match out.status.code() {
+        Some(0) => {}
+        // No matches
+        Some(1) => return Ok(Vec::new()),
+        _ => bail!(
+            "git grep failed in {}: {}",
+            path.display(),
+            String::from_utf8_lossy(&out.stderr).trim()
+        ),
+    }
+    // file NUL line NUL text
+    Ok(String::from_utf8_lossy(&out.stdout)
+        .lines()
+        .filter_map(|l| {
+            let mut parts = l.splitn(3, '\0');
+            let file = parts.next()?.to_string();
+            let line = parts.next()?.parse().ok()?;
+            let text = parts.next()?.trim().to_string();
+            Some((file, line, text))
+        })
+        .collect())
+}
+
+pub fn cmd_grep(pattern: &str, ignore_case: bool, tags: &[String]) -> Result<()> {
+    crate::git::ensure_git()?;
+    let matches = search(pattern, ignore_case, tags)?;
+    if matches.is_empty() {
+        println!("No matches for {}.", pattern);
+        return Ok(());
+    }
+    let mut table = output::table(["Repo", "Location", "Linked", "Line"]);
+    for m in &matches {
+        let linked = match &m.target {
+            Some(target) => output::cell(target, Tone::Ok),
+            None => output::cell("-", Tone::Muted),
+        };
+        table.add_row(vec![
+            Cell::new(&m.repo),
+            Cell::new(format!("{}:{}", m.file, m.line)),
+            linked,
+            Cell::new(&m.text),
+        ]);
+    }
+    println!("{}", table);
+    let repos = matches
+        .iter()
+        .map(|m| m.repo.as_str())
+        .collect::<std::collections::BTreeSet<_>>()
+        .len();
+    println!("{} match(es) in {} repo(s)", matches.len(), repos);
+    Ok(())
+}
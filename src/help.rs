@@ -0,0 +1,310 @@
+use crate::Cli;
+use anyhow::{bail, Context, Result};
+use clap::CommandFactory;
+use std::fs;
+use std::path::Path;
+
+/// A concept page shown by `dothub help <topic>` and rendered as a section 7 man page.
+pub struct Topic {
+    pub name: &'static str,
+    pub summary: &'static str,
+    pub sections: &'static [(&'static str, &'static str)],
+}
+
+pub const TOPICS: &[Topic] = &[
+    Topic {
+        name: "store",
+        summary: "Where dothub keeps installed repositories",
+        sections: &[
+            (
+                "Location",
+                "Repositories are cloned into the store, which defaults to the XDG data \
+                 directory (e.g. ~/.local/share/dothub). Set DOTHUB_DIR to use a different \
+                 directory, for example to share one store between root and your user.",
+            ),
+            (
+                "Layout",
+                "Every top-level directory in the store is one installed repository, named \
+                 after the repository URL or the name given to `dothub install`. Anything \
+                 else in the store is ignored by list, status and update.",
+            ),
+            (
+                "Sources",
+                "Repositories are usually git clones, but local directories can be \
+                 symlinked (or copied with --copy) into the store and tarballs or zips \
+                 extracted into it. The source is recorded in state so `dothub update` \
+                 knows whether to pull, re-copy or re-extract.",
+            ),
+        ],
+    },
+    Topic {
+        name: "links",
+        summary: "How stored repos are deployed into ~/.config",
+        sections: &[
+            (
+                "Symlinks",
+                "`dothub link <name> <target>` replaces ~/.config/<target> with a symbolic \
+                 link to the stored repo. Earlier links and copies made by dothub are \
+                 replaced directly.",
+            ),
+            (
+                "Conflicts",
+                "When the target exists but dothub did not create it, link shows its size, \
+                 file count and whether it is a git repository, then asks whether to \
+                 back it up (renamed to <target>.dothub-backup-<time>), adopt it into the \
+                 store as <target>-local, skip, or view how it differs from the repo. \
+                 `--backup` (or `--yes`) backs it up without asking, `--force` replaces \
+                 it without a backup, moving it to the trash (`--permanent` deletes it), \
+                 and `--no-clobber` skips it with a warning; link, \
+                 apply, org apply and import share these flags. Without a terminal to ask on and \
+                 without one of them, the target is left alone and the command fails. \
+                 `dothub backups` lists, prunes and restores backups; [backup] in the \
+                 config limits how many are kept and for how long.",
+            ),
+            (
+                "Copies",
+                "`dothub link --copy` copies the repo contents (without .git) instead. \
+                 Copies do not follow `dothub update`; link again to refresh them.",
+            ),
+            (
+                "Read-only",
+                "`dothub link --read-only` removes write permission from the deployment and \
+                 the stored repo, and pins the repo at its current commit so `dothub update` \
+                 skips it. `dothub edit <target>` unlocks it while your editor is open and \
+                 locks it again afterwards, at the new commit if you chose to commit. \
+                 Unlinking the last read-only deployment unlocks the repo.",
+            ),
+            (
+                "Audit",
+                "The first link of a repo runs `dothub audit` on it: executables, \
+                 downloads piped into a shell, binary blobs, hooks that reach the network \
+                 and hardcoded credentials are listed and you are asked before linking. \
+                 Without a terminal the link fails with E015 until `dothub audit <name> \
+                 --accept` records that you reviewed them.",
+            ),
+            (
+                "Hooks",
+                "`[hooks]` in a repo's dothub.toml runs post_link after linking and \
+                 post_update after an update pulled commits. They run under bubblewrap \
+                 with a read-only system, an empty home apart from the repo, no network \
+                 and a minimal environment. `dothub sandbox <name> network|off` loosens \
+                 this for a repo whose hooks you have read.",
+            ),
+            (
+                "Trust",
+                "Before a hook or systemd unit of a repo runs, its trust level is checked: \
+                 `prompt` (the default) shows what would run and asks, `untrusted` skips \
+                 it and `trusted` runs it. Set it with `dothub trust <name> <level>`.",
+            ),
+            (
+                "Other users",
+                "As root, `dothub link --system user[:group]` deploys into that user's \
+                 ~/.config and hands the link or copy to the user and group. The owner \
+                 is recorded and checked by `dothub verify`.",
+            ),
+            (
+                "Flatpak",
+                "`dothub link <name> <target> --flatpak <appid>` links into the app's \
+                 sandbox, ~/.var/app/<appid>/config/<target>, as the target \
+                 <target>@<appid>. `--mirror` links the native ~/.config/<target> as well.",
+            ),
+            (
+                "Checking",
+                "`dothub active` lists symlinks in ~/.config that point into the store, and \
+                 `dothub verify` compares every deployment against what dothub recorded, \
+                 including file modes declared under [modes] in the repo's dothub.toml.",
+            ),
+        ],
+    },
+    Topic {
+        name: "state",
+        summary: "What dothub records about deployments",
+        sections: &[
+            (
+                "Contents",
+                "Every link or copy is recorded with its source, target, deploy mode and \
+                 time. Copies also record a sha256 for each file so `dothub verify` can \
+                 report edits made outside dothub. The time of the last `dothub update` \
+                 in which every repo succeeded is kept for `dothub metrics`.",
+            ),
+            (
+                "Location",
+                "State is per user and lives in the XDG state directory \
+                 (e.g. ~/.local/state/dothub/state.json). Set DOTHUB_STATE to override it.",
+            ),
+        ],
+    },
+    Topic {
+        name: "hub",
+        summary: "The curated list of community dotfiles",
+        sections: &[
+            (
+                "Browsing",
+                "Running `dothub` without a subcommand fetches hub.yml from the dothub \
+                 repository and shows every entry ranked by GitHub stars. Pass types \
+                 (e.g. `dothub nvim tmux`) to filter, or --url to use another index.",
+            ),
+            (
+                "Signatures",
+                "The index is checked against <url>.minisig with the minisign key in \
+                 hub.pub, or hub.public_key in the config. A bad signature is an error \
+                 (E014); a missing one a warning, or an error with hub.require_signature. \
+                 `dothub install` warns when a URL is not in the index and suggests the \
+                 entry with the closest name.",
+            ),
+            (
+                "Favorites",
+                "`dothub hub star <entry>` adds an entry, by URL or repo name, to a local \
+                 favorites list kept in state; `unstar` removes it. Favorites are listed \
+                 first and marked ★, and `dothub install --favorites` installs them all.",
+            ),
+            (
+                "Reviews",
+                "`dothub hub reviews <entry>` reads open issues, the last push, the latest \
+                 release and discussion activity from the entry's forge as a proxy for how \
+                 well it is maintained. Answers are cached for six hours; --refresh asks \
+                 again.",
+            ),
+            (
+                "Trending",
+                "Each unfiltered listing saves the star counts in the cache directory. \
+                 `dothub hub trending` compares them with the counts from a week back \
+                 (--days to change it, or the oldest saved) to show the entries gaining \
+                 stars fastest, and lists entries added since the previous listing.",
+            ),
+            (
+                "Dashboard",
+                "`dothub dashboard` browses the same index interactively. Opening an entry \
+                 shows its README, fetched from the forge and cached like other hub data, \
+                 with links numbered so they can be opened in a browser.",
+            ),
+            (
+                "Contributing",
+                "hub.yml maps a config type to a list of repository URLs. Fork the \
+                 repository, add your URL under its type and open a pull request. An \
+                 entry can also be a mapping with `url` plus optional `description`, \
+                 `screenshots` (image URLs), `min_version` of the application and `os` \
+                 (linux, macos, windows); these show up in the hub listing and above the \
+                 README in the dashboard. Entries that need another OS or a newer version \
+                 of the application than the one installed are hidden, and installing them \
+                 fails, unless --ignore-compat is given.",
+            ),
+            (
+                "Tokens",
+                "Log in with dothub auth login github, or set GITHUB_TOKEN, to fetch stars \
+                 with a single GraphQL request instead of one REST call per entry.",
+            ),
+        ],
+    },
+];
+
+pub fn find_topic(name: &str) -> Option<&'static Topic> {
+    TOPICS.iter().find(|t| t.name.eq_ignore_ascii_case(name))
+}
+
+pub fn cmd_help(topic: Option<&str>) -> Result<()> {
+    let mut cmd = Cli::command();
+    let Some(topic) = topic else {
+        cmd.print_long_help()?;
+        println!();
+        print_topic_index();
+        return Ok(());
+    };
+
+    if let Some(t) = find_topic(topic) {
+        print_topic(t);
+        return Ok(());
+    }
+    if let Some(sub) = cmd.find_subcommand_mut(topic) {
+        let mut sub = sub.clone().bin_name(format!("dothub {}", topic));
+        sub.print_long_help()?;
+        return Ok(());
+    }
+    print_topic_index();
+    bail!("No help topic or command named '{}'", topic);
+}
+
+fn print_topic_index() {
+    println!("Help topics (dothub help <topic>):");
+    for t in TOPICS {
+        println!("  {:<10} {}", t.name, t.summary);
+    }
+}
+
+fn print_topic(t: &Topic) {
+    println!("dothub {} - {}", t.name, t.summary);
+    for (heading, body) in t.sections {
+        println!();
+        println!("{}", heading.to_uppercase());
+        for line in crate::markdown::wrap(body, 76) {
+            println!("    {}", line);
+        }
+    }
+}
+
+/// Write dothub.1, one page per subcommand, and a section 7 page per help topic.
+pub fn cmd_manpages(dir: &Path) -> Result<()> {
+    let written = write_manpages(dir)?;
+    println!("Wrote {} man pages to {}", written, dir.display());
+    Ok(())
+}
+
+/// Write every man page into `dir` and return how many there are.
+pub fn write_manpages(dir: &Path) -> Result<usize> {
+    fs::create_dir_all(dir).with_context(|| format!("Failed creating {}", dir.display()))?;
+
+    let cmd = Cli::command();
+    let mut written = 0usize;
+    written += write_command_pages(&cmd, "dothub", dir)?;
+
+    for t in TOPICS {
+        let path = dir.join(format!("dothub-{}.7", t.name));
+        fs::write(&path, topic_roff(t)).with_context(|| format!("Writing {}", path.display()))?;
+        written += 1;
+    }
+    Ok(written)
+}
+
+fn write_command_pages(cmd: &clap::Command, name: &str, dir: &Path) -> Result<usize> {
+    let cmd = cmd.clone().name(name.to_string());
+    let mut buf = Vec::new();
+    clap_mangen::Man::new(cmd.clone())
+        .render(&mut buf)
+        .with_context(|| format!("Rendering man page for {}", name))?;
+    let path = dir.join(format!("{}.1", name));
+    fs::write(&path, buf).with_context(|| format!("Writing {}", path.display()))?;
+
+    let mut written = 1;
+    for sub in cmd.get_subcommands() {
+        if sub.is_hide_set() {
+            continue;
+        }
+        let sub_name = format!("{}-{}", name, sub.get_name());
+        written += write_command_pages(sub, &sub_name, dir)?;
+    }
+    Ok(written)
+}
+
+fn topic_roff(t: &Topic) -> String {
+    let mut out = format!(
+        ".TH DOTHUB-{} 7\n.SH NAME\ndothub-{} \\- {}\n",
+        t.name.to_uppercase(),
+        t.name,
+        roff_escape(t.summary)
+    );
+    for (heading, body) in t.sections {
+        out.push_str(&format!(".SH {}\n{}\n", heading.to_uppercase(), roff_escape(body)));
+    }
+    out.push_str(".SH SEE ALSO\ndothub(1)\n");
+    out
+}
+
+fn roff_escape(text: &str) -> String {
+    let escaped = text.replace('\\', "\\\\").replace('-', "\\-");
+    // A leading dot or quote would be read as a roff request
+    if escaped.starts_with('.') || escaped.starts_with('\'') {
+        format!("\\&{}", escaped)
+    } else {
+        escaped
+    }
+}
@@ -0,0 +1,301 @@
+//! HTTP client with a persistent conditional-request cache.
+//!
+//! GET responses are stored under the cache dir together with their ETag and
+//! Last-Modified headers, and revalidated with If-None-Match/If-Modified-Since
+//! so unchanged resources come back as cheap 304s. When GitHub reports that
+//! the rate limit is exhausted, later requests in the same run are answered
+//! from the cache (or fail fast) instead of hammering the API. In offline
+//! mode the cache is the only source.
+
+use crate::output;
+use crate::state;
+use anyhow::{bail, Context, Result};
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use reqwest::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED, RETRY_AFTER};
+use reqwest::{Proxy, StatusCode};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::thread;
+use std::time::Duration;
+
+pub const USER_AGENT: &str = "dothub/0.1";
+/// Longest Retry-After we are willing to sleep through before giving up.
+const MAX_BACKOFF_SECS: u64 = 10;
+const MAX_RETRIES: usize = 2;
+
+/// Unix time at which the exhausted rate limit resets, 0 when not limited.
+static RATE_LIMIT_RESET: AtomicU64 = AtomicU64::new(0);
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+/// Fetch time of the oldest cached response served instead of the network, 0 when none.
+static OLDEST_CACHED: AtomicU64 = AtomicU64::new(0);
+
+/// Turn on offline mode for the rest of the run (`--offline`).
+pub fn set_offline(offline: bool) {
+    OFFLINE.store(offline, Ordering::SeqCst);
+}
+
+/// True when `--offline` was given or DOTHUB_OFFLINE is set to a truthy value.
+pub fn is_offline() -> bool {
+    OFFLINE.load(Ordering::SeqCst)
+        || env::var("DOTHUB_OFFLINE").is_ok_and(|v| !v.is_empty() && v != "0")
+}
+
+/// Record that data fetched at `fetched_at` was shown in place of a live response.
+pub fn note_cached(fetched_at: u64) {
+    let _ = OLDEST_CACHED.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |cur| {
+        (cur == 0 || fetched_at < cur).then_some(fetched_at)
+    });
+}
+
+/// Oldest fetch time of cached data served during this run, if any.
+pub fn served_from_cache_since() -> Option<u64> {
+    match OLDEST_CACHED.load(Ordering::SeqCst) {
+        0 => None,
+        t => Some(t),
+    }
+}
+
+pub fn client() -> Result<Client> {
+    let mut builder = Client::builder()
+        .user_agent(USER_AGENT)
+        .proxy(Proxy::custom(crate::network::proxy_for));
+    if let Some(certs) = crate::network::ca_certificates()? {
+        builder = builder.tls_built_in_root_certs(false);
+        for cert in certs {
+            builder = builder.add_root_certificate(cert);
+        }
+    }
+    builder.build().context("building http client")
+}
+
+pub fn fetch_text(url: &str) -> Result<String> {
+    get_cached(client()?.get(url), url)
+}
+
+/// Download a binary resource without caching it.
+pub fn fetch_bytes(url: &str) -> Result<Vec<u8>> {
+    if is_offline() {
+        bail!("Offline; cannot download {}", url);
+    }
+    let _span = crate::perf::span(crate::perf::Phase::Network, || crate::perf::host_subject(url));
+    let req = client()?.get(url);
+    crate::network::retry(&format!("GET {}", url), || {
+        let resp = send(&req, url)?;
+        if !resp.status().is_success() {
+            bail!("HTTP {} for {}", resp.status(), url);
+        }
+        // A connection dropped halfway through is worth another try
+        crate::network::read_capped(resp)
+            .map_err(|e| transient(format!("reading {}: {}", url, e)))
+    })
+}
+
+/// Send `req` once; failures to connect, timeouts and server errors are
+/// transient so callers can retry them with `network::retry`.
+fn send(req: &RequestBuilder, url: &str) -> Result<Response> {
+    let resp = req
+        .try_clone()
+        .with_context(|| format!("GET {}", url))?
+        .send()
+        .map_err(|e| {
+            let transient_kind = e.is_timeout() || e.is_connect();
+            // reqwest's own message is generic; the innermost cause says what failed
+            let cause = anyhow::Error::from(e).root_cause().to_string();
+            let msg = format!("GET {}: {}", url, cause);
+            // A certificate that isn't trusted now won't be on the next try either
+            if transient_kind && !cause.contains("certificate") {
+                transient(msg)
+            } else {
+                anyhow::anyhow!(msg)
+            }
+        })?;
+    if resp.status().is_server_error() {
+        return Err(transient(format!("HTTP {} for {}", resp.status(), url)));
+    }
+    Ok(resp)
+}
+
+fn transient(msg: String) -> anyhow::Error {
+    anyhow::Error::new(crate::network::Transient(msg))
+}
+
+pub fn cache_dir() -> PathBuf {
+    if let Ok(p) = env::var("DOTHUB_CACHE") {
+        return PathBuf::from(p);
+    }
+    if let Some(mut dir) = dirs::cache_dir() {
+        dir.push("dothub");
+        return dir;
+    }
+    crate::dothub_dir().join(".cache")
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    url: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    fetched_at: u64,
+    body: String,
+}
+
+fn cache_file(url: &str) -> PathBuf {
+    let digest = Sha256::digest(url.as_bytes());
+    let name: String = digest.iter().take(16).map(|b| format!("{:02x}", b)).collect();
+    cache_dir().join("http").join(format!("{}.json", name))
+}
+
+fn read_entry(url: &str) -> Option<CacheEntry> {
+    let text = fs::read_to_string(cache_file(url)).ok()?;
+    serde_json::from_str::<CacheEntry>(&text)
+        .ok()
+        .filter(|e| e.url == url)
+}
+
+fn write_entry(entry: &CacheEntry) {
+    let path = cache_file(&entry.url);
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    // The cache is an optimisation; failing to write it is not an error
+    if let Ok(text) = serde_json::to_string(entry) {
+        let _ = fs::write(path, text);
+    }
+}
+
+/// Unix time the rate limit resets, if it was exhausted during this run.
+pub fn rate_limited_until() -> Option<u64> {
+    match RATE_LIMIT_RESET.load(Ordering::SeqCst) {
+        0 => None,
+        t => Some(t),
+    }
+}
+
+fn rate_limited(reset: u64) -> crate::error::Error {
+    crate::error::Error::new(crate::error::Code::RateLimited, rate_limit_message(reset))
+        .hint("dothub auth login github, or pass --offline to use cached data")
+}
+
+pub fn rate_limit_message(reset: u64) -> String {
+    let wait = reset.saturating_sub(state::now_unix());
+    format!(
+        "API rate limit exceeded; it resets in {}. Log in with dothub auth login github to raise the limit.",
+        output::duration(wait)
+    )
+}
+
+/// Send a GET with conditional headers from the cache and return the body.
+/// Falls back to the cached body when rate limited.
+pub fn get_cached(req: RequestBuilder, url: &str) -> Result<String> {
+    let cached = read_entry(url);
+
+    if is_offline() {
+        return match cached {
+            Some(c) => {
+                note_cached(c.fetched_at);
+                Ok(c.body)
+            }
+            None => bail!("Offline and {} has not been cached yet", url),
+        };
+    }
+
+    if let Some(reset) = rate_limited_until() {
+        if reset > state::now_unix() {
+            return match cached {
+                Some(c) => {
+                    note_cached(c.fetched_at);
+                    Ok(c.body)
+                }
+                None => bail!(rate_limited(reset)),
+            };
+        }
+    }
+
+    let mut attempt = 0;
+    loop {
+        let mut r = req
+            .try_clone()
+            .with_context(|| format!("GET {}", url))?;
+        if let Some(c) = &cached {
+            if let Some(etag) = &c.etag {
+                r = r.header(IF_NONE_MATCH, etag);
+            }
+            if let Some(lm) = &c.last_modified {
+                r = r.header(IF_MODIFIED_SINCE, lm);
+            }
+        }
+        let _span = crate::perf::span(crate::perf::Phase::Network, || {
+            crate::perf::host_subject(url)
+        });
+        let resp = crate::network::retry(&format!("GET {}", url), || send(&r, url))?;
+        let status = resp.status();
+
+        if status == StatusCode::NOT_MODIFIED {
+            if let Some(mut c) = cached {
+                c.fetched_at = state::now_unix();
+                write_entry(&c);
+                return Ok(c.body);
+            }
+            bail!("HTTP 304 for {} without a cached copy", url);
+        }
+
+        if status.is_success() {
+            let etag = header(&resp, ETAG);
+            let last_modified = header(&resp, LAST_MODIFIED);
+            let body = resp.text().context("reading response body")?;
+            write_entry(&CacheEntry {
+                url: url.to_string(),
+                etag,
+                last_modified,
+                fetched_at: state::now_unix(),
+                body: body.clone(),
+            });
+            return Ok(body);
+        }
+
+        if status == StatusCode::FORBIDDEN || status == StatusCode::TOO_MANY_REQUESTS {
+            // Secondary limits ask us to wait briefly; honour short waits
+            if let Some(wait) = header(&resp, RETRY_AFTER).and_then(|s| s.parse::<u64>().ok()) {
+                if wait <= MAX_BACKOFF_SECS && attempt < MAX_RETRIES {
+                    attempt += 1;
+                    thread::sleep(Duration::from_secs(wait.max(1)));
+                    continue;
+                }
+            }
+            if let Some(reset) = exhausted_reset(&resp) {
+                RATE_LIMIT_RESET.store(reset, Ordering::SeqCst);
+                if let Some(c) = cached {
+                    note_cached(c.fetched_at);
+                    return Ok(c.body);
+                }
+                bail!(rate_limited(reset));
+            }
+        }
+
+        bail!("HTTP {} for {}", status, url);
+    }
+}
+
+/// Reset time when the response says the primary rate limit is used up.
+fn exhausted_reset(resp: &Response) -> Option<u64> {
+    let remaining = resp.headers().get("x-ratelimit-remaining")?.to_str().ok()?;
+    if remaining != "0" {
+        return None;
+    }
+    resp.headers()
+        .get("x-ratelimit-reset")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok())
+        .or(Some(state::now_unix() + 60 * 60))
+}
+
+fn header(resp: &Response, name: reqwest::header::HeaderName) -> Option<String> {
+    resp.headers()
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(|s| s.to_string())
+}
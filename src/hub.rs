@@ -0,0 +1,528 @@
+use crate::error::{Code, Error};
+use crate::forge;
+use crate::output::{self, Tone};
+use crate::signature::{self, PublicKey};
+use crate::vcs::Vcs;
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use comfy_table::Cell;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU8, Ordering};
+
+pub const DEFAULT_HUB_URL: &str =
+    "https://raw.githubusercontent.com/huncholane/dothub/main/hub.yml";
+const GH_TOKEN_HELP_URL: &str = "https://github.com/settings/personal-access-tokens";
+const ASCII_BANNER: &str = include_str!("../ascii.txt");
+const DESCRIPTION_WIDTH: usize = 60;
+/// minisign key the default index is signed with; empty until one is published
+const BUILTIN_KEY: &str = include_str!("../hub.pub");
+
+/// How far the last fetched index could be trusted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Trust {
+    /// Signed with the expected key
+    Verified = 1,
+    /// A key is known but the index has no signature
+    Unsigned = 2,
+    /// No key to check against
+    NoKey = 3,
+}
+
+static TRUST: AtomicU8 = AtomicU8::new(0);
+
+/// Trust in the index fetched during this run, if one was.
+pub fn index_trust() -> Option<Trust> {
+    match TRUST.load(Ordering::SeqCst) {
+        1 => Some(Trust::Verified),
+        2 => Some(Trust::Unsigned),
+        3 => Some(Trust::NoKey),
+        _ => None,
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(untagged)]
+enum FlexEntry {
+    Single(RawEntry),
+    Many(Vec<RawEntry>),
+}
+
+/// A hub.yml entry: a bare URL, or a URL with details.
+#[derive(Debug, serde::Deserialize)]
+#[serde(untagged)]
+enum RawEntry {
+    Url(String),
+    Detailed {
+        url: String,
+        #[serde(flatten)]
+        info: EntryInfo,
+    },
+}
+
+/// Optional details the hub can give for an entry. Unknown keys are ignored
+/// so older dothub versions keep reading newer hub files.
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(default)]
+pub struct EntryInfo {
+    /// Shown instead of the forge's description
+    pub description: Option<String>,
+    /// Image URLs showing the config in use
+    pub screenshots: Vec<String>,
+    /// Oldest version of the application the config works with, e.g. "0.10"
+    pub min_version: Option<String>,
+    /// Supported operating systems ("linux", "macos", "windows"); empty means any
+    pub os: Vec<String>,
+    /// What to fetch the entry with: git (the default), hg, jj or tarball
+    pub vcs: Option<String>,
+    /// Types an all-in-one repo provides, each mapped to its directory in
+    /// the repo (e.g. `nvim: .config/nvim`)
+    pub provides: BTreeMap<String, String>,
+}
+
+impl EntryInfo {
+    /// The backend the entry asks for. A name this version doesn't know is
+    /// an error rather than a git clone that can only fail.
+    pub fn backend(&self) -> Result<Option<Vcs>> {
+        match &self.vcs {
+            Some(name) => match Vcs::from_str(name, true) {
+                Ok(vcs) => Ok(Some(vcs)),
+                Err(_) => bail!("the hub says to fetch it with {}, which dothub doesn't support", name),
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// "nvim >= 0.10 on linux, macos", or None when the entry states nothing.
+    pub fn requirements(&self, config_type: &str) -> Option<String> {
+        let version = self
+            .min_version
+            .as_ref()
+            .map(|v| format!("{} >= {}", config_type, v));
+        let os = (!self.os.is_empty()).then(|| self.os.join(", "));
+        match (version, os) {
+            (Some(v), Some(os)) => Some(format!("{} on {}", v, os)),
+            (Some(v), None) => Some(v),
+            (None, Some(os)) => Some(format!("{} only", os)),
+            (None, None) => None,
+        }
+    }
+
+    /// Markdown put in front of the README in the dashboard, so screenshots
+    /// can be opened like any other link.
+    pub fn markdown(&self, config_type: &str) -> String {
+        let mut lines = Vec::new();
+        if let Some(req) = self.requirements(config_type) {
+            lines.push(format!("> Requires {}", req));
+        }
+        if !self.screenshots.is_empty() {
+            let shots: Vec<String> = self
+                .screenshots
+                .iter()
+                .enumerate()
+                .map(|(i, url)| format!("[screenshot {}]({})", i + 1, url))
+                .collect();
+            lines.push(format!("> {}", shots.join(" ")));
+        }
+        if lines.is_empty() {
+            return String::new();
+        }
+        format!("{}\n\n", lines.join("\n>\n"))
+    }
+}
+
+/// One entry of the hub index.
+pub struct IndexEntry {
+    pub config_type: String,
+    pub link: String,
+    pub info: EntryInfo,
+}
+
+/// One hub entry with the metadata fetched from its forge.
+pub struct HubEntry {
+    pub config_type: String,
+    pub link: String,
+    pub info: EntryInfo,
+    pub meta: forge::RepoMeta,
+}
+
+impl HubEntry {
+    /// The hub's description of the entry, else the forge's.
+    pub fn description(&self) -> Option<&str> {
+        self.info
+            .description
+            .as_deref()
+            .or(self.meta.description.as_deref())
+    }
+}
+
+/// Download the hub index, with a readable error when it cannot be fetched,
+/// and check its signature.
+pub fn fetch_index(url: Option<&str>) -> Result<String> {
+    let url = url.unwrap_or(DEFAULT_HUB_URL);
+    let text = match crate::http::fetch_text(url) {
+        Ok(text) => text,
+        Err(_) if crate::http::is_offline() => {
+            bail!("Offline and no cached hub file found. Run dothub once while online.")
+        }
+        Err(_) => bail!("Failed to fetch the hub file. Please ensure you have internet connection."),
+    };
+    let trust = verify_index(url, &text)?;
+    TRUST.store(trust as u8, Ordering::SeqCst);
+    Ok(text)
+}
+
+/// The key the index at `url` should be signed with: hub.public_key from the
+/// config, else the built-in key for the default index.
+fn index_key(url: &str) -> Result<Option<PublicKey>> {
+    let config = crate::config::Config::load()?;
+    if let Some(key) = config.hub.public_key {
+        let path = crate::source::expand_tilde(&key);
+        let text = if path.is_file() {
+            std::fs::read_to_string(&path)
+                .with_context(|| format!("Reading {}", path.display()))?
+        } else {
+            key
+        };
+        return PublicKey::parse(&text).map(Some).context("hub.public_key in the config");
+    }
+    Ok((url == DEFAULT_HUB_URL)
+        .then(|| PublicKey::parse(BUILTIN_KEY).ok())
+        .flatten())
+}
+
+/// Check the index against `<url>.minisig`. A bad signature is always an
+/// error; a missing one only when hub.require_signature is set.
+fn verify_index(url: &str, text: &str) -> Result<Trust> {
+    let Some(key) = index_key(url)? else {
+        return Ok(Trust::NoKey);
+    };
+    let sig_url = format!("{}.minisig", url);
+    let Ok(sig) = crate::http::fetch_text(&sig_url) else {
+        if crate::config::Config::load()?.hub.require_signature {
+            bail!(Error::new(
+                Code::SignatureInvalid,
+                format!("{} has no signature at {}", url, sig_url),
+            )
+            .hint("sign it with minisign -Sm hub.yml, or turn off hub.require_signature"));
+        }
+        return Ok(Trust::Unsigned);
+    };
+    match signature::verify(&key, text.as_bytes(), &sig) {
+        Ok(_) => Ok(Trust::Verified),
+        Err(e) => bail!(Error::new(
+            Code::SignatureInvalid,
+            format!("The hub index at {} failed its signature check: {:#}", url, e),
+        )
+        .hint(format!(
+            "it may have been tampered with; install nothing from it and report it at {}/issues",
+            env!("CARGO_PKG_REPOSITORY")
+        ))),
+    }
+}
+
+/// Flatten hub.yml into entries, keeping only `types` when given.
+pub fn parse_index(yaml: &str, types: &[String]) -> Result<Vec<IndexEntry>> {
+    let map: HashMap<String, FlexEntry> =
+        serde_yaml::from_str(yaml).context("Parsing YAML for hub")?;
+
+    let filters: Vec<String> = types.iter().map(|s| s.to_lowercase()).collect();
+
+    let mut items = Vec::new();
+    for (ty, entry) in map.into_iter() {
+        let raw = match entry {
+            FlexEntry::Single(e) => vec![e],
+            FlexEntry::Many(v) => v,
+        };
+        for e in raw {
+            let (link, info) = match e {
+                RawEntry::Url(url) => (url, EntryInfo::default()),
+                RawEntry::Detailed { url, info } => (url, info),
+            };
+            // An all-in-one repo shows up under each type it provides
+            let wanted = filters.is_empty()
+                || filters.contains(&ty.to_lowercase())
+                || info.provides.keys().any(|t| filters.contains(&t.to_lowercase()));
+            if !wanted {
+                continue;
+            }
+            items.push(IndexEntry {
+                config_type: ty.clone(),
+                link,
+                info,
+            });
+        }
+    }
+    Ok(items)
+}
+
+/// Whether two links name the same repo, ignoring case, a trailing slash or ".git".
+pub fn same_link(a: &str, b: &str) -> bool {
+    let norm = |l: &str| {
+        l.trim_end_matches('/')
+            .trim_end_matches(".git")
+            .to_lowercase()
+    };
+    norm(a) == norm(b)
+}
+
+/// The index entry for `link`.
+pub fn find_entry(yaml: &str, link: &str) -> Result<Option<IndexEntry>> {
+    Ok(parse_index(yaml, &[])?
+        .into_iter()
+        .find(|e| same_link(&e.link, link)))
+}
+
+/// The entry whose link looks most like `link` (same repo name under another
+/// owner, or a name a typo or two away), to catch typosquatted URLs.
+pub fn closest_entry(yaml: &str, link: &str) -> Result<Option<String>> {
+    let name = crate::derive_repo_name(link).to_lowercase();
+    let best = parse_index(yaml, &[])?
+        .into_iter()
+        .map(|e| {
+            let distance = edit_distance(&crate::derive_repo_name(&e.link).to_lowercase(), &name);
+            (distance, e.link)
+        })
+        .filter(|(d, _)| *d <= 2)
+        .min_by_key(|(d, _)| *d);
+    Ok(best.map(|(_, link)| link))
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cur = row[j + 1];
+            row[j + 1] = (prev + usize::from(ca != *cb)).min(row[j] + 1).min(cur + 1);
+            prev = cur;
+        }
+    }
+    row[b.len()]
+}
+
+/// The link `entry` stands for: a URL as given, or the hub entry whose repo
+/// name it is.
+pub fn resolve_entry(entry: &str, url: Option<&str>) -> Result<String> {
+    if entry.contains("://") || entry.starts_with("git@") {
+        return Ok(entry.to_string());
+    }
+    let yaml = fetch_index(url)?;
+    let matches: Vec<String> = parse_index(&yaml, &[])?
+        .into_iter()
+        .filter(|e| crate::derive_repo_name(&e.link).eq_ignore_ascii_case(entry))
+        .map(|e| e.link)
+        .collect();
+    match matches.as_slice() {
+        [link] => Ok(link.clone()),
+        [] => bail!("No hub entry is named {}; pass its URL instead", entry),
+        _ => bail!(
+            "Several hub entries are named {}; pass one of: {}",
+            entry,
+            matches.join(", ")
+        ),
+    }
+}
+
+/// Attach fetched metadata to each entry and rank them by stars.
+pub fn rank(items: Vec<IndexEntry>, results: &forge::MetaResults) -> Vec<HubEntry> {
+    let mut entries: Vec<HubEntry> = items
+        .into_iter()
+        .map(|item| {
+            let meta = results.meta.get(&item.link).cloned().unwrap_or_default();
+            HubEntry {
+                config_type: item.config_type,
+                link: item.link,
+                info: item.info,
+                meta,
+            }
+        })
+        .collect();
+    entries.sort_by_key(|e| std::cmp::Reverse(e.meta.stars));
+    entries
+}
+
+pub fn cmd_hub(types: Vec<String>, url: Option<String>, ignore_compat: bool) -> Result<()> {
+    // Print ASCII banner at the top
+    if !output::plain() {
+        println!("{}", ASCII_BANNER);
+    }
+    let yaml = match fetch_index(url.as_deref()) {
+        Ok(text) => text,
+        // Coded errors carry a hint; let the usual report print it
+        Err(e) if crate::error::code_of(&e).is_some() => return Err(e),
+        Err(e) => {
+            output::error(&e.to_string());
+            std::process::exit(1);
+        }
+    };
+    let items = parse_index(&yaml, &types)?;
+
+    // Show a spinner while metadata is fetched from each entry's forge
+    let spinner_stop = output::start_spinner("Downloading repository info..");
+    let links: Vec<String> = items.iter().map(|i| i.link.clone()).collect();
+    let results = forge::fetch_all(&links)?;
+    spinner_stop.store(true, Ordering::SeqCst);
+    // Leave the last line in place; print a newline to cleanly end spinner
+    eprintln!();
+    // A filtered listing would make the other entries look newly added later
+    if types.is_empty() {
+        crate::trending::record(url.as_deref(), &links, &results);
+    }
+
+    // Entries that cannot work here are hidden unless asked for
+    let mut entries = Vec::new();
+    let mut hidden: Vec<String> = Vec::new();
+    let mut hidden_count = 0;
+    let favorites = crate::state::State::load()?.favorites;
+    let mut ranked = rank(items, &results);
+    crate::favorites::sort_first(&favorites, &mut ranked);
+    for entry in ranked {
+        let problem = crate::compat::problem(&entry.config_type, &entry.info);
+        if problem.is_some() && !ignore_compat {
+            hidden_count += 1;
+            if !hidden.contains(&entry.config_type) {
+                hidden.push(entry.config_type.clone());
+            }
+            continue;
+        }
+        entries.push((entry, problem));
+    }
+
+    let mut table = output::table(["#", "Stars", "Installed", "Source", "Description"]);
+
+    for (idx, (entry, problem)) in entries.iter().enumerate() {
+        let rank = (idx + 1).to_string();
+        let name = crate::derive_repo_name(&entry.link);
+        let installed = crate::dothub_dir().join(&name).exists();
+        let installed_cell = if installed {
+            output::cell("y", Tone::Ok)
+        } else {
+            output::cell("n", Tone::Muted)
+        };
+        let mut description: Vec<String> = entry
+            .description()
+            .map(|d| truncate(d, DESCRIPTION_WIDTH))
+            .into_iter()
+            .collect();
+        if let Some(req) = entry.info.requirements(&entry.config_type) {
+            description.push(format!("[{}]", req));
+        }
+        if !entry.info.screenshots.is_empty() {
+            description.push(format!("[{} screenshot(s)]", entry.info.screenshots.len()));
+        }
+        if !entry.info.provides.is_empty() {
+            let types: Vec<&str> = entry.info.provides.keys().map(String::as_str).collect();
+            description.push(format!("[{}]", types.join("+")));
+        }
+        let description_cell = match problem {
+            Some(p) => {
+                description.push(format!("[incompatible: {}]", p));
+                output::cell(description.join(" "), Tone::Warn)
+            }
+            None => Cell::new(description.join(" ")),
+        };
+        table.add_row(vec![
+            Cell::new(rank),
+            Cell::new(entry.meta.stars),
+            installed_cell,
+            if crate::favorites::is_favorite(&favorites, &entry.link) {
+                output::cell(format!("★ {}", entry.link), Tone::Info)
+            } else {
+                Cell::new(&entry.link)
+            },
+            description_cell,
+        ]);
+    }
+
+    println!("{}", table);
+    let unverified = match index_trust() {
+        Some(Trust::Unsigned) => {
+            Some("This hub index has no signature, so its entries can't be verified.")
+        }
+        Some(Trust::NoKey) => Some(
+            "dothub has no key to check this hub index's signature with, so its entries can't be verified.",
+        ),
+        _ => None,
+    };
+    if let Some(warning) = unverified {
+        println!("{}", output::paint(warning, Tone::Warn));
+    }
+    if hidden_count > 0 {
+        let types: Vec<&str> = hidden.iter().map(String::as_str).collect();
+        println!(
+            "{}",
+            output::paint(
+                &format!(
+                    "Hid {} incompatible entr{} (checked against {}); --ignore-compat lists everything.",
+                    hidden_count,
+                    if hidden_count == 1 { "y" } else { "ies" },
+                    crate::compat::describe_system(&types)
+                ),
+                Tone::Info
+            )
+        );
+    }
+    if let Some(ts) = crate::http::served_from_cache_since() {
+        println!(
+            "{}",
+            output::paint(
+                &format!(
+                    "Showing cached data as of {}.",
+                    output::ago(ts, crate::state::now_unix())
+                ),
+                Tone::Info
+            )
+        );
+    }
+    if crate::forge::ForgeKind::GitHub.token().is_none() && !crate::http::is_offline() {
+        println!(
+            "{}",
+            output::paint(
+                &format!(
+                    "To improve performance, run dothub auth login github or set GITHUB_TOKEN.\nLearn more: {}",
+                    GH_TOKEN_HELP_URL
+                ),
+                Tone::Warn
+            )
+        );
+    }
+    if let Some(reset) = crate::http::rate_limited_until() {
+        println!(
+            "{}",
+            output::paint(
+                &format!(
+                    "{}\nStars shown may be cached or missing.",
+                    crate::http::rate_limit_message(reset)
+                ),
+                Tone::Warn
+            )
+        );
+    }
+    if results.graphql_failed {
+        println!(
+            "{}",
+            output::paint(
+                &format!(
+                    "A GitHub token is set but GitHub GraphQL failed; falling back to REST.\nLearn more: {}",
+                    GH_TOKEN_HELP_URL
+                ),
+                Tone::Warn
+            )
+        );
+    }
+
+    println!("Run dothub --help to see more options.");
+
+    Ok(())
+}
+
+pub fn truncate(s: &str, max: usize) -> String {
+    if s.chars().count() <= max {
+        return s.to_string();
+    }
+    let mut out: String = s.chars().take(max.saturating_sub(1)).collect();
+    out.push('…');
+    out
+}
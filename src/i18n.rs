@@ -0,0 +1,79 @@
+//! Translated output messages.
+//!
+//! Catalogs live in `locales/<lang>.toml`, one `key = "text"` per message with
+//! `{name}` placeholders, and are built into the binary. The language comes
+//! from `language` in the config file, else from LC_ALL, LC_MESSAGES or LANG.
+//! Keys missing from a catalog fall back to English.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt::Display;
+use std::sync::OnceLock;
+
+const ENGLISH: &str = include_str!("../locales/en.toml");
+
+/// Language code and catalog of every translation besides English.
+const CATALOGS: &[(&str, &str)] = &[("es", include_str!("../locales/es.toml"))];
+
+type Catalog = BTreeMap<String, String>;
+
+static MESSAGES: OnceLock<(Catalog, Catalog)> = OnceLock::new();
+
+/// The language code messages are shown in, e.g. "es".
+pub fn language() -> String {
+    let configured = crate::config::Config::load()
+        .ok()
+        .and_then(|c| c.language)
+        .filter(|l| !l.is_empty());
+    let locale = configured.or_else(|| {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|var| env::var(var).ok())
+            .find(|v| !v.is_empty())
+    });
+    let Some(locale) = locale else {
+        return "en".to_string();
+    };
+    // es_ES.UTF-8, es@euro and plain es all mean Spanish
+    let code = locale
+        .split(['_', '-', '.', '@'])
+        .next()
+        .unwrap_or("")
+        .to_lowercase();
+    if CATALOGS.iter().any(|(lang, _)| *lang == code) {
+        code
+    } else {
+        "en".to_string()
+    }
+}
+
+fn parse(text: &str) -> Catalog {
+    toml::from_str(text).unwrap_or_default()
+}
+
+fn messages() -> &'static (Catalog, Catalog) {
+    MESSAGES.get_or_init(|| {
+        let lang = language();
+        let translated = CATALOGS
+            .iter()
+            .find(|(code, _)| *code == lang)
+            .map(|(_, text)| parse(text))
+            .unwrap_or_default();
+        (translated, parse(ENGLISH))
+    })
+}
+
+/// The message for `key` with its `{name}` placeholders filled from `args`.
+pub fn t(key: &str, args: &[(&str, &dyn Display)]) -> String {
+    let (translated, english) = messages();
+    let template = translated
+        .get(key)
+        .or_else(|| english.get(key))
+        .map(String::as_str)
+        .unwrap_or(key);
+    let mut text = template.to_string();
+    for (name, value) in args {
+        text = text.replace(&format!("{{{}}}", name), &value.to_string());
+    }
+    text
+}
@@ -0,0 +1,215 @@
+//! `dothub import`: migrate from other dotfile setups into the store.
+//!
+//! `import bare` handles the "bare git repo with its work tree at $HOME"
+//! pattern used directly and by yadm. The repo is cloned into the store, any
+//! uncommitted edits in the home directory are carried over, and every tracked
+//! path is linked back to where it was: directories under ~/.config as whole
+//! targets, like `dothub link`, and everything else file by file.
+
+use crate::conflict::{self, Resolution};
+use crate::git;
+use crate::output::{self, Tone};
+use crate::state::{self, RepoSource, State};
+use anyhow::{bail, Context, Result};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Something to link: store path, target name and target path.
+struct Unit {
+    source: PathBuf,
+    target_name: String,
+    target: PathBuf,
+}
+
+pub fn cmd_import_bare(
+    git_dir: &Path,
+    name: &str,
+    work_tree: Option<&Path>,
+    keep_remote: bool,
+) -> Result<()> {
+    git::ensure_git()?;
+    crate::ensure_store_dir()?;
+    let git_dir = git_dir
+        .canonicalize()
+        .with_context(|| format!("Git directory not found: {}", git_dir.display()))?;
+    let work_tree = match work_tree {
+        Some(dir) => dir.to_path_buf(),
+        None => dirs::home_dir().context("Unable to determine home directory")?,
+    };
+    let dest = crate::dothub_dir().join(name);
+    if dest.exists() {
+        bail!(crate::error::Error::new(
+            crate::error::Code::RepoExists,
+            format!("{} already exists in the store", name),
+        )
+        .hint("choose another name with --name"));
+    }
+
+    let bare = |args: &[&str]| {
+        let mut full = vec![
+            "--git-dir",
+            path_str(&git_dir)?,
+            "--work-tree",
+            path_str(&work_tree)?,
+        ];
+        full.extend_from_slice(args);
+        git::output(&work_tree, &full)
+    };
+    let tracked: Vec<String> = bare(&["ls-files", "-z"])
+        .with_context(|| format!("{} is not a git directory", git_dir.display()))?
+        .split('\0')
+        .filter(|p| !p.is_empty())
+        .map(str::to_string)
+        .collect();
+    if tracked.is_empty() {
+        bail!("{} does not track any files", git_dir.display());
+    }
+    let remote = bare(&["remote", "get-url", "origin"]).ok();
+    if keep_remote && remote.is_none() {
+        bail!("{} has no origin remote to keep", git_dir.display());
+    }
+
+    git::output(
+        Path::new("."),
+        &["clone", "-q", path_str(&git_dir)?, path_str(&dest)?],
+    )?;
+    match remote.as_deref().filter(|_| keep_remote) {
+        Some(url) => {
+            git::output(&dest, &["remote", "set-url", "origin", url])?;
+            // The clone tracked the bare repo's branch; track the remote's instead
+            let _ = git::network(&dest, &["fetch", "-q", "origin"], false);
+        }
+        None => {
+            git::output(&dest, &["remote", "remove", "origin"])?;
+        }
+    }
+
+    // Edits not yet committed in the bare repo live only in the work tree
+    let mut carried = 0;
+    for path in &tracked {
+        let live = work_tree.join(path);
+        let stored = dest.join(path);
+        let is_file = fs::symlink_metadata(&live).is_ok_and(|md| md.file_type().is_file());
+        if is_file && state::hash_file(&live).ok() != state::hash_file(&stored).ok() {
+            if let Some(parent) = stored.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::copy(&live, &stored)
+                .with_context(|| format!("Copying {} into the store", live.display()))?;
+            carried += 1;
+        }
+    }
+
+    let mut st = State::load()?;
+    let rec = st.repos.entry(name.to_string()).or_default();
+    rec.installed_at = state::now_unix();
+    rec.source = remote
+        .filter(|_| keep_remote)
+        .map(|url| RepoSource::Git { url });
+    st.save()?;
+
+    println!(
+        "Imported {} tracked file(s) from {} into {}",
+        tracked.len(),
+        git_dir.display(),
+        dest.display()
+    );
+    if carried > 0 {
+        println!(
+            "Carried over {} uncommitted change(s); commit them in {}",
+            carried,
+            dest.display()
+        );
+    }
+
+    let (mut linked, mut skipped) = (0, 0);
+    for unit in units(&tracked, &dest, &work_tree) {
+        if conflict::is_unmanaged(&unit.target_name, &unit.target)?
+            && !same_content(&unit.target, &unit.source)
+        {
+            match conflict::prompt(&unit.target, &unit.source)? {
+                Resolution::Skip => {
+                    println!(
+                        "{}",
+                        output::paint(
+                            &crate::i18n::t(
+                                "skipped-unchanged",
+                                &[("target", &unit.target.display())]
+                            ),
+                            Tone::Warn
+                        )
+                    );
+                    skipped += 1;
+                    continue;
+                }
+                resolution => println!(
+                    "{}",
+                    conflict::resolve(resolution, &unit.target_name, &unit.target)?
+                ),
+            }
+        }
+        println!(
+            "{}",
+            crate::deploy(name, &unit.source, &unit.target_name, &unit.target, false)?
+        );
+        linked += 1;
+    }
+
+    println!(
+        "{}",
+        output::paint(
+            &format!("Linked {} target(s), skipped {}.", linked, skipped),
+            Tone::Ok
+        )
+    );
+    if keep_remote {
+        println!(
+            "Commits pushed from {} go to the same remote as before.",
+            dest.display()
+        );
+    }
+    println!(
+        "{} is no longer needed once you have checked the result.",
+        git_dir.display()
+    );
+    Ok(())
+}
+
+/// Group tracked paths into link targets. A directory under ~/.config is
+/// linked whole; anything else is linked file by file, named by its path
+/// relative to the work tree.
+fn units(tracked: &[String], dest: &Path, work_tree: &Path) -> Vec<Unit> {
+    let mut out = BTreeMap::new();
+    for path in tracked {
+        let (target_name, rel) = match path.strip_prefix(".config/") {
+            Some(rest) => {
+                let top = rest.split('/').next().unwrap_or(rest);
+                (top.to_string(), format!(".config/{}", top))
+            }
+            None => (path.clone(), path.clone()),
+        };
+        out.insert(target_name, rel);
+    }
+    out.into_iter()
+        .map(|(target_name, rel)| Unit {
+            source: dest.join(&rel),
+            target: work_tree.join(&rel),
+            target_name,
+        })
+        .collect()
+}
+
+/// True when `target` holds exactly the files about to be linked in its place,
+/// so replacing it loses nothing.
+fn same_content(target: &Path, source: &Path) -> bool {
+    match (state::hash_tree(target), state::hash_tree(source)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+fn path_str(path: &Path) -> Result<&str> {
+    path.to_str()
+        .with_context(|| format!("Path is not valid UTF-8: {}", path.display()))
+}
@@ -0,0 +1,147 @@
+//! Repos that keep wallpapers, fonts and other large files in Git LFS.
+//!
+//! A clone made without git-lfs, or before it was set up, has a three-line
+//! pointer file where each such file should be. A repo uses LFS when one of
+//! its .gitattributes routes paths through `filter=lfs`; for those, install
+//! and update fetch the real files with `git lfs pull`, setting up the LFS
+//! filters in the clone first when git has none. Without git-lfs the pointers
+//! are left and reported, and `dothub status` counts the ones still there.
+
+use crate::git;
+use crate::output::{self, Tone};
+use crate::vcs::Update;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// How every LFS pointer file starts.
+const POINTER: &[u8] = b"version https://git-lfs.github.com/spec/v1";
+/// Pointers are a little over 100 bytes; anything larger is real content.
+const MAX_POINTER: u64 = 1024;
+
+/// What fetching a repo's LFS files did.
+pub enum Fetched {
+    /// The repo doesn't use LFS, or has no pointers left
+    Nothing,
+    Files(usize),
+    /// git-lfs isn't installed; this many pointers are left
+    Missing(usize),
+    Failed(usize, String),
+}
+
+impl Fetched {
+    /// A line about it, and how it should look.
+    pub fn note(&self) -> Option<(String, Tone)> {
+        match self {
+            Fetched::Nothing => None,
+            Fetched::Files(n) => Some((format!("fetched {} Git LFS file(s)", n), Tone::Ok)),
+            Fetched::Missing(n) => Some((
+                format!(
+                    "{} file(s) are Git LFS pointers; install git-lfs and run dothub update to fetch them",
+                    n
+                ),
+                Tone::Warn,
+            )),
+            Fetched::Failed(n, e) => Some((
+                format!("{} Git LFS file(s) are still pointers: {}", n, e),
+                Tone::Warn,
+            )),
+        }
+    }
+}
+
+/// Whether a .gitattributes in the repo at `path` sends files through LFS.
+pub fn uses(path: &Path) -> bool {
+    let Ok(listed) = git::output(path, &["ls-files", ":(glob)**/.gitattributes"]) else {
+        return false;
+    };
+    listed
+        .lines()
+        .any(|rel| fs::read_to_string(path.join(rel)).is_ok_and(|text| text.contains("filter=lfs")))
+}
+
+/// Tracked files in the repo at `path` that are still LFS pointers.
+pub fn pointers(path: &Path) -> Vec<PathBuf> {
+    if !uses(path) {
+        return Vec::new();
+    }
+    let Ok(listed) = git::output(path, &["ls-files"]) else {
+        return Vec::new();
+    };
+    listed
+        .lines()
+        .map(|rel| path.join(rel))
+        .filter(|file| is_pointer(file))
+        .collect()
+}
+
+fn is_pointer(file: &Path) -> bool {
+    let Ok(md) = fs::symlink_metadata(file) else {
+        return false;
+    };
+    if !md.is_file() || md.len() > MAX_POINTER {
+        return false;
+    }
+    let mut start = [0u8; POINTER.len()];
+    fs::File::open(file)
+        .and_then(|mut f| f.read_exact(&mut start))
+        .is_ok_and(|_| start == POINTER)
+}
+
+/// Replace the LFS pointers in the clone at `path` with the files they stand
+/// for.
+pub fn fetch(path: &Path) -> Fetched {
+    let before = pointers(path).len();
+    if before == 0 {
+        return Fetched::Nothing;
+    }
+    if which::which("git-lfs").is_err() {
+        return Fetched::Missing(before);
+    }
+    // Without the filters git would show every fetched file as modified
+    if git::output(path, &["config", "--get", "filter.lfs.smudge"]).is_err() {
+        if let Err(e) = git::output(path, &["lfs", "install", "--local"]) {
+            return Fetched::Failed(before, e.to_string());
+        }
+    }
+    if let Err(e) = git::network(path, &["lfs", "pull"], false) {
+        return Fetched::Failed(before, e.to_string());
+    }
+    match pointers(path).len() {
+        0 => Fetched::Files(before),
+        left => Fetched::Failed(left, "the LFS server didn't have them".to_string()),
+    }
+}
+
+/// Fetch the LFS files of the clone at `path` after installing it, and say so.
+pub fn after_install(path: &Path) {
+    if let Some((note, tone)) = fetch(path).note() {
+        println!("{}", output::paint(&note, tone));
+    }
+}
+
+/// `update` with the LFS files fetched after it pulled, or even when it
+/// found nothing new, so pointers left by an earlier install are filled in.
+pub fn after_update(path: &Path, update: Update) -> Update {
+    if matches!(update, Update::Skipped { .. }) {
+        return update;
+    }
+    let fetched = fetch(path);
+    let Some((note, _)) = fetched.note() else {
+        return update;
+    };
+    match (update, fetched) {
+        (Update::Updated { detail, range }, _) => Update::Updated {
+            detail: format!("{}; {}", detail, note),
+            range,
+        },
+        (Update::UpToDate, Fetched::Files(_)) => Update::Updated {
+            detail: note,
+            range: None,
+        },
+        (_, _) => Update::Skipped {
+            status: "lfs pointers",
+            detail: note,
+        },
+    }
+}
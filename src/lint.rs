@@ -0,0 +1,221 @@
+//! `dothub lint`: catch a broken config before the application reads it.
+//!
+//! Each config type can have a linter, a shell command run in the stored
+//! directory: luacheck or selene for nvim, tmux parsing the config without
+//! applying it, `fish -n` and `zsh -n`, shellcheck for bash. The first
+//! built-in one whose program is installed runs, and `[lint.commands]` in
+//! the config replaces them by type. Every TOML and YAML file is parsed as
+//! well, whatever the type, which covers alacritty, starship, helix and the
+//! like. With `before_commit = true` under `[lint]`, `dothub edit` lints
+//! before it commits.
+
+use crate::config::Config;
+use crate::manifest::RepoManifest;
+use crate::output::{self, Tone};
+use crate::state::State;
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Config type, program that has to be installed, and the shell command to
+/// run in the stored directory, in order of preference.
+const BUILTIN: &[(&str, &str, &str)] = &[
+    ("nvim", "luacheck", "luacheck --globals vim --no-color -q ."),
+    ("nvim", "selene", "selene --display-style quiet ."),
+    (
+        "tmux",
+        "tmux",
+        "for f in tmux.conf .tmux.conf; do [ -f \"$f\" ] && \
+         exec tmux -L dothub-lint -f /dev/null start-server \\; source-file -n \"$f\"; done; true",
+    ),
+    (
+        "fish",
+        "fish",
+        "for f in $(find . -name '*.fish' -not -path './.git/*'); do fish -n \"$f\" || s=1; done; \
+         exit ${s:-0}",
+    ),
+    (
+        "zsh",
+        "zsh",
+        "for f in $(find . \\( -name '*.zsh' -o -name '.zshrc' -o -name zshrc -o -name '.zshenv' \
+         -o -name '.zprofile' \\) -not -path './.git/*'); do zsh -n \"$f\" || s=1; done; exit ${s:-0}",
+    ),
+    (
+        "bash",
+        "shellcheck",
+        "shellcheck -s bash $(find . \\( -name '*.sh' -o -name '*.bash' -o -name '.bashrc' \
+         -o -name bashrc -o -name '.bash_profile' \\) -not -path './.git/*')",
+    ),
+];
+
+/// How one check went.
+enum Outcome {
+    Passed,
+    Failed(String),
+    /// Why it didn't run
+    Skipped(String),
+}
+
+/// `dothub lint <target>`: a deployed target, or a stored repo by name.
+pub fn cmd_lint(target_name: &str) -> Result<()> {
+    let st = State::load()?;
+    let (dir, repo) = match st.links.get(target_name) {
+        Some(rec) => (
+            rec.source.clone(),
+            crate::dothub_dir().join(rec.name.split('/').next().unwrap_or(&rec.name)),
+        ),
+        None if crate::dothub_dir().join(target_name).is_dir() => {
+            let repo = crate::dothub_dir().join(target_name);
+            (repo.clone(), repo)
+        }
+        None => bail!(
+            "{} is neither a deployed target nor a stored repo",
+            target_name
+        ),
+    };
+    if !check(&dir, &config_type(&repo, target_name))? {
+        bail!("{} has lint problems", target_name);
+    }
+    Ok(())
+}
+
+/// The config type of `target_name` deployed from `repo`: the one the
+/// repo's manifest declares, or the target's own name.
+pub fn config_type(repo: &Path, target_name: &str) -> String {
+    RepoManifest::load(repo)
+        .ok()
+        .flatten()
+        .and_then(|m| m.config_type.or(m.target))
+        .unwrap_or_else(|| target_name.to_string())
+}
+
+/// Lint `dir` as a config of `config_type`, printing each check. Returns
+/// whether nothing failed.
+pub fn check(dir: &Path, config_type: &str) -> Result<bool> {
+    let mut outcomes = vec![("syntax".to_string(), parse_files(dir))];
+    let configured = Config::load()?.lint.commands.get(config_type).cloned();
+    match configured {
+        Some(command) if command.trim().is_empty() => {}
+        Some(command) => outcomes.push((command.clone(), run(dir, &command)?)),
+        None => {
+            let builtin = BUILTIN
+                .iter()
+                .filter(|(t, _, _)| *t == config_type)
+                .collect::<Vec<_>>();
+            match builtin
+                .iter()
+                .find(|(_, program, _)| which::which(program).is_ok())
+            {
+                Some((_, program, command)) => {
+                    outcomes.push((program.to_string(), run(dir, command)?))
+                }
+                None if !builtin.is_empty() => {
+                    let programs = builtin.iter().map(|(_, p, _)| *p).collect::<Vec<_>>();
+                    outcomes.push((
+                        programs.join(" or "),
+                        Outcome::Skipped("not installed".to_string()),
+                    ));
+                }
+                None => {}
+            }
+        }
+    }
+
+    let mut passed = true;
+    for (name, outcome) in outcomes {
+        match outcome {
+            Outcome::Passed => {
+                println!("{}", output::paint(&format!("ok       {}", name), Tone::Ok))
+            }
+            Outcome::Skipped(why) => println!(
+                "{}",
+                output::paint(&format!("skipped  {} ({})", name, why), Tone::Muted)
+            ),
+            Outcome::Failed(report) => {
+                passed = false;
+                println!(
+                    "{}",
+                    output::paint(&format!("failed   {}", name), Tone::Error)
+                );
+                for line in report.lines() {
+                    println!("  {}", line);
+                }
+            }
+        }
+    }
+    Ok(passed)
+}
+
+/// Run a linter command in `dir`; its output is the report when it fails.
+fn run(dir: &Path, command: &str) -> Result<Outcome> {
+    let out = Command::new("sh")
+        .args(["-c", command])
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .output()
+        .context("Running sh")?;
+    if out.status.success() {
+        return Ok(Outcome::Passed);
+    }
+    let report = format!(
+        "{}{}",
+        String::from_utf8_lossy(&out.stdout),
+        String::from_utf8_lossy(&out.stderr)
+    );
+    Ok(Outcome::Failed(report.trim().to_string()))
+}
+
+/// Parse every TOML and YAML file under `dir`.
+fn parse_files(dir: &Path) -> Outcome {
+    let mut files = Vec::new();
+    collect(dir, &mut files);
+    if files.is_empty() {
+        return Outcome::Skipped("no TOML or YAML files".to_string());
+    }
+    let mut errors = Vec::new();
+    for file in &files {
+        let Ok(text) = fs::read_to_string(file) else {
+            continue;
+        };
+        let rel = file.strip_prefix(dir).unwrap_or(file).display();
+        let error = match file.extension().and_then(|e| e.to_str()) {
+            Some("toml") => toml::from_str::<toml::Value>(&text)
+                .err()
+                .map(|e| e.to_string()),
+            _ => serde_yaml::from_str::<serde_yaml::Value>(&text)
+                .err()
+                .map(|e| e.to_string()),
+        };
+        if let Some(error) = error {
+            errors.push(format!("{}: {}", rel, error.trim()));
+        }
+    }
+    if errors.is_empty() {
+        Outcome::Passed
+    } else {
+        Outcome::Failed(errors.join("\n"))
+    }
+}
+
+fn collect(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Ok(kind) = entry.file_type() else {
+            continue;
+        };
+        if kind.is_dir() && entry.file_name() != ".git" {
+            collect(&path, files);
+        } else if kind.is_file()
+            && matches!(
+                path.extension().and_then(|e| e.to_str()),
+                Some("toml" | "yml" | "yaml")
+            )
+        {
+            files.push(path);
+        }
+    }
+}
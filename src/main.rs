@@ -1,38 +1,142 @@
 use anyhow::{bail, Context, Result};
-use clap::{Args, Parser, Subcommand, ValueEnum};
+use clap::{Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
 use clap_complete::{generate, shells};
-use std::collections::HashMap;
-use comfy_table::{Table, presets::UTF8_BORDERS_ONLY, modifiers::UTF8_ROUND_CORNERS};
+use std::collections::BTreeMap;
+use comfy_table::Cell;
 use std::env;
-use std::sync::{Arc, atomic::{AtomicBool, Ordering}};
-use std::thread;
-use std::time::Duration;
 use std::fs;
 use std::io;
-use std::io::Write;
+use std::num::NonZeroUsize;
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
+
+mod anonymize;
+mod apply;
+mod archive;
+mod assets;
+mod audit;
+mod auth;
+mod backups;
+mod bench;
+mod blame;
+mod bundle;
+mod changelog;
+mod compare;
+mod compat;
+mod config;
+mod conflict;
+mod daemon;
+mod doctor;
+mod edit;
+mod error;
+mod export;
+mod exports;
+mod favorites;
+mod forge;
+mod git;
+mod grep;
+mod help;
+mod http;
+mod i18n;
+mod hub;
+mod import;
+mod lfs;
+mod lint;
+mod manifest;
+mod modes;
+mod markdown;
+mod metrics;
+mod mirror;
+mod network;
+mod org;
+mod output;
+mod owner;
+mod package;
+mod perf;
+mod prompt;
+mod protect;
+mod publish;
+mod readonly;
+mod recover;
+mod reload;
+mod remote;
+mod repair;
+mod resolve;
+mod reviews;
+mod sandbox;
+mod screenshot;
+mod secrets;
+mod selinux;
+mod shared;
+mod shell;
+mod signature;
+mod snapshot;
+mod source;
+mod startup;
+mod state;
+mod stats;
+mod status;
+mod tags;
+mod targets;
+mod templates;
+mod trending;
+mod trust;
+mod tui;
+mod units;
+mod usage;
+mod vcs;
+mod vendor;
+mod verify;
+mod verify_repo;
+mod vfs;
+mod which;
+
+use output::Tone;
+use state::{DeployMode, LinkRecord, RepoSource, State};
 
 const DEFAULT_DOTHUB_PATH: &str = ".local/share/dothub";
-const DEFAULT_HUB_URL: &str =
-    "https://raw.githubusercontent.com/huncholane/dothub/main/hub.yml";
-const GH_TOKEN_HELP_URL: &str = "https://github.com/settings/personal-access-tokens";
-const ASCII_BANNER: &str = include_str!("../ascii.txt");
+
+/// Set by `link --relative`; link.relative in the config turns it on for every link.
+static RELATIVE_LINKS: AtomicBool = AtomicBool::new(false);
+/// Set by `link --read-only`; link.read_only in the config turns it on for every link.
+static READ_ONLY: AtomicBool = AtomicBool::new(false);
 
 #[derive(Parser)]
 #[command(
     name = "dothub",
     about = "Manage dotfile repos and links",
     version,
-    after_help = "Environment variables:\n  DOTHUB_DIR     Override the store directory (default: XDG data dir, e.g. ~/.local/share/dothub)\n  GITHUB_TOKEN   GitHub token to speed up star fetching via GraphQL (optional)"
+    disable_help_subcommand = true,
+    after_help = "Environment variables:\n  DOTHUB_DIR     Override the store directory (default: XDG data dir, e.g. ~/.local/share/dothub)\n  DOTHUB_STATE   Override the state file path (default: XDG state dir)\n  DOTHUB_CONFIG  Override the config file path (default: XDG config dir)\n  DOTHUB_CACHE   Override the download cache directory (default: XDG cache dir)\n  DOTHUB_SOCKET  Override the status daemon socket (default: XDG runtime dir)\n  DOTHUB_OFFLINE Set to 1 to behave as if --offline was given\n  GITHUB_TOKEN   GitHub token to speed up star fetching via GraphQL (optional; or dothub auth login github)"
 )]
 struct Cli {
     /// Optional filter: types to include (e.g. nvim, tmux). Comma-separated or space-separated.
     #[arg(value_name = "TYPE", num_args = 0.., value_delimiter = ',')]
     types: Vec<String>,
-    /// Optional override URL to YAML (defaults to https://github.com/hub.yml)
+    /// Optional override URL to YAML (defaults to the hub.yml in the dothub repository)
     #[arg(long)]
     url: Option<String>,
+    /// List hub entries whose required OS or application version doesn't match this system
+    #[arg(long)]
+    ignore_compat: bool,
+    /// Work without network access: use cached hub data, skip updates, install only local repos
+    #[arg(long, global = true)]
+    offline: bool,
+    /// Print how long network, git and filesystem work took, and record it for `dothub perf`
+    #[arg(long, global = true)]
+    profile_perf: bool,
+    /// Line-oriented output without tables, spinners, box drawing or color, for
+    /// screen readers and logs (also on with TERM=dumb or plain = true in the config)
+    #[arg(long, global = true)]
+    plain: bool,
+    /// How to print a failure: text with a hint, or a JSON object with its code
+    #[arg(long, global = true, value_enum, default_value_t = error::ErrorFormat::Text)]
+    error_format: error::ErrorFormat,
+    /// Make dothub's own commits (edit, adopting a target, new) unsigned, even
+    /// when git is set to sign them with commit.gpgsign
+    #[arg(long, global = true)]
+    no_sign: bool,
 
     #[command(subcommand)]
     command: Option<Commands>,
@@ -44,44 +148,815 @@ enum Commands {
     Install(InstallArgs),
     /// Replace ~/.config/<target> with a symlink to a stored repo
     Link(LinkArgs),
+    /// Install and link everything in a manifest, optionally via a reviewed plan file
+    Apply(ApplyArgs),
+    /// Restore what the last apply changed from the snapshot taken before it
+    Undo {
+        /// Restore without asking
+        #[arg(long, short = 'y')]
+        yes: bool,
+    },
+    /// Apply, update and remove manifests published by your organization
+    Org {
+        #[command(subcommand)]
+        action: OrgCommand,
+    },
+    /// Keep forge tokens in the OS keychain for API calls and private clones
+    Auth {
+        #[command(subcommand)]
+        action: AuthCommand,
+    },
+    /// More views of the hub index (the bare `dothub` lists it)
+    Hub {
+        #[command(subcommand)]
+        action: HubCommand,
+    },
+    /// Install an application and a config for it together
+    Bundle {
+        #[command(subcommand)]
+        action: BundleCommand,
+    },
+    /// List, prune and restore the backups of replaced targets
+    Backups {
+        #[command(subcommand)]
+        action: BackupsCommand,
+    },
+    /// Migrate an existing dotfiles setup into the store
+    Import {
+        #[command(subcommand)]
+        from: ImportCommand,
+    },
+    /// Write the deployed configuration out in another tool's layout
+    Export {
+        #[command(subcommand)]
+        to: ExportCommand,
+    },
+    /// Scaffold a new config repo in the store from a starter template
+    New(NewArgs),
+    /// Remove a link or copy created by dothub from ~/.config
+    Unlink(UnlinkArgs),
+    /// Open the stored config behind a target in $EDITOR, then review and commit the changes
+    Edit {
+        /// Target name (e.g. nvim) or stored repo name
+        target: String,
+    },
+    /// Run the linters for a target's config type on its stored files
+    Lint {
+        /// Target name (e.g. nvim) or stored repo name
+        target: String,
+    },
+    /// Lint, start headless and scan for secrets before publishing a config repo
+    VerifyRepo {
+        /// Stored repo name, or a path to a checkout
+        name: String,
+        /// Run these checks from the repo's pre-push hook instead
+        #[arg(long)]
+        install_hook: bool,
+    },
+    /// Keep your name, email, hostname and secrets out of a config repo's commits
+    Anonymize {
+        /// Stored repo name, or a path to a checkout
+        name: String,
+        /// Apply every transformation without asking
+        #[arg(long)]
+        yes: bool,
+    },
+    /// git filter that swaps personal values for placeholders, run by git
+    #[command(hide = true)]
+    AnonymizeFilter {
+        #[arg(value_parser = ["clean", "smudge"])]
+        direction: String,
+    },
+    /// Put a config repo on GitHub with topics and a README, ready for the hub
+    Publish {
+        /// Stored repo name, or a path to a checkout
+        name: String,
+        /// Create the GitHub repo as private
+        #[arg(long)]
+        private: bool,
+        /// Open the hub index in GitHub's editor to submit the repo
+        #[arg(long)]
+        submit: bool,
+    },
+    /// Capture the application running with a config into the repo's doc/ and README
+    Screenshot {
+        /// Target name (e.g. nvim) or stored repo name
+        target: String,
+        /// Command to start instead of the config type's application
+        #[arg(long)]
+        command: Option<String>,
+        /// Seconds to let it draw before capturing
+        #[arg(long, default_value_t = 2)]
+        delay: u64,
+    },
+    /// Show the commit and author behind a line of a deployed config
+    Blame {
+        /// Target name (e.g. nvim)
+        target: String,
+        /// File inside the target and line number, e.g. lua/options.lua:12
+        #[arg(value_name = "FILE:LINE")]
+        location: String,
+    },
+    /// Show how a target resolves: link, store repo, subpath, commit and origin
+    Which {
+        /// Target name (e.g. nvim), or a path inside a deployed target
+        target: String,
+    },
+    /// Search every stored repo and show which matches are in linked configs
+    Grep {
+        /// Extended regular expression, as for `git grep -E`
+        pattern: String,
+        /// Match case-insensitively
+        #[arg(long, short = 'i')]
+        ignore_case: bool,
+        /// Only repos with one of these tags
+        #[arg(long, value_delimiter = ',')]
+        tag: Vec<String>,
+    },
+    /// Open the mergetool on each conflicted file of a stopped merge or rebase, then continue it
+    Resolve {
+        /// Repository name stored under dothub
+        name: String,
+        /// Mergetool to use instead of git's merge.tool
+        #[arg(long)]
+        tool: Option<String>,
+    },
     /// Pull latest changes for all stored repos
-    Update,
+    Update {
+        /// Only repos with one of these tags
+        #[arg(long, value_delimiter = ',')]
+        tag: Vec<String>,
+        /// Also update repos whose schedule says they are not due
+        #[arg(long)]
+        force: bool,
+        /// What to show about pulled commits afterwards
+        #[arg(long, value_enum, default_value = "summary")]
+        changelog: changelog::Changelog,
+        /// Don't reload running applications whose config was updated
+        #[arg(long)]
+        no_reload: bool,
+        /// Follow upstreams that renamed their branch or force-pushed without asking:
+        /// re-track the new branch, or reset with local changes stashed
+        #[arg(long)]
+        recover: bool,
+        /// Pull the repos in the shared store instead of yours (needs write access to it)
+        #[arg(long, conflicts_with_all = ["tag", "force"])]
+        shared: bool,
+    },
+    /// Set how often `dothub update` pulls a repo
+    Schedule {
+        /// Repository name stored under dothub
+        name: String,
+        frequency: state::Frequency,
+    },
+    /// Set how far a repo's hooks are confined: strict (no network), network or off
+    Sandbox {
+        /// Repository name stored under dothub
+        name: String,
+        level: state::Sandbox,
+    },
+    /// Show or set whether a repo may run its hooks and units: untrusted, prompt or trusted
+    Trust {
+        /// Repository name stored under dothub
+        name: String,
+        level: Option<state::Trust>,
+    },
+    /// Interactive dashboard for installed repos and the hub
+    Dashboard(DashboardArgs),
+    /// Show git status (branch, local changes, upstream drift) for stored repos
+    Status {
+        /// Only repos with one of these tags
+        #[arg(long, value_delimiter = ',')]
+        tag: Vec<String>,
+        /// Run git in every repo instead of reusing status cached for unchanged ones
+        #[arg(long)]
+        refresh: bool,
+    },
+    /// Print a short status string for shell prompts (repos behind and dirty)
+    Prompt(PromptArgs),
+    /// Keep repo status fresh in the background and serve it to other dothub commands
+    Daemon(DaemonArgs),
+    /// Show the slowest repos and hosts recorded by --profile-perf runs, or time a target starting
+    Perf(PerfArgs),
+    /// Time status scanning, plan generation and hub parsing on a synthetic store
+    #[command(hide = true)]
+    Bench {
+        #[command(subcommand)]
+        action: BenchCommand,
+    },
+    /// Time two configs for the same application starting, and compare them side by side
+    Compare {
+        /// Config type, e.g. nvim
+        target: String,
+        /// Stored repo name, or a path to a checkout
+        a: String,
+        /// Stored repo name, or a path to a checkout
+        b: String,
+        /// Timed starts of each, after one untimed start
+        #[arg(long, default_value_t = 5)]
+        runs: u32,
+    },
+    /// Show local usage statistics: installs, updates, upstream activity and deploy ages
+    Stats {
+        /// Only show this repository
+        name: Option<String>,
+    },
     /// List active links in ~/.config that point into dothub
     Active,
     /// List repositories installed in the dothub store
-    List,
+    List {
+        /// Only repos with one of these tags
+        #[arg(long, value_delimiter = ',')]
+        tag: Vec<String>,
+        /// Ask git for each origin instead of reusing cached ones
+        #[arg(long)]
+        refresh: bool,
+    },
+    /// Tag a stored repo (e.g. terminal, editor, work) for --tag filters, or show its tags
+    Tag {
+        /// Repository name stored under dothub
+        name: String,
+        tags: Vec<String>,
+        /// Remove the tags instead of adding them
+        #[arg(long)]
+        remove: bool,
+    },
     /// Remove a repository from the dothub store
     Remove(RemoveArgs),
+    /// Unlink a repo and move it to the store's archive, or list the archive
+    Archive {
+        /// Repository name stored under dothub; lists the archive without one
+        name: Option<String>,
+        /// Why it was archived, shown in the list
+        #[arg(long)]
+        reason: Option<String>,
+    },
+    /// Move an archived repo back into the store
+    Unarchive { name: String },
+    /// Mirror a repo to your own remote and point the store clone at the mirror
+    Vendor(VendorArgs),
+    /// Change where a stored repo updates from
+    Remote {
+        #[command(subcommand)]
+        action: RemoteCommand,
+    },
+    /// Check deployed targets for changes made outside dothub
+    Verify,
+    /// Repoint the symlinks left broken by moving the store
+    Repair(RepairArgs),
+    /// Scan a stored repo for red flags (executables, curl | sh, secrets) before linking it
+    Audit {
+        /// Repository name stored under dothub
+        name: String,
+        /// Accept the findings so the repo can be linked
+        #[arg(long)]
+        accept: bool,
+    },
+    /// Print Prometheus metrics (repos, drift, broken links, last update) for node_exporter
+    Metrics(MetricsArgs),
+    /// Diagnose the setup and suggest cleanups (stale repos, missing apps, drift)
+    Doctor(DoctorArgs),
     /// Generate shell completions to stdout (bash|zsh|fish|powershell|elvish)
     Completions { shell: Shell },
+    /// Print the store path behind a target or repo (the store itself without one)
+    Path {
+        /// Target name (e.g. nvim) or stored repo name
+        name: Option<String>,
+    },
+    /// Change into the repo behind a target; needs the function from `dothub shell-init`
+    Cd { name: Option<String> },
+    /// Print the shell integration (completions, `dothub cd`, prompt), or install it into the rc file
+    ShellInit(ShellInitArgs),
+    /// Write man pages for dothub, its subcommands and help topics into a directory
+    Manpages { dir: PathBuf },
+    /// Build release artifacts from this binary: tarball, Homebrew formula, AUR PKGBUILD, deb
+    Package(PackageArgs),
+    /// Show help for a command or a topic (store, links, state, hub)
+    Help { topic: Option<String> },
 }
 
 // No separate args struct for hub; top-level args cover it
 
 #[derive(Args)]
 struct InstallArgs {
-    /// Git repository URL, e.g. https://github.com/hygo-nvim
-    repo: String,
+    /// Git repository URL (e.g. https://github.com/hygo-nvim), hg+ or jj+ URL
+    /// for Mercurial or jujutsu, local directory, or tarball/zip (path or URL)
+    #[arg(required_unless_present = "favorites")]
+    repo: Option<String>,
     /// Optional explicit name for the repo in the store
     /// Example: dothub install https://github.com/foo/bar my-bar
     name: Option<String>,
+    /// Copy a local directory into the store instead of symlinking it
+    #[arg(long)]
+    copy: bool,
+    /// Link the repo to ~/.config/<TARGET> after installing
+    #[arg(long, value_name = "TARGET")]
+    link: Option<String>,
+    /// Install every hub entry starred with `dothub hub star`
+    #[arg(long, conflicts_with_all = ["repo", "name", "copy", "link"])]
+    favorites: bool,
+    /// Install even when the hub says the config needs another OS or a newer application
+    #[arg(long)]
+    ignore_compat: bool,
+    /// Hub index to check compatibility against (defaults to the hub.yml in the dothub repository)
+    #[arg(long, value_name = "URL")]
+    hub_url: Option<String>,
+    /// Fetch with this instead of going by the URL (same as an hg+, jj+, tar+ or git+ prefix)
+    #[arg(long, value_enum, conflicts_with = "favorites")]
+    vcs: Option<vcs::Vcs>,
+    /// For a repo providing several types, link only these (comma-separated)
+    #[arg(
+        long,
+        value_name = "TYPES",
+        value_delimiter = ',',
+        conflicts_with_all = ["favorites", "link"]
+    )]
+    only: Vec<String>,
 }
 
 #[derive(Args)]
 struct LinkArgs {
     /// Repository name stored under dothub (e.g. hygo-nvim)
+    #[arg(required_unless_present = "from")]
+    name: Option<String>,
+    /// Target directory name under ~/.config (e.g. nvim, alacritty, fish).
+    /// Defaults to the target or type in the repo's dothub.toml. A glob such as
+    /// 'config/*' links every matching subdirectory under its own name
+    target: Option<String>,
+    /// Copy the repo contents instead of symlinking (checksums are recorded for `verify`)
+    #[arg(long)]
+    copy: bool,
+    /// List what would change instead of changing it
+    #[arg(long)]
+    dry_run: bool,
+    /// Point symlinks at the store with a relative path, so they survive moving
+    /// the home directory or store together (default: link.relative in the config)
+    #[arg(long, conflicts_with = "copy")]
+    relative: bool,
+    /// Write-protect the deployment and pin the repo at its current commit;
+    /// `dothub edit` unlocks it (default: link.read_only in the config)
+    #[arg(long)]
+    read_only: bool,
+    /// Don't reload running applications whose config was linked
+    #[arg(long)]
+    no_reload: bool,
+    #[command(flatten)]
+    clobber: ClobberArgs,
+    /// As root, deploy into USER's ~/.config and make the link or copy owned by
+    /// USER (and GROUP, else the user's primary group)
+    #[arg(long, value_name = "USER[:GROUP]")]
+    system: Option<String>,
+    /// Link into the sandbox of a Flatpak app, ~/.var/app/APPID/config/<target>
+    #[arg(long, value_name = "APPID", conflicts_with = "from")]
+    flatpak: Option<String>,
+    /// With --flatpak, also link the native ~/.config/<target>
+    #[arg(long, requires = "flatpak")]
+    mirror: bool,
+    /// Apply every link in a TOML file mapping targets to repos, e.g. nvim = "hygo-nvim"
+    /// or git = "dotfiles/git" for a subdirectory
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["name", "target"])]
+    from: Option<PathBuf>,
+}
+
+/// What to do with targets that exist but were not deployed by dothub. By
+/// default link asks, and fails without a terminal to ask on.
+#[derive(Args)]
+struct ClobberArgs {
+    /// Move them aside as <target>.dothub-backup-<time> and replace them
+    #[arg(long, short = 'y', visible_alias = "yes", conflicts_with_all = ["force", "no_clobber"])]
+    backup: bool,
+    /// Replace them without a backup, moving them to the trash
+    #[arg(long, conflicts_with = "no_clobber")]
+    force: bool,
+    /// Leave them alone and skip them with a warning
+    #[arg(long)]
+    no_clobber: bool,
+    /// With --force, delete them instead of moving them to the trash
+    #[arg(long, requires = "force")]
+    permanent: bool,
+}
+
+impl ClobberArgs {
+    fn apply(&self) {
+        let mode = if self.backup {
+            conflict::Clobber::Backup
+        } else if self.force {
+            conflict::Clobber::Force
+        } else if self.no_clobber {
+            conflict::Clobber::Keep
+        } else {
+            conflict::Clobber::Ask
+        };
+        conflict::set_clobber(mode);
+        conflict::set_permanent(self.permanent);
+    }
+
+    /// As `apply`, except that an unattended run backs up unless told otherwise.
+    fn apply_unattended(&self, unattended: bool) {
+        self.apply();
+        if unattended && !(self.backup || self.force || self.no_clobber) {
+            conflict::set_clobber(conflict::Clobber::Backup);
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum HubCommand {
+    /// Entries gaining stars fastest, and entries added since your last refresh
+    Trending(TrendingArgs),
+    /// Add a hub entry (URL or repo name) to your local favorites
+    Star {
+        entry: String,
+        /// Hub index to look names up in (defaults to the hub.yml in the dothub repository)
+        #[arg(long)]
+        url: Option<String>,
+    },
+    /// Remove a hub entry from your favorites
+    Unstar { entry: String },
+    /// How well maintained an entry looks: open issues, last push and release, discussions
+    Reviews {
+        /// Hub entry by URL or repo name
+        entry: String,
+        /// Ask the forge again instead of using answers cached in the last few hours
+        #[arg(long)]
+        refresh: bool,
+        /// Hub index to look names up in (defaults to the hub.yml in the dothub repository)
+        #[arg(long)]
+        url: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum AuthCommand {
+    /// Store an access token for a forge, asked for without echo
+    Login {
+        forge: forge::ForgeKind,
+        /// Read the token from stdin, e.g. from a password manager
+        #[arg(long)]
+        with_token: bool,
+    },
+    /// Show where each forge's token comes from and whose it is
+    Status,
+    /// Remove a forge's token from the keychain
+    Logout { forge: forge::ForgeKind },
+    /// git credential helper protocol, run by git during clones and pulls
+    #[command(hide = true)]
+    GitCredential { operation: String },
+}
+
+#[derive(Subcommand)]
+enum BenchCommand {
+    /// Build a store of synthetic repos, a manifest and a hub index in DIR
+    Generate {
+        dir: PathBuf,
+        /// Number of repos
+        #[arg(long, default_value_t = 100)]
+        repos: usize,
+        /// Files in each repo
+        #[arg(long, default_value_t = 20)]
+        files: usize,
+    },
+    /// Time one hot path on a store made by `dothub bench generate`
+    Run {
+        #[arg(value_enum)]
+        target: bench::Target,
+        /// Directory given to `dothub bench generate`
+        #[arg(long)]
+        dir: PathBuf,
+        /// Timed runs, after one untimed warm-up run
+        #[arg(long, default_value_t = 10)]
+        iterations: u32,
+        /// Print the timings in nanoseconds as JSON
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum OrgCommand {
+    /// Apply an organization's manifest as a layer of its own, kept apart from yours
+    Apply {
+        /// Manifest URL (https://) or file
+        url: String,
+        /// Name of the layer (defaults to the URL's host)
+        #[arg(long)]
+        name: Option<String>,
+        /// Never ask: back up conflicting targets (unless told otherwise), install git
+        /// if missing and accept the audit of the repos the manifest links
+        #[arg(long)]
+        unattended: bool,
+        #[command(flatten)]
+        clobber: ClobberArgs,
+    },
+    /// Fetch org manifests again and apply what changed
+    Update {
+        /// Only this org (default: all)
+        name: Option<String>,
+        /// Never ask, as with org apply --unattended
+        #[arg(long)]
+        unattended: bool,
+        #[command(flatten)]
+        clobber: ClobberArgs,
+    },
+    /// Unlink everything an org's manifest linked and forget it
+    Remove { name: String },
+    /// Show the org manifests applied, and what they link
+    List,
+}
+
+#[derive(Args)]
+struct TrendingArgs {
+    /// Compare with the star counts from this many days ago (or the oldest saved)
+    #[arg(long, default_value_t = 7)]
+    days: u64,
+    /// Show at most this many entries
+    #[arg(long, default_value_t = 10)]
+    limit: usize,
+    /// Hub index to use (defaults to the hub.yml in the dothub repository)
+    #[arg(long)]
+    url: Option<String>,
+}
+
+#[derive(Subcommand)]
+enum BundleCommand {
+    /// Install the application with the system package manager, then link a config for it
+    Install(BundleInstallArgs),
+}
+
+#[derive(Args)]
+struct BundleInstallArgs {
+    /// Config type (e.g. kitty, nvim, tmux)
+    config_type: String,
+    /// Config repo to use instead of choosing from the hub
+    #[arg(long)]
+    repo: Option<String>,
+    /// Package manager to use (brew, paru, yay, pacman, apt-get, dnf, zypper, apk)
+    #[arg(long)]
+    manager: Option<String>,
+    /// Hub index to choose from (defaults to the hub.yml in the dothub repository)
+    #[arg(long)]
+    url: Option<String>,
+}
+
+#[derive(Subcommand)]
+enum RemoteCommand {
+    /// Point a repo at a new upstream, e.g. after the old one was deleted or renamed
+    Set {
+        /// Repository name stored under dothub
+        name: String,
+        url: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum BackupsCommand {
+    /// List backups with their age and size, marking those past the [backup] limits
+    List {
+        /// Only backups of this target (a name or a path)
+        target: Option<String>,
+    },
+    /// Remove backups past the [backup] limits in the config
+    Prune {
+        /// Keep this many backups of each target instead of [backup] keep (at least 1)
+        #[arg(long)]
+        keep: Option<NonZeroUsize>,
+        /// Remove backups older than this many days instead of [backup] max_age_days
+        #[arg(long)]
+        max_age_days: Option<u64>,
+        /// List what would be removed instead of removing it
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Put the newest backup of a target back, unlinking what dothub deployed there
+    Restore {
+        /// Target name or path
+        target: String,
+        /// Restore the backup taken at this time (the number in its name) instead
+        #[arg(long)]
+        at: Option<u64>,
+        /// List what would change instead of changing it
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ImportCommand {
+    /// Import a bare git repo whose work tree is $HOME (the pattern yadm uses)
+    Bare(ImportBareArgs),
+}
+
+#[derive(Args)]
+struct ImportBareArgs {
+    /// The bare repository (e.g. ~/.cfg or ~/.local/share/yadm/repo.git)
+    git_dir: PathBuf,
+    /// Name for the repo in the store
+    #[arg(long, default_value = "dotfiles")]
+    name: String,
+    /// Work tree the bare repo checks out into (defaults to your home directory)
+    #[arg(long, value_name = "DIR")]
+    work_tree: Option<PathBuf>,
+    /// Keep the bare repo's origin remote so pushes from the store go to the same place
+    #[arg(long)]
+    keep_remote: bool,
+    #[command(flatten)]
+    clobber: ClobberArgs,
+}
+
+#[derive(Subcommand)]
+enum ExportCommand {
+    /// Write a GNU Stow directory with one package per repo
+    Stow {
+        /// Directory to create the packages in
+        dir: PathBuf,
+    },
+    /// Print a home-manager module (xdg.configFile / home.file) deploying the same files
+    HomeManager {
+        /// Write the module to this file instead of stdout
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+}
+
+#[derive(Args)]
+struct NewArgs {
+    /// Config type to scaffold (e.g. nvim, tmux, zsh)
+    #[arg(required_unless_present = "list")]
+    config_type: Option<String>,
+    /// Starter template to use
+    #[arg(long, default_value = "minimal")]
+    from_template: String,
+    /// Name for the new repo in the store (defaults to my-<type>)
+    #[arg(long)]
+    name: Option<String>,
+    /// Link the new repo into ~/.config right away
+    #[arg(long)]
+    link: bool,
+    /// List available templates
+    #[arg(long)]
+    list: bool,
+}
+
+#[derive(Args)]
+struct UnlinkArgs {
+    /// Target names under ~/.config to unlink (e.g. nvim)
+    #[arg(required = true)]
+    targets: Vec<String>,
+    /// List what would change instead of changing it
+    #[arg(long)]
+    dry_run: bool,
+}
+
+#[derive(Args)]
+struct DashboardArgs {
+    /// Hub index to browse (defaults to the hub.yml in the dothub repository)
+    #[arg(long, conflicts_with = "watch")]
+    url: Option<String>,
+    /// Print a plain status view that refreshes in place instead of the interactive dashboard
+    #[arg(long)]
+    watch: bool,
+    /// Seconds between refreshes with --watch
+    #[arg(long, value_name = "SECS", default_value_t = 5, requires = "watch")]
+    interval: u64,
+}
+
+#[derive(Args)]
+struct PromptArgs {
+    /// Refresh the saved status in the background once it is older than this many seconds
+    #[arg(long, value_name = "SECS", default_value_t = 300)]
+    max_age: u64,
+    /// Print a starship custom module that shows this status
+    #[arg(long)]
+    starship: bool,
+    /// Recompute the saved status now and print nothing (used by the background refresh)
+    #[arg(long, hide = true)]
+    refresh: bool,
+}
+
+#[derive(Args)]
+struct DaemonArgs {
+    /// Seconds between fetches of every repo with an upstream (at least 60)
+    #[arg(long, value_name = "SECS", default_value_t = 900)]
+    fetch_interval: u64,
+}
+
+#[derive(Args)]
+struct PerfArgs {
+    /// Time the startup of this deployed target (e.g. nvim) instead
+    target: Option<String>,
+    /// Timed starts of the target, after one untimed start
+    #[arg(long, default_value_t = 10)]
+    runs: u32,
+    /// Number of rows to show
+    #[arg(long, default_value_t = 20)]
+    limit: usize,
+    /// Forget all recorded timings
+    #[arg(long)]
+    reset: bool,
+}
+
+#[derive(Args)]
+struct MetricsArgs {
+    /// Write to this file atomically instead of stdout (for the textfile collector, use a .prom name)
+    #[arg(long, value_name = "PATH")]
+    textfile: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct ApplyArgs {
+    /// Manifest of repos and links, a file or https:// URL (default: manifest.toml
+    /// next to the config file)
+    #[arg(long, value_name = "FILE|URL", conflicts_with = "commit")]
+    manifest: Option<PathBuf>,
+    /// Only plan: write the operations to a file for review instead of running them
+    #[arg(long, value_name = "PLAN", num_args = 0..=1, default_missing_value = "dothub-plan.json")]
+    check: Option<PathBuf>,
+    /// Run exactly the operations in a plan written by --check
+    #[arg(long, value_name = "PLAN", conflicts_with = "check")]
+    commit: Option<PathBuf>,
+    /// Never ask: back up conflicting targets (unless told otherwise), install git
+    /// if missing and accept the audit of the repos the manifest links
+    #[arg(long)]
+    unattended: bool,
+    /// Run the plan recording what would change instead of changing it
+    #[arg(long, conflicts_with = "check")]
+    dry_run: bool,
+    #[command(flatten)]
+    clobber: ClobberArgs,
+}
+
+#[derive(Args)]
+struct ShellInitArgs {
+    shell: Shell,
+    /// Leave the prompt alone instead of showing `dothub prompt` in front of it
+    #[arg(long)]
+    no_prompt: bool,
+    /// Run a quiet `dothub update` in the background when a login shell starts
+    #[arg(long)]
+    auto_update: bool,
+    /// Add (or refresh) a managed block loading the integration to the shell's rc file
+    #[arg(long, conflicts_with = "uninstall")]
+    install: bool,
+    /// Remove the managed block from the shell's rc file
+    #[arg(long)]
+    uninstall: bool,
+}
+
+#[derive(Args)]
+struct PackageArgs {
+    /// Directory to write the artifacts to
+    #[arg(long, default_value = "dist")]
+    out: PathBuf,
+    /// Artifacts to build, comma-separated (default: all of them)
+    #[arg(long, value_enum, value_delimiter = ',')]
+    format: Vec<package::Format>,
+    /// Where the tarball will be published, for the formula and PKGBUILD
+    /// (default: the GitHub release for this version)
+    #[arg(long, value_name = "BASE_URL")]
+    url: Option<String>,
+}
+
+#[derive(Args)]
+struct DoctorArgs {
+    /// Flag repos whose upstream has had no commits for this many days
+    #[arg(long, default_value_t = 365)]
+    stale_days: u64,
+    /// Convert absolute links into the store to relative ones
+    #[arg(long)]
+    relativize: bool,
+}
+
+#[derive(Args)]
+struct VendorArgs {
+    /// Repository name stored under dothub
     name: String,
-    /// Target directory name under ~/.config (e.g. nvim, alacritty, fish)
-    target: String,
+    /// Mirror URL (defaults to vendor.url_template from the config file)
+    #[arg(long)]
+    url: Option<String>,
 }
 
 #[derive(Args)]
 struct RemoveArgs {
     /// Repository name stored under dothub to remove
     name: String,
+    /// List what would be removed instead of removing it
+    #[arg(long)]
+    dry_run: bool,
+}
+
+#[derive(Args)]
+struct RepairArgs {
+    /// Rewrite symlinks into the store's old location (e.g.
+    /// /usr/local/share/dotman) to point at its new one
+    #[arg(long, num_args = 2, value_names = ["OLD", "NEW"], required = true)]
+    relocate: Vec<PathBuf>,
+    /// List the links that would be rewritten instead of rewriting them
+    #[arg(long)]
+    dry_run: bool,
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+#[allow(clippy::enum_variant_names)]
 enum Shell {
     Bash,
     Zsh,
@@ -90,21 +965,301 @@ enum Shell {
     Elvish,
 }
 
-fn main() -> Result<()> {
-    let cli = Cli::parse();
+fn main() {
+    let matches = Cli::command().get_matches();
+    let cli = match Cli::from_arg_matches(&matches) {
+        Ok(cli) => cli,
+        Err(e) => e.exit(),
+    };
+    http::set_offline(cli.offline);
+    if cli.profile_perf {
+        perf::enable();
+    }
+    if cli.no_sign {
+        git::disable_signing();
+    }
+    if cli.plain || config::Config::load().is_ok_and(|c| c.plain) {
+        output::set_plain();
+    }
 
+    let error_format = cli.error_format;
+    let started = Instant::now();
+    let result = run(cli);
+    if perf::enabled() {
+        perf::report(matches.subcommand_name().unwrap_or("hub"), started.elapsed());
+    }
+    if let Err(e) = result {
+        error::report(&e, error_format);
+        std::process::exit(1);
+    }
+}
+
+fn run(cli: Cli) -> Result<()> {
     match cli.command {
-        Some(Commands::Install(args)) => cmd_install(&args.repo, args.name.as_deref()),
-        Some(Commands::Link(args)) => cmd_link(&args.name, &args.target),
-        Some(Commands::Update) => cmd_update(),
+        Some(Commands::Install(args)) => cmd_install(&args),
+        Some(Commands::Link(args)) => {
+            if args.relative {
+                RELATIVE_LINKS.store(true, Ordering::SeqCst);
+            }
+            if args.read_only {
+                READ_ONLY.store(true, Ordering::SeqCst);
+            }
+            if args.no_reload {
+                reload::disable();
+            }
+            if let Some(spec) = &args.system {
+                owner::set(spec)?;
+            }
+            args.clobber.apply();
+            with_dry_run(args.dry_run, || match (&args.from, &args.name) {
+                (Some(file), _) => cmd_link_from(file, args.copy),
+                (None, Some(name)) => match &args.flatpak {
+                    Some(app_id) => cmd_link_flatpak(
+                        name,
+                        args.target.as_deref(),
+                        app_id,
+                        args.mirror,
+                        args.copy,
+                    ),
+                    None => cmd_link(name, args.target.as_deref(), args.copy),
+                },
+                (None, None) => unreachable!("clap requires a name without --from"),
+            })
+        }
+        Some(Commands::Apply(args)) => {
+            args.clobber.apply_unattended(args.unattended);
+            with_dry_run(args.dry_run, || {
+                apply::cmd_apply(
+                    args.manifest.as_deref(),
+                    args.check.as_deref(),
+                    args.commit.as_deref(),
+                    args.unattended,
+                )
+            })
+        }
+        Some(Commands::Undo { yes }) => snapshot::cmd_undo(yes),
+        Some(Commands::Org { action }) => match action {
+            OrgCommand::Apply {
+                url,
+                name,
+                unattended,
+                clobber,
+            } => {
+                clobber.apply_unattended(unattended);
+                org::cmd_org_apply(&url, name.as_deref(), unattended)
+            }
+            OrgCommand::Update {
+                name,
+                unattended,
+                clobber,
+            } => {
+                clobber.apply_unattended(unattended);
+                org::cmd_org_update(name.as_deref(), unattended)
+            }
+            OrgCommand::Remove { name } => org::cmd_org_remove(&name),
+            OrgCommand::List => org::cmd_org_list(),
+        },
+        Some(Commands::Auth { action }) => match action {
+            AuthCommand::Login { forge, with_token } => auth::cmd_auth_login(forge, with_token),
+            AuthCommand::Status => auth::cmd_auth_status(),
+            AuthCommand::Logout { forge } => auth::cmd_auth_logout(forge),
+            AuthCommand::GitCredential { operation } => auth::cmd_git_credential(&operation),
+        },
+        Some(Commands::Hub { action }) => match action {
+            HubCommand::Trending(args) => {
+                trending::cmd_trending(args.url.as_deref(), args.days, args.limit)
+            }
+            HubCommand::Star { entry, url } => favorites::cmd_star(&entry, url.as_deref(), false),
+            HubCommand::Unstar { entry } => favorites::cmd_star(&entry, None, true),
+            HubCommand::Reviews {
+                entry,
+                refresh,
+                url,
+            } => reviews::cmd_reviews(&entry, url.as_deref(), refresh),
+        },
+        Some(Commands::Bundle { action }) => match action {
+            BundleCommand::Install(args) => bundle::cmd_bundle_install(
+                &args.config_type,
+                args.repo.as_deref(),
+                args.manager.as_deref(),
+                args.url.as_deref(),
+            ),
+        },
+        Some(Commands::Backups { action }) => match action {
+            BackupsCommand::List { target } => backups::cmd_list(target.as_deref()),
+            BackupsCommand::Prune {
+                keep,
+                max_age_days,
+                dry_run,
+            } => with_dry_run(dry_run, || backups::cmd_prune(keep, max_age_days)),
+            BackupsCommand::Restore { target, at, dry_run } => {
+                with_dry_run(dry_run, || backups::cmd_restore(&target, at))
+            }
+        },
+        Some(Commands::Import { from }) => match from {
+            ImportCommand::Bare(args) => {
+                args.clobber.apply();
+                import::cmd_import_bare(
+                    &args.git_dir,
+                    &args.name,
+                    args.work_tree.as_deref(),
+                    args.keep_remote,
+                )
+            }
+        },
+        Some(Commands::Export { to }) => match to {
+            ExportCommand::Stow { dir } => export::cmd_export_stow(&dir),
+            ExportCommand::HomeManager { out } => export::cmd_export_home_manager(out.as_deref()),
+        },
+        Some(Commands::New(args)) => match args.config_type.as_deref() {
+            Some(ty) if !args.list => templates::cmd_new(
+                ty,
+                &args.from_template,
+                args.name.as_deref(),
+                args.link,
+            ),
+            _ => {
+                templates::cmd_templates();
+                Ok(())
+            }
+        },
+        Some(Commands::Update {
+            tag,
+            force,
+            changelog,
+            no_reload,
+            recover,
+            shared,
+        }) => {
+            if no_reload {
+                reload::disable();
+            }
+            if recover {
+                recover::enable();
+            }
+            if shared {
+                shared::cmd_update()
+            } else {
+                cmd_update(&tag, force, changelog)
+            }
+        }
+        Some(Commands::Schedule { name, frequency }) => cmd_schedule(&name, frequency),
+        Some(Commands::Sandbox { name, level }) => sandbox::cmd_sandbox(&name, level),
+        Some(Commands::Trust { name, level }) => trust::cmd_trust(&name, level),
+        Some(Commands::Dashboard(args)) if args.watch => status::cmd_watch(args.interval),
+        Some(Commands::Dashboard(args)) => tui::cmd_dashboard(args.url),
+        Some(Commands::Status { tag, refresh }) => {
+            status::set_refresh(refresh);
+            status::cmd_status(&tag)
+        }
+        Some(Commands::Prompt(args)) => prompt::cmd_prompt(args.max_age, args.refresh, args.starship),
+        Some(Commands::Daemon(args)) => daemon::cmd_daemon(args.fetch_interval),
+        Some(Commands::Perf(args)) => match args.target.as_deref() {
+            Some(target) => startup::cmd_startup(target, args.runs),
+            None => perf::cmd_perf(args.limit, args.reset),
+        },
+        Some(Commands::Bench { action }) => match action {
+            BenchCommand::Generate { dir, repos, files } => {
+                bench::cmd_bench_generate(&dir, repos, files)
+            }
+            BenchCommand::Run {
+                target,
+                dir,
+                iterations,
+                json,
+            } => bench::cmd_bench_run(target, &dir, iterations, json),
+        },
+        Some(Commands::Compare { target, a, b, runs }) => {
+            compare::cmd_compare(&target, &a, &b, runs)
+        }
+        Some(Commands::Stats { name }) => stats::cmd_stats(name.as_deref()),
         Some(Commands::Active) => cmd_active(),
-        Some(Commands::List) => cmd_list(),
-        Some(Commands::Remove(args)) => cmd_remove(&args.name),
+        Some(Commands::List { tag, refresh }) => {
+            status::set_refresh(refresh);
+            cmd_list(&tag)
+        }
+        Some(Commands::Tag { name, tags, remove }) => tags::cmd_tag(&name, &tags, remove),
+        Some(Commands::Remove(args)) => with_dry_run(args.dry_run, || cmd_remove(&args.name)),
+        Some(Commands::Archive { name, reason }) => match name {
+            Some(name) => archive::cmd_archive(&name, reason.as_deref()),
+            None => archive::cmd_list(),
+        },
+        Some(Commands::Unarchive { name }) => archive::cmd_unarchive(&name),
+        Some(Commands::Vendor(args)) => vendor::cmd_vendor(&args.name, args.url.as_deref()),
+        Some(Commands::Remote { action }) => match action {
+            RemoteCommand::Set { name, url } => remote::cmd_remote_set(&name, &url),
+        },
+        Some(Commands::Unlink(args)) => {
+            with_dry_run(args.dry_run, || cmd_unlink(&args.targets))
+        }
+        Some(Commands::Edit { target }) => edit::cmd_edit(&target),
+        Some(Commands::Lint { target }) => lint::cmd_lint(&target),
+        Some(Commands::Publish {
+            name,
+            private,
+            submit,
+        }) => publish::cmd_publish(&name, private, submit),
+        Some(Commands::Screenshot {
+            target,
+            command,
+            delay,
+        }) => screenshot::cmd_screenshot(&target, command.as_deref(), delay),
+        Some(Commands::Anonymize { name, yes }) => anonymize::cmd_anonymize(&name, yes),
+        Some(Commands::AnonymizeFilter { direction }) => anonymize::cmd_filter(&direction),
+        Some(Commands::VerifyRepo { name, install_hook }) => {
+            verify_repo::cmd_verify_repo(&name, install_hook)
+        }
+        Some(Commands::Blame { target, location }) => blame::cmd_blame(&target, &location),
+        Some(Commands::Which { target }) => which::cmd_which(&target),
+        Some(Commands::Grep {
+            pattern,
+            ignore_case,
+            tag,
+        }) => grep::cmd_grep(&pattern, ignore_case, &tag),
+        Some(Commands::Resolve { name, tool }) => resolve::cmd_resolve(&name, tool.as_deref()),
+        Some(Commands::Verify) => verify::cmd_verify(),
+        Some(Commands::Repair(args)) => with_dry_run(args.dry_run, || {
+            repair::cmd_relocate(&args.relocate[0], &args.relocate[1])
+        }),
+        Some(Commands::Audit { name, accept }) => audit::cmd_audit(&name, accept),
+        Some(Commands::Metrics(args)) => metrics::cmd_metrics(args.textfile.as_deref()),
+        Some(Commands::Doctor(args)) => doctor::cmd_doctor(args.stale_days, args.relativize),
         Some(Commands::Completions { shell }) => cmd_completions(shell),
-        None => cmd_hub(cli.types, cli.url),
+        Some(Commands::Path { name }) => cmd_path(name.as_deref()),
+        Some(Commands::Cd { .. }) => bail!(
+            "dothub cd has to change your shell's directory, which only a shell function can do; \
+             add eval \"$(dothub shell-init bash)\" (or zsh, fish) to your shell's rc file"
+        ),
+        Some(Commands::ShellInit(args)) => shell::cmd_shell_init(
+            args.shell,
+            shell::Options {
+                prompt: !args.no_prompt,
+                auto_update: args.auto_update,
+            },
+            args.install,
+            args.uninstall,
+        ),
+        Some(Commands::Manpages { dir }) => help::cmd_manpages(&dir),
+        Some(Commands::Package(args)) => {
+            package::cmd_package(&args.out, &args.format, args.url.as_deref())
+        }
+        Some(Commands::Help { topic }) => help::cmd_help(topic.as_deref()),
+        None => hub::cmd_hub(cli.types, cli.url, cli.ignore_compat),
     }
 }
 
+/// Run `command`, with `dry_run` recording its changes and listing them
+/// instead of making them.
+fn with_dry_run(dry_run: bool, command: impl FnOnce() -> Result<()>) -> Result<()> {
+    if !dry_run {
+        return command();
+    }
+    vfs::start_dry_run();
+    command()?;
+    vfs::print_changes();
+    Ok(())
+}
+
 fn dothub_dir() -> PathBuf {
     if let Ok(p) = env::var("DOTHUB_DIR") {
         return PathBuf::from(p);
@@ -122,8 +1277,9 @@ fn dothub_dir() -> PathBuf {
 
 fn ensure_store_dir() -> Result<()> {
     let path = dothub_dir();
-    if !path.exists() {
-        fs::create_dir_all(&path).with_context(|| format!("Failed creating {}", path.display()))?;
+    let fs = vfs::fs();
+    if !fs.exists(&path) {
+        fs.create_dir_all(&path).map_err(|e| error::store_permission(e, &path))?;
     }
     Ok(())
 }
@@ -133,175 +1289,948 @@ fn derive_repo_name(repo_url: &str) -> String {
     trimmed.rsplit('/').next().unwrap_or(trimmed).to_string()
 }
 
-fn cmd_install(repo: &str, name_override: Option<&str>) -> Result<()> {
+fn cmd_install(args: &InstallArgs) -> Result<()> {
+    let Some(repo) = args.repo.as_deref() else {
+        return cmd_install_favorites(args.ignore_compat, args.hub_url.as_deref());
+    };
+    let repo = &source::expand_shorthand(repo);
+    let entry = check_hub(repo, args.hub_url.as_deref(), args.ignore_compat)?;
+    let backend = entry.as_ref().map(|e| e.backend()).transpose()?.flatten();
+    let repo = vcs::qualify(repo, args.vcs.or(backend));
+    let Some(name) = install_repo(&repo, args.name.as_deref(), args.copy, true)? else {
+        return Ok(());
+    };
+    println!("{}", i18n::t("installed", &[("name", &name)]));
+    if let Some(target) = args.link.as_deref() {
+        return cmd_link(&name, Some(target), false);
+    }
+    // The hub's word on what an all-in-one repo provides, else the repo's own
+    let provides = match entry.map(|e| e.provides).filter(|p| !p.is_empty()) {
+        Some(provides) => provides,
+        None => manifest::RepoManifest::load(&dothub_dir().join(&name))?
+            .map(|m| m.provides)
+            .unwrap_or_default(),
+    };
+    link_provided(&name, &provides, &args.only)
+}
+
+/// Link each type the all-in-one repo `name` provides from its directory in
+/// the repo to the target of the same name; with `only`, just those types.
+fn link_provided(name: &str, provides: &BTreeMap<String, String>, only: &[String]) -> Result<()> {
+    if let Some(missing) = only.iter().find(|t| !provides.contains_key(*t)) {
+        let known: Vec<&str> = provides.keys().map(String::as_str).collect();
+        bail!(
+            "{} doesn't provide {}; it provides {}",
+            name,
+            missing,
+            if known.is_empty() { "no types".to_string() } else { known.join(", ") }
+        );
+    }
+    let mut linked = Vec::new();
+    for (config_type, dir) in provides {
+        if !only.is_empty() && !only.contains(config_type) {
+            continue;
+        }
+        let dir = dir.trim_start_matches("./").trim_matches('/');
+        if Path::new(dir).components().any(|c| c == std::path::Component::ParentDir) {
+            bail!("{} provides {} from {}, which is outside the repo", name, config_type, dir);
+        }
+        let source = match dir {
+            "" | "." => name.to_string(),
+            dir => format!("{}/{}", name, dir),
+        };
+        cmd_link(&source, Some(config_type), false)?;
+        linked.push(config_type.as_str());
+    }
+    if linked.len() > 1 {
+        println!(
+            "{}",
+            output::paint(&format!("{} is linked as {}", name, linked.join("+")), Tone::Ok)
+        );
+    }
+    Ok(())
+}
+
+/// `dothub install --favorites`: install every favorite, going on past
+/// failures and reporting them at the end.
+fn cmd_install_favorites(ignore_compat: bool, hub_url: Option<&str>) -> Result<()> {
+    let favorites = State::load()?.favorites;
+    if favorites.is_empty() {
+        println!("No favorites yet; add some with dothub hub star <entry>");
+        return Ok(());
+    }
+    let mut failed = Vec::new();
+    for link in &favorites {
+        let result = check_hub(link, hub_url, ignore_compat).and_then(|entry| {
+            let backend = entry.map(|e| e.backend()).transpose()?.flatten();
+            install_repo(&vcs::qualify(link, backend), None, false, true)
+        });
+        match result {
+            Ok(Some(name)) => println!("{}", i18n::t("installed", &[("name", &name)])),
+            Ok(None) => {}
+            Err(e) => {
+                output::error(&format!("{}: {:#}", link, e));
+                failed.push(link.as_str());
+            }
+        }
+    }
+    if !failed.is_empty() {
+        bail!("{} of {} favorites failed to install", failed.len(), favorites.len());
+    }
+    Ok(())
+}
+
+/// Whether `url` is a path or file:// URL on this machine.
+fn is_local(url: &str) -> bool {
+    Path::new(url.strip_prefix("file://").unwrap_or(url)).exists()
+}
+
+/// Look a remote repo up in the hub before installing it: warn when the
+/// index doesn't list it, and refuse an entry that says it does not work on
+/// this system. An index with a bad signature stops the install; one that
+/// can't be fetched lets it through. Returns the details of the entry.
+fn check_hub(repo: &str, hub_url: Option<&str>, ignore_compat: bool) -> Result<Option<hub::EntryInfo>> {
+    let repo = vcs::split(repo).1;
+    if http::is_offline() || is_local(repo) {
+        return Ok(None);
+    }
+    let yaml = match hub::fetch_index(hub_url) {
+        Ok(yaml) => yaml,
+        Err(e) if error::code_of(&e) == Some(error::Code::SignatureInvalid) => return Err(e),
+        Err(_) => return Ok(None),
+    };
+    let Some(entry) = hub::find_entry(&yaml, repo)? else {
+        let index = match hub::index_trust() {
+            Some(hub::Trust::Verified) => "the verified hub index",
+            Some(hub::Trust::Unsigned) => "the hub index (which is not signed)",
+            _ => "the hub index (no signing key to verify it with)",
+        };
+        let mut warning = format!(
+            "WARNING: {} is not in {}. Make sure it is the repo you meant before running anything from it.",
+            repo, index
+        );
+        if let Some(similar) = hub::closest_entry(&yaml, repo)? {
+            warning.push_str(&format!("\nThe hub lists {}; did you mean that?", similar));
+        }
+        eprintln!("{}", output::paint(&warning, Tone::Error));
+        return Ok(None);
+    };
+    entry.info.backend().with_context(|| format!("Installing {}", repo))?;
+    if ignore_compat {
+        return Ok(Some(entry.info));
+    }
+    if let Some(problem) = compat::problem(&entry.config_type, &entry.info) {
+        bail!(error::Error::new(
+            error::Code::Incompatible,
+            format!("{} doesn't fit this system: {}", repo, problem),
+        )
+        .hint(format!("dothub install --ignore-compat {}", repo)));
+    }
+    Ok(Some(entry.info))
+}
+
+/// Install `repo` into the store and record its source. Returns the store
+/// name, or None when a repo of that name is already installed. With
+/// `progress`, steps and git's clone progress are shown on the terminal.
+fn install_repo(repo: &str, name: Option<&str>, copy: bool, progress: bool) -> Result<Option<String>> {
     ensure_store_dir()?;
+    let source = source::detect(repo, copy)?;
 
     // Determine repo name
-    let name = match name_override {
+    let name = match name {
         Some(n) if !n.trim().is_empty() => n.trim().to_string(),
-        _ => derive_repo_name(repo),
+        _ => source::derive_name(&source),
     };
     if name.is_empty() {
         bail!("Could not infer repository name from URL: {}", repo);
     }
 
     let dest = dothub_dir().join(&name);
-    if dest.exists() {
-        println!("Repo already exists: {}", dest.display());
-        return Ok(());
+    if vfs::fs().exists(&dest) {
+        if progress {
+            println!("{}", i18n::t("repo-exists", &[("path", &dest.display())]));
+        }
+        return Ok(None);
     }
+    if vfs::dry_run() {
+        vfs::note(format!("install {} into {}", repo, dest.display()));
+        vfs::pretend_made(&dest);
+        return Ok(Some(name));
+    }
+
+    vcs::backend(&source).fetch(&dest, progress)?;
 
-    // Ensure git is available
-    if which::which("git").is_err() {
-        bail!("git is not installed or not found in PATH");
+    let mut st = State::load()?;
+    let rec = st.repos.entry(name.clone()).or_default();
+    rec.installed_at = state::now_unix();
+    rec.source = Some(source);
+    st.save()?;
+    Ok(Some(name))
+}
+
+fn cmd_link(name: &str, target_name: Option<&str>, copy: bool) -> Result<()> {
+    shared::provide(name)?;
+    if let Some(pattern) = target_name.filter(|t| t.contains(['*', '?', '['])) {
+        return cmd_link_glob(name, pattern, copy);
     }
+    if target_name.is_none() {
+        // An all-in-one repo without a target of its own links everything it provides
+        if let Some(m) = manifest::RepoManifest::load(&dothub_dir().join(name))? {
+            if m.default_target().is_none() && !m.provides.is_empty() {
+                return link_provided(name, &m.provides, &[]);
+            }
+        }
+    }
+    let (source, target_name, target) = link_paths(name, target_name)?;
+    audit::check_first_link(name, true)?;
+    if conflict::is_unmanaged(&target_name, &target)? {
+        match conflict::prompt(&target, &source)? {
+            conflict::Resolution::Skip => {
+                println!(
+                    "{}",
+                    output::paint(
+                        &i18n::t("skipped-unchanged", &[("target", &target.display())]),
+                        Tone::Warn
+                    )
+                );
+                return Ok(());
+            }
+            resolution => println!("{}", conflict::resolve(resolution, &target_name, &target)?),
+        }
+    }
+    println!("{}", deploy_link(name, &source, &target_name, &target, copy)?);
+    Ok(())
+}
 
-    println!("Cloning {} -> {}", repo, dest.display());
-    let status = Command::new("git")
-        .args(["clone", repo, dest.to_string_lossy().as_ref()])
-        .status()
-        .with_context(|| "Failed to spawn git clone")?;
+/// Deploy `source` as `target_name` with everything linking a repo brings:
+/// its assets first, then, when the whole repo is linked, its systemd units,
+/// `[env]` exports and post_link hook, and last a reload of the application.
+/// Returns what `deploy` reports.
+fn deploy_link(name: &str, source: &Path, target_name: &str, target: &Path, copy: bool) -> Result<String> {
+    assets::fetch(name)?;
+    let deployed = deploy(name, source, target_name, target, copy)?;
+    if source == dothub_dir().join(name) {
+        deploy_units(name, target_name)?;
+        exports::deploy(name, target_name)?;
+        sandbox::run_hook(name, sandbox::Hook::PostLink, Some(target))?;
+    }
+    reload::after_change(target_name, target);
+    Ok(deployed)
+}
 
-    if !status.success() {
-        bail!("git clone failed with status: {}", status);
+/// `dothub link --flatpak`: link the target inside the app's sandbox, and
+/// with `mirror` the native one as well.
+fn cmd_link_flatpak(
+    name: &str,
+    target_name: Option<&str>,
+    app_id: &str,
+    mirror: bool,
+    copy: bool,
+) -> Result<()> {
+    targets::check_app_id(app_id)?;
+    if target_name.is_some_and(|t| t.contains(['*', '?', '[', '@'])) {
+        bail!("--flatpak links a single plain target");
+    }
+    let (_, target_name, _) = link_paths(name, target_name)?;
+    if mirror {
+        cmd_link(name, Some(&target_name), copy)?;
+    }
+    cmd_link(name, Some(&targets::flatpak(&target_name, app_id)), copy)
+}
+
+/// Install the systemd user units a repo declares and record them with the
+/// deployment, so unlinking removes them.
+fn deploy_units(name: &str, target_name: &str) -> Result<()> {
+    let units = units::install(name)?;
+    if units.is_empty() {
+        return Ok(());
+    }
+    let mut st = State::load()?;
+    if let Some(rec) = st.links.get_mut(target_name) {
+        rec.units = units;
     }
+    st.save()
+}
 
-    println!("Installed {}", name);
+/// Link everything in a mapping file of `target = "repo[/subpath]"` lines.
+/// Each entry gets the same conflict handling as `dothub link`; a failing
+/// entry is reported in the summary without stopping the others.
+fn cmd_link_from(file: &Path, copy: bool) -> Result<()> {
+    let text = fs::read_to_string(file).with_context(|| format!("Reading {}", file.display()))?;
+    let links: BTreeMap<String, String> =
+        toml::from_str(&text).with_context(|| format!("Parsing {}", file.display()))?;
+    if links.is_empty() {
+        println!("{} does not map any targets.", file.display());
+        return Ok(());
+    }
+    let planned = links
+        .iter()
+        .map(|(target_name, spec)| {
+            let (name, source) = match spec.split_once('/') {
+                Some((name, sub)) => (name, dothub_dir().join(name).join(sub)),
+                None => (spec.as_str(), dothub_dir().join(spec)),
+            };
+            PlannedLink {
+                target_name: target_name.clone(),
+                name: name.to_string(),
+                source,
+                label: spec.clone(),
+            }
+        })
+        .collect::<Vec<_>>();
+    let failed = link_many(&planned, copy)?;
+    if failed > 0 {
+        bail!("{} link(s) from {} failed", failed, file.display());
+    }
     Ok(())
 }
 
-fn cmd_link(name: &str, target_name: &str) -> Result<()> {
+/// Link one target per subdirectory of `name` matching `pattern`
+/// (e.g. `config/*`), each named after its directory.
+fn cmd_link_glob(name: &str, pattern: &str, copy: bool) -> Result<()> {
+    let repo = dothub_dir().join(name);
+    if !repo.exists() {
+        bail!(error::repo_not_found(name, &repo));
+    }
+    let full = format!("{}/{}", glob::Pattern::escape(&repo.to_string_lossy()), pattern);
+    let mut planned: Vec<PlannedLink> = Vec::new();
+    for path in glob::glob(&full).with_context(|| format!("Invalid pattern '{}'", pattern))? {
+        let path = path?;
+        let Some(dir_name) = path.file_name().map(|n| n.to_string_lossy().to_string()) else {
+            continue;
+        };
+        if !path.is_dir() || dir_name == ".git" {
+            continue;
+        }
+        let label = path.strip_prefix(dothub_dir()).unwrap_or(&path).display().to_string();
+        if let Some(other) = planned.iter().find(|p| p.target_name == dir_name) {
+            bail!("{} and {} would both be linked as {}", other.label, label, dir_name);
+        }
+        planned.push(PlannedLink {
+            label,
+            target_name: dir_name,
+            name: name.to_string(),
+            source: path,
+        });
+    }
+    if planned.is_empty() {
+        bail!("No directories in {} match '{}'", name, pattern);
+    }
+    let failed = link_many(&planned, copy)?;
+    if failed > 0 {
+        bail!("{} of {} link(s) failed", failed, planned.len());
+    }
+    Ok(())
+}
+
+/// A link made as part of a batch; `label` shows the source in the summary.
+struct PlannedLink {
+    target_name: String,
+    name: String,
+    source: PathBuf,
+    label: String,
+}
+
+/// Make each link, print a summary table and return how many failed.
+fn link_many(links: &[PlannedLink], copy: bool) -> Result<usize> {
+    let mut table = output::table(["Target", "Source", "Result"]);
+    let (mut linked, mut skipped, mut failed) = (0, 0, 0);
+    let mut done = Vec::new();
+    for link in links {
+        let target = targets::path(&link.target_name)?;
+        let result = link_one(&link.name, &link.source, &link.target_name, &target, copy);
+        let cell = match result {
+            Ok(true) => {
+                linked += 1;
+                done.push((link, target));
+                output::cell("linked", Tone::Ok)
+            }
+            Ok(false) => {
+                skipped += 1;
+                output::cell("skipped", Tone::Warn)
+            }
+            Err(e) => {
+                failed += 1;
+                output::cell(format!("failed: {:#}", e), Tone::Error)
+            }
+        };
+        table.add_row(vec![Cell::new(&link.target_name), Cell::new(&link.label), cell]);
+    }
+    println!("{}", table);
+    println!(
+        "{}",
+        i18n::t(
+            "link-summary",
+            &[("linked", &linked), ("skipped", &skipped), ("failed", &failed)]
+        )
+    );
+    for (link, target) in &done {
+        reload::after_change(&link.target_name, target);
+    }
+    Ok(failed)
+}
+
+/// One link of a batch. Returns false when the user chose to skip it.
+fn link_one(name: &str, source: &Path, target_name: &str, target: &Path, copy: bool) -> Result<bool> {
+    if name.is_empty() || target_name.is_empty() || target_name.contains('/') {
+        bail!("expected target = \"repo[/subpath]\" with a plain target name");
+    }
+    if source.components().any(|c| c == std::path::Component::ParentDir) {
+        bail!("subpaths may not leave the repo");
+    }
+    if !vfs::fs().exists(source) {
+        bail!("{} not found", source.display());
+    }
+    audit::check_first_link(name, true)?;
+    if conflict::is_unmanaged(target_name, target)? {
+        match conflict::prompt(target, source)? {
+            conflict::Resolution::Skip => return Ok(false),
+            resolution => println!("{}", conflict::resolve(resolution, target_name, target)?),
+        }
+    }
+    deploy_link(name, source, target_name, target, copy)?;
+    Ok(true)
+}
+
+/// Store path, target name and ~/.config path for linking `name`. The target
+/// name defaults to the one declared in the repo's dothub.toml.
+fn link_paths(name: &str, target_name: Option<&str>) -> Result<(PathBuf, String, PathBuf)> {
+    shared::provide(name)?;
     let source = dothub_dir().join(name);
-    if !source.exists() {
-        bail!("Source repo not found: {}", source.display());
+    if !vfs::fs().exists(&source) {
+        bail!(error::repo_not_found(name, &source));
     }
+    let manifest = manifest::RepoManifest::load(&source)?;
+    let target_name = match target_name {
+        Some(t) => t.to_string(),
+        None => manifest
+            .as_ref()
+            .and_then(|m| m.default_target())
+            .map(|t| t.to_string())
+            .ok_or_else(|| {
+                error::Error::new(
+                    error::Code::NoTarget,
+                    format!("No target given and {} has no dothub.toml declaring one", name),
+                )
+                .hint(format!("dothub link {} <target>, e.g. dothub link {} nvim", name, name))
+            })?,
+    };
 
-    // Target: ~/.config/<target_name>
-    let home = dirs::home_dir().context("Unable to determine home directory")?;
-    let config_dir = home.join(".config");
-    let target = config_dir.join(target_name);
+    let target = targets::path(&target_name)?;
+    Ok((source, target_name, target))
+}
 
-    if !config_dir.exists() {
-        fs::create_dir_all(&config_dir)
+/// Replace `target` with a symlink to (or copy of) `source` and record the
+/// deployment. Unmanaged targets must be resolved by the caller first.
+fn deploy(name: &str, source: &Path, target_name: &str, target: &Path, copy: bool) -> Result<String> {
+    protect::check(target)?;
+    let copy = copy || {
+        let reason = targets::copy_reason(target);
+        if let Some(reason) = reason {
+            println!("{}", output::paint(&format!("{}; copying instead", reason), Tone::Info));
+        }
+        reason.is_some()
+    };
+    let fs = vfs::fs();
+    let config_dir = target.parent().context("Target has no parent directory")?;
+    if !fs.exists(config_dir) {
+        fs.create_dir_all(config_dir)
             .with_context(|| format!("Failed creating {}", config_dir.display()))?;
+        if let Some(owner) = owner::current() {
+            owner::apply(config_dir, owner)?;
+        }
     }
 
-    if target.exists() || symlink_exists(&target) {
-        remove_path(&target)
+    if fs.exists(target) {
+        // A read-only copy has to be opened up before it can be removed
+        readonly::set_writable(target, true)?;
+        remove_path(target)
             .with_context(|| format!("Failed removing existing {}", target.display()))?;
     }
 
-    // Create symlink
-    #[cfg(unix)]
-    {
-        std::os::unix::fs::symlink(&source, &target).with_context(|| {
-            format!(
-                "Failed creating symlink {} -> {}",
-                target.display(),
-                source.display()
-            )
-        })?;
-    }
-    #[cfg(windows)]
-    {
-        if source.is_dir() {
-            std::os::windows::fs::symlink_dir(&source, &target).with_context(|| {
-                format!(
-                    "Failed creating symlink {} -> {}",
-                    target.display(),
-                    source.display()
-                )
-            })?;
+    if copy {
+        copy_tree(source, target)?;
+        modes::apply(name, source, target)?;
+        if let Some(owner) = owner::current() {
+            owner::apply(target, owner)?;
+        }
+        selinux::restore(target);
+        // A dry run's copy is only recorded, so there is nothing to hash
+        let files = if vfs::dry_run() {
+            Default::default()
         } else {
-            std::os::windows::fs::symlink_file(&source, &target).with_context(|| {
-                format!(
-                    "Failed creating symlink {} -> {}",
-                    target.display(),
-                    source.display()
-                )
-            })?;
+            state::hash_tree(target)?
+        };
+        if read_only_links()? {
+            readonly::set_writable(target, false)?;
         }
+        record_deployment(target_name, name, source, target, DeployMode::Copy, files)?;
+        return Ok(i18n::t(
+            "copied",
+            &[("source", &source.display()), ("target", &target.display())],
+        ));
     }
 
-    println!("Linked {} -> {}", source.display(), target.display());
-    Ok(())
+    let points_to = if relative_links()? {
+        relative_path(config_dir, source)?
+    } else {
+        source.to_path_buf()
+    };
+
+    fs.symlink(&points_to, target).with_context(|| {
+        format!(
+            "Failed creating symlink {} -> {}",
+            target.display(),
+            source.display()
+        )
+    })?;
+
+    modes::apply(name, source, target)?;
+    if let Some(owner) = owner::current() {
+        owner::apply(target, owner)?;
+    }
+    selinux::restore(target);
+    record_deployment(
+        target_name,
+        name,
+        source,
+        target,
+        DeployMode::Symlink,
+        Default::default(),
+    )?;
+    Ok(i18n::t(
+        "linked",
+        &[("source", &source.display()), ("target", &target.display())],
+    ))
 }
 
-fn cmd_update() -> Result<()> {
-    ensure_store_dir()?;
-    if which::which("git").is_err() {
-        bail!("git is not installed or not found in PATH");
+fn relative_links() -> Result<bool> {
+    Ok(RELATIVE_LINKS.load(Ordering::SeqCst) || config::Config::load()?.link.relative)
+}
+
+fn read_only_links() -> Result<bool> {
+    Ok(READ_ONLY.load(Ordering::SeqCst) || config::Config::load()?.link.read_only)
+}
+
+/// Path to `to` relative to the directory `from`, both resolved first so the
+/// link still works when either is reached through a symlink.
+fn relative_path(from: &Path, to: &Path) -> Result<PathBuf> {
+    let from = from
+        .canonicalize()
+        .with_context(|| format!("Resolving {}", from.display()))?;
+    let to = to
+        .canonicalize()
+        .with_context(|| format!("Resolving {}", to.display()))?;
+    let common = from
+        .components()
+        .zip(to.components())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut rel = PathBuf::new();
+    for _ in from.components().skip(common) {
+        rel.push("..");
+    }
+    for part in to.components().skip(common) {
+        rel.push(part);
     }
+    Ok(rel)
+}
 
-    let root = dothub_dir();
+fn cmd_unlink(targets: &[String]) -> Result<()> {
+    let mut st = State::load()?;
+    for target_name in targets {
+        println!("{}", unlink_target(&mut st, target_name)?);
+    }
+    st.save()
+}
+
+/// Remove a recorded deployment from disk and from `st` (saving is left to
+/// the caller). Returns a line describing what was done.
+fn unlink_target(st: &mut State, target_name: &str) -> Result<String> {
+    let Some(record) = st.links.remove(target_name) else {
+        bail!(error::Error::new(
+            error::Code::NotManaged,
+            format!("{} is not managed by dothub", target_name),
+        )
+        .hint("dothub active lists the targets dothub deployed"));
+    };
+    // Only delete what dothub put there; leave anything that replaced it
+    let ours = match record.mode {
+        DeployMode::Symlink => symlink_exists(&record.target),
+        DeployMode::Copy => vfs::fs()
+            .kind(&record.target)
+            .is_some_and(|kind| kind != vfs::Kind::Symlink),
+    };
+    units::remove(&record.units)?;
+    if !record.env.is_empty() {
+        exports::write(st)?;
+    }
+    if record.read_only && !readonly::in_use(st, &record.name) {
+        readonly::unlock_repo(st, &record.name)?;
+    }
+    if ours {
+        protect::check(&record.target)?;
+        readonly::set_writable(&record.target, true)?;
+        remove_path(&record.target)?;
+        Ok(i18n::t("unlinked", &[("target", &record.target.display())]))
+    } else {
+        Ok(i18n::t("unlink-gone", &[("target", &record.target.display())]))
+    }
+}
+
+fn record_deployment(
+    target_name: &str,
+    name: &str,
+    source: &Path,
+    target: &Path,
+    mode: DeployMode,
+    files: BTreeMap<String, String>,
+) -> Result<()> {
+    let read_only = read_only_links()?;
+    let mut st = State::load()?;
+    if read_only {
+        readonly::lock_repo(&mut st, name)?;
+    }
+    // Relinking the same repo keeps the variables it set
+    let env = st
+        .links
+        .get(target_name)
+        .filter(|old| old.name == name)
+        .map(|old| old.env.clone())
+        .unwrap_or_default();
+    let replaced = st.links.insert(
+        target_name.to_string(),
+        LinkRecord {
+            name: name.to_string(),
+            source: source.to_path_buf(),
+            target: target.to_path_buf(),
+            mode,
+            files,
+            deployed_at: state::now_unix(),
+            read_only,
+            units: Vec::new(),
+            owner: owner::current().map(|o| o.ids()),
+            env,
+        },
+    );
+    if replaced.as_ref().is_some_and(|old| old.name != name && !old.env.is_empty()) {
+        exports::write(&st)?;
+    }
+    // Relinked without --read-only: release the lock once nothing needs it
+    if let Some(old) = replaced.filter(|old| old.read_only) {
+        if !readonly::in_use(&st, &old.name) {
+            readonly::unlock_repo(&mut st, &old.name)?;
+        }
+    }
+    st.save()
+}
+
+fn cmd_update(tags: &[String], force: bool, changelog: changelog::Changelog) -> Result<()> {
+    ensure_store_dir()?;
+
+    let mut st = State::load()?;
+    let repos = tags::filter(&st, installed_repos()?, tags);
+    if repos.is_empty() && !tags.is_empty() {
+        println!("No repositories tagged {}.", tags.join(" or "));
+        return Ok(());
+    }
+    let mut table = output::table(["Repo", "Result", "Detail"]);
     let mut updated = 0usize;
     let mut skipped = 0usize;
+    let mut failed = 0usize;
+
+    let now = state::now_unix();
+    let mut pulled = Vec::new();
+    let mut changed = Vec::new();
+    let mut checked = Vec::new();
+    for (name, path) in repos {
+        let due_in = st.repos.get(&name).map_or(Some(0), |r| r.due_in(now));
+        let result = match due_in {
+            _ if force => None,
+            None => Some(UpdateResult::new(UpdateKind::Skipped, "manual", "update with --force")),
+            Some(0) => None,
+            Some(secs) => {
+                let detail = format!("next check in {}", output::duration(secs));
+                Some(UpdateResult::new(UpdateKind::Skipped, "scheduled", detail))
+            }
+        };
+        let result = result.unwrap_or_else(|| {
+            eprintln!("{}", i18n::t("updating", &[("path", &path.display())]));
+            update_repo(&mut st, &name, &path)
+        });
+        if let Some(range) = &result.range {
+            pulled.push((name.clone(), path.clone(), range.clone()));
+        }
+        if result.kind == UpdateKind::Updated {
+            changed.push(name.clone());
+        }
+        if matches!(result.kind, UpdateKind::Updated | UpdateKind::UpToDate) {
+            checked.push(name.clone());
+        }
+        match result.kind {
+            UpdateKind::Updated => updated += 1,
+            UpdateKind::Skipped => skipped += 1,
+            UpdateKind::Failed => failed += 1,
+            UpdateKind::UpToDate => {}
+        }
+        table.add_row(vec![
+            Cell::new(&name),
+            output::cell(result.status, result.kind.tone()),
+            Cell::new(result.detail),
+        ]);
+    }
+
+    // A partial run doesn't vouch for the repos it left out
+    if failed == 0 && !http::is_offline() && tags.is_empty() {
+        st.last_update = state::now_unix();
+    }
+    st.save()?;
+    println!("{}", table);
+    if http::is_offline() {
+        println!("{}", i18n::t("offline-no-update", &[]));
+    }
+    println!(
+        "Updated {} repositories (skipped {}, failed {}).",
+        updated, skipped, failed
+    );
+    for (name, path, (before, after)) in &pulled {
+        changelog::show(changelog, name, path, before, after)?;
+    }
+    for name in checked.iter().filter(|name| assets::pending(name)) {
+        if let Err(e) = assets::fetch(name) {
+            println!("{}", output::paint(&format!("{:#}", e), Tone::Warn));
+        }
+    }
+    for name in &changed {
+        if let Err(e) = sandbox::run_hook(name, sandbox::Hook::PostUpdate, None) {
+            println!("{}", output::paint(&format!("{:#}", e), Tone::Warn));
+        }
+    }
+    // Copies don't follow updates, so only links see the new files
+    for (target_name, record) in &st.links {
+        if record.mode == DeployMode::Symlink && changed.contains(&record.name) {
+            // Pulled files come back with git's modes
+            if let Err(e) = modes::apply(&record.name, &record.source, &record.target) {
+                println!("{}", output::paint(&format!("{:#}", e), Tone::Warn));
+            }
+            reload::after_change(target_name, &record.target);
+            if let Err(e) = startup::after_update(&st, target_name) {
+                println!("{}", output::paint(&format!("{:#}", e), Tone::Warn));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum UpdateKind {
+    Updated,
+    UpToDate,
+    Skipped,
+    Failed,
+}
 
+impl UpdateKind {
+    fn tone(self) -> Tone {
+        match self {
+            UpdateKind::Updated => Tone::Ok,
+            UpdateKind::Failed => Tone::Error,
+            UpdateKind::UpToDate | UpdateKind::Skipped => Tone::Muted,
+        }
+    }
+}
+
+/// Outcome of updating one repo, as shown in the Result and Detail columns.
+struct UpdateResult {
+    kind: UpdateKind,
+    status: &'static str,
+    detail: String,
+    /// Commits before and after a pull that changed something
+    range: Option<(String, String)>,
+}
+
+impl UpdateResult {
+    fn new(kind: UpdateKind, status: &'static str, detail: impl Into<String>) -> Self {
+        UpdateResult {
+            kind,
+            status,
+            detail: detail.into(),
+            range: None,
+        }
+    }
+}
+
+/// Pull (or refresh, for non-git sources) one stored repo, counting the update
+/// in `st` when something changed. Saving `st` is left to the caller.
+fn update_repo(st: &mut State, name: &str, path: &Path) -> UpdateResult {
+    let result = pull_repo(st, name, path);
+    if matches!(result.kind, UpdateKind::Updated | UpdateKind::UpToDate) {
+        st.repos.entry(name.to_string()).or_default().last_checked = state::now_unix();
+    }
+    result
+}
+
+fn pull_repo(st: &mut State, name: &str, path: &Path) -> UpdateResult {
+    if let Some(lock) = st.repos.get(name).and_then(|r| r.lock.as_ref()) {
+        let detail = format!("read-only at {}", short_sha(Some(&lock.rev)));
+        return UpdateResult::new(UpdateKind::Skipped, "locked", detail);
+    }
+    // Repos installed before sources were recorded are git clones
+    let source = st.repos.get(name).and_then(|r| r.source.clone()).unwrap_or(RepoSource::Git {
+        url: String::new(),
+    });
+    if shared::holds(&source) {
+        return UpdateResult::new(UpdateKind::Skipped, "shared", "dothub update --shared updates it");
+    }
+    let update = vcs::backend(&source).update(path);
+    // Reaching the upstream again means it is back
+    if matches!(update, Ok(vcs::Update::Updated { .. } | vcs::Update::UpToDate)) {
+        if let Some(rec) = st.repos.get_mut(name) {
+            rec.upstream_gone = None;
+        }
+    }
+    match update {
+        Ok(vcs::Update::Updated { detail, range }) => {
+            let rec = st.repos.entry(name.to_string()).or_default();
+            rec.updates += 1;
+            rec.last_updated = state::now_unix();
+            let status = match source {
+                RepoSource::Local { .. } | RepoSource::Archive { .. } => "refreshed",
+                _ => "updated",
+            };
+            let mut result = UpdateResult::new(UpdateKind::Updated, status, detail);
+            result.range = range;
+            result
+        }
+        Ok(vcs::Update::UpToDate) => UpdateResult::new(UpdateKind::UpToDate, "up to date", ""),
+        Ok(vcs::Update::Skipped { status, detail }) => {
+            UpdateResult::new(UpdateKind::Skipped, status, detail)
+        }
+        Err(e) if network::git_gone(&e.to_string()) => {
+            let rec = st.repos.entry(name.to_string()).or_default();
+            rec.upstream_gone.get_or_insert(state::now_unix());
+            let detail = "upstream deleted or renamed; dothub doctor shows what to do";
+            UpdateResult::new(UpdateKind::Failed, "gone", detail)
+        }
+        Err(e) => {
+            // Keep the first line of the tool's stderr; the rest is usually advice
+            let msg = e.to_string();
+            let detail = msg.split_once(": ").map(|(_, d)| d).unwrap_or(&msg);
+            let first = detail.lines().next().unwrap_or("");
+            UpdateResult::new(UpdateKind::Failed, "failed", first)
+        }
+    }
+}
+
+fn cmd_schedule(name: &str, frequency: state::Frequency) -> Result<()> {
+    if !dothub_dir().join(name).exists() {
+        bail!(error::repo_not_found(name, &dothub_dir().join(name)));
+    }
+    let mut st = State::load()?;
+    st.repos.entry(name.to_string()).or_default().frequency = frequency;
+    st.save()?;
+    match frequency {
+        state::Frequency::Always => println!("{} is updated on every dothub update", name),
+        state::Frequency::Manual => println!("{} is only updated with dothub update --force", name),
+        other => println!("{} is updated at most {}", name, other.name()),
+    }
+    Ok(())
+}
+
+fn short_sha(sha: Option<&str>) -> &str {
+    sha.map(|s| &s[..s.len().min(7)]).unwrap_or("?")
+}
+
+/// Directories in the store, sorted by name.
+fn installed_repos() -> Result<Vec<(String, PathBuf)>> {
+    let root = dothub_dir();
+    let mut repos = Vec::new();
     for entry in fs::read_dir(&root).with_context(|| format!("Reading {}", root.display()))? {
         let entry = entry?;
         let path = entry.path();
         if !path.is_dir() {
             continue;
         }
-        if !path.join(".git").exists() {
-            skipped += 1;
-            continue;
-        }
-
-        println!("Updating {}", path.display());
-        let status = Command::new("git")
-            .args(["-C", path.to_string_lossy().as_ref(), "pull", "--ff-only"])
-            .status()
-            .with_context(|| format!("Running git pull in {}", path.display()))?;
-        if status.success() {
-            updated += 1;
-        } else {
-            eprintln!(
-                "git pull failed in {} with status {}",
-                path.display(),
-                status
-            );
-        }
+        let name = match path.file_name().and_then(|s| s.to_str()) {
+            // Hidden entries are dothub's own staging directories
+            Some(s) if !s.starts_with('.') => s.to_string(),
+            _ => continue,
+        };
+        repos.push((name, path));
     }
+    repos.sort();
+    Ok(repos)
+}
 
-    println!("Updated {} repositories (skipped {}).", updated, skipped);
-    Ok(())
+/// Targets currently deployed from the repo `name`, according to state.
+fn linked_targets(state: &State, name: &str) -> Vec<String> {
+    state
+        .links
+        .iter()
+        .filter(|(_, rec)| rec.name == name)
+        .map(|(target, _)| target.clone())
+        .collect()
 }
 
 fn cmd_completions(shell: Shell) -> Result<()> {
+    write_completions(shell, &mut io::stdout());
+    Ok(())
+}
+
+fn write_completions(shell: Shell, out: &mut dyn io::Write) {
     use clap::CommandFactory;
     let mut cmd = Cli::command();
     let name = cmd.get_name().to_string();
     match shell {
-        Shell::Bash => generate(shells::Bash, &mut cmd, name, &mut io::stdout()),
-        Shell::Zsh => generate(shells::Zsh, &mut cmd, name, &mut io::stdout()),
-        Shell::Fish => generate(shells::Fish, &mut cmd, name, &mut io::stdout()),
-        Shell::PowerShell => generate(shells::PowerShell, &mut cmd, name, &mut io::stdout()),
-        Shell::Elvish => generate(shells::Elvish, &mut cmd, name, &mut io::stdout()),
+        Shell::Bash => generate(shells::Bash, &mut cmd, name, out),
+        Shell::Zsh => generate(shells::Zsh, &mut cmd, name, out),
+        Shell::Fish => generate(shells::Fish, &mut cmd, name, out),
+        Shell::PowerShell => generate(shells::PowerShell, &mut cmd, name, out),
+        Shell::Elvish => generate(shells::Elvish, &mut cmd, name, out),
     }
+}
+
+/// `dothub path`: where a target or repo lives in the store.
+fn cmd_path(name: Option<&str>) -> Result<()> {
+    let path = match name {
+        None => dothub_dir(),
+        Some(name) => {
+            let st = State::load()?;
+            match st.links.get(name) {
+                Some(rec) => rec.source.clone(),
+                None if dothub_dir().join(name).is_dir() => dothub_dir().join(name),
+                None => bail!("{} is neither a deployed target nor a stored repo", name),
+            }
+        }
+    };
+    println!("{}", path.display());
     Ok(())
 }
 
 fn symlink_exists(path: &Path) -> bool {
-    match fs::symlink_metadata(path) {
-        Ok(md) => md.file_type().is_symlink(),
-        Err(_) => false,
-    }
+    vfs::fs().kind(path) == Some(vfs::Kind::Symlink)
 }
 
 fn remove_path(path: &Path) -> Result<()> {
-    match fs::symlink_metadata(path) {
-        Ok(md) if md.file_type().is_symlink() => {
-            fs::remove_file(path).with_context(|| format!("Removing symlink {}", path.display()))
-        }
-        Ok(md) if md.is_dir() => fs::remove_dir_all(path)
-            .with_context(|| format!("Removing directory {}", path.display())),
-        Ok(_md) => {
-            fs::remove_file(path).with_context(|| format!("Removing file {}", path.display()))
-        }
-        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
-        Err(e) => Err(e).with_context(|| format!("Accessing {}", path.display())),
-    }
+    protect::check(path)?;
+    let _span = perf::span(perf::Phase::Fs, || perf::repo_subject(path));
+    let fs = vfs::fs();
+    let what = match fs.kind(path) {
+        Some(vfs::Kind::Symlink) => "symlink",
+        Some(vfs::Kind::Dir) => "directory",
+        Some(vfs::Kind::File) => "file",
+        None => return Ok(()),
+    };
+    fs.remove(path)
+        .with_context(|| format!("Removing {} {}", what, path.display()))
+}
+
+/// Recursively copy `src` to `dst`, skipping the repo's .git directory.
+fn copy_tree(src: &Path, dst: &Path) -> Result<()> {
+    let _span = perf::span(perf::Phase::Fs, || perf::repo_subject(src));
+    vfs::fs()
+        .copy_tree(src, dst)
+        .with_context(|| format!("Copying {} -> {}", src.display(), dst.display()))
 }
 
 fn cmd_active() -> Result<()> {
@@ -353,359 +2282,63 @@ fn cmd_active() -> Result<()> {
     }
 
     if found.is_empty() {
-        println!("No active dothub links in ~/.config.");
+        println!("{}", i18n::t("no-active-links", &[]));
     } else {
+        let mut table = output::table(["Target", "Points to"]);
         for (name, target) in found {
-            println!("{} -> {}", name, target.display());
+            table.add_row(vec![name, target.display().to_string()]);
         }
+        println!("{}", table);
     }
     Ok(())
 }
 
-fn cmd_list() -> Result<()> {
+fn cmd_list(tags: &[String]) -> Result<()> {
     ensure_store_dir()?;
-    let root = dothub_dir();
-    let mut repos: Vec<String> = Vec::new();
-    for entry in fs::read_dir(&root).with_context(|| format!("Reading {}", root.display()))? {
-        let entry = entry?;
-        let path = entry.path();
-        if !path.is_dir() {
-            continue;
-        }
-        let name = match path.file_name().and_then(|s| s.to_str()) {
-            Some(s) => s.to_string(),
-            None => continue,
-        };
-        repos.push(name);
-    }
-    repos.sort();
+    let state = State::load()?;
+    let repos = tags::filter(&state, installed_repos()?, tags);
     if repos.is_empty() {
-        println!("No repositories installed in {}.", root.display());
-    } else {
-        for r in repos {
-            println!("{}", r);
+        if tags.is_empty() {
+            println!("{}", i18n::t("no-repos", &[("store", &dothub_dir().display())]));
+        } else {
+            println!("{}", i18n::t("no-repos-tagged", &[("tags", &tags.join(" or "))]));
         }
+        return Ok(());
     }
+    let origins = status::origins(&repos);
+    let mut table = output::table(["Name", "Tags", "Linked", "Origin"]);
+    for (name, path) in repos {
+        let repo_tags: Vec<String> = tags::repo_tags(&state, &name, &path).into_iter().collect();
+        let linked = linked_targets(&state, &name);
+        let linked_cell = if linked.is_empty() {
+            output::cell("-", Tone::Muted)
+        } else {
+            output::cell(linked.join(", "), Tone::Ok)
+        };
+        let origin = origins.get(&name).map_or("-", String::as_str);
+        table.add_row(vec![
+            Cell::new(name),
+            Cell::new(repo_tags.join(", ")),
+            linked_cell,
+            Cell::new(origin),
+        ]);
+    }
+    println!("{}", table);
     Ok(())
 }
 
 fn cmd_remove(name: &str) -> Result<()> {
     ensure_store_dir()?;
     let path = dothub_dir().join(name);
-    if !path.exists() {
-        bail!("Repository not found: {}", path.display());
+    if !vfs::fs().exists(&path) {
+        bail!(error::repo_not_found(name, &path));
     }
-    let md = fs::symlink_metadata(&path).with_context(|| format!("stat {}", path.display()))?;
-    if md.is_dir() {
-        fs::remove_dir_all(&path)
-            .with_context(|| format!("Removing directory {}", path.display()))?;
-    } else {
-        fs::remove_file(&path)
-            .with_context(|| format!("Removing file {}", path.display()))?;
+    readonly::set_writable(&path, true)?;
+    remove_path(&path)?;
+    let mut st = State::load()?;
+    if st.repos.remove(name).is_some() {
+        st.save()?;
     }
-    println!("Removed {}", name);
+    println!("{}", i18n::t("removed", &[("name", &name)]));
     Ok(())
 }
-
-#[derive(Debug, serde::Deserialize)]
-#[serde(untagged)]
-enum FlexEntry {
-    Single(String),
-    Many(Vec<String>),
-}
-
-fn cmd_hub(types: Vec<String>, url: Option<String>) -> Result<()> {
-    // Print ASCII banner at the top
-    println!("{}", ASCII_BANNER);
-    let url = url.as_deref().unwrap_or(DEFAULT_HUB_URL);
-    let yaml = match fetch_text(url) {
-        Ok(text) => text,
-        Err(_) => {
-            eprintln!("\x1b[31mFailed to fetch the hub file. Please ensure you have internet connection.\x1b[0m");
-            std::process::exit(1);
-        }
-    };
-
-    let map: HashMap<String, FlexEntry> =
-        serde_yaml::from_str(&yaml).context("Parsing YAML for hub")?;
-
-    let filters: Vec<String> = types.iter().map(|s| s.to_lowercase()).collect();
-
-    // Flatten entries into (type, url)
-    let mut items: Vec<(String, String)> = Vec::new();
-    for (ty, entry) in map.into_iter() {
-        if !filters.is_empty() && !filters.contains(&ty.to_lowercase()) {
-            continue;
-        }
-        match entry {
-            FlexEntry::Single(u) => items.push((ty.clone(), u)),
-            FlexEntry::Many(v) => {
-                for u in v {
-                    items.push((ty.clone(), u));
-                }
-            }
-        }
-    }
-
-    // Collect stars efficiently (GraphQL when token present; REST fallback otherwise)
-    let token = env::var("GITHUB_TOKEN").ok();
-    let mut warn_graphql_failed = false;
-    // Show a spinner during star fetching
-    let spinner_stop = start_spinner("Downloading stars from github..");
-
-    let mut detailed: Vec<(String, String, u64)> = Vec::with_capacity(items.len());
-    if let Some(ref t) = token {
-        let links_only: Vec<String> = items.iter().map(|(_, l)| l.clone()).collect();
-        match github_stars_batch(&links_only, Some(t.as_str())) {
-            Ok(stars_map) => {
-                for (ty, link) in items {
-                    let stars = *stars_map.get(&link).unwrap_or(&0);
-                    detailed.push((ty, link, stars));
-                }
-            }
-            Err(_) => {
-                warn_graphql_failed = true;
-                for (ty, link) in items {
-                    let stars = github_stars(&link).unwrap_or(0);
-                    detailed.push((ty, link, stars));
-                }
-            }
-        }
-    } else {
-        for (ty, link) in items {
-            let stars = github_stars(&link).unwrap_or(0);
-            detailed.push((ty, link, stars));
-        }
-    }
-
-    spinner_stop.store(true, Ordering::SeqCst);
-    // Leave the last line in place; print a newline to cleanly end spinner
-    eprintln!("");
-
-    // Sort by stars desc
-    detailed.sort_by(|a, b| b.2.cmp(&a.2));
-
-    let mut table = Table::new();
-    table.load_preset(UTF8_BORDERS_ONLY).apply_modifier(UTF8_ROUND_CORNERS);
-    table.set_header(["#", "Stars", "Installed", "Source"]);
-
-    for (idx, (_ty, link, stars)) in detailed.into_iter().enumerate() {
-        let rank = (idx + 1).to_string();
-        let name = derive_repo_name(&link);
-        let installed = dothub_dir().join(&name).exists();
-        let installed_str = if installed { "y" } else { "n" };
-        table.add_row(vec![rank, stars.to_string(), installed_str.to_string(), link]);
-    }
-
-    println!("{}", table);
-    if token.is_none() {
-        println!(
-            "\x1b[33mTo improve performance, please set your GITHUB_TOKEN environment variable.\nLearn more: {}\x1b[0m",
-            GH_TOKEN_HELP_URL
-        );
-    }
-    if warn_graphql_failed {
-        println!(
-            "\x1b[33mGITHUB_TOKEN detected but GitHub GraphQL failed; falling back to REST.\nLearn more: {}\x1b[0m",
-            GH_TOKEN_HELP_URL
-        );
-    }
-
-    println!("Run dothub --help to see more options.");
-
-    Ok(())
-}
-
-fn start_spinner(message: &str) -> Arc<AtomicBool> {
-    let stop = Arc::new(AtomicBool::new(false));
-    let stop_clone = Arc::clone(&stop);
-    let msg = message.to_string();
-    thread::spawn(move || {
-        let frames = ["-", "\\", "|", "/"]; // simple spinner
-        let mut i = 0usize;
-        // Print initial line
-        eprint!("{} {}\r", frames[i % frames.len()], msg);
-        let _ = std::io::stderr().flush();
-        while !stop_clone.load(Ordering::SeqCst) {
-            i = (i + 1) % frames.len();
-            eprint!("{} {}\r", frames[i], msg);
-            let _ = std::io::stderr().flush();
-            thread::sleep(Duration::from_millis(120));
-        }
-    });
-    stop
-}
-
-fn fetch_text(url: &str) -> Result<String> {
-    let client = reqwest::blocking::Client::builder()
-        .user_agent("dothub/0.1")
-        .build()
-        .context("building http client")?;
-    let resp = client
-        .get(url)
-        .send()
-        .with_context(|| format!("GET {}", url))?;
-    if !resp.status().is_success() {
-        bail!("HTTP {} for {}", resp.status(), url);
-    }
-    let text = resp.text().context("reading response body")?;
-    Ok(text)
-}
-
-fn github_stars(link: &str) -> Result<u64> {
-    // Expect forms like https://github.com/owner/repo or git@github.com:owner/repo.git
-    let lower = link.to_lowercase();
-    if !lower.contains("github.com") {
-        bail!("not github");
-    }
-
-    // Try to extract owner/repo or infer repo if only owner provided
-    let mut try_owner_repo: Option<(String, String)> = None;
-    if let Ok(parsed) = url::Url::parse(link) {
-        if parsed.domain().unwrap_or("") != "github.com" {
-            bail!("not github");
-        }
-        let mut segs = parsed
-            .path_segments()
-            .ok_or_else(|| anyhow::anyhow!("no path"))?;
-        let owner = segs
-            .next()
-            .ok_or_else(|| anyhow::anyhow!("no owner"))?
-            .to_string();
-        if let Some(mut repo) = segs.next() {
-            if let Some(stripped) = repo.strip_suffix('.').or_else(|| repo.strip_suffix(".git")) {
-                repo = stripped;
-            }
-            try_owner_repo = Some((owner, repo.to_string()));
-        } else {
-            // Heuristic: try owner/owner as the repository
-            try_owner_repo = Some((owner.clone(), owner));
-        }
-    } else if let Some(rest) = lower.strip_prefix("git@github.com:") {
-        let parts: Vec<&str> = rest.split('/').collect();
-        if parts.len() >= 2 {
-            let mut repo = parts[1].to_string();
-            if let Some(stripped) = repo.strip_suffix('.').or_else(|| repo.strip_suffix(".git")) {
-                repo = stripped.to_string();
-            }
-            try_owner_repo = Some((parts[0].to_string(), repo));
-        } else if parts.len() == 1 {
-            let owner = parts[0].to_string();
-            try_owner_repo = Some((owner.clone(), owner));
-        }
-    }
-
-    let (owner, repo) = try_owner_repo.ok_or_else(|| anyhow::anyhow!("unrecognized github url"))?;
-
-    let api = format!("https://api.github.com/repos/{}/{}", owner, repo);
-    let client = reqwest::blocking::Client::builder()
-        .user_agent("dothub/0.1")
-        .build()
-        .context("building http client")?;
-    let resp = client
-        .get(&api)
-        .send()
-        .with_context(|| format!("GET {}", api))?;
-    if !resp.status().is_success() {
-        bail!("bad status")
-    }
-    let v: serde_json::Value = resp.json().context("parsing github json")?;
-    let stars = v
-        .get("stargazers_count")
-        .and_then(|n| n.as_u64())
-        .unwrap_or(0);
-    Ok(stars)
-}
-
-fn parse_github_owner_repo(link: &str) -> Option<(String, String)> {
-    let lower = link.to_lowercase();
-    if !lower.contains("github.com") {
-        return None;
-    }
-    if let Ok(parsed) = url::Url::parse(link) {
-        if parsed.domain().unwrap_or("") != "github.com" {
-            return None;
-        }
-        let mut segs = parsed.path_segments()?;
-        let owner = segs.next()?.to_string();
-        if let Some(mut repo) = segs.next() {
-            if let Some(stripped) = repo.strip_suffix('.').or_else(|| repo.strip_suffix(".git")) {
-                repo = stripped;
-            }
-            return Some((owner, repo.to_string()));
-        } else {
-            return Some((owner.clone(), owner));
-        }
-    } else if let Some(rest) = lower.strip_prefix("git@github.com:") {
-        let parts: Vec<&str> = rest.split('/').collect();
-        if parts.len() >= 2 {
-            let mut repo = parts[1].to_string();
-            if let Some(stripped) = repo.strip_suffix('.').or_else(|| repo.strip_suffix(".git")) {
-                repo = stripped.to_string();
-            }
-            return Some((parts[0].to_string(), repo));
-        } else if parts.len() == 1 {
-            let owner = parts[0].to_string();
-            return Some((owner.clone(), owner));
-        }
-    }
-    None
-}
-
-fn github_stars_batch(links: &[String], token: Option<&str>) -> Result<HashMap<String, u64>> {
-    let mut entries: Vec<(String, (String, String))> = Vec::new();
-    for l in links {
-        if let Some((o, r)) = parse_github_owner_repo(l) {
-            entries.push((l.clone(), (o, r)));
-        }
-    }
-    if entries.is_empty() {
-        return Ok(HashMap::new());
-    }
-
-    let client = reqwest::blocking::Client::builder()
-        .user_agent("dothub/0.1")
-        .build()
-        .context("building http client")?;
-
-    let mut out: HashMap<String, u64> = HashMap::new();
-
-    for chunk in entries.chunks(50) {
-        let mut q = String::from("query { ");
-        for (i, (_link, (owner, repo))) in chunk.iter().enumerate() {
-            let alias = format!("r{}", i);
-            let owner_esc = owner.replace('"', "\\\"");
-            let repo_esc = repo.replace('"', "\\\"");
-            q.push_str(&format!(
-                "{}: repository(owner:\"{}\", name:\"{}\") {{ stargazerCount }} ",
-                alias, owner_esc, repo_esc
-            ));
-        }
-        q.push('}');
-
-        let mut req = client
-            .post("https://api.github.com/graphql")
-            .json(&serde_json::json!({"query": q}));
-        if let Some(t) = token {
-            req = req.header("Authorization", format!("Bearer {}", t));
-        }
-        let resp = req.send().context("graphql request failed")?;
-        if !resp.status().is_success() {
-            return Err(anyhow::anyhow!("graphql status {}", resp.status()));
-        }
-        let v: serde_json::Value = resp.json().context("parse graphql json")?;
-        if let Some(data) = v.get("data").and_then(|d| d.as_object()) {
-            for (i, (link, _)) in chunk.iter().enumerate() {
-                let alias = format!("r{}", i);
-                let count = data
-                    .get(&alias)
-                    .and_then(|obj| obj.get("stargazerCount"))
-                    .and_then(|n| n.as_u64())
-                    .unwrap_or(0);
-                out.insert(link.clone(), count);
-            }
-        }
-    }
-
-    Ok(out)
-}
@@ -0,0 +1,113 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// File at the root of a config repo describing what it provides.
+pub const REPO_MANIFEST: &str = "dothub.toml";
+
+/// Per-repo manifest (`dothub.toml`). Every field is optional so hand-written
+/// manifests can start small.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RepoManifest {
+    /// Config type, matching the hub.yml keys (e.g. nvim, tmux)
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub config_type: Option<String>,
+    /// Directory under ~/.config to link to; defaults to the type
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// System package providing the application, for `dothub bundle install`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub package: Option<PackageSpec>,
+    /// Tags for grouping, e.g. `tags = ["editor"]`; added to those from `dothub tag`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// systemd user units in the repo to link, enable and start, e.g. "systemd/foo.service"
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub units: Vec<String>,
+    /// Shell commands run in a sandbox after linking or updating the repo
+    #[serde(default, skip_serializing_if = "Hooks::is_empty")]
+    pub hooks: Hooks,
+    /// Octal file modes by path or glob relative to the repo, e.g. `"ssh/config" = "600"`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub modes: BTreeMap<String, String>,
+    /// Environment variables to export while linked; `{target}` is the deployed path
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// Files downloaded into the repo instead of being committed to it
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub assets: Vec<Asset>,
+    /// Types an all-in-one repo provides, each mapped to its directory in the
+    /// repo, e.g. `nvim = "nvim"`; each is linked to the target of that name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub provides: BTreeMap<String, String>,
+}
+
+/// `[[assets]]` in dothub.toml: a download checked against its sha256 and
+/// placed at `dest` in the repo, extracted when it is an archive.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Asset {
+    pub url: String,
+    pub sha256: String,
+    /// Path relative to the repo root
+    pub dest: String,
+}
+
+/// `[hooks]` in dothub.toml. Each runs with `sh -c` in the repo directory.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Hooks {
+    /// After the repo is linked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_link: Option<String>,
+    /// After `dothub update` pulled new commits
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_update: Option<String>,
+}
+
+impl Hooks {
+    fn is_empty(&self) -> bool {
+        self.post_link.is_none() && self.post_update.is_none()
+    }
+}
+
+/// One package name for every package manager, or a name per manager
+/// (e.g. `package = { brew = "neovim", apt-get = "neovim" }`).
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum PackageSpec {
+    Name(String),
+    PerManager(BTreeMap<String, String>),
+}
+
+impl RepoManifest {
+    /// Read `dothub.toml` from a repo root; None when the repo has no manifest.
+    pub fn load(repo: &Path) -> Result<Option<Self>> {
+        let path = repo.join(REPO_MANIFEST);
+        let text = match fs::read_to_string(&path) {
+            Ok(t) => t,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).with_context(|| format!("Reading {}", path.display())),
+        };
+        toml::from_str(&text)
+            .map(Some)
+            .with_context(|| format!("Parsing {}", path.display()))
+    }
+
+    pub fn save(&self, repo: &Path) -> Result<()> {
+        let path = repo.join(REPO_MANIFEST);
+        let text = toml::to_string_pretty(self).context("Serializing manifest")?;
+        fs::write(&path, text).with_context(|| format!("Writing {}", path.display()))
+    }
+
+    /// Target directory name this repo wants under ~/.config.
+    pub fn default_target(&self) -> Option<&str> {
+        self.target.as_deref().or(self.config_type.as_deref())
+    }
+}
@@ -0,0 +1,313 @@
+//! Minimal markdown rendering for terminal views.
+//!
+//! Only the parts of a README that read well in a terminal are handled:
+//! headings, paragraphs, lists, quotes and code blocks. Links are numbered
+//! inline and listed at the end so they can be opened by index; images cannot
+//! be shown, so their URLs are collected instead.
+
+/// How a rendered line should be drawn.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Style {
+    Text,
+    Heading,
+    Code,
+    Quote,
+    Muted,
+}
+
+pub struct Line {
+    pub style: Style,
+    pub text: String,
+}
+
+#[derive(Default)]
+pub struct Document {
+    pub lines: Vec<Line>,
+    /// Link targets; `[n]` in the text refers to `links[n - 1]`
+    pub links: Vec<String>,
+    pub images: Vec<String>,
+}
+
+/// Render `md` into lines no wider than `width` characters.
+pub fn render(md: &str, width: usize) -> Document {
+    let width = width.max(20);
+    let mut doc = Document::default();
+    let mut para = String::new();
+    let mut in_code = false;
+
+    for raw in md.lines() {
+        let line = raw.trim_end();
+        let trimmed = line.trim_start();
+
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            doc.flush(&mut para, width);
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            doc.push(Style::Code, format!("  {}", line));
+            continue;
+        }
+        if trimmed.is_empty() {
+            doc.flush(&mut para, width);
+            doc.blank();
+            continue;
+        }
+        if let Some(text) = heading(trimmed) {
+            doc.flush(&mut para, width);
+            doc.blank();
+            let text = doc.inline(text);
+            doc.push(Style::Heading, text);
+            continue;
+        }
+        if is_rule(trimmed) {
+            doc.flush(&mut para, width);
+            doc.push(Style::Muted, "─".repeat(width.min(40)));
+            continue;
+        }
+        if let Some(rest) = trimmed.strip_prefix('>') {
+            doc.flush(&mut para, width);
+            let text = doc.inline(rest.trim_start());
+            doc.wrapped(Style::Quote, &text, width, "│ ", "│ ");
+            continue;
+        }
+        if let Some((marker, rest)) = list_item(trimmed) {
+            doc.flush(&mut para, width);
+            let indent = " ".repeat((line.len() - trimmed.len()) / 2 * 2);
+            let first = format!("{}{} ", indent, marker);
+            let hanging = " ".repeat(first.chars().count());
+            let text = doc.inline(rest);
+            doc.wrapped(Style::Text, &text, width, &first, &hanging);
+            continue;
+        }
+        if trimmed.starts_with('<') {
+            // Inline HTML: keep image and link targets, drop the markup
+            doc.collect_html(trimmed);
+            let text = strip_tags(trimmed);
+            if !text.trim().is_empty() {
+                para.push(' ');
+                para.push_str(text.trim());
+            }
+            continue;
+        }
+        para.push(' ');
+        para.push_str(trimmed);
+    }
+    doc.flush(&mut para, width);
+
+    while doc.lines.last().is_some_and(|l| l.text.is_empty()) {
+        doc.lines.pop();
+    }
+    if !doc.links.is_empty() {
+        doc.blank();
+        doc.push(Style::Heading, "Links".to_string());
+        let links: Vec<String> = doc.links.clone();
+        for (i, link) in links.iter().enumerate() {
+            doc.push(Style::Muted, format!("[{}] {}", i + 1, link));
+        }
+    }
+    if !doc.images.is_empty() {
+        doc.blank();
+        doc.push(Style::Heading, "Images".to_string());
+        let images: Vec<String> = doc.images.clone();
+        for image in images {
+            doc.push(Style::Muted, format!("- {}", image));
+        }
+    }
+    doc
+}
+
+impl Document {
+    fn push(&mut self, style: Style, text: String) {
+        self.lines.push(Line { style, text });
+    }
+
+    /// Add an empty line unless the previous one is already empty.
+    fn blank(&mut self) {
+        if self.lines.last().is_some_and(|l| !l.text.is_empty()) {
+            self.push(Style::Text, String::new());
+        }
+    }
+
+    fn flush(&mut self, para: &mut String, width: usize) {
+        if para.trim().is_empty() {
+            para.clear();
+            return;
+        }
+        let text = self.inline(para.trim());
+        self.wrapped(Style::Text, &text, width, "", "");
+        para.clear();
+    }
+
+    fn wrapped(&mut self, style: Style, text: &str, width: usize, first: &str, rest: &str) {
+        let avail = width.saturating_sub(first.chars().count()).max(10);
+        for (i, line) in wrap(text, avail).into_iter().enumerate() {
+            let prefix = if i == 0 { first } else { rest };
+            self.push(style, format!("{}{}", prefix, line));
+        }
+    }
+
+    /// Resolve inline markup: number links, collect images, drop emphasis.
+    fn inline(&mut self, text: &str) -> String {
+        let chars: Vec<char> = text.chars().collect();
+        let mut out = String::new();
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            let is_image = c == '!' && chars.get(i + 1) == Some(&'[');
+            if c == '[' || is_image {
+                let open = if is_image { i + 1 } else { i };
+                if let Some((label, url, end)) = link_at(&chars, open) {
+                    if is_image {
+                        self.images.push(url);
+                        if !label.is_empty() {
+                            out.push_str(&format!("[image: {}]", label));
+                        }
+                    } else {
+                        let label = self.inline(&label);
+                        self.links.push(url);
+                        out.push_str(&format!("{} [{}]", label.trim(), self.links.len()));
+                    }
+                    i = end;
+                    continue;
+                }
+            }
+            if c == '<' {
+                let rest: String = chars[i + 1..].iter().collect();
+                if let Some(close) = rest.find('>') {
+                    let inner = &rest[..close];
+                    if inner.starts_with("http://") || inner.starts_with("https://") {
+                        self.links.push(inner.to_string());
+                        out.push_str(&format!("{} [{}]", inner, self.links.len()));
+                        i += inner.chars().count() + 2;
+                        continue;
+                    }
+                }
+            }
+            if c == '`' || c == '*' || (c == '_' && chars.get(i + 1) == Some(&'_')) {
+                i += if c == '_' { 2 } else { 1 };
+                continue;
+            }
+            out.push(c);
+            i += 1;
+        }
+        out
+    }
+
+    fn collect_html(&mut self, html: &str) {
+        for (attr, images) in [("src=\"", true), ("href=\"", false)] {
+            let mut rest = html;
+            while let Some(pos) = rest.find(attr) {
+                rest = &rest[pos + attr.len()..];
+                let Some(end) = rest.find('"') else { break };
+                let url = rest[..end].to_string();
+                if images {
+                    self.images.push(url);
+                } else {
+                    self.links.push(url);
+                }
+                rest = &rest[end..];
+            }
+        }
+    }
+}
+
+/// Parse `[label](url)` starting at the `[` at `open`. Returns the label, the
+/// url and the index just past the closing parenthesis.
+fn link_at(chars: &[char], open: usize) -> Option<(String, String, usize)> {
+    let mut depth = 0;
+    let mut close = None;
+    for (j, c) in chars.iter().enumerate().skip(open) {
+        match c {
+            '[' => depth += 1,
+            ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    close = Some(j);
+                    break;
+                }
+            }
+            _ => {}
+        }
+    }
+    let close = close?;
+    if chars.get(close + 1) != Some(&'(') {
+        return None;
+    }
+    let end = chars[close + 2..].iter().position(|c| *c == ')')? + close + 2;
+    let label: String = chars[open + 1..close].iter().collect();
+    let target: String = chars[close + 2..end].iter().collect();
+    // Drop an optional title: [x](url "title")
+    let url = target.split_whitespace().next().unwrap_or("").to_string();
+    Some((label, url, end + 1))
+}
+
+fn heading(line: &str) -> Option<&str> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    if level == 0 || level > 6 {
+        return None;
+    }
+    let rest = &line[level..];
+    if !rest.is_empty() && !rest.starts_with(' ') {
+        return None;
+    }
+    Some(rest.trim().trim_end_matches('#').trim())
+}
+
+fn is_rule(line: &str) -> bool {
+    let compact: String = line.chars().filter(|c| !c.is_whitespace()).collect();
+    compact.len() >= 3
+        && ["-", "*", "_"]
+            .iter()
+            .any(|m| compact.chars().all(|c| c.to_string() == *m))
+}
+
+fn list_item(line: &str) -> Option<(String, &str)> {
+    for bullet in ["- ", "* ", "+ "] {
+        if let Some(rest) = line.strip_prefix(bullet) {
+            return Some(("•".to_string(), rest));
+        }
+    }
+    let digits = line.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits > 0 {
+        let rest = &line[digits..];
+        if let Some(rest) = rest.strip_prefix(". ").or_else(|| rest.strip_prefix(") ")) {
+            return Some((format!("{}.", &line[..digits]), rest));
+        }
+    }
+    None
+}
+
+fn strip_tags(html: &str) -> String {
+    let mut out = String::new();
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            c if !in_tag => out.push(c),
+            _ => {}
+        }
+    }
+    out
+}
+
+/// Greedy word wrap to `width` characters.
+pub fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    for word in text.split_whitespace() {
+        let len = current.chars().count();
+        if len > 0 && len + 1 + word.chars().count() > width {
+            lines.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+    lines
+}
@@ -0,0 +1,65 @@
+//! `dothub metrics`: gauges in the Prometheus text format, for the
+//! node_exporter textfile collector.
+
+use crate::state::State;
+use crate::{status, verify};
+use anyhow::{Context, Result};
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+pub fn cmd_metrics(textfile: Option<&Path>) -> Result<()> {
+    crate::ensure_store_dir()?;
+    let text = render()?;
+    let Some(path) = textfile else {
+        print!("{}", text);
+        return Ok(());
+    };
+    // node_exporter may read the file at any moment, so never expose a partial write
+    let tmp = path.with_extension("prom.tmp");
+    fs::write(&tmp, &text).with_context(|| format!("Writing {}", tmp.display()))?;
+    fs::rename(&tmp, path).with_context(|| format!("Writing {}", path.display()))
+}
+
+fn render() -> Result<String> {
+    let st = State::load()?;
+    let rows = status::collect()?;
+    let behind = rows.iter().filter(|r| r.behind() > 0).count();
+    let broken = st
+        .links
+        .values()
+        .filter(|rec| {
+            verify::check_record(rec)
+                .iter()
+                .any(|p| !matches!(p, verify::Problem::Mode { .. }))
+        })
+        .count();
+
+    let mut out = String::new();
+    let mut gauge = |name: &str, help: &str, value: u64| {
+        let _ = writeln!(out, "# HELP dothub_{} {}", name, help);
+        let _ = writeln!(out, "# TYPE dothub_{} gauge", name);
+        let _ = writeln!(out, "dothub_{} {}", name, value);
+    };
+    gauge(
+        "repos_total",
+        "Repositories in the dothub store.",
+        rows.len() as u64,
+    );
+    gauge(
+        "repos_behind",
+        "Repositories behind their upstream as of the last fetch.",
+        behind as u64,
+    );
+    gauge(
+        "links_broken",
+        "Deployments that are missing, broken or changed outside dothub.",
+        broken as u64,
+    );
+    gauge(
+        "last_update_timestamp_seconds",
+        "Unix time of the last dothub update run in which no repository failed, 0 if never.",
+        st.last_update,
+    );
+    Ok(out)
+}
@@ -0,0 +1,122 @@
+//! A download cache that several machines can share.
+//!
+//! With `mirror = true` or a `dir` under `[cache]` in the config, every repo
+//! cloned from a URL is first mirrored (`git clone --mirror`) into the cache,
+//! keyed by its URL, and the clone is made from the mirror; its origin still
+//! points at the URL. Assets are kept under their sha256. A mirror is fetched
+//! again at most every few minutes, so a lab of machines running `dothub
+//! apply` against a mirror on a network share asks the forge once, and an
+//! offline machine can still clone what is mirrored.
+
+use crate::output::{self, Tone};
+use anyhow::{bail, Context, Result};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A mirror fetched this recently is used as it is.
+const FRESH_SECS: u64 = 600;
+
+/// File in a mirror whose contents is when it was last fetched.
+const STAMP: &str = "dothub-fetched";
+
+/// The mirror directory, or None when mirroring is off.
+pub fn dir() -> Option<PathBuf> {
+    let config = crate::config::Config::load().ok()?.cache;
+    match config.dir {
+        Some(dir) => Some(crate::source::expand_tilde(&dir)),
+        None if config.mirror => Some(crate::http::cache_dir().join("mirror")),
+        None => None,
+    }
+}
+
+fn key(text: &str) -> String {
+    Sha256::digest(text.as_bytes())
+        .iter()
+        .take(16)
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Up-to-date mirror of the repo at `url` to clone from, or None when
+/// mirroring is off or failed, in which case the clone goes to `url` itself.
+pub fn for_clone(url: &str) -> Option<PathBuf> {
+    let dir = dir()?;
+    let mirror = dir.join("git").join(format!("{}.git", key(url)));
+    match refresh(&mirror, url) {
+        Ok(()) => Some(mirror),
+        Err(e) if mirror.exists() => {
+            let msg = format!("{:#}; cloning from the mirror as it is", e);
+            println!("{}", output::paint(&msg, Tone::Warn));
+            Some(mirror)
+        }
+        Err(e) => {
+            let msg = format!("No mirror of {}: {:#}", url, e);
+            println!("{}", output::paint(&msg, Tone::Warn));
+            None
+        }
+    }
+}
+
+fn refresh(mirror: &Path, url: &str) -> Result<()> {
+    let now = crate::state::now_unix();
+    if mirror.exists() {
+        let fetched = fs::read_to_string(mirror.join(STAMP))
+            .ok()
+            .and_then(|t| t.trim().parse::<u64>().ok())
+            .unwrap_or(0);
+        if crate::http::is_offline() || now.saturating_sub(fetched) < FRESH_SECS {
+            return Ok(());
+        }
+        crate::git::network(mirror, &["remote", "update", "--prune"], false)
+            .with_context(|| format!("Fetching the mirror of {}", url))?;
+    } else {
+        if crate::http::is_offline() {
+            bail!("offline");
+        }
+        let parent = mirror.parent().context("Mirror path has no parent")?;
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed creating {}", parent.display()))?;
+        // Cloned aside and renamed, so other machines never see half a mirror
+        let tmp = parent.join(format!(".{}.{}", key(url), std::process::id()));
+        let tmp_arg = tmp.to_string_lossy();
+        let cloned = crate::git::network(
+            Path::new("."),
+            &["clone", "-q", "--mirror", url, tmp_arg.as_ref()],
+            false,
+        );
+        if let Err(e) = cloned {
+            let _ = crate::remove_path(&tmp);
+            return Err(e);
+        }
+        if fs::rename(&tmp, mirror).is_err() {
+            // Another machine got there first
+            crate::remove_path(&tmp)?;
+        }
+    }
+    fs::write(mirror.join(STAMP), now.to_string())
+        .with_context(|| format!("Writing {}", mirror.join(STAMP).display()))
+}
+
+/// Contents of the asset with checksum `sha256`, if it is mirrored intact.
+pub fn asset(sha256: &str) -> Option<Vec<u8>> {
+    let bytes = fs::read(dir()?.join("assets").join(sha256)).ok()?;
+    let actual: String = Sha256::digest(&bytes)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    (actual == sha256).then_some(bytes)
+}
+
+/// Keep a verified download for the next machine.
+pub fn store_asset(sha256: &str, bytes: &[u8]) -> Result<()> {
+    let Some(dir) = dir() else {
+        return Ok(());
+    };
+    let dir = dir.join("assets");
+    fs::create_dir_all(&dir).with_context(|| format!("Failed creating {}", dir.display()))?;
+    let tmp = dir.join(format!(".{}.{}", sha256, std::process::id()));
+    fs::write(&tmp, bytes).with_context(|| format!("Writing {}", tmp.display()))?;
+    fs::rename(&tmp, dir.join(sha256))
+        .with_context(|| format!("Moving {} into the mirror", tmp.display()))
+}
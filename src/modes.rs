@@ -0,0 +1,158 @@
+//! File modes declared in dothub.toml.
+//!
+//! git only records whether a file is executable, so a private file like an
+//! ssh or mail config comes out of a clone as 644. A repo lists the modes it
+//! needs under `[modes]`, keyed by path or glob relative to the repo root
+//! (`"ssh/config" = "600"`). Linking and copying set them on the deployed
+//! files, updates set them again, and `dothub verify` reports drift.
+
+use crate::manifest::RepoManifest;
+use crate::state::LinkRecord;
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::Path;
+
+/// Parse an octal mode such as "600" or "0o755".
+pub fn parse(text: &str) -> Result<u32> {
+    let digits = text.trim().trim_start_matches("0o");
+    match u32::from_str_radix(digits, 8) {
+        Ok(mode) if mode <= 0o7777 => Ok(mode),
+        _ => bail!("Invalid file mode '{}'; expected octal like \"600\"", text),
+    }
+}
+
+/// Modes declared by repo `name` for files under `source`, keyed by path
+/// relative to `source` ("" when `source` itself is the file).
+pub fn declared(name: &str, source: &Path) -> Result<Vec<(String, u32)>> {
+    let repo = crate::dothub_dir().join(name);
+    let Some(manifest) = RepoManifest::load(&repo)? else {
+        return Ok(Vec::new());
+    };
+    let mut out = Vec::new();
+    for (pattern, mode) in &manifest.modes {
+        let mode =
+            parse(mode).with_context(|| format!("[modes] \"{}\" in dothub.toml", pattern))?;
+        let full = format!(
+            "{}/{}",
+            glob::Pattern::escape(&repo.to_string_lossy()),
+            pattern
+        );
+        let paths = glob::glob(&full).with_context(|| format!("Invalid pattern '{}'", pattern))?;
+        for path in paths.flatten() {
+            if let Ok(rel) = path.strip_prefix(source) {
+                out.push((rel.to_string_lossy().to_string(), mode));
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Set the declared modes on a deployment of `source` at `target`. Through a
+/// symlink this changes the files in the store.
+pub fn apply(name: &str, source: &Path, target: &Path) -> Result<()> {
+    if crate::vfs::dry_run() {
+        return Ok(());
+    }
+    for (rel, mode) in declared(name, source)? {
+        let path = if rel.is_empty() {
+            target.to_path_buf()
+        } else {
+            target.join(&rel)
+        };
+        set_mode(&path, mode)?;
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+pub fn set_mode(path: &Path, mode: u32) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+        .with_context(|| format!("Changing permissions of {}", path.display()))
+}
+
+#[cfg(not(unix))]
+pub fn set_mode(_path: &Path, _mode: u32) -> Result<()> {
+    Ok(())
+}
+
+/// Declared files of a deployment whose mode differs, with the expected and
+/// actual mode. Read-only deployments are expected without write bits.
+#[cfg(unix)]
+pub fn drift(record: &LinkRecord) -> Vec<(String, u32, u32)> {
+    use std::os::unix::fs::PermissionsExt;
+    let Ok(declared) = declared(&record.name, &record.source) else {
+        return Vec::new();
+    };
+    let mut out = Vec::new();
+    for (rel, mode) in declared {
+        let path = if rel.is_empty() {
+            record.target.clone()
+        } else {
+            record.target.join(&rel)
+        };
+        let Ok(md) = fs::metadata(&path) else {
+            continue;
+        };
+        let expected = if record.read_only {
+            mode & !0o222
+        } else {
+            mode
+        };
+        let actual = md.permissions().mode() & 0o7777;
+        if actual != expected {
+            let shown = if rel.is_empty() {
+                record.target.display().to_string()
+            } else {
+                rel
+            };
+            out.push((shown, expected, actual));
+        }
+    }
+    out
+}
+
+#[cfg(not(unix))]
+pub fn drift(_record: &LinkRecord) -> Vec<(String, u32, u32)> {
+    Vec::new()
+}
+
+/// Files under `dir` that not everyone may read, which a git clone would
+/// make readable, as `[modes]` entries. Used when adopting a directory.
+#[cfg(unix)]
+pub fn unusual(dir: &Path) -> Result<std::collections::BTreeMap<String, String>> {
+    use std::os::unix::fs::PermissionsExt;
+    let mut out = std::collections::BTreeMap::new();
+    let mut stack = vec![dir.to_path_buf()];
+    while let Some(current) = stack.pop() {
+        for entry in
+            fs::read_dir(&current).with_context(|| format!("Reading {}", current.display()))?
+        {
+            let entry = entry?;
+            let md = fs::symlink_metadata(entry.path())?;
+            if entry.file_name() == ".git" || md.file_type().is_symlink() {
+                continue;
+            }
+            if md.is_dir() {
+                stack.push(entry.path());
+                continue;
+            }
+            let mode = md.permissions().mode() & 0o7777;
+            if mode & 0o044 != 0o044 {
+                let rel = entry
+                    .path()
+                    .strip_prefix(dir)
+                    .unwrap_or(&entry.path())
+                    .to_string_lossy()
+                    .to_string();
+                out.insert(glob::Pattern::escape(&rel), format!("{:o}", mode));
+            }
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(not(unix))]
+pub fn unusual(_dir: &Path) -> Result<std::collections::BTreeMap<String, String>> {
+    Ok(Default::default())
+}
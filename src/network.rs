@@ -0,0 +1,431 @@
+//! Retries and a bandwidth cap for everything that goes over the network.
+//!
+//! Clones, fetches, pulls, pushes and HTTP requests that fail in a way that
+//! may pass (a timeout, a reset connection, an unreachable host, a 5xx) are
+//! tried again after 1, 2, 4... seconds, up to `retries` more times (3 by
+//! default) from `[network]` in the config. `bandwidth` (e.g. "500k" or "2m"
+//! bytes per second) throttles downloads dothub makes itself, and git's
+//! transfers through `trickle` when it is installed.
+//!
+//! The same section sets up corporate networks: a `proxy` for every host,
+//! `proxies` for particular ones, `no_proxy` hosts and a `ca_bundle` to trust
+//! besides the system's roots. Without them HTTPS_PROXY, HTTP_PROXY, ALL_PROXY
+//! and NO_PROXY are honored. Both dothub's HTTP client and git get them; git
+//! takes a single CA file that replaces its own, so the system's roots and the
+//! bundle are written together to `ca-bundle.pem` in the cache, and dothub's
+//! client trusts exactly that file too.
+
+use crate::config::NetworkConfig;
+use crate::output::{self, Tone};
+use anyhow::{bail, Context, Result};
+use reqwest::Url;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const DEFAULT_RETRIES: u32 = 3;
+/// Longest wait between two attempts.
+const MAX_DELAY_SECS: u64 = 30;
+/// Where systems keep their CA certificates, in the order OpenSSL-based
+/// tools like git look for them when SSL_CERT_FILE is unset.
+const SYSTEM_CA_FILES: &[&str] = &[
+    "/etc/ssl/certs/ca-certificates.crt",
+    "/etc/pki/tls/certs/ca-bundle.crt",
+    "/etc/ssl/ca-bundle.pem",
+    "/etc/ssl/cert.pem",
+    "/usr/local/etc/openssl/cert.pem",
+];
+
+/// Marks an error as worth another attempt.
+#[derive(Debug)]
+pub struct Transient(pub String);
+
+impl fmt::Display for Transient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Transient {}
+
+struct Settings {
+    retries: u32,
+    bandwidth: Option<u64>,
+    config: NetworkConfig,
+}
+
+fn settings() -> &'static Settings {
+    static SETTINGS: OnceLock<Settings> = OnceLock::new();
+    SETTINGS.get_or_init(|| {
+        let config = crate::config::Config::load()
+            .map(|c| c.network)
+            .unwrap_or_default();
+        let bandwidth = config
+            .bandwidth
+            .as_deref()
+            .and_then(|b| match parse_rate(b) {
+                Some(rate) => Some(rate),
+                None => {
+                    let msg = format!("Ignoring bandwidth = \"{}\": expected e.g. 500k or 2m", b);
+                    eprintln!("{}", output::paint(&msg, Tone::Warn));
+                    None
+                }
+            });
+        Settings {
+            retries: config.retries.unwrap_or(DEFAULT_RETRIES),
+            bandwidth,
+            config,
+        }
+    })
+}
+
+/// "500k" -> 512000 bytes per second.
+fn parse_rate(text: &str) -> Option<u64> {
+    let text = text.trim().to_lowercase();
+    let (number, unit) = match text.find(|c: char| !c.is_ascii_digit()) {
+        Some(at) => text.split_at(at),
+        None => (text.as_str(), ""),
+    };
+    let factor = match unit.trim_end_matches("/s").trim_end_matches('b') {
+        "" => 1,
+        "k" => 1024,
+        "m" => 1024 * 1024,
+        _ => return None,
+    };
+    number
+        .parse::<u64>()
+        .ok()
+        .filter(|n| *n > 0)
+        .map(|n| n * factor)
+}
+
+/// Bytes per second downloads may use, if capped.
+pub fn bandwidth() -> Option<u64> {
+    settings().bandwidth
+}
+
+/// Run `attempt` until it succeeds, fails for good, or runs out of retries.
+pub fn retry<T>(what: &str, mut attempt: impl FnMut() -> Result<T>) -> Result<T> {
+    let retries = settings().retries;
+    let mut tries = 0;
+    loop {
+        match attempt() {
+            Err(e) if tries < retries && e.chain().any(|c| c.is::<Transient>()) => {
+                let delay = (1u64 << tries.min(5)).min(MAX_DELAY_SECS);
+                tries += 1;
+                let msg = format!(
+                    "{} failed: {}; retrying in {}s ({}/{})",
+                    what,
+                    reason(&e, what),
+                    delay,
+                    tries,
+                    retries
+                );
+                eprintln!("{}", output::paint(&msg, Tone::Warn));
+                thread::sleep(Duration::from_secs(delay));
+            }
+            result => return result,
+        }
+    }
+}
+
+/// The gist of a transient error: its last line, from git's `fatal:` on, and
+/// without the `what` it repeats.
+fn reason(e: &anyhow::Error, what: &str) -> String {
+    let text = e
+        .chain()
+        .find_map(|c| c.downcast_ref::<Transient>())
+        .map_or_else(|| e.to_string(), |t| t.0.clone());
+    let line = text.lines().rfind(|l| !l.trim().is_empty()).unwrap_or("");
+    let line = match line.find("fatal: ") {
+        Some(at) => &line[at + "fatal: ".len()..],
+        None => line.strip_prefix(what).unwrap_or(line),
+    };
+    line.trim_start_matches([':', ' ']).to_string()
+}
+
+/// Whether git's stderr describes a failure that may pass on another try.
+pub fn git_transient(stderr: &str) -> bool {
+    const SIGNS: &[&str] = &[
+        "could not resolve host",
+        "connection timed out",
+        "operation timed out",
+        "connection reset",
+        "connection refused",
+        "early eof",
+        "rpc failed",
+        "the remote end hung up unexpectedly",
+        "failed to connect",
+        "network is unreachable",
+        "temporary failure in name resolution",
+        "gnutls_handshake",
+        "ssl_read",
+        "http/2 stream",
+        "502",
+        "503",
+        "504",
+    ];
+    let stderr = stderr.to_lowercase();
+    SIGNS.iter().any(|s| stderr.contains(s))
+}
+
+/// Whether git's stderr says the remote repository no longer exists, as
+/// when its owner deleted or renamed it.
+pub fn git_gone(stderr: &str) -> bool {
+    const SIGNS: &[&str] = &[
+        "repository not found",
+        "does not appear to be a git repository",
+        "the requested url returned error: 404",
+        "the requested url returned error: 410",
+    ];
+    let stderr = stderr.to_lowercase();
+    SIGNS.iter().any(|s| stderr.contains(s))
+        || (stderr.contains("fatal: repository '") && stderr.contains("' not found"))
+}
+
+/// The program and leading arguments to run git through: `trickle` when a
+/// cap is set and it is installed, plain git otherwise.
+pub fn git_program() -> (String, Vec<String>) {
+    let plain = ("git".to_string(), Vec::new());
+    let Some(rate) = bandwidth() else {
+        return plain;
+    };
+    if which::which("trickle").is_err() {
+        static WARNED: OnceLock<()> = OnceLock::new();
+        WARNED.get_or_init(|| {
+            let msg = "git runs without the bandwidth cap; install trickle to hold it to it";
+            eprintln!("{}", output::paint(msg, Tone::Warn));
+        });
+        return plain;
+    }
+    let kb = (rate / 1024).max(1).to_string();
+    let args = ["-s", "-d", &kb, "-u", &kb, "git"];
+    (
+        "trickle".to_string(),
+        args.iter().map(|a| a.to_string()).collect(),
+    )
+}
+
+/// Read all of `reader`, sleeping as needed to stay under the bandwidth cap.
+pub fn read_capped(mut reader: impl Read) -> std::io::Result<Vec<u8>> {
+    let Some(rate) = bandwidth() else {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        return Ok(bytes);
+    };
+    let start = Instant::now();
+    let mut bytes = Vec::new();
+    let mut chunk = vec![0; (rate as usize / 10).clamp(1024, 64 * 1024)];
+    loop {
+        let n = reader.read(&mut chunk)?;
+        if n == 0 {
+            return Ok(bytes);
+        }
+        bytes.extend_from_slice(&chunk[..n]);
+        let due = Duration::from_secs_f64(bytes.len() as f64 / rate as f64);
+        if let Some(wait) = due.checked_sub(start.elapsed()) {
+            thread::sleep(wait);
+        }
+    }
+}
+
+/// `-c` options that give git the configured proxies and CA bundle, and the
+/// NO_PROXY to run it with. git honors the proxy variables on its own.
+pub fn git_config() -> Result<(Vec<String>, Option<String>)> {
+    let config = &settings().config;
+    let mut args = Vec::new();
+    let mut set = |key: String, value: &str| {
+        args.push("-c".to_string());
+        args.push(format!("{}={}", key, value));
+    };
+    if let Some(proxy) = &config.proxy {
+        set("http.proxy".to_string(), proxy);
+    }
+    for (host, proxy) in &config.proxies {
+        // An empty proxy turns it off; git matches `*` in hosts itself
+        let proxy = if proxy == "direct" {
+            ""
+        } else {
+            proxy.as_str()
+        };
+        for scheme in ["https", "http"] {
+            set(format!("http.{}://{}/.proxy", scheme, host), proxy);
+        }
+    }
+    if let Some(path) = ca_file()? {
+        set("http.sslCAInfo".to_string(), &path.to_string_lossy());
+    }
+    Ok((args, config.no_proxy.as_ref().map(|hosts| hosts.join(","))))
+}
+
+/// The proxy to reach `url` through, if any.
+pub fn proxy_for(url: &Url) -> Option<String> {
+    let host = url.host_str()?;
+    let config = &settings().config;
+    let no_proxy = match &config.no_proxy {
+        Some(hosts) => hosts.clone(),
+        None => env_var(&["NO_PROXY", "no_proxy"])
+            .map(|v| v.split(',').map(|h| h.trim().to_string()).collect())
+            .unwrap_or_default(),
+    };
+    if no_proxy.iter().any(|entry| bypasses(host, entry)) {
+        return None;
+    }
+    let by_host = config
+        .proxies
+        .iter()
+        .find(|(pattern, _)| glob::Pattern::new(pattern).is_ok_and(|p| p.matches(host)));
+    if let Some((_, proxy)) = by_host {
+        return (proxy != "direct").then(|| proxy.clone());
+    }
+    if let Some(proxy) = &config.proxy {
+        return Some(proxy.clone());
+    }
+    match url.scheme() {
+        "https" => env_var(&["HTTPS_PROXY", "https_proxy", "ALL_PROXY", "all_proxy"]),
+        _ => env_var(&["HTTP_PROXY", "http_proxy", "ALL_PROXY", "all_proxy"]),
+    }
+}
+
+fn env_var(names: &[&str]) -> Option<String> {
+    names
+        .iter()
+        .find_map(|n| env::var(n).ok().filter(|v| !v.is_empty()))
+}
+
+/// NO_PROXY matching: `*` is every host, and `corp.example` or
+/// `.corp.example` is that domain and everything under it.
+fn bypasses(host: &str, entry: &str) -> bool {
+    let entry = entry.trim();
+    if entry == "*" {
+        return true;
+    }
+    let entry = entry.trim_start_matches("*.").trim_start_matches('.');
+    !entry.is_empty()
+        && (host == entry
+            || host
+                .strip_suffix(entry)
+                .is_some_and(|rest| rest.ends_with('.')))
+}
+
+/// The roots to trust in place of the built-in ones when `ca_bundle` is set:
+/// the same certificates git is pointed at.
+pub fn ca_certificates() -> Result<Option<Vec<reqwest::Certificate>>> {
+    let Some(path) = ca_file()? else {
+        return Ok(None);
+    };
+    let pem = fs::read(&path).with_context(|| format!("Reading {}", path.display()))?;
+    certificates(&pem)
+        .map(Some)
+        .with_context(|| format!("{} holds no PEM certificates", path.display()))
+}
+
+/// The CA file git and dothub's client both trust when `ca_bundle` is set,
+/// refreshed from the system's roots and the bundle.
+fn ca_file() -> Result<Option<PathBuf>> {
+    let Some(bundle) = &settings().config.ca_bundle else {
+        return Ok(None);
+    };
+    let system = env::var_os("SSL_CERT_FILE")
+        .map(PathBuf::from)
+        .into_iter()
+        .chain(SYSTEM_CA_FILES.iter().map(PathBuf::from))
+        .find(|p| p.is_file())
+        .context("No system CA certificates to add ca_bundle to; set SSL_CERT_FILE to them")?;
+    let path = crate::http::cache_dir().join("ca-bundle.pem");
+    combine(&system, &crate::source::expand_tilde(bundle), &path)?;
+    Ok(Some(path))
+}
+
+/// Write the certificates in `system` followed by those in `extra` to `dest`,
+/// unless it already holds exactly them.
+fn combine(system: &Path, extra: &Path, dest: &Path) -> Result<()> {
+    let mut pem = fs::read(system)
+        .with_context(|| format!("Reading system CA certificates {}", system.display()))?;
+    let bundle =
+        fs::read(extra).with_context(|| format!("Reading ca_bundle {}", extra.display()))?;
+    if certificates(&bundle).is_none() {
+        bail!("ca_bundle {} holds no PEM certificates", extra.display());
+    }
+    if !pem.is_empty() && !pem.ends_with(b"\n") {
+        pem.push(b'\n');
+    }
+    pem.extend(bundle);
+    if fs::read(dest).is_ok_and(|current| current == pem) {
+        return Ok(());
+    }
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    // Other dothub runs may be reading it; swap the whole file in at once
+    let tmp = dest.with_extension(format!("{}.new", std::process::id()));
+    fs::write(&tmp, &pem).with_context(|| format!("Writing {}", tmp.display()))?;
+    fs::rename(&tmp, dest)
+        .with_context(|| format!("Moving {} -> {}", tmp.display(), dest.display()))
+}
+
+fn certificates(pem: &[u8]) -> Option<Vec<reqwest::Certificate>> {
+    reqwest::Certificate::from_pem_bundle(pem)
+        .ok()
+        .filter(|c| !c.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SYSTEM_ROOT: &str = "-----BEGIN CERTIFICATE-----\n\
+        MIIBhjCCAS2gAwIBAgIUR1jPNbKaSWR4ekTaiv6O06hUiNgwCgYIKoZIzj0EAwIw\n\
+        GDEWMBQGA1UEAwwNZG90aHViIHRlc3QgYTAgFw0yNjEwMTUwNzI4MTVaGA8yMTI2\n\
+        MDkyMTA3MjgxNVowGDEWMBQGA1UEAwwNZG90aHViIHRlc3QgYTBZMBMGByqGSM49\n\
+        AgEGCCqGSM49AwEHA0IABC3JLKot3avWdZkwExMgejSfvTbfzuD7kQNPMnbvwvWo\n\
+        wUMpW6ygPqs849n7fcaowFG7Npy6qAvhX0v5zXh/K2ejUzBRMB0GA1UdDgQWBBTD\n\
+        EPB+skE0j0PFgkc7sowUZUPRNzAfBgNVHSMEGDAWgBTDEPB+skE0j0PFgkc7sowU\n\
+        ZUPRNzAPBgNVHRMBAf8EBTADAQH/MAoGCCqGSM49BAMCA0cAMEQCICpDOS3kwbgS\n\
+        cIampN13MWQa+rF0BiLrlfbRdWOZHoydAiBKcSxpNonDm3XRYGPCN6s9fCcoyJ5c\n\
+        fDpe21xsZqmoyg==\n\
+        -----END CERTIFICATE-----";
+    const CORP_ROOT: &str = "-----BEGIN CERTIFICATE-----\n\
+        MIIBhzCCAS2gAwIBAgIUTjvJonHhNimuaq/BzwsmWszMEZMwCgYIKoZIzj0EAwIw\n\
+        GDEWMBQGA1UEAwwNZG90aHViIHRlc3QgYjAgFw0yNjEwMTUwNzI4MTVaGA8yMTI2\n\
+        MDkyMTA3MjgxNVowGDEWMBQGA1UEAwwNZG90aHViIHRlc3QgYjBZMBMGByqGSM49\n\
+        AgEGCCqGSM49AwEHA0IABBhTVRpEjYgQGXp7QQpSipi6hSlYk+01mf++r0RmxvLE\n\
+        xu6hQDgE/fUlzHE9Jhu0LyZ/gNE67oV7Hku6JejiI1GjUzBRMB0GA1UdDgQWBBSp\n\
+        RVnoovMNXOqv9InTwEsR2w7yHDAfBgNVHSMEGDAWgBSpRVnoovMNXOqv9InTwEsR\n\
+        2w7yHDAPBgNVHRMBAf8EBTADAQH/MAoGCCqGSM49BAMCA0gAMEUCIAEbYOFPRLmg\n\
+        2wz+AdR/KpaVWMaztPTf+qFa4JvWc0u8AiEAplEwqwDRUosT3G1fhF9qekUGhCYd\n\
+        fUvWoGldQx5sMQs=\n\
+        -----END CERTIFICATE-----";
+
+    #[test]
+    fn git_and_http_trust_the_same_roots() {
+        let dir = tempfile::tempdir().unwrap();
+        let (system, extra, dest) = (
+            dir.path().join("system.pem"),
+            dir.path().join("corp.pem"),
+            dir.path().join("cache/ca-bundle.pem"),
+        );
+        fs::write(&system, SYSTEM_ROOT).unwrap();
+        fs::write(&extra, format!("{}\n", CORP_ROOT)).unwrap();
+        combine(&system, &extra, &dest).unwrap();
+
+        // git reads this file as its whole store, and the client gets its certificates
+        let pem = fs::read_to_string(&dest).unwrap();
+        assert_eq!(pem, format!("{}\n{}\n", SYSTEM_ROOT, CORP_ROOT));
+        let http = certificates(pem.as_bytes()).unwrap();
+        let git: Vec<_> = [SYSTEM_ROOT, CORP_ROOT]
+            .iter()
+            .flat_map(|root| certificates(root.as_bytes()).unwrap())
+            .collect();
+        assert_eq!(http.len(), git.len());
+
+        fs::write(&extra, "not a certificate").unwrap();
+        let err = combine(&system, &extra, &dest).unwrap_err();
+        assert!(err.to_string().contains("holds no PEM certificates"));
+    }
+}
@@ -0,0 +1,147 @@
+//! `dothub org`: manifests published by an organization, applied as layers.
+//!
+//! An organization's manifest is applied like the personal one, but its
+//! targets are recorded under the organization's name in the state file.
+//! They are left out of personal applies, so company-mandated configs and
+//! your own coexist, and `dothub org update` or `dothub org remove` changes
+//! only what the organization linked.
+
+use crate::apply;
+use crate::output::{self, Tone};
+use crate::state::{self, OrgLayer, State};
+use anyhow::{bail, Context, Result};
+use comfy_table::Cell;
+use std::path::Path;
+
+/// Layer name for a manifest at `url`: its host, or the file's name.
+fn default_name(url: &str) -> Result<String> {
+    let name = match reqwest::Url::parse(url) {
+        Ok(parsed) => parsed
+            .host_str()
+            .map(|h| h.trim_start_matches("www.").to_string()),
+        Err(_) => Path::new(url)
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string()),
+    };
+    name.filter(|n| !n.is_empty())
+        .with_context(|| format!("Can't name an org after {}; pass --name", url))
+}
+
+/// `dothub org apply`: apply the manifest at `url` as the layer `name`.
+pub fn cmd_org_apply(url: &str, name: Option<&str>, unattended: bool) -> Result<()> {
+    let name = match name {
+        Some(name) => name.to_string(),
+        None => default_name(url)?,
+    };
+    let st = State::load()?;
+    if let Some(layer) = st.orgs.get(&name).filter(|l| l.url != url) {
+        bail!(
+            "Org {} is already applied from {}; remove it first or pick another --name",
+            name,
+            layer.url
+        );
+    }
+    apply_layer(&name, url, unattended)
+}
+
+fn apply_layer(name: &str, url: &str, unattended: bool) -> Result<()> {
+    let path = apply::resolve_manifest(Some(Path::new(url)))?;
+    let plan = apply::plan(&path, Some(name))?;
+    apply::print_plan(&plan);
+    let result = apply::execute(&plan, unattended);
+    // Record the layer even if some links failed, so it can be updated or removed
+    let mut st = State::load()?;
+    let layer = st.orgs.entry(name.to_string()).or_default();
+    layer.url = url.to_string();
+    layer.applied_at = state::now_unix();
+    st.save()?;
+    result
+}
+
+/// `dothub org update`: fetch the layers' manifests again and apply them.
+pub fn cmd_org_update(name: Option<&str>, unattended: bool) -> Result<()> {
+    let st = State::load()?;
+    let layers: Vec<(String, OrgLayer)> = match name {
+        Some(name) => vec![(name.to_string(), layer(&st, name)?.clone())],
+        None => st.orgs.clone().into_iter().collect(),
+    };
+    if layers.is_empty() {
+        println!("No org manifests applied; add one with dothub org apply <url>.");
+        return Ok(());
+    }
+    let mut failed = 0;
+    for (name, layer) in &layers {
+        println!("{}", output::paint(&format!("org {}", name), Tone::Info));
+        if let Err(e) = apply_layer(name, &layer.url, unattended) {
+            failed += 1;
+            println!("{}", output::paint(&format!("{:#}", e), Tone::Error));
+        }
+    }
+    if failed > 0 {
+        bail!("{} of {} org(s) failed to update", failed, layers.len());
+    }
+    Ok(())
+}
+
+/// `dothub org remove`: unlink what the layer linked and forget it.
+pub fn cmd_org_remove(name: &str) -> Result<()> {
+    let mut st = State::load()?;
+    let targets = layer(&st, name)?.applied.clone();
+    for target_name in &targets {
+        if let Some(rec) = st.links.get(target_name) {
+            crate::protect::check(&rec.target)
+                .with_context(|| format!("Removing org {} would unlink {}", name, target_name))?;
+        }
+    }
+    for target_name in &targets {
+        if st.links.contains_key(target_name) {
+            println!("{}", crate::unlink_target(&mut st, target_name)?);
+        }
+    }
+    st.orgs.remove(name);
+    st.save()?;
+    println!(
+        "{}",
+        output::paint(
+            &format!(
+                "Removed org {}; your own manifest may link its targets again",
+                name
+            ),
+            Tone::Ok
+        )
+    );
+    Ok(())
+}
+
+/// `dothub org list`
+pub fn cmd_org_list() -> Result<()> {
+    let st = State::load()?;
+    if st.orgs.is_empty() {
+        println!("No org manifests applied; add one with dothub org apply <url>.");
+        return Ok(());
+    }
+    let now = state::now_unix();
+    let mut table = output::table(["Org", "Manifest", "Targets", "Applied"]);
+    for (name, layer) in &st.orgs {
+        let targets = layer.applied.iter().cloned().collect::<Vec<_>>().join(", ");
+        table.add_row(vec![
+            Cell::new(name),
+            Cell::new(&layer.url),
+            Cell::new(targets),
+            output::cell(output::ago(layer.applied_at, now), Tone::Muted),
+        ]);
+    }
+    println!("{}", table);
+    Ok(())
+}
+
+fn layer<'a>(st: &'a State, name: &str) -> Result<&'a OrgLayer> {
+    let Some(layer) = st.orgs.get(name) else {
+        bail!(crate::error::Error::new(
+            crate::error::Code::NotManaged,
+            format!("No org manifest named {} is applied", name),
+        )
+        .hint("dothub org list"));
+    };
+    Ok(layer)
+}
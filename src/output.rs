@@ -0,0 +1,212 @@
+//! Terminal output helpers shared by the commands.
+//!
+//! Tables and colors are only used when stdout is a terminal and `NO_COLOR`
+//! is unset; otherwise output degrades to plain, whitespace-aligned columns
+//! so it stays pipeable.
+//!
+//! Plain mode (`--plain`, `TERM=dumb` or `plain = true` in the config) goes
+//! further for screen readers and logs: no color, no spinners, and tables
+//! become one line per row that names each value ("Repo: nvim; Branch: main").
+
+use comfy_table::{modifiers::UTF8_ROUND_CORNERS, presets, Cell, Color};
+use owo_colors::OwoColorize;
+use std::env;
+use std::fmt;
+use std::io::{IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// Semantic color for a status word.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tone {
+    Ok,
+    Warn,
+    Error,
+    Info,
+    Muted,
+}
+
+static PLAIN: AtomicBool = AtomicBool::new(false);
+
+/// Switch to plain, line-oriented output for the rest of the run.
+pub fn set_plain() {
+    PLAIN.store(true, Ordering::SeqCst);
+}
+
+/// True in plain mode: `--plain`, the config's `plain` or a dumb terminal.
+pub fn plain() -> bool {
+    PLAIN.load(Ordering::SeqCst) || env::var("TERM").is_ok_and(|t| t == "dumb")
+}
+
+/// True when stdout is an interactive terminal that takes decorated output.
+pub fn is_tty() -> bool {
+    !plain() && std::io::stdout().is_terminal()
+}
+
+fn no_color() -> bool {
+    env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty())
+}
+
+/// Colors are enabled for terminals unless NO_COLOR is set (https://no-color.org).
+pub fn color_enabled() -> bool {
+    !no_color() && is_tty()
+}
+
+/// Color `text` according to `tone` when colors are enabled.
+pub fn paint(text: &str, tone: Tone) -> String {
+    if !color_enabled() {
+        return text.to_string();
+    }
+    colorize(text, tone)
+}
+
+fn colorize(text: &str, tone: Tone) -> String {
+    match tone {
+        Tone::Ok => text.green().to_string(),
+        Tone::Warn => text.yellow().to_string(),
+        Tone::Error => text.red().to_string(),
+        Tone::Info => text.cyan().to_string(),
+        Tone::Muted => text.dimmed().to_string(),
+    }
+}
+
+/// Print an error line to stderr.
+pub fn error(msg: &str) {
+    if !no_color() && !plain() && std::io::stderr().is_terminal() {
+        eprintln!("{}", colorize(msg, Tone::Error));
+    } else {
+        eprintln!("{}", msg);
+    }
+}
+
+/// A table of rows, printed as a table or, in plain mode, as one labelled
+/// line per row.
+pub struct Table {
+    inner: comfy_table::Table,
+    header: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    pub fn add_row<T: Into<Cell>>(&mut self, row: Vec<T>) -> &mut Self {
+        let row: Vec<Cell> = row.into_iter().map(Into::into).collect();
+        self.rows.push(row.iter().map(Cell::content).collect());
+        self.inner.add_row(row);
+        self
+    }
+}
+
+impl fmt::Display for Table {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !plain() {
+            return self.inner.fmt(f);
+        }
+        for (i, row) in self.rows.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            let fields: Vec<String> = self
+                .header
+                .iter()
+                .zip(row)
+                .filter(|(_, value)| !value.trim().is_empty() && value.as_str() != "-")
+                .map(|(name, value)| format!("{}: {}", name, value.trim()))
+                .collect();
+            write!(f, "{}", fields.join("; "))?;
+        }
+        Ok(())
+    }
+}
+
+/// New table with the given header, styled for the current output.
+pub fn table<I, T>(header: I) -> Table
+where
+    I: IntoIterator<Item = T>,
+    T: Into<Cell>,
+{
+    let header: Vec<Cell> = header.into_iter().map(Into::into).collect();
+    let mut table = comfy_table::Table::new();
+    if is_tty() {
+        table
+            .load_preset(presets::UTF8_BORDERS_ONLY)
+            .apply_modifier(UTF8_ROUND_CORNERS);
+    } else {
+        table.load_preset(presets::NOTHING);
+    }
+    if color_enabled() {
+        table.enforce_styling();
+    } else {
+        table.force_no_tty();
+    }
+    let names = header.iter().map(Cell::content).collect();
+    table.set_header(header);
+    Table {
+        inner: table,
+        header: names,
+        rows: Vec::new(),
+    }
+}
+
+/// Table cell colored by `tone` (only rendered when styling is enabled).
+pub fn cell(text: impl ToString, tone: Tone) -> Cell {
+    let color = match tone {
+        Tone::Ok => Color::Green,
+        Tone::Warn => Color::Yellow,
+        Tone::Error => Color::Red,
+        Tone::Info => Color::Cyan,
+        Tone::Muted => Color::DarkGrey,
+    };
+    Cell::new(text.to_string()).fg(color)
+}
+
+/// Compact human duration such as "3d" or "5mo".
+pub fn duration(secs: u64) -> String {
+    const MIN: u64 = 60;
+    const HOUR: u64 = 60 * MIN;
+    const DAY: u64 = 24 * HOUR;
+    match secs {
+        s if s < MIN => format!("{}s", s),
+        s if s < HOUR => format!("{}m", s / MIN),
+        s if s < DAY => format!("{}h", s / HOUR),
+        s if s < 14 * DAY => format!("{}d", s / DAY),
+        s if s < 60 * DAY => format!("{}w", s / (7 * DAY)),
+        s if s < 365 * DAY => format!("{}mo", s / (30 * DAY)),
+        s => format!("{}y", s / (365 * DAY)),
+    }
+}
+
+/// "3d ago" for a past unix timestamp, or "-" when unknown (0).
+pub fn ago(ts: u64, now: u64) -> String {
+    if ts == 0 {
+        return "-".to_string();
+    }
+    format!("{} ago", duration(now.saturating_sub(ts)))
+}
+
+/// Animate a spinner on stderr until the returned flag is set. In plain mode
+/// the message is printed once instead.
+pub fn start_spinner(message: &str) -> Arc<AtomicBool> {
+    let stop = Arc::new(AtomicBool::new(false));
+    if plain() {
+        eprintln!("{}", message);
+        return stop;
+    }
+    let stop_clone = Arc::clone(&stop);
+    let msg = message.to_string();
+    thread::spawn(move || {
+        let frames = ["-", "\\", "|", "/"]; // simple spinner
+        let mut i = 0usize;
+        // Print initial line
+        eprint!("{} {}\r", frames[i % frames.len()], msg);
+        let _ = std::io::stderr().flush();
+        while !stop_clone.load(Ordering::SeqCst) {
+            i = (i + 1) % frames.len();
+            eprint!("{} {}\r", frames[i], msg);
+            let _ = std::io::stderr().flush();
+            thread::sleep(Duration::from_millis(120));
+        }
+    });
+    stop
+}
@@ -0,0 +1,148 @@
+//! `dothub link --system user[:group]`: root deploying into another user's
+//! ~/.config, for service accounts.
+//!
+//! The target goes under that user's home directory, and the symlink or copy
+//! (and a ~/.config dothub had to create) is owned by the user and group
+//! instead of root. The ownership is recorded with the deployment so
+//! `dothub verify` can report when it changes.
+
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+#[derive(Clone, Debug)]
+pub struct Owner {
+    pub uid: u32,
+    pub gid: u32,
+    pub home: PathBuf,
+}
+
+impl Owner {
+    /// "uid:gid", as recorded in state.
+    pub fn ids(&self) -> String {
+        format!("{}:{}", self.uid, self.gid)
+    }
+}
+
+static OWNER: OnceLock<Owner> = OnceLock::new();
+
+/// Deploy for the user in `spec` for the rest of this run.
+pub fn set(spec: &str) -> Result<()> {
+    if crate::targets::is_termux() {
+        bail!("--system needs root, which Termux doesn't have");
+    }
+    let owner = lookup(spec)?;
+    let _ = OWNER.set(owner);
+    Ok(())
+}
+
+/// The user deployments are made for, when `--system` was given.
+pub fn current() -> Option<&'static Owner> {
+    OWNER.get()
+}
+
+/// Home directory of the user deployments are made for.
+pub fn home() -> Result<PathBuf> {
+    match current() {
+        Some(owner) => Ok(owner.home.clone()),
+        None => dirs::home_dir().context("Unable to determine home directory"),
+    }
+}
+
+/// ~/.config of the user deployments are made for.
+pub fn config_root() -> Result<PathBuf> {
+    Ok(home()?.join(".config"))
+}
+
+/// Resolve "user[:group]" (names or numeric ids) through /etc/passwd and
+/// /etc/group. Without a group, the user's primary group is used.
+fn lookup(spec: &str) -> Result<Owner> {
+    let (user, group) = match spec.split_once(':') {
+        Some((u, g)) => (u, Some(g)),
+        None => (spec, None),
+    };
+    let passwd = fs::read_to_string("/etc/passwd").context("Reading /etc/passwd")?;
+    // name:password:uid:gid:gecos:home:shell
+    let Some(entry) = passwd
+        .lines()
+        .map(|l| l.split(':').collect::<Vec<_>>())
+        .find(|f| f.len() >= 7 && (f[0] == user || f[2] == user))
+    else {
+        bail!("No user {} in /etc/passwd", user);
+    };
+    let uid = entry[2].parse().context("Malformed /etc/passwd")?;
+    let mut gid = entry[3].parse().context("Malformed /etc/passwd")?;
+    if let Some(group) = group {
+        gid = match group.parse() {
+            Ok(id) => id,
+            Err(_) => {
+                let groups = fs::read_to_string("/etc/group").context("Reading /etc/group")?;
+                groups
+                    .lines()
+                    .map(|l| l.split(':').collect::<Vec<_>>())
+                    .find(|f| f.len() >= 3 && f[0] == group)
+                    .and_then(|f| f[2].parse().ok())
+                    .with_context(|| format!("No group {} in /etc/group", group))?
+            }
+        };
+    }
+    Ok(Owner {
+        uid,
+        gid,
+        home: PathBuf::from(entry[5]),
+    })
+}
+
+/// Give `path` (and, for a copied directory, everything in it) to `owner`.
+/// Symlinks themselves are changed, not what they point at.
+#[cfg(unix)]
+pub fn apply(path: &Path, owner: &Owner) -> Result<()> {
+    if crate::vfs::dry_run() {
+        return Ok(());
+    }
+    std::os::unix::fs::lchown(path, Some(owner.uid), Some(owner.gid)).with_context(|| {
+        format!(
+            "Changing the owner of {} to {} (--system needs root)",
+            path.display(),
+            owner.ids()
+        )
+    })?;
+    let md = fs::symlink_metadata(path)?;
+    if md.is_dir() {
+        for entry in fs::read_dir(path).with_context(|| format!("Reading {}", path.display()))? {
+            apply(&entry?.path(), owner)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn apply(_path: &Path, _owner: &Owner) -> Result<()> {
+    bail!("--system is only supported on unix systems")
+}
+
+/// The first path under `target` not owned by `ids` ("uid:gid"), with the
+/// owner it has instead.
+#[cfg(unix)]
+pub fn drift(target: &Path, ids: &str) -> Option<(PathBuf, String)> {
+    use std::os::unix::fs::MetadataExt;
+    let md = fs::symlink_metadata(target).ok()?;
+    let actual = format!("{}:{}", md.uid(), md.gid());
+    if actual != ids {
+        return Some((target.to_path_buf(), actual));
+    }
+    if md.is_dir() {
+        for entry in fs::read_dir(target).ok()?.flatten() {
+            if let Some(found) = drift(&entry.path(), ids) {
+                return Some(found);
+            }
+        }
+    }
+    None
+}
+
+#[cfg(not(unix))]
+pub fn drift(_target: &Path, _ids: &str) -> Option<(PathBuf, String)> {
+    None
+}
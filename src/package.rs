@@ -0,0 +1,287 @@
+//! `dothub package`: release artifacts built from the running binary.
+//!
+//! Everything derives from one artifact, `dothub-<version>-<arch>-<os>.tar.gz`,
+//! holding the binary, shell completions and man pages. The Homebrew formula
+//! and AUR PKGBUILD download that tarball by its checksum, and the deb carries
+//! the same files in the places Debian expects them.
+
+use crate::output::{self, Tone};
+use crate::Shell;
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Default download location of release tarballs, `{version}` filled in.
+const RELEASE_URL: &str = concat!(env!("CARGO_PKG_REPOSITORY"), "/releases/download/v{version}");
+
+/// Completion files in the tarball, by shell.
+const COMPLETIONS: &[(Shell, &str)] = &[
+    (Shell::Bash, "dothub.bash"),
+    (Shell::Zsh, "_dothub"),
+    (Shell::Fish, "dothub.fish"),
+    (Shell::PowerShell, "_dothub.ps1"),
+];
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// dothub-<version>-<arch>-<os>.tar.gz with a .sha256 next to it
+    Tarball,
+    /// Homebrew formula, dothub.rb
+    Brew,
+    /// AUR PKGBUILD for dothub-bin
+    Aur,
+    /// Debian package, built with dpkg-deb
+    Deb,
+}
+
+/// Base name of the release tarball for this platform, e.g.
+/// "dothub-0.1.2-x86_64-linux".
+pub fn artifact_name() -> String {
+    format!("dothub-{}-{}-{}", VERSION, env::consts::ARCH, env::consts::OS)
+}
+
+/// Where the release tarball for this platform is published.
+pub fn artifact_url(base: Option<&str>) -> String {
+    let base = base
+        .map(str::to_string)
+        .unwrap_or_else(|| RELEASE_URL.replace("{version}", VERSION));
+    format!("{}/{}.tar.gz", base.trim_end_matches('/'), artifact_name())
+}
+
+/// `dothub package`: write the requested artifacts (all of them by default)
+/// into `out`.
+pub fn cmd_package(out: &Path, formats: &[Format], url: Option<&str>) -> Result<()> {
+    let formats = if formats.is_empty() {
+        Format::value_variants().to_vec()
+    } else {
+        formats.to_vec()
+    };
+    fs::create_dir_all(out).with_context(|| format!("Failed creating {}", out.display()))?;
+    let stage = out.join(artifact_name());
+    crate::remove_path(&stage)?;
+    stage_files(&stage)?;
+
+    // The formula and PKGBUILD pin the tarball's checksum, so it always exists
+    let tarball = tarball(out)?;
+    let sha256 = crate::state::hash_file(&tarball)?;
+    let sum_file = out.join(format!("{}.tar.gz.sha256", artifact_name()));
+    write(&sum_file, &format!("{}  {}.tar.gz\n", sha256, artifact_name()))?;
+    if formats.contains(&Format::Tarball) {
+        println!("Wrote {}", tarball.display());
+    }
+
+    let url = artifact_url(url);
+    if formats.contains(&Format::Brew) {
+        let path = out.join("dothub.rb");
+        write(&path, &brew_formula(&url, &sha256))?;
+        println!("Wrote {}", path.display());
+    }
+    if formats.contains(&Format::Aur) {
+        let path = out.join("PKGBUILD");
+        write(&path, &pkgbuild(&url, &sha256))?;
+        println!("Wrote {}", path.display());
+    }
+    if formats.contains(&Format::Deb) {
+        match deb(out, &stage) {
+            Ok(path) => println!("Wrote {}", path.display()),
+            Err(e) => println!(
+                "{}",
+                output::paint(&format!("Skipped the deb: {:#}", e), Tone::Warn)
+            ),
+        }
+    }
+    crate::remove_path(&stage)?;
+    if !formats.contains(&Format::Tarball) {
+        crate::remove_path(&tarball)?;
+        crate::remove_path(&sum_file)?;
+    }
+    Ok(())
+}
+
+fn write(path: &Path, text: &str) -> Result<()> {
+    fs::write(path, text).with_context(|| format!("Writing {}", path.display()))
+}
+
+/// Lay out bin/, completions/ and man/ under `stage`.
+fn stage_files(stage: &Path) -> Result<()> {
+    let exe = env::current_exe().context("Locating the dothub binary")?;
+    let bin = stage.join("bin");
+    fs::create_dir_all(&bin).with_context(|| format!("Failed creating {}", bin.display()))?;
+    fs::copy(&exe, bin.join("dothub"))
+        .with_context(|| format!("Copying {}", exe.display()))?;
+
+    let completions = stage.join("completions");
+    fs::create_dir_all(&completions)
+        .with_context(|| format!("Failed creating {}", completions.display()))?;
+    for (shell, file) in COMPLETIONS {
+        let mut buf = Vec::new();
+        crate::write_completions(*shell, &mut buf);
+        fs::write(completions.join(file), buf)
+            .with_context(|| format!("Writing {} completions", file))?;
+    }
+    crate::help::write_manpages(&stage.join("man"))?;
+    Ok(())
+}
+
+fn tarball(out: &Path) -> Result<PathBuf> {
+    let path = out.join(format!("{}.tar.gz", artifact_name()));
+    let status = Command::new("tar")
+        .arg("-czf")
+        .arg(&path)
+        .arg("-C")
+        .arg(out)
+        .arg(artifact_name())
+        .status()
+        .context("Failed to spawn tar (is it installed?)")?;
+    if !status.success() {
+        bail!("tar failed writing {} with status {}", path.display(), status);
+    }
+    Ok(path)
+}
+
+fn brew_formula(url: &str, sha256: &str) -> String {
+    format!(
+        r##"class Dothub < Formula
+  desc "{desc}"
+  homepage "{homepage}"
+  url "{url}"
+  sha256 "{sha256}"
+  version "{version}"
+  license "MIT OR Apache-2.0"
+
+  def install
+    bin.install "bin/dothub"
+    bash_completion.install "completions/dothub.bash" => "dothub"
+    zsh_completion.install "completions/_dothub"
+    fish_completion.install "completions/dothub.fish"
+    man1.install Dir["man/*.1"]
+    man7.install Dir["man/*.7"]
+  end
+
+  test do
+    assert_match "dothub", shell_output("#{{bin}}/dothub --help")
+  end
+end
+"##,
+        desc = env!("CARGO_PKG_DESCRIPTION"),
+        homepage = env!("CARGO_PKG_REPOSITORY"),
+        url = url,
+        sha256 = sha256,
+        version = VERSION,
+    )
+}
+
+fn pkgbuild(url: &str, sha256: &str) -> String {
+    format!(
+        r#"# Generated by dothub package
+pkgname=dothub-bin
+pkgver={version}
+pkgrel=1
+pkgdesc="{desc}"
+arch=('{arch}')
+url="{homepage}"
+license=('MIT' 'Apache-2.0')
+depends=('git')
+provides=('dothub')
+conflicts=('dothub')
+source=("{url}")
+sha256sums=('{sha256}')
+
+package() {{
+  cd "{name}"
+  install -Dm755 bin/dothub "$pkgdir/usr/bin/dothub"
+  install -Dm644 completions/dothub.bash "$pkgdir/usr/share/bash-completion/completions/dothub"
+  install -Dm644 completions/_dothub "$pkgdir/usr/share/zsh/site-functions/_dothub"
+  install -Dm644 completions/dothub.fish "$pkgdir/usr/share/fish/vendor_completions.d/dothub.fish"
+  install -Dm644 -t "$pkgdir/usr/share/man/man1" man/*.1
+  install -Dm644 -t "$pkgdir/usr/share/man/man7" man/*.7
+}}
+"#,
+        version = VERSION,
+        desc = env!("CARGO_PKG_DESCRIPTION"),
+        arch = env::consts::ARCH,
+        homepage = env!("CARGO_PKG_REPOSITORY"),
+        url = url,
+        sha256 = sha256,
+        name = artifact_name(),
+    )
+}
+
+/// Debian's name for the architecture dothub was built for.
+fn deb_arch() -> &'static str {
+    match env::consts::ARCH {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        "x86" => "i386",
+        "arm" => "armhf",
+        other => other,
+    }
+}
+
+fn deb(out: &Path, stage: &Path) -> Result<PathBuf> {
+    if which::which("dpkg-deb").is_err() {
+        bail!("dpkg-deb not found");
+    }
+    let root = out.join(format!("{}-deb", artifact_name()));
+    crate::remove_path(&root)?;
+    let place = |from: &str, to: &str| -> Result<()> {
+        let dest = root.join(to);
+        if let Some(dir) = dest.parent() {
+            fs::create_dir_all(dir).with_context(|| format!("Failed creating {}", dir.display()))?;
+        }
+        fs::copy(stage.join(from), &dest)
+            .with_context(|| format!("Copying {} into the deb", from))?;
+        Ok(())
+    };
+    place("bin/dothub", "usr/bin/dothub")?;
+    place("completions/dothub.bash", "usr/share/bash-completion/completions/dothub")?;
+    place("completions/_dothub", "usr/share/zsh/vendor-completions/_dothub")?;
+    place("completions/dothub.fish", "usr/share/fish/vendor_completions.d/dothub.fish")?;
+    for entry in fs::read_dir(stage.join("man"))? {
+        let name = entry?.file_name().to_string_lossy().to_string();
+        let section = name.rsplit('.').next().unwrap_or("1").to_string();
+        place(
+            &format!("man/{}", name),
+            &format!("usr/share/man/man{}/{}", section, name),
+        )?;
+    }
+    // DEBFULLNAME and DEBEMAIL name the packager, as for other Debian tools
+    let maintainer = format!(
+        "{} <{}>",
+        env::var("DEBFULLNAME").unwrap_or_else(|_| "dothub developers".to_string()),
+        env::var("DEBEMAIL").unwrap_or_else(|_| "noreply@github.com".to_string())
+    );
+    let control = format!(
+        "Package: dothub\nVersion: {}\nArchitecture: {}\nMaintainer: {}\n\
+         Depends: git\nSection: utils\nPriority: optional\nHomepage: {}\nDescription: {}\n",
+        VERSION,
+        deb_arch(),
+        maintainer,
+        env!("CARGO_PKG_REPOSITORY"),
+        env!("CARGO_PKG_DESCRIPTION"),
+    );
+    fs::create_dir_all(root.join("DEBIAN"))?;
+    write(&root.join("DEBIAN").join("control"), &control)?;
+
+    let path = out.join(format!("dothub_{}_{}.deb", VERSION, deb_arch()));
+    let result = Command::new("dpkg-deb")
+        .args(["--build", "--root-owner-group"])
+        .arg(&root)
+        .arg(&path)
+        .output()
+        .context("Running dpkg-deb");
+    crate::remove_path(&root)?;
+    let result = result?;
+    if !result.status.success() {
+        bail!(
+            "dpkg-deb failed: {}",
+            String::from_utf8_lossy(&result.stderr).trim()
+        );
+    }
+    Ok(path)
+}
@@ -0,0 +1,212 @@
+//! Timing of the slow parts of a command: network requests, git and
+//! filesystem work. With `--profile-perf` each run prints where its time went
+//! and adds its timings to running totals per repo and host, which
+//! `dothub perf` shows.
+
+use crate::output::{self, Tone};
+use anyhow::{Context, Result};
+use comfy_table::Cell;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const TOTALS_FILE: &str = "perf.json";
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static SPANS: Mutex<Vec<(Phase, String, Duration)>> = Mutex::new(Vec::new());
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Phase {
+    Network,
+    Git,
+    Fs,
+}
+
+impl Phase {
+    fn name(self) -> &'static str {
+        match self {
+            Phase::Network => "network",
+            Phase::Git => "git",
+            Phase::Fs => "fs",
+        }
+    }
+}
+
+/// Running totals for one phase and subject across profiled runs.
+#[derive(Default, Serialize, Deserialize)]
+struct Total {
+    calls: u64,
+    total_ms: f64,
+    max_ms: f64,
+}
+
+/// One phase of the current run.
+#[derive(Default)]
+struct PhaseRun<'a> {
+    calls: u64,
+    time: Duration,
+    slowest: Option<(&'a str, Duration)>,
+}
+
+/// Start recording spans for the rest of the run (`--profile-perf`).
+pub fn enable() {
+    ENABLED.store(true, Ordering::SeqCst);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::SeqCst)
+}
+
+/// Times work until dropped. The subject is only built when profiling.
+pub struct Span(Option<(Phase, String, Instant)>);
+
+pub fn span(phase: Phase, subject: impl FnOnce() -> String) -> Span {
+    Span(enabled().then(|| (phase, subject(), Instant::now())))
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        if let Some((phase, subject, start)) = self.0.take() {
+            let mut spans = SPANS.lock().unwrap_or_else(|e| e.into_inner());
+            spans.push((phase, subject, start.elapsed()));
+        }
+    }
+}
+
+/// Subject for a repo path: its directory name.
+pub fn repo_subject(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+/// Subject for a URL: its host.
+pub fn host_subject(url: &str) -> String {
+    url::Url::parse(url)
+        .ok()
+        .and_then(|u| u.host_str().map(str::to_string))
+        .unwrap_or_else(|| url.to_string())
+}
+
+fn totals_path() -> PathBuf {
+    crate::http::cache_dir().join(TOTALS_FILE)
+}
+
+fn load_totals() -> BTreeMap<String, Total> {
+    fs::read_to_string(totals_path())
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or_default()
+}
+
+fn ms(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
+
+fn show_ms(ms: f64) -> String {
+    if ms >= 1000.0 {
+        format!("{:.2}s", ms / 1000.0)
+    } else {
+        format!("{:.1}ms", ms)
+    }
+}
+
+/// Print where this run's time went and add it to the stored totals. Goes to
+/// stderr so command output stays clean.
+pub fn report(command: &str, elapsed: Duration) {
+    let spans = std::mem::take(&mut *SPANS.lock().unwrap_or_else(|e| e.into_inner()));
+
+    let mut phases: BTreeMap<Phase, PhaseRun> = BTreeMap::new();
+    for (phase, subject, took) in &spans {
+        let run = phases.entry(*phase).or_default();
+        run.calls += 1;
+        run.time += *took;
+        if run.slowest.is_none_or(|(_, max)| *took > max) {
+            run.slowest = Some((subject, *took));
+        }
+    }
+    let mut table = output::table(["Phase", "Calls", "Time", "Slowest"]);
+    for (phase, run) in &phases {
+        let slowest = run
+            .slowest
+            .map(|(s, d)| format!("{} ({})", s, show_ms(ms(d))))
+            .unwrap_or_default();
+        table.add_row(vec![
+            Cell::new(phase.name()),
+            Cell::new(run.calls),
+            Cell::new(show_ms(ms(run.time))),
+            Cell::new(slowest),
+        ]);
+    }
+    eprintln!();
+    if !phases.is_empty() {
+        eprintln!("{}", table);
+    }
+    eprintln!(
+        "{}",
+        output::paint(
+            &format!(
+                "dothub {} took {}; phases run in parallel can add up to more.",
+                command,
+                show_ms(ms(elapsed))
+            ),
+            Tone::Muted
+        )
+    );
+
+    let mut totals = load_totals();
+    for (phase, subject, took) in &spans {
+        let t = totals
+            .entry(format!("{} {}", phase.name(), subject))
+            .or_default();
+        t.calls += 1;
+        t.total_ms += ms(*took);
+        t.max_ms = t.max_ms.max(ms(*took));
+    }
+    // Like the HTTP cache, losing the totals is not worth failing a command over
+    let path = totals_path();
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    if let Ok(text) = serde_json::to_string(&totals) {
+        let _ = fs::write(path, text);
+    }
+}
+
+/// `dothub perf`: the slowest repos and hosts across profiled runs.
+pub fn cmd_perf(limit: usize, reset: bool) -> Result<()> {
+    let path = totals_path();
+    if reset {
+        if path.exists() {
+            fs::remove_file(&path).with_context(|| format!("Removing {}", path.display()))?;
+        }
+        println!("Cleared the recorded timings.");
+        return Ok(());
+    }
+    let totals = load_totals();
+    if totals.is_empty() {
+        println!("No timings recorded yet. Run any command with --profile-perf first.");
+        return Ok(());
+    }
+    let mut rows: Vec<(&String, &Total)> = totals.iter().collect();
+    rows.sort_by(|a, b| b.1.total_ms.total_cmp(&a.1.total_ms));
+
+    let mut table = output::table(["Phase", "Subject", "Calls", "Average", "Slowest", "Total"]);
+    for (key, t) in rows.into_iter().take(limit) {
+        let (phase, subject) = key.split_once(' ').unwrap_or((key, ""));
+        table.add_row(vec![
+            Cell::new(phase),
+            Cell::new(subject),
+            Cell::new(t.calls),
+            Cell::new(show_ms(t.total_ms / t.calls.max(1) as f64)),
+            Cell::new(show_ms(t.max_ms)),
+            Cell::new(show_ms(t.total_ms)),
+        ]);
+    }
+    println!("{}", table);
+    Ok(())
+}
@@ -0,0 +1,149 @@
+//! `dothub prompt`: a compact status string for shell prompts.
+//!
+//! Running git in every repo takes far too long for a prompt, so the prompt
+//! prints a summary saved by the last status check and, when that summary is
+//! old, starts a refresh in the background for the next prompt to pick up.
+//! With `dothub daemon` running, the daemon's status is used instead.
+
+use crate::state;
+use crate::status::{self, StatusRow};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+const SUMMARY_FILE: &str = "status.json";
+/// A refresh still running after this long is assumed to have died.
+const REFRESH_TIMEOUT: u64 = 60;
+
+const STARSHIP_MODULE: &str = r#"# Add to ~/.config/starship.toml; `$custom` in your format shows it
+[custom.dothub]
+command = "dothub prompt"
+when = true
+format = "[dotfiles $output]($style) "
+style = "bold yellow"
+"#;
+
+/// Counts from the last status check, as shown in the prompt.
+#[derive(Default, Serialize, Deserialize)]
+struct Summary {
+    behind: u64,
+    dirty: u64,
+    checked_at: u64,
+}
+
+fn summary_path() -> PathBuf {
+    crate::http::cache_dir().join(SUMMARY_FILE)
+}
+
+fn lock_path() -> PathBuf {
+    summary_path().with_extension("lock")
+}
+
+impl Summary {
+    fn from_rows(rows: &[StatusRow]) -> Summary {
+        Summary {
+            behind: rows.iter().filter(|r| r.behind() > 0).count() as u64,
+            dirty: rows.iter().filter(|r| r.is_dirty()).count() as u64,
+            checked_at: state::now_unix(),
+        }
+    }
+}
+
+/// Save the counts from a fresh status check for later prompts.
+pub fn record(rows: &[StatusRow]) {
+    let summary = Summary::from_rows(rows);
+    let path = summary_path();
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    // Like the HTTP cache, this is an optimisation; failing to write it is not an error
+    if let Ok(text) = serde_json::to_string(&summary) {
+        // Per-process temp name: the daemon and a background refresh may write at once
+        let tmp = path.with_extension(format!("json.{}.tmp", std::process::id()));
+        if fs::write(&tmp, text).is_ok() {
+            let _ = fs::rename(&tmp, &path);
+        }
+    }
+}
+
+pub fn cmd_prompt(max_age: u64, refresh: bool, starship: bool) -> Result<()> {
+    if starship {
+        print!("{}", STARSHIP_MODULE);
+        return Ok(());
+    }
+    if refresh {
+        let result = status::collect();
+        let _ = fs::remove_file(lock_path());
+        return result.map(|_| ());
+    }
+
+    // A running daemon always has fresh counts and answers within a millisecond
+    let summary = match crate::daemon::cached_status() {
+        Some(rows) => Some(Summary::from_rows(&rows)),
+        None => {
+            let saved: Option<Summary> = fs::read_to_string(summary_path())
+                .ok()
+                .and_then(|text| serde_json::from_str(&text).ok());
+            let now = state::now_unix();
+            if saved
+                .as_ref()
+                .is_none_or(|s| now.saturating_sub(s.checked_at) > max_age)
+            {
+                start_refresh()?;
+            }
+            saved
+        }
+    };
+    let summary = summary.unwrap_or_default();
+    let mut parts = Vec::new();
+    if summary.behind > 0 {
+        parts.push(format!("⇣{}", summary.behind));
+    }
+    if summary.dirty > 0 {
+        parts.push(format!("✚{}", summary.dirty));
+    }
+    if !parts.is_empty() {
+        println!("{}", parts.join(" "));
+    }
+    Ok(())
+}
+
+/// Run `dothub prompt --refresh` detached, unless one is already running.
+fn start_refresh() -> Result<()> {
+    let lock = lock_path();
+    let started = fs::metadata(&lock)
+        .and_then(|md| md.modified())
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs());
+    match started {
+        Some(t) if state::now_unix().saturating_sub(t) < REFRESH_TIMEOUT => return Ok(()),
+        Some(_) => {
+            let _ = fs::remove_file(&lock);
+        }
+        None => {}
+    }
+    if let Some(parent) = lock.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    // Another prompt won the race to start the refresh
+    if OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&lock)
+        .is_err()
+    {
+        return Ok(());
+    }
+    let exe = std::env::current_exe().context("Locating the dothub executable")?;
+    Command::new(exe)
+        .args(["prompt", "--refresh"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .context("Starting a background status refresh")?;
+    Ok(())
+}
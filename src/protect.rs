@@ -0,0 +1,67 @@
+//! Paths dothub must never modify, whatever the flags.
+//!
+//! `protected_paths` in the config file lists them (`~/.ssh` and `~/.gnupg`
+//! unless set). Linking, copying, backing up and removing all refuse a path
+//! that is protected, lies inside a protected path or contains one.
+
+use crate::config::Config;
+use crate::error::{Code, Error};
+use anyhow::{bail, Result};
+use std::path::{Component, Path, PathBuf};
+
+const DEFAULT_PROTECTED: &[&str] = &["~/.ssh", "~/.gnupg"];
+
+/// The protected paths from the config file, or the defaults.
+pub fn protected_paths() -> Result<Vec<PathBuf>> {
+    let paths = Config::load()?
+        .protected_paths
+        .unwrap_or_else(|| DEFAULT_PROTECTED.iter().map(|p| p.to_string()).collect());
+    Ok(paths
+        .iter()
+        .map(|p| normalize(&crate::source::expand_tilde(p)))
+        .collect())
+}
+
+/// Fail when changing `path` could touch a protected path.
+pub fn check(path: &Path) -> Result<()> {
+    let path = normalize(path);
+    for protected in protected_paths()? {
+        if path.starts_with(&protected) || protected.starts_with(&path) {
+            bail!(Error::new(
+                Code::ProtectedPath,
+                format!(
+                    "{} is protected ({} is listed in protected_paths); dothub will not modify it, even with --force",
+                    path.display(),
+                    protected.display()
+                ),
+            )
+            .hint(format!(
+                "remove it from protected_paths in {} if dothub should manage it",
+                crate::config::config_path().display()
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Absolute path with `.` and `..` removed and the parent directories
+/// resolved, without following `path` itself (it may be a symlink we manage).
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for part in path.components() {
+        match part {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            other => out.push(other),
+        }
+    }
+    match (out.parent(), out.file_name()) {
+        (Some(dir), Some(name)) => match dir.canonicalize() {
+            Ok(dir) => dir.join(name),
+            Err(_) => out,
+        },
+        _ => out,
+    }
+}
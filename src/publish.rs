@@ -0,0 +1,285 @@
+//! `dothub publish`: from a local config repo to one anyone can install.
+//!
+//! The repo is scanned for secrets first and not published while any are
+//! left. Then it is created on GitHub under the token's account when it
+//! isn't there yet, tagged with the `dotfiles` and `dothub` topics and its
+//! config types, given a README saying how to install it (a README from
+//! `dothub new` gets its `<repo-url>` filled in), and pushed. `--submit`
+//! opens the hub index in GitHub's editor, which forks it and opens the pull
+//! request adding the repo.
+
+use crate::forge::ForgeKind;
+use crate::git;
+use crate::manifest::RepoManifest;
+use crate::output::{self, Tone};
+use anyhow::{bail, Context, Result};
+use reqwest::blocking::Client;
+use reqwest::{Method, StatusCode};
+use serde_json::{json, Value};
+use std::fs;
+use std::path::Path;
+
+const API: &str = "https://api.github.com";
+
+/// Where `--submit` sends you: GitHub's editor for the hub index.
+const HUB_EDIT_URL: &str = "https://github.com/huncholane/dothub/edit/main/hub.yml";
+
+/// `dothub publish <name>`: `name` is a stored repo, or a path to a checkout.
+pub fn cmd_publish(name: &str, private: bool, submit: bool) -> Result<()> {
+    let repo = crate::verify_repo::repo_dir(name)?;
+    if git::head(&repo).is_none() {
+        bail!(
+            "{} has no commits to publish; commit it first",
+            repo.display()
+        );
+    }
+    let leaks = crate::secrets::scan_dir(&repo)?;
+    if !leaks.is_empty() {
+        crate::secrets::print(&leaks);
+        bail!(crate::error::Error::new(
+            crate::error::Code::SecretsFound,
+            format!(
+                "Not publishing {}: {} possible secret(s)",
+                repo.display(),
+                leaks.len()
+            ),
+        )
+        .hint(format!(
+            "dothub anonymize {} keeps them out of git",
+            repo.display()
+        )));
+    }
+    if crate::http::is_offline() {
+        bail!("Publishing needs the network");
+    }
+    let Some(token) = ForgeKind::GitHub.token() else {
+        bail!(crate::error::Error::new(
+            crate::error::Code::TokenMissing,
+            "Publishing to GitHub needs a token that can create repositories",
+        )
+        .hint("dothub auth login github, or set GITHUB_TOKEN"));
+    };
+    let owner = crate::auth::whoami(ForgeKind::GitHub, &token)?;
+    let repo_name = repo
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let full = format!("{}/{}", owner, repo_name);
+    let manifest = RepoManifest::load(&repo)?;
+    let description = manifest.as_ref().and_then(|m| m.description.clone());
+    let types = crate::verify_repo::types(&repo)
+        .into_iter()
+        .map(|(config_type, _)| config_type)
+        .collect::<Vec<_>>();
+
+    let client = crate::http::client()?;
+    let api = |method: Method, path: &str, body: Option<Value>| {
+        call(&client, &token, method, &format!("{}{}", API, path), body)
+    };
+    let (status, _) = api(Method::GET, &format!("/repos/{}", full), None)?;
+    if status == StatusCode::NOT_FOUND {
+        let body = json!({
+            "name": repo_name,
+            "description": description.clone().unwrap_or_default(),
+            "private": private,
+        });
+        let (status, v) = api(Method::POST, "/user/repos", Some(body))?;
+        if !status.is_success() {
+            bail!("GitHub didn't create {}: {}", full, message(&v, status));
+        }
+        println!(
+            "{}",
+            output::paint(&format!("Created github.com/{}", full), Tone::Ok)
+        );
+    } else if !status.is_success() {
+        bail!("HTTP {} asking GitHub for {}", status, full);
+    }
+
+    let (_, existing) = api(Method::GET, &format!("/repos/{}/topics", full), None)?;
+    let mut topics = existing
+        .get("names")
+        .and_then(|n| n.as_array())
+        .map(|names| {
+            names
+                .iter()
+                .filter_map(|n| n.as_str().map(str::to_string))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    for topic in ["dotfiles".to_string(), "dothub".to_string()]
+        .into_iter()
+        .chain(types.iter().map(|t| topic_name(t)))
+    {
+        if !topic.is_empty() && !topics.contains(&topic) {
+            topics.push(topic);
+        }
+    }
+    let (status, v) = api(
+        Method::PUT,
+        &format!("/repos/{}/topics", full),
+        Some(json!({ "names": topics })),
+    )?;
+    if !status.is_success() {
+        bail!(
+            "GitHub didn't take the topics for {}: {}",
+            full,
+            message(&v, status)
+        );
+    }
+
+    if let Some(path) = write_readme(&repo, &full, description.as_deref(), manifest.as_ref())? {
+        git::output(&repo, &["add", "--", &path])?;
+        git::commit(&repo, "Add install instructions to the README")?;
+        println!("Wrote {} with install instructions", path);
+    }
+
+    let remote = remote_for(&repo, &full)?;
+    git::network(&repo, &["push", "-u", &remote, "HEAD"], true)?;
+    let url = format!("https://github.com/{}", full);
+    println!(
+        "{}",
+        output::paint(
+            &format!("Published {}; install it with dothub install {}", url, full),
+            Tone::Ok
+        )
+    );
+
+    if submit {
+        let config_type = types.first().cloned().unwrap_or(repo_name);
+        let mut entry = format!("  {}:\n", config_type);
+        let images = crate::screenshot::images(&repo);
+        if images.is_empty() {
+            entry.push_str(&format!("    - {}\n", url));
+        } else {
+            entry.push_str(&format!("    - url: {}\n      screenshots:\n", url));
+            for image in images {
+                entry.push_str(&format!(
+                    "        - https://raw.githubusercontent.com/{}/HEAD/{}\n",
+                    full, image
+                ));
+            }
+        }
+        println!(
+            "Add it to hub.yml in the editor that opens and propose the change as a pull request:\n{}",
+            entry.trim_end()
+        );
+        if let Err(e) = crate::tui::open_url(HUB_EDIT_URL) {
+            println!("{}; open {} yourself", e, HUB_EDIT_URL);
+        }
+    }
+    Ok(())
+}
+
+/// One GitHub API request, with the status and JSON body of the answer.
+fn call(
+    client: &Client,
+    token: &str,
+    method: Method,
+    url: &str,
+    body: Option<Value>,
+) -> Result<(StatusCode, Value)> {
+    let _span = crate::perf::span(crate::perf::Phase::Network, || {
+        crate::perf::host_subject(url)
+    });
+    let mut req = client
+        .request(method.clone(), url)
+        .bearer_auth(token)
+        .header("Accept", "application/vnd.github+json");
+    if let Some(body) = body {
+        req = req.json(&body);
+    }
+    let resp = req.send().with_context(|| format!("{} {}", method, url))?;
+    let status = resp.status();
+    let text = resp.text().unwrap_or_default();
+    Ok((status, serde_json::from_str(&text).unwrap_or(Value::Null)))
+}
+
+/// GitHub's explanation in an error answer.
+fn message(v: &Value, status: StatusCode) -> String {
+    v.get("message")
+        .and_then(|m| m.as_str())
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("HTTP {}", status))
+}
+
+/// A config type as a GitHub topic: lowercase letters, digits and hyphens.
+fn topic_name(config_type: &str) -> String {
+    config_type
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect::<String>()
+        .trim_matches('-')
+        .to_string()
+}
+
+/// Give `repo` a README saying how to install it as `full`, or fill in the
+/// `<repo-url>` of one from `dothub new`. Returns the file it wrote, if any.
+fn write_readme(
+    repo: &Path,
+    full: &str,
+    description: Option<&str>,
+    manifest: Option<&RepoManifest>,
+) -> Result<Option<String>> {
+    let existing = ["README.md", "README", "README.markdown", "readme.md"]
+        .into_iter()
+        .find(|name| repo.join(name).is_file());
+    if let Some(name) = existing {
+        let path = repo.join(name);
+        let text = fs::read_to_string(&path).unwrap_or_default();
+        if !text.contains("<repo-url>") {
+            return Ok(None);
+        }
+        fs::write(&path, text.replace("<repo-url>", full))
+            .with_context(|| format!("Writing {}", path.display()))?;
+        return Ok(Some(name.to_string()));
+    }
+
+    let name = full.rsplit('/').next().unwrap_or(full);
+    let mut text = format!("# {}\n\n", name);
+    if let Some(description) = description.filter(|d| !d.is_empty()) {
+        text.push_str(&format!("{}\n\n", description.trim_end_matches('.')));
+    }
+    for image in crate::screenshot::images(repo) {
+        text.push_str(&format!("![{}]({})\n\n", name, image));
+    }
+    text.push_str(
+        "## Install\n\nWith [dothub](https://github.com/huncholane/dothub):\n\n```bash\n",
+    );
+    text.push_str(&format!("dothub install {}\n", full));
+    // All-in-one repos are linked by install; others need a target
+    if manifest.is_none_or(|m| m.provides.is_empty()) {
+        let target = manifest
+            .and_then(|m| m.default_target())
+            .unwrap_or(name)
+            .to_string();
+        text.push_str(&format!("dothub link {} {}\n", name, target));
+    }
+    text.push_str("```\n");
+    fs::write(repo.join("README.md"), text).context("Writing README.md")?;
+    Ok(Some("README.md".to_string()))
+}
+
+/// The remote of `repo` that points at github.com/`full`, adding one named
+/// origin (or github, when origin points elsewhere) if there is none.
+fn remote_for(repo: &Path, full: &str) -> Result<String> {
+    let remotes = git::output(repo, &["remote"])?;
+    for remote in remotes.lines() {
+        let url = git::output(repo, &["remote", "get-url", remote]).unwrap_or_default();
+        let same = crate::forge::RepoRef::parse(&url).is_some_and(|r| {
+            r.kind == ForgeKind::GitHub
+                && format!("{}/{}", r.owner, r.name).eq_ignore_ascii_case(full)
+        });
+        if same {
+            return Ok(remote.to_string());
+        }
+    }
+    let name = if remotes.lines().any(|r| r == "origin") {
+        "github"
+    } else {
+        "origin"
+    };
+    let url = format!("https://github.com/{}.git", full);
+    git::output(repo, &["remote", "add", name, &url])?;
+    Ok(name.to_string())
+}
@@ -0,0 +1,114 @@
+//! Read-only deployments for machines where configs must not drift.
+//!
+//! Linking with `--read-only` (or `link.read_only` in the config) removes write
+//! permission from the deployed copy and from the stored repo, and checks the
+//! repo out detached at its current commit so `update` leaves it alone. The
+//! repo stays locked while any read-only deployment uses it; `dothub edit`
+//! unlocks it for the length of an editing session.
+
+use crate::git;
+use crate::state::{RepoLock, State};
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::Path;
+
+/// Add or remove write permission on everything under `path`, leaving `.git`
+/// alone so git keeps working. Symlinks are not followed.
+pub fn set_writable(path: &Path, writable: bool) -> Result<()> {
+    if crate::vfs::dry_run() {
+        return Ok(());
+    }
+    let md = fs::symlink_metadata(path).with_context(|| format!("stat {}", path.display()))?;
+    if md.file_type().is_symlink() {
+        return Ok(());
+    }
+    // Directories are opened up before their entries and closed after them
+    if writable {
+        set_mode(path, &md, true)?;
+    }
+    if md.is_dir() {
+        for entry in fs::read_dir(path).with_context(|| format!("Reading {}", path.display()))? {
+            let entry = entry?;
+            if entry.file_name() != ".git" {
+                set_writable(&entry.path(), writable)?;
+            }
+        }
+    }
+    if !writable {
+        set_mode(path, &md, false)?;
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn set_mode(path: &Path, md: &fs::Metadata, writable: bool) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    let mode = md.permissions().mode();
+    let mode = if writable { mode | 0o200 } else { mode & !0o222 };
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+        .with_context(|| format!("Changing permissions of {}", path.display()))
+}
+
+#[cfg(not(unix))]
+fn set_mode(path: &Path, md: &fs::Metadata, writable: bool) -> Result<()> {
+    let mut perms = md.permissions();
+    #[allow(clippy::permissions_set_readonly_false)]
+    perms.set_readonly(!writable);
+    fs::set_permissions(path, perms)
+        .with_context(|| format!("Changing permissions of {}", path.display()))
+}
+
+/// The stored repo behind `name`, which is a subdirectory of it when a single
+/// config is linked out of a dotfiles repo (`dotfiles/git`).
+fn repo_of(name: &str) -> &str {
+    name.split('/').next().unwrap_or(name)
+}
+
+/// Pin a stored repo at its current commit and make its files read-only.
+/// Already locked repos are left as they are.
+pub fn lock_repo(st: &mut State, name: &str) -> Result<()> {
+    let name = repo_of(name);
+    let path = crate::dothub_dir().join(name);
+    let rec = st.repos.entry(name.to_string()).or_default();
+    if rec.lock.is_some() {
+        return Ok(());
+    }
+    if crate::vfs::dry_run() {
+        crate::vfs::note(format!("pin {} at its current commit, read-only", name));
+        return Ok(());
+    }
+    let mut lock = RepoLock::default();
+    if git::is_repo(&path) {
+        lock.rev = git::head(&path).unwrap_or_default();
+        lock.branch = git::output(&path, &["symbolic-ref", "-q", "--short", "HEAD"]).ok();
+        git::output(&path, &["checkout", "-q", "--detach"])?;
+    }
+    set_writable(&path, false)?;
+    rec.lock = Some(lock);
+    Ok(())
+}
+
+/// Undo `lock_repo`: files become writable and the branch is checked out again.
+pub fn unlock_repo(st: &mut State, name: &str) -> Result<()> {
+    let name = repo_of(name);
+    let path = crate::dothub_dir().join(name);
+    let Some(lock) = st.repos.get_mut(name).and_then(|r| r.lock.take()) else {
+        return Ok(());
+    };
+    if crate::vfs::dry_run() {
+        crate::vfs::note(format!("unpin {} and make it writable again", name));
+        return Ok(());
+    }
+    set_writable(&path, true)?;
+    if let Some(branch) = &lock.branch {
+        git::output(&path, &["checkout", "-q", branch])?;
+    }
+    Ok(())
+}
+
+/// True when a read-only deployment of the repo behind `name` is recorded.
+pub fn in_use(st: &State, name: &str) -> bool {
+    st.links
+        .values()
+        .any(|rec| rec.read_only && repo_of(&rec.name) == repo_of(name))
+}
@@ -0,0 +1,195 @@
+//! Getting a stored clone going again after its upstream rewrote history or
+//! renamed the branch it tracks.
+//!
+//! `git pull --ff-only` refuses both, so `dothub update` checks for them
+//! after fetching. A branch that vanished from the remote while the remote's
+//! default branch moved (master -> main) is a rename: the local branch is
+//! renamed and set to track the new one. A remote branch that no longer
+//! contains what it pointed at before the fetch was force-pushed: the clone
+//! is reset to it, with uncommitted changes stashed and the old commits kept
+//! on a branch. Either one is explained and only done after a yes, or with
+//! `dothub update --recover`; otherwise the repo is skipped.
+
+use crate::git;
+use crate::output::{self, Tone};
+use crate::vcs::Update;
+use anyhow::{bail, Result};
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static AUTO: AtomicBool = AtomicBool::new(false);
+
+/// Recover without asking for the rest of the run (`update --recover`).
+pub fn enable() {
+    AUTO.store(true, Ordering::SeqCst);
+}
+
+/// The remote and branch the current branch tracks, from its config, so it
+/// is known even when the remote branch is gone.
+fn tracked(path: &Path) -> Option<(String, String, String)> {
+    let local = git::output(path, &["symbolic-ref", "--short", "HEAD"]).ok()?;
+    let remote = git::output(path, &["config", &format!("branch.{}.remote", local)]).ok()?;
+    let merge = git::output(path, &["config", &format!("branch.{}.merge", local)]).ok()?;
+    let branch = merge.strip_prefix("refs/heads/")?.to_string();
+    Some((local, remote, branch))
+}
+
+fn rev(path: &Path, name: &str) -> Option<String> {
+    git::output(path, &["rev-parse", "--verify", "-q", name]).ok()
+}
+
+/// Pull the clone at `path`, recovering from a renamed or rewritten upstream.
+pub fn pull(path: &Path) -> Result<Update> {
+    let Some((local, remote, branch)) = tracked(path) else {
+        return Ok(Update::Skipped {
+            status: "skipped",
+            detail: "no upstream branch configured".to_string(),
+        });
+    };
+    let tracking = format!("refs/remotes/{}/{}", remote, branch);
+    let seen = rev(path, &tracking);
+    let before = git::head(path);
+    git::network(path, &["fetch", "--prune", "-q", &remote], false)?;
+
+    let mut upstream = format!("{}/{}", remote, branch);
+    if rev(path, &tracking).is_none() {
+        let Some(renamed) = default_branch(path, &remote).filter(|b| *b != branch) else {
+            bail!("{} no longer has a branch named {}", remote, branch);
+        };
+        let question = format!(
+            "{} renamed its branch {} to {}. Rename {} to {} here and track {}/{}?",
+            remote, branch, renamed, local, renamed, remote, renamed
+        );
+        if !confirm(path, &question)? {
+            return Ok(skipped(
+                "renamed",
+                &format!("upstream renamed {} to {}", branch, renamed),
+            ));
+        }
+        if local != renamed && rev(path, &format!("refs/heads/{}", renamed)).is_none() {
+            git::output(path, &["branch", "-m", &local, &renamed])?;
+        }
+        let local = git::output(path, &["symbolic-ref", "--short", "HEAD"])?;
+        upstream = format!("{}/{}", remote, renamed);
+        git::output(path, &["branch", "--set-upstream-to", &upstream, &local])?;
+    }
+
+    // A clone that can't fast-forward, because what the remote branch pointed
+    // at before (this fetch, or an earlier one found in its reflog) is no
+    // longer in it
+    let contains = |commit: &str| {
+        git::output(path, &["merge-base", "--is-ancestor", commit, &upstream]).is_ok()
+    };
+    let current = rev(path, &upstream);
+    let previous = seen
+        .filter(|seen| Some(seen) != current.as_ref())
+        .or_else(|| rev(path, &format!("{}@{{1}}", upstream)));
+    let rewritten = !contains("HEAD") && previous.is_some_and(|p| !contains(&p));
+    if rewritten {
+        let question = format!(
+            "{} was force-pushed, so its history no longer contains what you have. \
+             Reset to it, stashing local changes and keeping your commits on a branch?",
+            upstream
+        );
+        if !confirm(path, &question)? {
+            return Ok(skipped(
+                "rewritten",
+                &format!("{} was force-pushed", upstream),
+            ));
+        }
+        let detail = reset(path, &upstream)?;
+        return Ok(Update::Updated {
+            detail,
+            range: None,
+        });
+    }
+
+    git::network(path, &["merge", "--ff-only", "-q", &upstream], false)?;
+    let after = git::head(path);
+    if before == after {
+        return Ok(Update::UpToDate);
+    }
+    Ok(Update::Updated {
+        detail: format!(
+            "{} -> {}",
+            crate::short_sha(before.as_deref()),
+            crate::short_sha(after.as_deref())
+        ),
+        range: before.zip(after),
+    })
+}
+
+fn skipped(status: &'static str, what: &str) -> Update {
+    Update::Skipped {
+        status,
+        detail: format!("{}; dothub update --recover follows it", what),
+    }
+}
+
+/// The remote's default branch, asking the remote.
+fn default_branch(path: &Path, remote: &str) -> Option<String> {
+    git::network(path, &["remote", "set-head", remote, "--auto"], false).ok()?;
+    let head = git::output(
+        path,
+        &[
+            "symbolic-ref",
+            "--short",
+            &format!("refs/remotes/{}/HEAD", remote),
+        ],
+    )
+    .ok()?;
+    head.strip_prefix(&format!("{}/", remote))
+        .map(|b| b.to_string())
+}
+
+/// Reset to `upstream`, keeping what would be lost. Returns what was kept.
+fn reset(path: &Path, upstream: &str) -> Result<String> {
+    let mut kept = Vec::new();
+    if git::status(path)?.is_dirty() {
+        git::output(
+            path,
+            &[
+                "stash",
+                "push",
+                "-u",
+                "-q",
+                "-m",
+                "dothub: before resetting to a force-pushed upstream",
+            ],
+        )?;
+        kept.push("local changes in git stash".to_string());
+    }
+    let own = git::output(
+        path,
+        &["rev-list", "--count", &format!("{}..HEAD", upstream)],
+    )?;
+    if own != "0" {
+        let backup = format!("dothub-before-force-push-{}", crate::state::now_unix());
+        git::output(path, &["branch", &backup])?;
+        kept.push(format!("{} old commit(s) on branch {}", own, backup));
+    }
+    git::output(path, &["reset", "--hard", "-q", upstream])?;
+    let mut detail = format!("reset to force-pushed {}", upstream);
+    if !kept.is_empty() {
+        detail.push_str(&format!("; {}", kept.join(", ")));
+    }
+    Ok(detail)
+}
+
+/// Whether to go ahead: yes with `--recover`, the user's answer on a
+/// terminal, no otherwise.
+fn confirm(path: &Path, question: &str) -> Result<bool> {
+    if AUTO.load(Ordering::SeqCst) {
+        return Ok(true);
+    }
+    if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        return Ok(false);
+    }
+    println!("{}", output::paint(&path.display().to_string(), Tone::Warn));
+    print!("{} [y/N] ", question);
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
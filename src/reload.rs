@@ -0,0 +1,122 @@
+//! Reloading applications after their config changed under them.
+//!
+//! After `link` and `update`, each changed target whose application is running
+//! gets its reload action: tmux re-sources its config, kitty gets SIGUSR1,
+//! sway and i3 reload, and so on. `[reload.<target>]` in the config file adds
+//! actions or replaces the built-in ones.
+
+use crate::config::{Config, ReloadConfig};
+use crate::output::{self, Tone};
+use anyhow::{bail, Context, Result};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static DISABLED: AtomicBool = AtomicBool::new(false);
+
+/// Target, process that has to be running, and the shell command to run.
+/// `{target}` is replaced by the deployed path.
+const BUILTIN: &[(&str, &str, &str)] = &[
+    ("tmux", "tmux", "tmux source-file {target}/tmux.conf"),
+    ("kitty", "kitty", "pkill -USR1 -x kitty"),
+    ("sway", "sway", "swaymsg reload"),
+    ("i3", "i3", "i3-msg reload"),
+    ("hypr", "Hyprland", "hyprctl reload"),
+    ("waybar", "waybar", "pkill -USR2 -x waybar"),
+    ("mako", "mako", "makoctl reload"),
+    ("dunst", "dunst", "dunstctl reload"),
+];
+
+/// Skip reloads for the rest of the run (`--no-reload`).
+pub fn disable() {
+    DISABLED.store(true, Ordering::SeqCst);
+}
+
+/// Reload the application reading `target`, if it has an action and is
+/// running, and print what happened. Failures are reported, not returned:
+/// the config change itself went through.
+pub fn after_change(target_name: &str, target: &Path) {
+    if DISABLED.load(Ordering::SeqCst) {
+        return;
+    }
+    let configured = Config::load()
+        .map(|c| c.reload.get(target_name).cloned())
+        .unwrap_or_default();
+    let action = match configured {
+        Some(action) => action,
+        None => match BUILTIN.iter().find(|(t, _, _)| *t == target_name) {
+            Some((_, process, command)) => ReloadConfig {
+                process: Some(process.to_string()),
+                command: Some(command.to_string()),
+                unit: None,
+            },
+            None => return,
+        },
+    };
+    if crate::vfs::dry_run() {
+        crate::vfs::note(format!("reload what reads {}, if it is running", target_name));
+        return;
+    }
+    match run(&action, target) {
+        Ok(false) => {}
+        Ok(true) => println!(
+            "{}",
+            output::paint(&crate::i18n::t("reloaded", &[("target", &target_name)]), Tone::Ok)
+        ),
+        Err(e) => println!(
+            "{}",
+            output::paint(
+                &format!("Reloading {} failed: {:#}", target_name, e),
+                Tone::Warn
+            )
+        ),
+    }
+}
+
+/// Run an action; false when its application is not running.
+fn run(action: &ReloadConfig, target: &Path) -> Result<bool> {
+    let mut ran = false;
+    if let Some(unit) = &action.unit {
+        if quiet(Command::new("systemctl").args(["--user", "is-active", "--quiet", unit])) {
+            let status = Command::new("systemctl")
+                .args(["--user", "restart", unit])
+                .status()
+                .context("Running systemctl")?;
+            if !status.success() {
+                bail!("systemctl --user restart {} failed", unit);
+            }
+            ran = true;
+        }
+    }
+    let Some(command) = action.command.as_deref().filter(|c| !c.trim().is_empty()) else {
+        return Ok(ran);
+    };
+    if let Some(process) = &action.process {
+        if !quiet(Command::new("pgrep").args(["-x", process])) {
+            return Ok(ran);
+        }
+    }
+    let command = command.replace("{target}", &shell_quote(&target.to_string_lossy()));
+    let out = Command::new("sh")
+        .args(["-c", &command])
+        .stdin(Stdio::null())
+        .output()
+        .context("Running sh")?;
+    if !out.status.success() {
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        bail!("{}: {}", command, stderr.trim());
+    }
+    Ok(true)
+}
+
+/// True when the command ran and succeeded, with its output discarded.
+fn quiet(cmd: &mut Command) -> bool {
+    cmd.stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|s| s.success())
+}
+
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
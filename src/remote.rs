@@ -0,0 +1,58 @@
+//! `dothub remote set`: point a stored repo at a new upstream, for when the
+//! one it was cloned from is deleted or renamed.
+
+use crate::git;
+use crate::output::{self, Tone};
+use crate::state::{RepoSource, State};
+use anyhow::{bail, Result};
+
+pub fn cmd_remote_set(name: &str, url: &str) -> Result<()> {
+    git::ensure_git()?;
+    let path = crate::dothub_dir().join(name);
+    if !path.exists() {
+        bail!(crate::error::repo_not_found(name, &path));
+    }
+    if !git::is_repo(&path) {
+        bail!(
+            "{} is not a git repository, so it has no remote to change",
+            name
+        );
+    }
+    let old = git::output(&path, &["remote", "get-url", "origin"]).ok();
+    match &old {
+        Some(_) => git::output(&path, &["remote", "set-url", "origin", url])?,
+        None => git::output(&path, &["remote", "add", "origin", url])?,
+    };
+    // Keep the old remote unless the new one can be fetched
+    if let Err(e) = git::network(&path, &["fetch", "origin"], false) {
+        match &old {
+            Some(old) => git::output(&path, &["remote", "set-url", "origin", old])?,
+            None => git::output(&path, &["remote", "remove", "origin"])?,
+        };
+        return Err(e.context(format!("Fetching {}; the remote was left as it was", url)));
+    }
+    let branch = git::output(&path, &["symbolic-ref", "--short", "HEAD"])?;
+    let tracking = format!("origin/{}", branch);
+    if git::output(&path, &["rev-parse", "--verify", "-q", &tracking]).is_ok() {
+        git::output(&path, &["branch", "--set-upstream-to", &tracking, &branch])?;
+    }
+
+    let mut st = State::load()?;
+    let rec = st.repos.entry(name.to_string()).or_default();
+    rec.source = Some(match rec.source.take() {
+        // jujutsu repos are colocated, so they fetch from git's remotes
+        Some(RepoSource::Jj { .. }) => RepoSource::Jj {
+            url: url.to_string(),
+        },
+        _ => RepoSource::Git {
+            url: url.to_string(),
+        },
+    });
+    rec.upstream_gone = None;
+    st.save()?;
+    println!(
+        "{}",
+        output::paint(&format!("{} now updates from {}", name, url), Tone::Ok)
+    );
+    Ok(())
+}
@@ -0,0 +1,167 @@
+//! `dothub repair --relocate`: fix the links a moved store leaves behind.
+//!
+//! Moving the store, or migrating one from dotman's /usr/local/share/dotman,
+//! breaks every symlink into it. The home directory is searched for symlinks
+//! into the old location, along with every target dothub recorded wherever it
+//! is, and each one is pointed at the same path under the new location. The
+//! new link is made beside the old one and renamed over it, so the target is
+//! never missing. Recorded deployments are moved over in the state file too.
+
+use crate::output::{self, Tone};
+use crate::state::State;
+use anyhow::{bail, Context, Result};
+use std::collections::BTreeSet;
+use std::env;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// `dothub repair --relocate <old> <new>`.
+pub fn cmd_relocate(old: &Path, new: &Path) -> Result<()> {
+    let old = absolute(old)?;
+    let new = absolute(new)?;
+    if old == new {
+        bail!("{} and {} are the same place", old.display(), new.display());
+    }
+    if !new.is_dir() {
+        bail!(
+            "{} is not a directory; move the store there before relinking to it",
+            new.display()
+        );
+    }
+
+    let mut st = State::load()?;
+    let mut links = BTreeSet::new();
+    if let Some(home) = dirs::home_dir() {
+        scan(&home, &new, &mut links);
+    }
+    links.extend(st.links.values().map(|record| record.target.clone()));
+
+    let fs = crate::vfs::fs();
+    let mut relinked = 0usize;
+    for link in &links {
+        let Ok(dest) = fs.read_link(link) else {
+            continue;
+        };
+        let dir = link.parent().unwrap_or(Path::new("/"));
+        // Relative links (`link --relative`) resolve from the link's directory
+        let resolved = if dest.is_relative() {
+            normalize(&dir.join(&dest))
+        } else {
+            dest.clone()
+        };
+        let Ok(rest) = resolved.strip_prefix(&old) else {
+            continue;
+        };
+        let moved = new.join(rest);
+        // and stay relative, when the new location can be resolved
+        let points_to = if dest.is_relative() {
+            crate::relative_path(dir, &moved).unwrap_or(moved.clone())
+        } else {
+            moved.clone()
+        };
+        let mut sibling = link.file_name().unwrap_or_default().to_os_string();
+        sibling.push(".dothub-tmp");
+        let sibling = dir.join(sibling);
+        if fs.kind(&sibling).is_some() {
+            fs.remove(&sibling)?;
+        }
+        fs.symlink(&points_to, &sibling)
+            .and_then(|_| fs.rename(&sibling, link))
+            .with_context(|| format!("Relinking {}", link.display()))?;
+        let note = if moved.exists() {
+            ""
+        } else {
+            " (not in the new store)"
+        };
+        println!("  {} -> {}{}", link.display(), moved.display(), note);
+        relinked += 1;
+    }
+
+    let mut recorded = 0usize;
+    for record in st.links.values_mut() {
+        if let Ok(rest) = record.source.strip_prefix(&old) {
+            record.source = new.join(rest);
+            recorded += 1;
+        }
+    }
+    if recorded > 0 {
+        st.save()?;
+    }
+
+    if relinked == 0 && recorded == 0 {
+        println!("Nothing points into {}.", old.display());
+    } else {
+        println!(
+            "{}",
+            output::paint(
+                &format!(
+                    "Relinked {} symlink(s) and {} deployment(s) from {} to {}",
+                    relinked,
+                    recorded,
+                    old.display(),
+                    new.display()
+                ),
+                Tone::Ok
+            )
+        );
+    }
+    if crate::dothub_dir() != new {
+        println!(
+            "{}",
+            output::paint(
+                &format!(
+                    "dothub still keeps its store at {}; set DOTHUB_DIR={} to use the new one",
+                    crate::dothub_dir().display(),
+                    new.display()
+                ),
+                Tone::Warn
+            )
+        );
+    }
+    Ok(())
+}
+
+fn absolute(path: &Path) -> Result<PathBuf> {
+    let path = crate::source::expand_tilde(&path.to_string_lossy());
+    if path.is_absolute() {
+        return Ok(path);
+    }
+    Ok(env::current_dir()
+        .context("Reading the current directory")?
+        .join(path))
+}
+
+/// `path` with `.` and `..` taken out, without looking at the filesystem,
+/// which may no longer have what a broken link names.
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for part in path.components() {
+        match part {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            part => out.push(part),
+        }
+    }
+    out
+}
+
+/// Every symlink under `dir`, without following any or going into .git or
+/// the store at `store`. Unreadable directories are passed over.
+fn scan(dir: &Path, store: &Path, links: &mut BTreeSet<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Ok(kind) = entry.file_type() else {
+            continue;
+        };
+        if kind.is_symlink() {
+            links.insert(path);
+        } else if kind.is_dir() && entry.file_name() != ".git" && path != store {
+            scan(&path, store, links);
+        }
+    }
+}
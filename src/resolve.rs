@@ -0,0 +1,141 @@
+//! `dothub resolve`: finish a merge or rebase that stopped on conflicts.
+//!
+//! When pulling your own changes together with upstream's stops on conflicts,
+//! the conflicted files are listed and git's configured mergetool is opened on
+//! each in turn. Once none are left, the merge, rebase or cherry-pick is
+//! continued, which may stop on the next commit's conflicts; run resolve again
+//! then.
+
+use crate::git;
+use crate::output::{self, Tone};
+use anyhow::{bail, Context, Result};
+use std::path::Path;
+use std::process::Command;
+
+/// A git operation that can stop halfway on conflicts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum InProgress {
+    Rebase,
+    Merge,
+    CherryPick,
+}
+
+impl InProgress {
+    fn name(self) -> &'static str {
+        match self {
+            InProgress::Rebase => "rebase",
+            InProgress::Merge => "merge",
+            InProgress::CherryPick => "cherry-pick",
+        }
+    }
+}
+
+/// What `repo` is in the middle of, if anything.
+fn in_progress(repo: &Path) -> Option<InProgress> {
+    let git_dir = git::output(repo, &["rev-parse", "--absolute-git-dir"]).ok()?;
+    let git_dir = Path::new(&git_dir);
+    if git_dir.join("rebase-merge").exists() || git_dir.join("rebase-apply").exists() {
+        Some(InProgress::Rebase)
+    } else if git_dir.join("MERGE_HEAD").exists() {
+        Some(InProgress::Merge)
+    } else if git_dir.join("CHERRY_PICK_HEAD").exists() {
+        Some(InProgress::CherryPick)
+    } else {
+        None
+    }
+}
+
+/// Paths with unresolved conflicts, relative to the repo.
+fn conflicted(repo: &Path) -> Result<Vec<String>> {
+    let out = git::output(repo, &["diff", "--name-only", "--diff-filter=U"])?;
+    Ok(out.lines().map(str::to_string).collect())
+}
+
+pub fn cmd_resolve(name: &str, tool: Option<&str>) -> Result<()> {
+    let repo = crate::dothub_dir().join(name);
+    if !repo.exists() {
+        bail!(crate::error::repo_not_found(name, &repo));
+    }
+    git::ensure_git()?;
+    let Some(op) = in_progress(&repo) else {
+        println!("{} is not in the middle of a merge or rebase.", name);
+        return Ok(());
+    };
+
+    let files = conflicted(&repo)?;
+    if !files.is_empty() {
+        println!("{} conflicted file(s) in the {} of {}:", files.len(), op.name(), name);
+        for file in &files {
+            println!("  {}", file);
+        }
+    }
+    for file in &files {
+        let mut cmd = Command::new("git");
+        cmd.arg("-C").arg(&repo).args(["mergetool", "--no-prompt"]);
+        if let Some(tool) = tool {
+            cmd.arg(format!("--tool={}", tool));
+        }
+        let status = cmd
+            .arg("--")
+            .arg(file)
+            .status()
+            .context("Failed to spawn git mergetool")?;
+        if !status.success() {
+            println!(
+                "{}",
+                output::paint(&format!("{} is still conflicted", file), Tone::Warn)
+            );
+        }
+    }
+
+    let left = conflicted(&repo)?;
+    if !left.is_empty() {
+        bail!(
+            "{} file(s) in {} are still conflicted: {}; run dothub resolve {} again",
+            left.len(),
+            name,
+            left.join(", "),
+            name
+        );
+    }
+    // Keep the prepared commit message instead of opening an editor on it
+    let out = Command::new("git")
+        .arg("-C")
+        .arg(&repo)
+        .args([op.name(), "--continue"])
+        .env("GIT_EDITOR", "true")
+        .output()
+        .with_context(|| format!("Running git {} --continue", op.name()))?;
+    if !out.status.success() {
+        if in_progress(&repo).is_some() && !conflicted(&repo)?.is_empty() {
+            println!(
+                "{}",
+                output::paint(
+                    &format!(
+                        "The {} of {} stopped on the next conflicts; run dothub resolve {} again",
+                        op.name(),
+                        name,
+                        name
+                    ),
+                    Tone::Warn
+                )
+            );
+            return Ok(());
+        }
+        bail!(
+            "git {} --continue failed in {}: {}",
+            op.name(),
+            repo.display(),
+            String::from_utf8_lossy(&out.stderr).trim()
+        );
+    }
+    let head = git::output(&repo, &["log", "-1", "--format=%h %s"]).unwrap_or_default();
+    println!(
+        "{}",
+        output::paint(
+            &format!("Finished the {} of {}; now at {}", op.name(), name, head),
+            Tone::Ok
+        )
+    );
+    Ok(())
+}
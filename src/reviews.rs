@@ -0,0 +1,130 @@
+//! `dothub hub reviews`: how well maintained a hub entry looks, judged from
+//! its forge: open issues, the last push and release, and discussion
+//! activity. Results are cached for a few hours, and for good when offline.
+
+use crate::forge::{self, ForgeKind, RepoHealth};
+use crate::output::{self, Tone};
+use crate::state;
+use anyhow::{bail, Result};
+use comfy_table::Cell;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+const CACHE_FILE: &str = "health.json";
+/// Cached answers younger than this are used without asking the forge.
+const FRESH_FOR: u64 = 6 * 60 * 60;
+/// No push for this long and a repo counts as quiet.
+const QUIET_AFTER: u64 = 180 * 24 * 60 * 60;
+
+#[derive(Serialize, Deserialize)]
+struct Cached {
+    health: RepoHealth,
+    fetched_at: u64,
+}
+
+fn cache_path() -> PathBuf {
+    crate::http::cache_dir().join(CACHE_FILE)
+}
+
+fn load_cache() -> BTreeMap<String, Cached> {
+    fs::read_to_string(cache_path())
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or_default()
+}
+
+fn save_cache(cache: &BTreeMap<String, Cached>) {
+    let path = cache_path();
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    if let Ok(text) = serde_json::to_string(cache) {
+        let _ = fs::write(path, text);
+    }
+}
+
+/// Health of `link`, from the cache when it is fresh, we are offline or the
+/// forge can't be reached.
+fn health(link: &str, refresh: bool) -> Result<(RepoHealth, u64)> {
+    let mut cache = load_cache();
+    let now = state::now_unix();
+    if let Some(c) = cache.get(link) {
+        if crate::http::is_offline() || (!refresh && c.fetched_at + FRESH_FOR > now) {
+            return Ok((c.health.clone(), c.fetched_at));
+        }
+    }
+    if crate::http::is_offline() {
+        bail!("Offline and no reviews of {} are cached yet", link);
+    }
+    match forge::fetch_health(&crate::http::client()?, link) {
+        Ok(health) => {
+            cache.insert(
+                link.to_string(),
+                Cached {
+                    health: health.clone(),
+                    fetched_at: now,
+                },
+            );
+            save_cache(&cache);
+            Ok((health, now))
+        }
+        Err(e) => match cache.remove(link) {
+            Some(c) => Ok((c.health, c.fetched_at)),
+            None => Err(e),
+        },
+    }
+}
+
+pub fn cmd_reviews(entry: &str, url: Option<&str>, refresh: bool) -> Result<()> {
+    let link = crate::hub::resolve_entry(entry, url)?;
+    let (health, fetched_at) = health(&link, refresh)?;
+    let now = state::now_unix();
+    let when = |ts: Option<u64>| ts.map_or("-".to_string(), |t| output::ago(t, now));
+
+    let release = match &health.last_release {
+        Some((tag, at)) => format!("{} ({})", tag, output::ago(*at, now)),
+        None => "none".to_string(),
+    };
+    let discussions = match health.discussions {
+        Some((count, last)) => format!("{}, last active {}", count, when(last)),
+        None if link.contains("github.com") && ForgeKind::GitHub.token().is_none() => {
+            "unknown (dothub auth login github)".to_string()
+        }
+        None => "-".to_string(),
+    };
+    let mut table = output::table(["Repo", "Open issues", "Last push", "Last release", "Discussions"]);
+    table.add_row(vec![
+        Cell::new(&link),
+        Cell::new(health.open_issues.map_or("-".to_string(), |n| n.to_string())),
+        Cell::new(when(health.last_push)),
+        Cell::new(release),
+        Cell::new(discussions),
+    ]);
+    println!("{}", table);
+
+    let (verdict, tone) = if health.archived {
+        ("Archived: the owner no longer maintains it.".to_string(), Tone::Error)
+    } else {
+        match health.last_push {
+            Some(t) if t + QUIET_AFTER < now => (
+                format!("Quiet: nothing pushed for {}.", output::duration(now - t)),
+                Tone::Warn,
+            ),
+            Some(_) => ("Recently active.".to_string(), Tone::Ok),
+            None => ("No activity information.".to_string(), Tone::Muted),
+        }
+    };
+    println!("{}", output::paint(&verdict, tone));
+    if fetched_at + 60 < now {
+        println!(
+            "{}",
+            output::paint(
+                &format!("Cached {}; --refresh asks the forge again.", output::ago(fetched_at, now)),
+                Tone::Info
+            )
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,208 @@
+//! Hooks from a repo's dothub.toml, run in a sandbox.
+//!
+//! A repo can declare shell commands to run after it is linked or updated
+//! (`[hooks] post_link = "./install.sh"`). They are someone else's code, so
+//! by default they run under bubblewrap: the system is read-only, the home
+//! directory and the per-user runtime directories (session bus, agent
+//! sockets) are empty tmpfs mounts apart from the repo, the network is cut
+//! and only a handful of environment variables are passed. Without
+//! bubblewrap, `unshare` still cuts the network, but the hook can read and
+//! write everything you can. `dothub sandbox <name> network|off` loosens this per repo once
+//! its hooks have been reviewed.
+
+use crate::manifest::RepoManifest;
+use crate::output::{self, Tone};
+use crate::state::{Sandbox, State};
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Variables passed into sandboxed hooks; everything else is dropped.
+const KEEP_ENV: &[&str] = &["PATH", "LANG", "LC_ALL", "TERM"];
+
+#[derive(Clone, Copy)]
+pub enum Hook {
+    PostLink,
+    PostUpdate,
+}
+
+impl Hook {
+    fn name(self) -> &'static str {
+        match self {
+            Hook::PostLink => "post_link",
+            Hook::PostUpdate => "post_update",
+        }
+    }
+
+    fn command(self, manifest: &RepoManifest) -> Option<&str> {
+        match self {
+            Hook::PostLink => manifest.hooks.post_link.as_deref(),
+            Hook::PostUpdate => manifest.hooks.post_update.as_deref(),
+        }
+    }
+}
+
+/// Run `hook` of repo `name` if its dothub.toml declares one. `target` is the
+/// deployment a post_link hook was run for.
+pub fn run_hook(name: &str, hook: Hook, target: Option<&Path>) -> Result<()> {
+    let repo = crate::dothub_dir().join(name);
+    let Some(manifest) = RepoManifest::load(&repo)? else {
+        return Ok(());
+    };
+    let Some(script) = hook.command(&manifest) else {
+        return Ok(());
+    };
+    if crate::vfs::dry_run() {
+        crate::vfs::note(format!("run the {} hook of {}", hook.name(), name));
+        return Ok(());
+    }
+    if crate::targets::is_termux() && level_of(name)? != Sandbox::Off {
+        println!(
+            "{}",
+            output::paint(
+                &format!(
+                    "Skipped the {} hook of {}: Termux can't sandbox it (dothub sandbox {} off runs it as you)",
+                    hook.name(),
+                    name,
+                    name
+                ),
+                Tone::Warn
+            )
+        );
+        return Ok(());
+    }
+    if !crate::trust::allow(name, &format!("the {} hook", hook.name()), script)? {
+        return Ok(());
+    }
+    let level = level_of(name)?;
+    // Local sources are symlinked into the store; bind the real directory
+    let repo = fs::canonicalize(&repo).with_context(|| format!("Resolving {}", repo.display()))?;
+    let mut cmd = command(name, &repo, script, level)?;
+    cmd.env("DOTHUB_REPO", &repo);
+    if let Some(target) = target {
+        cmd.env("DOTHUB_TARGET", target);
+    }
+    println!(
+        "{}",
+        output::paint(
+            &format!(
+                "Running the {} hook of {} (sandbox: {}): {}",
+                hook.name(),
+                name,
+                level.name(),
+                script
+            ),
+            Tone::Info
+        )
+    );
+    let status = cmd
+        .status()
+        .with_context(|| format!("Running the {} hook of {}", hook.name(), name))?;
+    if !status.success() {
+        bail!("The {} hook of {} failed ({})", hook.name(), name, status);
+    }
+    Ok(())
+}
+
+fn level_of(name: &str) -> Result<Sandbox> {
+    Ok(State::load()?
+        .repos
+        .get(name)
+        .map(|r| r.sandbox)
+        .unwrap_or_default())
+}
+
+/// The command running `script` in `repo` under `level`.
+fn command(name: &str, repo: &Path, script: &str, level: Sandbox) -> Result<Command> {
+    if level == Sandbox::Off {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(script).current_dir(repo);
+        return Ok(cmd);
+    }
+    let home = dirs::home_dir().context("Unable to determine home directory")?;
+    let mut cmd = if cfg!(target_os = "linux") && which::which("bwrap").is_ok() {
+        let mut cmd = Command::new("bwrap");
+        cmd.args([
+            "--ro-bind",
+            "/",
+            "/",
+            "--dev",
+            "/dev",
+            "--proc",
+            "/proc",
+            "--tmpfs",
+            "/tmp",
+        ])
+        .arg("--tmpfs")
+        .arg(&home);
+        // The session bus, keyring and ssh and gpg agents listen in the
+        // runtime directory
+        let runtime = std::env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from);
+        for dir in [Some(PathBuf::from("/run/user")), runtime]
+            .into_iter()
+            .flatten()
+            .filter(|dir| dir.is_dir() && !repo.starts_with(dir))
+        {
+            cmd.arg("--tmpfs").arg(dir);
+        }
+        cmd.arg("--bind").args([repo, repo]).args([
+            "--unshare-all",
+            "--die-with-parent",
+            "--new-session",
+        ]);
+        if level == Sandbox::Network {
+            cmd.arg("--share-net");
+        }
+        cmd.arg("--chdir").arg(repo);
+        cmd
+    } else if cfg!(target_os = "linux") && which::which("unshare").is_ok() {
+        println!(
+            "{}",
+            output::paint(
+                "bubblewrap (bwrap) is not installed; the hook can read and write all your files",
+                Tone::Warn
+            )
+        );
+        let mut cmd = Command::new("unshare");
+        cmd.args(["--user", "--map-root-user"]);
+        if level == Sandbox::Strict {
+            cmd.arg("--net");
+        }
+        cmd.current_dir(repo);
+        cmd
+    } else {
+        bail!(
+            "No sandbox is available for the hooks of {}; install bubblewrap (bwrap), or run \
+             them unconfined with dothub sandbox {} off after reading them",
+            name,
+            name
+        );
+    };
+    cmd.args(["sh", "-c", script])
+        .env_clear()
+        .env("HOME", &home);
+    for key in KEEP_ENV {
+        if let Ok(value) = std::env::var(key) {
+            cmd.env(key, value);
+        }
+    }
+    Ok(cmd)
+}
+
+/// `dothub sandbox`: set how far a repo's hooks are confined.
+pub fn cmd_sandbox(name: &str, level: Sandbox) -> Result<()> {
+    let path = crate::dothub_dir().join(name);
+    if !path.exists() {
+        bail!(crate::error::repo_not_found(name, &path));
+    }
+    let mut st = State::load()?;
+    st.repos.entry(name.to_string()).or_default().sandbox = level;
+    st.save()?;
+    match level {
+        Sandbox::Strict => println!("Hooks of {} run sandboxed without network", name),
+        Sandbox::Network => println!("Hooks of {} run sandboxed with network", name),
+        Sandbox::Off => println!("Hooks of {} run unconfined, as you", name),
+    }
+    Ok(())
+}
@@ -0,0 +1,221 @@
+//! `dothub screenshot`: a picture of a config for its README and hub entry.
+//!
+//! The application is started in a detached tmux session of a fixed size,
+//! so it draws the same on every machine, and the pane is captured with its
+//! colors once it has had time to settle. freeze or termshot turns the
+//! capture into a PNG in the repo's `doc/` directory, and the README gets a
+//! link to it. Hub entries with screenshots get installed far more.
+
+use crate::state::State;
+use anyhow::{bail, Context, Result};
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+/// Config type, program that has to be installed, and the command that
+/// starts it; other types start the program of the same name.
+const APPS: &[(&str, &str, &str)] = &[
+    ("zsh", "zsh", "zsh -i"),
+    ("bash", "bash", "bash -i"),
+    ("fish", "fish", "fish -i"),
+    // Its own server, so the capture isn't of the session it runs in
+    (
+        "tmux",
+        "tmux",
+        "env -u TMUX tmux -L dothub-screenshot-app new-session",
+    ),
+    ("helix", "hx", "hx"),
+];
+
+/// Program that has to be installed, and the shell command that renders the
+/// capture in `$IN` to the PNG at `$OUT`, in order of preference.
+const RENDERERS: &[(&str, &str)] = &[
+    ("freeze", "freeze --output \"$OUT\" < \"$IN\""),
+    ("termshot", "termshot --filename \"$OUT\" -- cat \"$IN\""),
+];
+
+/// Size of the terminal the application draws in.
+const COLUMNS: &str = "100";
+const LINES: &str = "30";
+
+/// `dothub screenshot <target>`: `target` is a deployed target or a stored
+/// repo. `command` replaces the application's usual start command.
+pub fn cmd_screenshot(target_name: &str, command: Option<&str>, delay: u64) -> Result<()> {
+    let st = State::load()?;
+    let (repo, config_type) = match st.links.get(target_name) {
+        Some(rec) => {
+            let repo = crate::dothub_dir().join(rec.name.split('/').next().unwrap_or(&rec.name));
+            let config_type = crate::lint::config_type(&repo, target_name);
+            (repo, config_type)
+        }
+        None if crate::dothub_dir().join(target_name).is_dir() => {
+            let repo = crate::dothub_dir().join(target_name);
+            let config_type = crate::lint::config_type(&repo, target_name);
+            (repo, config_type)
+        }
+        None => bail!(
+            "{} is neither a deployed target nor a stored repo",
+            target_name
+        ),
+    };
+    let command = match command {
+        Some(command) => command.to_string(),
+        None => app_command(&config_type)?,
+    };
+    if which::which("tmux").is_err() {
+        bail!(missing("tmux"));
+    }
+    let Some((renderer, render)) = RENDERERS
+        .iter()
+        .find(|(program, _)| which::which(program).is_ok())
+    else {
+        bail!(missing("freeze or termshot"));
+    };
+
+    let capture = capture(&command, delay)?;
+    let dir = repo.join("doc");
+    fs::create_dir_all(&dir).with_context(|| format!("Creating {}", dir.display()))?;
+    let file = format!("{}.png", config_type);
+    let image = dir.join(&file);
+    let input = env::temp_dir().join(format!("dothub-screenshot-{}.ansi", std::process::id()));
+    fs::write(&input, capture).with_context(|| format!("Writing {}", input.display()))?;
+    let out = Command::new("sh")
+        .args(["-c", render])
+        .env("IN", &input)
+        .env("OUT", &image)
+        .stdin(Stdio::null())
+        .output()
+        .with_context(|| format!("Running {}", renderer));
+    let _ = fs::remove_file(&input);
+    let out = out?;
+    if !out.status.success() || !image.is_file() {
+        bail!(
+            "{} didn't render the screenshot: {}",
+            renderer,
+            String::from_utf8_lossy(&out.stderr).trim()
+        );
+    }
+    println!("Saved {}", image.display());
+
+    let link = format!("doc/{}", file);
+    if add_to_readme(&repo, &config_type, &link)? {
+        println!("Linked it from README.md");
+    }
+    Ok(())
+}
+
+/// The command that starts the application of `config_type`.
+fn app_command(config_type: &str) -> Result<String> {
+    let (program, command) = APPS
+        .iter()
+        .find(|(t, _, _)| *t == config_type)
+        .map(|(_, program, command)| (*program, *command))
+        .unwrap_or((config_type, config_type));
+    if which::which(program).is_err() {
+        bail!(
+            "No terminal application named {} to start for {}; pass --command with the one to run",
+            program,
+            config_type
+        );
+    }
+    Ok(command.to_string())
+}
+
+fn missing(program: &str) -> crate::error::Error {
+    crate::error::Error::new(
+        crate::error::Code::ToolMissing,
+        format!("{} is not installed or not found in PATH", program),
+    )
+    .hint("dothub screenshot needs tmux, and freeze or termshot to render the image")
+}
+
+/// Run `command` in a detached tmux pane for `delay` seconds and return
+/// what it drew, with its colors as escape codes.
+fn capture(command: &str, delay: u64) -> Result<String> {
+    let socket = format!("dothub-screenshot-{}", std::process::id());
+    let tmux = |args: &[&str]| {
+        Command::new("tmux")
+            .args(["-L", &socket, "-f", "/dev/null"])
+            .args(args)
+            .env_remove("TMUX")
+            .stdin(Stdio::null())
+            .output()
+            .context("Running tmux")
+    };
+    // Keep the pane after the command exits, so a short one is still captured
+    let started = tmux(&[
+        "start-server",
+        ";",
+        "set-option",
+        "-g",
+        "remain-on-exit",
+        "on",
+        ";",
+        "new-session",
+        "-d",
+        "-s",
+        "shot",
+        "-x",
+        COLUMNS,
+        "-y",
+        LINES,
+        command,
+    ])?;
+    if !started.status.success() {
+        bail!(
+            "tmux didn't start {}: {}",
+            command,
+            String::from_utf8_lossy(&started.stderr).trim()
+        );
+    }
+    std::thread::sleep(Duration::from_secs(delay));
+    let captured = tmux(&["capture-pane", "-p", "-e", "-t", "shot"]);
+    let _ = tmux(&["kill-server"]);
+    let captured = captured?;
+    if !captured.status.success() {
+        bail!(
+            "tmux couldn't capture {}: {}",
+            command,
+            String::from_utf8_lossy(&captured.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&captured.stdout).to_string())
+}
+
+/// The screenshots in `repo`, relative to it.
+pub fn images(repo: &Path) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(repo.join("doc")) else {
+        return Vec::new();
+    };
+    let mut images = entries
+        .flatten()
+        .map(|e| e.file_name().to_string_lossy().to_string())
+        .filter(|name| name.ends_with(".png"))
+        .map(|name| format!("doc/{}", name))
+        .collect::<Vec<_>>();
+    images.sort();
+    images
+}
+
+/// Link `image` from the README of `repo` below its title, unless it already
+/// is. Returns whether the README changed.
+fn add_to_readme(repo: &Path, config_type: &str, image: &str) -> Result<bool> {
+    let path = repo.join("README.md");
+    let Ok(text) = fs::read_to_string(&path) else {
+        return Ok(false);
+    };
+    if text.contains(image) {
+        return Ok(false);
+    }
+    let line = format!("![{}]({})\n", config_type, image);
+    let text = match text.split_once('\n') {
+        Some((title, rest)) if title.starts_with("# ") => {
+            format!("{}\n\n{}{}", title, line, rest)
+        }
+        _ => format!("{}\n{}", line, text),
+    };
+    fs::write(&path, text).with_context(|| format!("Writing {}", path.display()))?;
+    Ok(true)
+}
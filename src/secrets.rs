@@ -0,0 +1,280 @@
+//! Secrets about to leave the machine: a gitleaks-style scan.
+//!
+//! Each line of the files about to be committed is matched against rules for
+//! well-known token formats and private keys, credentials in URLs, and
+//! passwords written into mail and chat configs (msmtp, mbsync, neomutt).
+//! Values assigned to names like `api_key` or `token` are flagged when they
+//! look random enough to be a key, by their Shannon entropy. Values read from
+//! the environment or a password manager are fine. False positives go in
+//! `.dothub-secrets-allow` at the repo's root, one `path` or `path:rule` per
+//! line, where the path may be a glob.
+
+use crate::output::{self, Tone};
+use anyhow::Result;
+use comfy_table::Cell;
+use regex::Regex;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use std::sync::OnceLock;
+
+pub const ALLOWLIST: &str = ".dothub-secrets-allow";
+
+/// Files larger than this are not read.
+const MAX_SIZE: u64 = 1024 * 1024;
+
+/// Rule name and the pattern it matches; the first capture group, when
+/// there is one, is the secret itself.
+const RULES: &[(&str, &str)] = &[
+    ("private-key", r"-----BEGIN[A-Z ]*PRIVATE KEY-----"),
+    ("aws-access-key", r"\b((?:AKIA|ASIA)[0-9A-Z]{16})\b"),
+    (
+        "github-token",
+        r"\b((?:ghp|gho|ghu|ghs|ghr)_[A-Za-z0-9]{36}|github_pat_[A-Za-z0-9_]{22,})",
+    ),
+    ("gitlab-token", r"\b(glpat-[A-Za-z0-9_-]{20,})"),
+    ("slack-token", r"\b(xox[abprs]-[A-Za-z0-9-]{10,})"),
+    ("openai-key", r"\b(sk-(?:proj-)?[A-Za-z0-9_-]{32,})"),
+    ("google-api-key", r"\b(AIza[0-9A-Za-z_-]{35})"),
+    ("stripe-key", r"\b([rs]k_live_[0-9A-Za-z]{24,})"),
+    (
+        "url-credentials",
+        r"\b[a-z][a-z0-9+.-]*://[^/\s:@]+:([^/\s:@]{3,})@",
+    ),
+    (
+        "email-password",
+        r"\b[\w.+-]+@[\w-]+\.[\w.-]+:([^\s@:/]{6,})",
+    ),
+    (
+        "password",
+        r#"(?i)(?:^|[\s.-])(?:password|passwd|smtp_pass|imap_pass)(?:\s*[=:]\s*|\s+)["']?([^\s"']{6,})"#,
+    ),
+];
+
+/// Names whose values are checked for randomness.
+const KEY_ASSIGNMENT: &str = r#"(?i)[\w.-]*(?:api_?key|secret|token|auth|credential)[\w.-]*["']?\s*[=:]\s*["']?([A-Za-z0-9+/_=-]{20,})"#;
+
+/// Bits per character above which a value looks like a generated key.
+const ENTROPY_THRESHOLD: f64 = 4.0;
+
+/// Parts of a value that mean it is read from somewhere else.
+const INDIRECT: &[&str] = &[
+    "$",
+    "`",
+    "getenv",
+    "env.",
+    "pass show",
+    "op read",
+    "secret-tool",
+    "gpg",
+    "eval",
+    "keyring",
+];
+
+/// A possible secret.
+pub struct Leak {
+    pub rule: &'static str,
+    /// Path relative to the scanned directory
+    pub path: String,
+    pub line: usize,
+    /// The secret with all but its first characters hidden
+    pub redacted: String,
+}
+
+impl Leak {
+    /// The allowlist entry that silences this one.
+    pub fn allow_entry(&self) -> String {
+        format!("{}:{}", self.path, self.rule)
+    }
+}
+
+fn rules() -> &'static [(&'static str, Regex)] {
+    static RULES_RE: OnceLock<Vec<(&'static str, Regex)>> = OnceLock::new();
+    RULES_RE.get_or_init(|| {
+        RULES
+            .iter()
+            .map(|(rule, pattern)| (*rule, Regex::new(pattern).expect("valid rule pattern")))
+            .collect()
+    })
+}
+
+fn key_assignment() -> &'static Regex {
+    static KEY_RE: OnceLock<Regex> = OnceLock::new();
+    KEY_RE.get_or_init(|| Regex::new(KEY_ASSIGNMENT).expect("valid key pattern"))
+}
+
+/// Every file under `root` except .git.
+pub fn scan_dir(root: &Path) -> Result<Vec<Leak>> {
+    let mut files = Vec::new();
+    walk(root, root, &mut files);
+    scan_files(root, &files)
+}
+
+/// The files staged in the git repo at `repo`, as they are in the work tree.
+pub fn scan_staged(repo: &Path) -> Result<Vec<Leak>> {
+    let staged = crate::git::output(
+        repo,
+        &["diff", "--cached", "--name-only", "--diff-filter=ACMR"],
+    )?;
+    let files = staged.lines().map(str::to_string).collect::<Vec<_>>();
+    scan_files(repo, &files)
+}
+
+/// Scan `files`, relative to `root`, leaving out what the allowlist there
+/// allows.
+fn scan_files(root: &Path, files: &[String]) -> Result<Vec<Leak>> {
+    let allowed = allowlist(root);
+    let mut leaks = Vec::new();
+    for rel in files {
+        if rel == ALLOWLIST {
+            continue;
+        }
+        let Some(text) = read_text(&root.join(rel)) else {
+            continue;
+        };
+        for (idx, line) in text.lines().enumerate() {
+            if let Some((rule, secret)) = check_line(line) {
+                let leak = Leak {
+                    rule,
+                    path: rel.clone(),
+                    line: idx + 1,
+                    redacted: redact(secret),
+                };
+                if !allowed.iter().any(|entry| entry.allows(&leak)) {
+                    leaks.push(leak);
+                }
+            }
+        }
+    }
+    Ok(leaks)
+}
+
+/// The rule `line` breaks and the secret in it, if any.
+fn check_line(line: &str) -> Option<(&'static str, &str)> {
+    for (rule, re) in rules() {
+        let Some(caps) = re.captures(line) else {
+            continue;
+        };
+        let secret = caps.get(1).or(caps.get(0))?.as_str();
+        if *rule == "password" && is_indirect(line) {
+            continue;
+        }
+        return Some((rule, secret));
+    }
+    let caps = key_assignment().captures(line)?;
+    let value = caps.get(1)?.as_str();
+    (!is_indirect(line) && entropy(value) > ENTROPY_THRESHOLD).then_some(("high-entropy", value))
+}
+
+fn is_indirect(line: &str) -> bool {
+    let lower = line.to_lowercase();
+    INDIRECT.iter().any(|s| lower.contains(s))
+}
+
+/// Shannon entropy of `value` in bits per character.
+fn entropy(value: &str) -> f64 {
+    let mut counts = [0usize; 256];
+    for b in value.bytes() {
+        counts[b as usize] += 1;
+    }
+    let len = value.len() as f64;
+    counts
+        .iter()
+        .filter(|&&n| n > 0)
+        .map(|&n| {
+            let p = n as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+fn redact(secret: &str) -> String {
+    let shown = secret.chars().take(4).collect::<String>();
+    format!("{}…", shown)
+}
+
+/// Text of `path`, unless it is binary, large or unreadable.
+fn read_text(path: &Path) -> Option<String> {
+    let md = fs::symlink_metadata(path).ok()?;
+    if !md.is_file() || md.len() > MAX_SIZE {
+        return None;
+    }
+    let mut bytes = Vec::new();
+    fs::File::open(path).ok()?.read_to_end(&mut bytes).ok()?;
+    if bytes.contains(&0) {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&bytes).to_string())
+}
+
+fn walk(root: &Path, dir: &Path, files: &mut Vec<String>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut entries = entries.flatten().collect::<Vec<_>>();
+    entries.sort_by_key(|e| e.file_name());
+    for entry in entries {
+        let path = entry.path();
+        let Ok(kind) = entry.file_type() else {
+            continue;
+        };
+        if kind.is_dir() && entry.file_name() != ".git" {
+            walk(root, &path, files);
+        } else if kind.is_file() {
+            if let Ok(rel) = path.strip_prefix(root) {
+                files.push(rel.to_string_lossy().to_string());
+            }
+        }
+    }
+}
+
+/// One line of the allowlist: a path glob, and the rule it allows there.
+struct Allowed {
+    path: glob::Pattern,
+    rule: Option<String>,
+}
+
+impl Allowed {
+    fn allows(&self, leak: &Leak) -> bool {
+        self.path.matches(&leak.path) && self.rule.as_ref().is_none_or(|rule| rule == leak.rule)
+    }
+}
+
+fn allowlist(root: &Path) -> Vec<Allowed> {
+    let Ok(text) = fs::read_to_string(root.join(ALLOWLIST)) else {
+        return Vec::new();
+    };
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let (path, rule) = match line.rsplit_once(':') {
+                Some((path, rule)) => (path, Some(rule.to_string())),
+                None => (line, None),
+            };
+            Some(Allowed {
+                path: glob::Pattern::new(path).ok()?,
+                rule,
+            })
+        })
+        .collect()
+}
+
+/// Print `leaks` with how to allow the false positives among them.
+pub fn print(leaks: &[Leak]) {
+    let mut table = output::table(["Rule", "File", "Line", "Secret"]);
+    for leak in leaks {
+        table.add_row(vec![
+            output::cell(leak.rule, Tone::Error),
+            Cell::new(&leak.path),
+            Cell::new(leak.line),
+            Cell::new(&leak.redacted),
+        ]);
+    }
+    println!("{}", table);
+    println!(
+        "Remove them, or list false positives in {} (e.g. {})",
+        ALLOWLIST,
+        leaks.first().map(|l| l.allow_entry()).unwrap_or_default()
+    );
+}
@@ -0,0 +1,69 @@
+//! SELinux labels on deployments.
+//!
+//! On SELinux systems a confined application is only allowed to read files
+//! labeled for it, and a file keeps the label of wherever it was created or
+//! moved from. After deploying, dothub runs `restorecon` on the new link or
+//! copy so it gets the label the policy expects for its path, and `doctor`
+//! reports deployments whose labels still differ. AppArmor profiles match
+//! paths rather than labels, so there is nothing to restore there.
+
+use crate::output::{self, Tone};
+use std::path::Path;
+use std::process::Command;
+
+/// Whether SELinux is active and `restorecon` is there to relabel with.
+pub fn enabled() -> bool {
+    Path::new("/sys/fs/selinux/enforce").exists() && which::which("restorecon").is_ok()
+}
+
+/// Give `path` and everything under it the labels the policy assigns to
+/// their paths. Best effort: a failure is reported, not fatal.
+pub fn restore(path: &Path) {
+    if !enabled() || crate::vfs::dry_run() {
+        return;
+    }
+    let result = Command::new("restorecon").arg("-R").arg(path).output();
+    match result {
+        Ok(out) if out.status.success() => {}
+        Ok(out) => println!(
+            "{}",
+            output::paint(
+                &format!(
+                    "restorecon failed on {}: {}",
+                    path.display(),
+                    String::from_utf8_lossy(&out.stderr).trim()
+                ),
+                Tone::Warn
+            )
+        ),
+        Err(e) => println!(
+            "{}",
+            output::paint(&format!("Running restorecon: {}", e), Tone::Warn)
+        ),
+    }
+}
+
+/// Paths under `path` whose label differs from the policy's, as
+/// (path, current label, expected label).
+pub fn mismatches(path: &Path) -> Vec<(String, String, String)> {
+    if !enabled() {
+        return Vec::new();
+    }
+    let Ok(out) = Command::new("restorecon")
+        .args(["-R", "-n", "-v"])
+        .arg(path)
+        .output()
+    else {
+        return Vec::new();
+    };
+    // "Would relabel <path> from <old> to <new>"
+    String::from_utf8_lossy(&out.stdout)
+        .lines()
+        .filter_map(|line| {
+            let rest = line.trim().strip_prefix("Would relabel ")?;
+            let (path, labels) = rest.rsplit_once(" from ")?;
+            let (from, to) = labels.split_once(" to ")?;
+            Some((path.to_string(), from.to_string(), to.to_string()))
+        })
+        .collect()
+}
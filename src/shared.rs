@@ -0,0 +1,144 @@
+//! A store shared by every user of a machine, such as a lab's.
+//!
+//! An admin installs configs once into a store everyone can read, e.g.
+//! `sudo DOTHUB_DIR=/opt/dothub dothub install <url>`, and each user names it
+//! with `shared` under `[store]` in their config, or DOTHUB_SHARED. Users keep
+//! their own store, state and links. Linking a repo that isn't in the user's
+//! store but is in the shared one first installs it as a local directory,
+//! i.e. the user's store gets a link to it, so everything else treats it like
+//! any other repo. `dothub update` leaves those alone: the shared store is
+//! pulled with `dothub update --shared`, which needs write access to it.
+
+use crate::error::{Code, Error};
+use crate::output::{self, Tone};
+use crate::state::RepoSource;
+use anyhow::{bail, Result};
+use comfy_table::Cell;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The shared store, when one is configured.
+pub fn dir() -> Option<PathBuf> {
+    if let Ok(p) = env::var("DOTHUB_SHARED") {
+        return Some(PathBuf::from(p)).filter(|p| !p.as_os_str().is_empty());
+    }
+    let config = crate::config::Config::load().ok()?;
+    config.store.shared.map(|p| crate::source::expand_tilde(&p))
+}
+
+/// Whether `source` is a link to a repo in the shared store.
+pub fn holds(source: &RepoSource) -> bool {
+    match (source, dir()) {
+        (RepoSource::Local { path, copy: false }, Some(shared)) => path.starts_with(shared),
+        _ => false,
+    }
+}
+
+/// Make the repo `name` (or `name/subpath`) available in the user's store
+/// from the shared one, if the user's store doesn't have it and the shared
+/// one does.
+pub fn provide(name: &str) -> Result<()> {
+    let repo = name.split('/').next().unwrap_or(name);
+    if repo.is_empty() || crate::vfs::fs().exists(&crate::dothub_dir().join(repo)) {
+        return Ok(());
+    }
+    let Some(shared) = dir() else {
+        return Ok(());
+    };
+    let path = shared.join(repo);
+    if !path.is_dir() {
+        return Ok(());
+    }
+    crate::install_repo(&path.to_string_lossy(), Some(repo), false, false)?;
+    println!(
+        "{}",
+        output::paint(
+            &format!(
+                "Using {} from the shared store at {}",
+                repo,
+                shared.display()
+            ),
+            Tone::Info
+        )
+    );
+    Ok(())
+}
+
+/// `dothub update --shared`: pull every repo in the shared store.
+pub fn cmd_update() -> Result<()> {
+    let Some(shared) = dir() else {
+        bail!(
+            Error::new(Code::ConfigInvalid, "No shared store is configured")
+                .hint("set shared = \"/opt/dothub\" under [store] in the config, or DOTHUB_SHARED")
+        );
+    };
+    check_writable(&shared)?;
+    crate::git::ensure_git()?;
+
+    let mut table = output::table(["Repo", "Result", "Detail"]);
+    let mut failed = 0usize;
+    let mut entries = fs::read_dir(&shared)?
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.is_dir())
+        .collect::<Vec<_>>();
+    entries.sort();
+    for path in entries {
+        let name = path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        if name.starts_with('.') {
+            continue;
+        }
+        // Shared repos are clones; whatever made them isn't recorded here
+        let git = RepoSource::Git { url: String::new() };
+        let (status, tone, detail) = match crate::vcs::backend(&git).update(&path) {
+            Ok(crate::vcs::Update::Updated { detail, .. }) => ("updated", Tone::Ok, detail),
+            Ok(crate::vcs::Update::UpToDate) => ("up to date", Tone::Muted, String::new()),
+            Ok(crate::vcs::Update::Skipped { status, detail }) => (status, Tone::Muted, detail),
+            Err(e) => {
+                failed += 1;
+                ("failed", Tone::Error, format!("{:#}", e))
+            }
+        };
+        table.add_row(vec![
+            Cell::new(name),
+            output::cell(status, tone),
+            Cell::new(detail),
+        ]);
+    }
+    println!("{}", table);
+    if failed > 0 {
+        bail!("{} shared repo(s) failed to update", failed);
+    }
+    Ok(())
+}
+
+/// Fail with E012 unless the current user can write to `shared`.
+fn check_writable(shared: &Path) -> Result<()> {
+    let probe = shared.join(".dothub-write-check");
+    match fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&probe)
+    {
+        Ok(_) => {
+            fs::remove_file(&probe)?;
+            Ok(())
+        }
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => bail!(Error::new(
+            Code::StorePermission,
+            format!("The shared store {} is read-only for you", shared.display())
+        )
+        .hint(format!(
+            "ask its owner to update it, e.g. sudo DOTHUB_SHARED={} dothub update --shared",
+            shared.display()
+        ))),
+        Err(e) => Err(e.into()),
+    }
+}
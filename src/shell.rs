@@ -0,0 +1,289 @@
+//! `dothub shell-init`: everything dothub wants from an interactive shell in
+//! one script, printed for `eval` or installed into the rc file.
+//!
+//! The script exports the variables linked repos ask for, loads completions,
+//! defines the `dothub` function that handles `dothub cd` in the shell
+//! itself, puts `dothub prompt` in front of the prompt and, when asked,
+//! starts a quiet `dothub update` in the background of login shells. `--install` writes a managed block to the rc file that
+//! evals the script, so the integration follows the installed binary.
+
+use crate::Shell;
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+const BLOCK_START: &str = "# >>> dothub >>>";
+const BLOCK_END: &str = "# <<< dothub <<<";
+
+const SH_CD: &str = r#"dothub() {
+    if [ "$1" = cd ]; then
+        shift
+        local dir
+        dir="$(command dothub path "$@")" && builtin cd -- "$dir"
+    else
+        command dothub "$@"
+    fi
+}
+"#;
+
+const SH_PROMPT: &str = r#"__dothub_prompt() {
+    local s
+    s="$(command dothub prompt 2>/dev/null)"
+    [ -n "$s" ] && printf '%s ' "$s"
+}
+"#;
+
+const FISH_CD: &str = r#"function dothub
+    if test "$argv[1]" = cd
+        set -l dir (command dothub path $argv[2..-1]); and builtin cd $dir
+    else
+        command dothub $argv
+    end
+end
+"#;
+
+const FISH_PROMPT: &str = r#"if not functions -q __dothub_original_prompt
+    functions -c fish_prompt __dothub_original_prompt
+    function fish_prompt
+        set -l s (command dothub prompt 2>/dev/null)
+        test -n "$s"; and printf '%s ' $s
+        __dothub_original_prompt
+    end
+end
+"#;
+
+const POWERSHELL_CD: &str = r#"function dothub {
+    $exe = Get-Command dothub -CommandType Application | Select-Object -First 1
+    if ($args.Count -gt 0 -and $args[0] -eq 'cd') {
+        $dir = & $exe path @($args | Select-Object -Skip 1)
+        if ($LASTEXITCODE -eq 0) { Set-Location $dir }
+    } else {
+        & $exe @args
+    }
+}
+"#;
+
+const POWERSHELL_PROMPT: &str = r#"if (-not $global:__dothubOriginalPrompt) {
+    $global:__dothubOriginalPrompt = $function:prompt
+    function global:prompt {
+        $exe = Get-Command dothub -CommandType Application | Select-Object -First 1
+        $s = & $exe prompt 2>$null
+        if ($s) { "$s " + (& $global:__dothubOriginalPrompt) } else { & $global:__dothubOriginalPrompt }
+    }
+}
+"#;
+
+/// Parts of the integration beyond completions and `dothub cd`.
+#[derive(Clone, Copy)]
+pub struct Options {
+    pub prompt: bool,
+    pub auto_update: bool,
+}
+
+/// The command-line name of `shell`, e.g. "power-shell".
+fn shell_name(shell: Shell) -> String {
+    shell
+        .to_possible_value()
+        .map(|v| v.get_name().to_string())
+        .unwrap_or_default()
+}
+
+/// The integration script for `shell`.
+pub fn script(shell: Shell, opts: Options) -> Result<String> {
+    let mut out = String::new();
+    if shell != Shell::Elvish {
+        out.push_str(&crate::exports::source_line(shell));
+    }
+    crate::usage::sync()?;
+    if let Some(hook) = crate::usage::shell_hook(shell) {
+        out.push_str(&hook);
+    }
+    match shell {
+        Shell::Bash | Shell::Zsh => {
+            if shell == Shell::Zsh {
+                out.push_str("autoload -Uz compinit\n(( $+functions[compdef] )) || compinit\n");
+            }
+            out.push_str(&format!(
+                "source <(command dothub completions {})\n",
+                shell_name(shell)
+            ));
+            out.push_str(SH_CD);
+            if opts.prompt {
+                out.push_str(SH_PROMPT);
+                if shell == Shell::Zsh {
+                    out.push_str("setopt PROMPT_SUBST\nPROMPT='$(__dothub_prompt)'\"$PROMPT\"\n");
+                } else {
+                    out.push_str("PS1='$(__dothub_prompt)'\"$PS1\"\n");
+                }
+            }
+            if opts.auto_update {
+                let login = if shell == Shell::Zsh {
+                    "[[ -o login ]]"
+                } else {
+                    "shopt -q login_shell"
+                };
+                out.push_str(&format!(
+                    "{} && (command dothub update --changelog none --no-reload >/dev/null 2>&1 &)\n",
+                    login
+                ));
+            }
+        }
+        Shell::Fish => {
+            out.push_str("command dothub completions fish | source\n");
+            out.push_str(FISH_CD);
+            if opts.prompt {
+                out.push_str(FISH_PROMPT);
+            }
+            if opts.auto_update {
+                out.push_str(
+                    "status is-login; and command dothub update --changelog none --no-reload >/dev/null 2>&1 &\n",
+                );
+            }
+        }
+        Shell::PowerShell => {
+            out.push_str(
+                "dothub completions power-shell | Out-String | Invoke-Expression\n",
+            );
+            out.push_str(POWERSHELL_CD);
+            if opts.prompt {
+                out.push_str(POWERSHELL_PROMPT);
+            }
+            if opts.auto_update {
+                // PowerShell has no login sessions; update once per session
+                out.push_str(
+                    "Start-Job { dothub update --changelog none --no-reload } | Out-Null\n",
+                );
+            }
+        }
+        Shell::Elvish => bail!("No shell integration for elvish yet; use cd (dothub path <name>)"),
+    }
+    Ok(out)
+}
+
+/// The rc file the managed block goes into.
+fn rc_file(shell: Shell) -> Result<PathBuf> {
+    let home = dirs::home_dir().context("Unable to determine home directory")?;
+    let config = dirs::config_dir().context("Unable to determine config directory")?;
+    Ok(match shell {
+        Shell::Bash => home.join(".bashrc"),
+        Shell::Zsh => std::env::var_os("ZDOTDIR")
+            .map(PathBuf::from)
+            .unwrap_or(home)
+            .join(".zshrc"),
+        Shell::Fish => config.join("fish").join("config.fish"),
+        Shell::PowerShell => config
+            .join("powershell")
+            .join("Microsoft.PowerShell_profile.ps1"),
+        Shell::Elvish => bail!("No shell integration for elvish yet"),
+    })
+}
+
+/// The line in the rc file that loads the script with the same options.
+fn load_line(shell: Shell, opts: Options) -> String {
+    let mut cmd = format!("dothub shell-init {}", shell_name(shell));
+    if !opts.prompt {
+        cmd.push_str(" --no-prompt");
+    }
+    if opts.auto_update {
+        cmd.push_str(" --auto-update");
+    }
+    match shell {
+        Shell::Fish => format!("{} | source", cmd),
+        Shell::PowerShell => format!("{} | Out-String | Invoke-Expression", cmd),
+        _ => format!("eval \"$({})\"", cmd),
+    }
+}
+
+/// Replace the managed block in `text` with `block`, appending it when there
+/// is none; `None` removes it. Everything outside the block is kept byte for
+/// byte, and the block gets the file's line endings. `None` is returned when
+/// a start marker has no end marker after it, which only a person can sort
+/// out.
+fn replace_block(text: &str, block: Option<&str>) -> Option<String> {
+    let newline = if text.contains("\r\n") { "\r\n" } else { "\n" };
+    let block = block.map(|b| b.replace('\n', newline));
+    // Byte offsets of the start marker line and of the end of the end marker line
+    let mut start = None;
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        let end = offset + line.len();
+        match start {
+            None if line.trim() == BLOCK_START => start = Some(offset),
+            Some(from) if line.trim() == BLOCK_END => {
+                let mut out = text[..from].to_string();
+                out.push_str(block.as_deref().unwrap_or(""));
+                out.push_str(&text[end..]);
+                return Some(out);
+            }
+            _ => {}
+        }
+        offset = end;
+    }
+    if start.is_some() {
+        return None;
+    }
+    let mut out = text.to_string();
+    if let Some(block) = block {
+        if !out.is_empty() && !out.ends_with(newline) {
+            out.push_str(newline);
+        }
+        if !out.is_empty() && !out.ends_with(&newline.repeat(2)) {
+            out.push_str(newline);
+        }
+        out.push_str(&block);
+    }
+    Some(out)
+}
+
+/// `dothub shell-init`: print the script, or install or remove the block
+/// that loads it.
+pub fn cmd_shell_init(shell: Shell, opts: Options, install: bool, uninstall: bool) -> Result<()> {
+    let script = script(shell, opts)?;
+    if !install && !uninstall {
+        print!("{}", script);
+        return Ok(());
+    }
+    let rc = rc_file(shell)?;
+    let text = match fs::read_to_string(&rc) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e).with_context(|| format!("Reading {}", rc.display())),
+    };
+    let block = format!(
+        "{}\n# Managed by `dothub shell-init --install`; edits inside are replaced\n{}\n{}\n",
+        BLOCK_START,
+        load_line(shell, opts),
+        BLOCK_END
+    );
+    let Some(updated) = replace_block(&text, (!uninstall).then_some(block.as_str())) else {
+        bail!(
+            "{} has a `{}` line without a `{}` line after it; fix or remove it and try again",
+            rc.display(),
+            BLOCK_START,
+            BLOCK_END
+        );
+    };
+    if updated == text {
+        if uninstall {
+            println!("{} has no dothub block", rc.display());
+        } else {
+            println!("{} is already up to date", rc.display());
+        }
+        return Ok(());
+    }
+    if let Some(dir) = rc.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("Failed creating {}", dir.display()))?;
+    }
+    fs::write(&rc, updated).with_context(|| format!("Writing {}", rc.display()))?;
+    if uninstall {
+        println!("Removed the dothub block from {}", rc.display());
+    } else {
+        println!(
+            "Added the dothub block to {}; open a new shell to load it",
+            rc.display()
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,308 @@
+//! Filesystem snapshots before `dothub apply`, and `dothub undo`.
+//!
+//! With `snapshot = true` under `[apply]` in the config, and the home
+//! directory on Btrfs or ZFS, each apply that changes something first takes
+//! a read-only snapshot of the subvolume or dataset holding it. Applies are
+//! recorded in an operations log next to the state file, with the targets
+//! they touched and their snapshot. `dothub undo` copies those targets and
+//! the state file back from the snapshot of the last apply.
+
+use crate::output::{self, Tone};
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Operations kept in the log; older ones are dropped.
+const KEEP: usize = 20;
+
+/// A read-only snapshot taken before an operation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// "btrfs" or "zfs"
+    pub fs: String,
+    /// Snapshot subvolume path (Btrfs) or dataset@name (ZFS)
+    pub id: String,
+    /// Directory showing the home directory as it was
+    pub view: PathBuf,
+}
+
+/// An entry of the operations log.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Operation {
+    pub command: String,
+    pub at: u64,
+    /// Paths the operation created, replaced or removed
+    pub targets: Vec<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snapshot: Option<Snapshot>,
+}
+
+fn log_path() -> PathBuf {
+    crate::state::state_path().with_file_name("operations.json")
+}
+
+fn load_log() -> Result<Vec<Operation>> {
+    let path = log_path();
+    match fs::read_to_string(&path) {
+        Ok(text) => {
+            serde_json::from_str(&text).with_context(|| format!("Parsing {}", path.display()))
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e).with_context(|| format!("Reading {}", path.display())),
+    }
+}
+
+fn save_log(log: &[Operation]) -> Result<()> {
+    let path = log_path();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed creating {}", parent.display()))?;
+    }
+    let text = serde_json::to_string_pretty(log).context("Serializing operations")?;
+    fs::write(&path, text).with_context(|| format!("Writing {}", path.display()))
+}
+
+/// Append `op` to the operations log.
+pub fn record(op: Operation) -> Result<()> {
+    let mut log = load_log()?;
+    log.push(op);
+    let excess = log.len().saturating_sub(KEEP);
+    log.drain(..excess);
+    save_log(&log)
+}
+
+/// Snapshot the home directory before `command` when the config asks for
+/// it. Nothing is taken on other filesystems; a failed snapshot is an error,
+/// so nothing runs without the safety net that was configured.
+pub fn before(command: &str) -> Result<Option<Snapshot>> {
+    let enabled = crate::config::Config::load()
+        .map(|c| c.apply.snapshot)
+        .unwrap_or(false);
+    if !enabled {
+        return Ok(None);
+    }
+    let home = crate::owner::home()?;
+    let Some((fstype, source, mountpoint)) = mount_of(&home) else {
+        return Ok(None);
+    };
+    let name = format!("dothub-{}-{}", command, crate::state::now_unix());
+    let snapshot = match fstype.as_str() {
+        "btrfs" => {
+            // A subvolume nested below the mounted one would be empty in a
+            // snapshot of the mount, so take the one home is in
+            let subvolume = btrfs_subvolume(&home, &mountpoint);
+            let rel = home.strip_prefix(&subvolume).unwrap_or(Path::new(""));
+            let dir = mountpoint.join(".dothub-snapshots");
+            fs::create_dir_all(&dir)
+                .with_context(|| format!("Failed creating {}", dir.display()))?;
+            let dest = dir.join(&name);
+            run(Command::new("btrfs")
+                .args(["subvolume", "snapshot", "-r"])
+                .arg(&subvolume)
+                .arg(&dest))?;
+            Snapshot {
+                fs: fstype,
+                id: dest.display().to_string(),
+                view: dest.join(rel),
+            }
+        }
+        "zfs" => {
+            let rel = home.strip_prefix(&mountpoint).unwrap_or(Path::new(""));
+            let id = format!("{}@{}", source, name);
+            run(Command::new("zfs").args(["snapshot", &id]))?;
+            Snapshot {
+                fs: fstype,
+                id,
+                view: mountpoint.join(".zfs/snapshot").join(&name).join(rel),
+            }
+        }
+        _ => {
+            println!(
+                "{}",
+                output::paint(
+                    &format!(
+                        "{} is on {}, which has no snapshots; applying without one",
+                        home.display(),
+                        fstype
+                    ),
+                    Tone::Info
+                )
+            );
+            return Ok(None);
+        }
+    };
+    println!("Snapshot {} taken", snapshot.id);
+    Ok(Some(snapshot))
+}
+
+/// The Btrfs subvolume holding `path`: its nearest ancestor, up to the
+/// `mountpoint`, that is the root of one.
+#[cfg(unix)]
+fn btrfs_subvolume(path: &Path, mountpoint: &Path) -> PathBuf {
+    use std::os::unix::fs::MetadataExt;
+    // The inode number of every subvolume's root directory
+    const SUBVOLUME_INO: u64 = 256;
+    path.ancestors()
+        .take_while(|dir| dir.starts_with(mountpoint))
+        .find(|dir| fs::metadata(dir).is_ok_and(|m| m.ino() == SUBVOLUME_INO))
+        .unwrap_or(mountpoint)
+        .to_path_buf()
+}
+
+#[cfg(not(unix))]
+fn btrfs_subvolume(_path: &Path, mountpoint: &Path) -> PathBuf {
+    mountpoint.to_path_buf()
+}
+
+/// Filesystem type, source and mount point of the filesystem holding `path`.
+fn mount_of(path: &Path) -> Option<(String, String, PathBuf)> {
+    let out = Command::new("findmnt")
+        .args(["-n", "-o", "FSTYPE,SOURCE,TARGET", "--target"])
+        .arg(path)
+        .output()
+        .ok()?;
+    let text = String::from_utf8_lossy(&out.stdout);
+    let mut fields = text.split_whitespace();
+    let fstype = fields.next()?.to_string();
+    // Btrfs sources read "/dev/sda2[/@home]"; only the dataset matters for ZFS
+    let source = fields.next()?.to_string();
+    let target = PathBuf::from(fields.next()?);
+    Some((fstype, source, target))
+}
+
+fn run(cmd: &mut Command) -> Result<()> {
+    let program = cmd.get_program().to_string_lossy().to_string();
+    let out = cmd
+        .output()
+        .with_context(|| format!("Failed to spawn {}", program))?;
+    if !out.status.success() {
+        bail!(crate::error::Error::new(
+            crate::error::Code::SnapshotFailed,
+            format!(
+                "{} could not take a snapshot: {}",
+                program,
+                String::from_utf8_lossy(&out.stderr).trim()
+            ),
+        )
+        .hint("allow it (e.g. zfs allow <user> snapshot <dataset>) or set snapshot = false under [apply]"));
+    }
+    Ok(())
+}
+
+/// `dothub undo`: restore what the last apply touched from its snapshot.
+pub fn cmd_undo(yes: bool) -> Result<()> {
+    let mut log = load_log()?;
+    let Some(op) = log.last().cloned() else {
+        println!("Nothing to undo.");
+        return Ok(());
+    };
+    let Some(snapshot) = &op.snapshot else {
+        bail!(
+            "The last {} has no snapshot to restore from; snapshots need snapshot = true \
+             under [apply] in {} and a home directory on Btrfs or ZFS",
+            op.command,
+            crate::config::config_path().display()
+        );
+    };
+    if !snapshot.view.exists() {
+        bail!(
+            "Snapshot {} is gone ({} is missing)",
+            snapshot.id,
+            snapshot.view.display()
+        );
+    }
+    let home = crate::owner::home()?;
+    let mut paths = op.targets.clone();
+    let state = crate::state::state_path();
+    if state.starts_with(&home) {
+        paths.push(state);
+    }
+
+    println!(
+        "The {} from {} will be undone from snapshot {}:",
+        op.command,
+        output::ago(op.at, crate::state::now_unix()),
+        snapshot.id
+    );
+    for path in &paths {
+        println!("  {}", path.display());
+    }
+    if !yes {
+        if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+            bail!("Pass --yes to restore without a terminal to confirm on");
+        }
+        print!("Restore these paths? [y/N] ");
+        io::stdout().flush()?;
+        let mut answer = String::new();
+        io::stdin().lock().read_line(&mut answer)?;
+        if !matches!(answer.trim(), "y" | "Y" | "yes") {
+            println!("Nothing restored.");
+            return Ok(());
+        }
+    }
+
+    // Check every path before touching any: a copy missing from a directory
+    // the snapshot has means the path didn't exist yet, but without the
+    // directory the snapshot doesn't cover it and removing it would lose it
+    let mut restores = Vec::new();
+    for path in &paths {
+        let Ok(rel) = path.strip_prefix(&home) else {
+            println!(
+                "{}",
+                output::paint(
+                    &format!("Skipped {}: outside the home directory", path.display()),
+                    Tone::Warn
+                )
+            );
+            continue;
+        };
+        let saved = snapshot.view.join(rel);
+        let existed = fs::symlink_metadata(&saved).is_ok();
+        if !existed && !saved.parent().is_some_and(Path::is_dir) {
+            bail!(
+                "Snapshot {} doesn't cover {} ({} is missing); nothing was restored",
+                snapshot.id,
+                path.display(),
+                saved.parent().unwrap_or(&saved).display()
+            );
+        }
+        restores.push((path, saved, existed));
+    }
+    for (path, saved, existed) in &restores {
+        crate::remove_path(path)?;
+        if !existed {
+            // It didn't exist before the operation
+            continue;
+        }
+        let status = Command::new("cp")
+            .args(["-a", "--reflink=auto"])
+            .arg(saved)
+            .arg(path)
+            .status()
+            .context("Failed to spawn cp")?;
+        if !status.success() {
+            bail!("Could not copy {} back from the snapshot", path.display());
+        }
+    }
+    log.pop();
+    save_log(&log)?;
+    println!(
+        "{}",
+        output::paint(
+            &format!("Restored {} path(s) from {}", restores.len(), snapshot.id),
+            Tone::Ok
+        )
+    );
+    let full = match snapshot.fs.as_str() {
+        "zfs" => format!("zfs rollback {}", snapshot.id),
+        _ => format!("copy what you need from {}", snapshot.id),
+    };
+    println!(
+        "Anything else from before it is still in the snapshot: {}",
+        full
+    );
+    Ok(())
+}
@@ -0,0 +1,254 @@
+//! Non-git install sources: local directories and tarball/zip archives.
+
+use crate::state::RepoSource;
+use crate::vcs::Vcs;
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const ARCHIVE_EXTS: &[&str] = &[
+    ".tar.gz", ".tgz", ".tar.xz", ".txz", ".tar.bz2", ".tbz2", ".tar.zst", ".tar", ".zip",
+];
+
+pub fn archive_ext(s: &str) -> Option<&'static str> {
+    let lower = s.to_lowercase();
+    ARCHIVE_EXTS.iter().copied().find(|ext| lower.ends_with(ext))
+}
+
+/// Classify what the user passed to `dothub install`.
+pub fn detect(repo: &str, copy: bool) -> Result<RepoSource> {
+    match crate::vcs::split(repo) {
+        (Some(Vcs::Git), url) => return Ok(RepoSource::Git { url: url.to_string() }),
+        (Some(Vcs::Hg), url) => return Ok(RepoSource::Hg { url: url.to_string() }),
+        (Some(Vcs::Jj), url) => return Ok(RepoSource::Jj { url: url.to_string() }),
+        (Some(Vcs::Tarball), url) => {
+            return Ok(RepoSource::Archive {
+                location: url.to_string(),
+            })
+        }
+        (None, _) => {}
+    }
+    let expanded = expand_tilde(repo);
+    if expanded.is_dir() {
+        let path = expanded
+            .canonicalize()
+            .with_context(|| format!("Resolving {}", expanded.display()))?;
+        return Ok(RepoSource::Local { path, copy });
+    }
+    if expanded.is_file() {
+        if archive_ext(repo).is_none() {
+            bail!("{} is a file but not a supported archive", repo);
+        }
+        let path = expanded
+            .canonicalize()
+            .with_context(|| format!("Resolving {}", expanded.display()))?;
+        return Ok(RepoSource::Archive {
+            location: path.to_string_lossy().to_string(),
+        });
+    }
+    let is_url = repo.starts_with("http://") || repo.starts_with("https://");
+    if is_url && archive_ext(repo.split(['?', '#']).next().unwrap_or(repo)).is_some() {
+        return Ok(RepoSource::Archive {
+            location: repo.to_string(),
+        });
+    }
+    Ok(RepoSource::Git {
+        url: repo.to_string(),
+    })
+}
+
+/// Default store name for a source.
+pub fn derive_name(source: &RepoSource) -> String {
+    match source {
+        RepoSource::Git { url } | RepoSource::Hg { url } | RepoSource::Jj { url } => {
+            crate::derive_repo_name(url)
+        }
+        RepoSource::Local { path, .. } => path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        RepoSource::Archive { location } => {
+            let last = location
+                .split(['?', '#'])
+                .next()
+                .unwrap_or(location)
+                .trim_end_matches('/')
+                .rsplit(['/', '\\'])
+                .next()
+                .unwrap_or("")
+                .to_string();
+            match archive_ext(&last) {
+                Some(ext) => last[..last.len() - ext.len()].to_string(),
+                None => last,
+            }
+        }
+    }
+}
+
+/// `owner/repo` as the GitHub repo it names, unless it is a local path;
+/// anything else as it is.
+pub fn expand_shorthand(repo: &str) -> String {
+    let valid = |part: &str| {
+        !part.is_empty()
+            && !part.starts_with('.')
+            && part
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    };
+    match repo.split_once('/') {
+        Some((owner, name)) if valid(owner) && valid(name) && !Path::new(repo).exists() => {
+            format!("https://github.com/{}", repo)
+        }
+        _ => repo.to_string(),
+    }
+}
+
+pub fn expand_tilde(p: &str) -> PathBuf {
+    if let Some(rest) = p.strip_prefix("~/") {
+        if let Some(home) = dirs::home_dir() {
+            return home.join(rest);
+        }
+    }
+    PathBuf::from(p)
+}
+
+/// Materialise a local or archive source at `dest`, which must not exist yet.
+pub fn install(source: &RepoSource, dest: &Path) -> Result<()> {
+    match source {
+        RepoSource::Git { .. } | RepoSource::Hg { .. } | RepoSource::Jj { .. } => {
+            bail!("repositories are cloned, not installed from a path")
+        }
+        RepoSource::Local { path, copy: false } => symlink_dir(path, dest),
+        RepoSource::Local { path, copy: true } => crate::copy_tree(path, dest),
+        RepoSource::Archive { location } => {
+            let archive = obtain_archive(location)?;
+            extract(&archive, dest)
+        }
+    }
+}
+
+/// Re-materialise a non-git source in place. Returns a short description of what happened.
+pub fn refresh(source: &RepoSource, dest: &Path) -> Result<String> {
+    match source {
+        RepoSource::Git { .. } | RepoSource::Hg { .. } | RepoSource::Jj { .. } => {
+            bail!("repositories are updated by pulling")
+        }
+        RepoSource::Local { copy: false, .. } => Ok("linked to local directory".to_string()),
+        RepoSource::Local { path, copy: true } => {
+            if !path.is_dir() {
+                bail!("local source {} no longer exists", path.display());
+            }
+            swap_in(dest, |staging| crate::copy_tree(path, staging))?;
+            Ok(format!("re-copied from {}", path.display()))
+        }
+        RepoSource::Archive { location } => {
+            if crate::http::is_offline() && !Path::new(location).exists() {
+                bail!("offline; cannot download {}", location);
+            }
+            let archive = obtain_archive(location)?;
+            swap_in(dest, |staging| extract(&archive, staging))?;
+            Ok(format!("re-extracted {}", location))
+        }
+    }
+}
+
+/// Build the new content next to `dest`, then replace `dest` with it so a failed
+/// refresh leaves the old content untouched.
+pub fn swap_in(dest: &Path, build: impl FnOnce(&Path) -> Result<()>) -> Result<()> {
+    let staging = sibling(dest, "new");
+    crate::remove_path(&staging)?;
+    if let Err(e) = build(&staging) {
+        let _ = crate::remove_path(&staging);
+        return Err(e);
+    }
+    crate::remove_path(dest)?;
+    fs::rename(&staging, dest)
+        .with_context(|| format!("Moving {} -> {}", staging.display(), dest.display()))
+}
+
+/// Hidden sibling path, ignored by store listings.
+fn sibling(dest: &Path, tag: &str) -> PathBuf {
+    let name = dest
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    dest.with_file_name(format!(".{}.{}", name, tag))
+}
+
+fn symlink_dir(src: &Path, dest: &Path) -> Result<()> {
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(src, dest)
+        .with_context(|| format!("Failed creating symlink {} -> {}", dest.display(), src.display()))?;
+    #[cfg(windows)]
+    std::os::windows::fs::symlink_dir(src, dest)
+        .with_context(|| format!("Failed creating symlink {} -> {}", dest.display(), src.display()))?;
+    Ok(())
+}
+
+/// Local path of the archive, downloading it into the cache first for URLs.
+fn obtain_archive(location: &str) -> Result<PathBuf> {
+    let local = expand_tilde(location);
+    if local.is_file() {
+        return Ok(local);
+    }
+    let name = location
+        .split(['?', '#'])
+        .next()
+        .unwrap_or(location)
+        .rsplit('/')
+        .next()
+        .unwrap_or("archive");
+    let dir = crate::http::cache_dir().join("archives");
+    fs::create_dir_all(&dir).with_context(|| format!("Failed creating {}", dir.display()))?;
+    let path = dir.join(name);
+    println!("Downloading {}", location);
+    let bytes = crate::http::fetch_bytes(location)?;
+    let mut f = fs::File::create(&path).with_context(|| format!("Writing {}", path.display()))?;
+    f.write_all(&bytes)
+        .with_context(|| format!("Writing {}", path.display()))?;
+    Ok(path)
+}
+
+/// Extract `archive` to `dest`. A single top-level directory (as in most release
+/// tarballs) is unwrapped so the repo root lands directly at `dest`.
+pub fn extract(archive: &Path, dest: &Path) -> Result<()> {
+    let tmp = sibling(dest, "extract");
+    crate::remove_path(&tmp)?;
+    fs::create_dir_all(&tmp).with_context(|| format!("Failed creating {}", tmp.display()))?;
+
+    let is_zip = archive_ext(&archive.to_string_lossy()) == Some(".zip");
+    let (tool, status) = if is_zip {
+        (
+            "unzip",
+            Command::new("unzip")
+                .arg("-q")
+                .arg(archive)
+                .arg("-d")
+                .arg(&tmp)
+                .status(),
+        )
+    } else {
+        (
+            "tar",
+            Command::new("tar").arg("-xf").arg(archive).arg("-C").arg(&tmp).status(),
+        )
+    };
+    let status = status.with_context(|| format!("Failed to spawn {} (is it installed?)", tool))?;
+    if !status.success() {
+        let _ = crate::remove_path(&tmp);
+        bail!("{} failed extracting {} with status {}", tool, archive.display(), status);
+    }
+
+    let entries: Vec<PathBuf> = fs::read_dir(&tmp)?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .collect();
+    let root = match entries.as_slice() {
+        [only] if only.is_dir() => only.clone(),
+        _ => tmp.clone(),
+    };
+    fs::rename(&root, dest)
+        .with_context(|| format!("Moving {} -> {}", root.display(), dest.display()))?;
+    crate::remove_path(&tmp)
+}
@@ -0,0 +1,263 @@
+//! `dothub perf <target>`: how long a deployed config takes to start.
+//!
+//! The application is started on the linked config the way you start it,
+//! with your home and environment, several times in a row after an untimed
+//! warm-up start, like hyperfine. nvim also reports its own time with
+//! `--startuptime`, which shows the scripts that took longest. Each
+//! measurement is kept in state with the commit it was taken at, and
+//! `dothub update` measures those targets again after pulling into them,
+//! warning when startup got slower by more than `regression` percent under
+//! `[perf]`.
+
+use crate::output::{self, Tone};
+use crate::state::{StartupRun, State};
+use anyhow::{bail, Context, Result};
+use comfy_table::Cell;
+use std::ffi::OsStr;
+use std::fs;
+use std::time::{Duration, Instant};
+
+/// Config type, program that has to be installed, and the shell command that
+/// starts it and exits. nvim writes its own report to `$STARTUPTIME`.
+const STARTUP: &[(&str, &str, &str)] = &[
+    (
+        "nvim",
+        "nvim",
+        "nvim --headless -i NONE --startuptime \"$STARTUPTIME\" +qa!",
+    ),
+    ("zsh", "zsh", "zsh -i -c exit"),
+    ("bash", "bash", "bash -i -c exit"),
+    ("fish", "fish", "fish -i -c exit"),
+    ("powershell", "pwsh", "pwsh -NoLogo -Command exit"),
+];
+
+const DEFAULT_REGRESSION: u32 = 20;
+
+/// Measurements kept per target.
+const HISTORY: usize = 20;
+
+/// Timed starts after an update, fewer than `dothub perf` makes.
+const UPDATE_RUNS: u32 = 3;
+
+/// What the starts of a target measured.
+struct Measurement {
+    times: Vec<Duration>,
+    /// Totals nvim reported, in milliseconds
+    reported: Vec<f64>,
+    /// Scripts by the time nvim spent in them alone, slowest first
+    slowest: Vec<(f64, String)>,
+}
+
+/// `dothub perf <target>`: time `runs` starts of the config deployed as
+/// `target_name`, and compare them with the last measurement.
+pub fn cmd_startup(target_name: &str, runs: u32) -> Result<()> {
+    eprintln!("Starting {} {} times…", target_name, runs.max(1));
+    let m = measure(target_name, runs.max(1))?;
+    let ms = m
+        .times
+        .iter()
+        .map(|d| d.as_secs_f64() * 1000.0)
+        .collect::<Vec<_>>();
+    let mean = ms.iter().sum::<f64>() / ms.len() as f64;
+    let sd = (ms.iter().map(|t| (t - mean).powi(2)).sum::<f64>() / ms.len() as f64).sqrt();
+    let min = ms.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = ms.iter().copied().fold(0.0, f64::max);
+
+    let mut table = output::table(["Target", "Runs", "Mean ± σ", "Min", "Max"]);
+    table.add_row(vec![
+        Cell::new(target_name),
+        Cell::new(ms.len()),
+        Cell::new(format!("{:.1}ms ± {:.1}ms", mean, sd)),
+        Cell::new(format!("{:.1}ms", min)),
+        Cell::new(format!("{:.1}ms", max)),
+    ]);
+    println!("{}", table);
+    if let Some(reported) = median(&m.reported) {
+        println!("nvim reports {:.1}ms; slowest to load:", reported);
+        let mut slowest = output::table(["Self", "Script"]);
+        for (self_ms, script) in m.slowest.iter().take(5) {
+            slowest.add_row(vec![
+                Cell::new(format!("{:.1}ms", self_ms)),
+                Cell::new(script),
+            ]);
+        }
+        println!("{}", slowest);
+    }
+
+    let history = record(target_name, &m)?;
+    if history.len() > 1 {
+        let now = crate::state::now_unix();
+        let mut table = output::table(["Measured", "Commit", "Median"]);
+        for run in history.iter().rev().take(5) {
+            table.add_row(vec![
+                Cell::new(output::ago(run.at, now)),
+                Cell::new(crate::short_sha(run.rev.as_deref())),
+                Cell::new(format!("{:.1}ms", run.median_ms)),
+            ]);
+        }
+        println!("{}", table);
+    }
+    if let Some(warning) = regression(target_name, &history) {
+        println!("{}", output::paint(&warning, Tone::Warn));
+    }
+    Ok(())
+}
+
+/// After `dothub update` pulled into the repo behind `target_name`: measure
+/// it again if it has been measured before, and warn when it got slower.
+pub fn after_update(st: &State, target_name: &str) -> Result<()> {
+    if !st.startup.contains_key(target_name) {
+        return Ok(());
+    }
+    let m = measure(target_name, UPDATE_RUNS)?;
+    let history = record(target_name, &m)?;
+    if let Some(warning) = regression(target_name, &history) {
+        println!("{}", output::paint(&warning, Tone::Warn));
+    }
+    Ok(())
+}
+
+fn measure(target_name: &str, runs: u32) -> Result<Measurement> {
+    let st = State::load()?;
+    let Some(rec) = st.links.get(target_name) else {
+        bail!(crate::error::Error::new(
+            crate::error::Code::NotManaged,
+            format!("{} is not managed by dothub", target_name),
+        )
+        .hint("dothub active lists the targets dothub deployed"));
+    };
+    let repo = crate::dothub_dir().join(rec.name.split('/').next().unwrap_or(&rec.name));
+    let config_type = crate::lint::config_type(&repo, target_name);
+    let Some((_, program, command)) = STARTUP.iter().find(|(t, _, _)| *t == config_type) else {
+        let known = STARTUP.iter().map(|(t, _, _)| *t).collect::<Vec<_>>();
+        bail!(
+            "No way to time {} starting; dothub perf knows {}",
+            config_type,
+            known.join(", ")
+        );
+    };
+    if which::which(program).is_err() {
+        bail!("{} is not installed or not found in PATH", program);
+    }
+    let home = dirs::home_dir().context("Unable to determine home directory")?;
+    let report =
+        std::env::temp_dir().join(format!("dothub-startuptime-{}.log", std::process::id()));
+    let mut envs = vec![("STARTUPTIME", report.as_os_str())];
+    // nvim configs linked under another name are found through it
+    if config_type == "nvim" {
+        envs.push(("NVIM_APPNAME", OsStr::new(target_name)));
+    }
+
+    let mut m = Measurement {
+        times: Vec::new(),
+        reported: Vec::new(),
+        slowest: Vec::new(),
+    };
+    for run in 0..=runs {
+        let _ = fs::remove_file(&report);
+        let started = Instant::now();
+        let (ok, output) = crate::verify_repo::run(&home, None, command, &envs)?;
+        let elapsed = started.elapsed();
+        if !ok {
+            let _ = fs::remove_file(&report);
+            bail!(
+                "{} failed to start: {}; dothub verify-repo {} shows why",
+                target_name,
+                output.lines().next().unwrap_or("exited with an error"),
+                rec.source.display()
+            );
+        }
+        // The first start warms caches and isn't counted
+        if run == 0 {
+            continue;
+        }
+        m.times.push(elapsed);
+        if let Ok(text) = fs::read_to_string(&report) {
+            if let Some(ms) = crate::compare::reported_ms(&text) {
+                m.reported.push(ms);
+            }
+            m.slowest = scripts(&text, &home.to_string_lossy());
+        }
+    }
+    let _ = fs::remove_file(&report);
+    Ok(m)
+}
+
+/// Scripts in an nvim `--startuptime` report with the time spent in each
+/// alone (its third column), slowest first, with your home shown as `~`.
+fn scripts(text: &str, home: &str) -> Vec<(f64, String)> {
+    let mut scripts = text
+        .lines()
+        .filter_map(|line| {
+            let (times, what) = line.split_once(": ")?;
+            let columns = times.split_whitespace().collect::<Vec<_>>();
+            let [_, _, self_ms] = columns[..] else {
+                return None;
+            };
+            let what = what.trim().replacen(home, "~", 1);
+            Some((self_ms.parse::<f64>().ok()?, what))
+        })
+        .collect::<Vec<_>>();
+    scripts.sort_by(|a, b| b.0.total_cmp(&a.0));
+    scripts
+}
+
+fn median(values: &[f64]) -> Option<f64> {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    sorted.get(sorted.len() / 2).copied()
+}
+
+/// Add `m` to the history of `target_name` in state, and return the history.
+fn record(target_name: &str, m: &Measurement) -> Result<Vec<StartupRun>> {
+    let ms = m
+        .times
+        .iter()
+        .map(|d| d.as_secs_f64() * 1000.0)
+        .collect::<Vec<_>>();
+    let Some(median_ms) = median(&ms) else {
+        return Ok(Vec::new());
+    };
+    let mut st = State::load()?;
+    let rev = st.links.get(target_name).and_then(|rec| {
+        let repo = crate::dothub_dir().join(rec.name.split('/').next().unwrap_or(&rec.name));
+        crate::git::head(&repo)
+    });
+    let history = st.startup.entry(target_name.to_string()).or_default();
+    history.push(StartupRun {
+        at: crate::state::now_unix(),
+        rev,
+        median_ms,
+    });
+    if history.len() > HISTORY {
+        history.drain(..history.len() - HISTORY);
+    }
+    let history = history.clone();
+    st.save()?;
+    Ok(history)
+}
+
+/// A warning when the latest measurement is slower than the one taken at the
+/// previous commit by more than the configured percentage.
+fn regression(target_name: &str, history: &[StartupRun]) -> Option<String> {
+    let (latest, earlier) = history.split_last()?;
+    // Only a change of commit explains a change of speed; the rest is noise
+    let before = earlier.iter().rev().find(|run| run.rev != latest.rev)?;
+    let percent = crate::config::Config::load()
+        .ok()
+        .and_then(|c| c.perf.regression)
+        .unwrap_or(DEFAULT_REGRESSION);
+    let slower = (latest.median_ms / before.median_ms.max(0.001) - 1.0) * 100.0;
+    if slower <= percent as f64 {
+        return None;
+    }
+    Some(format!(
+        "{} starts {:.0}% slower since the update to {}: {:.1}ms, up from {:.1}ms at {}",
+        target_name,
+        slower,
+        crate::short_sha(latest.rev.as_deref()),
+        latest.median_ms,
+        before.median_ms,
+        crate::short_sha(before.rev.as_deref())
+    ))
+}
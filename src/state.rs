@@ -0,0 +1,382 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const STATE_FILE: &str = "state.json";
+
+/// How a repo was deployed to its target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum DeployMode {
+    #[default]
+    Symlink,
+    Copy,
+}
+
+/// A single deployment of a stored repo into ~/.config.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LinkRecord {
+    /// Repository name in the store
+    pub name: String,
+    /// Absolute path the link points at (or the copy was taken from)
+    pub source: PathBuf,
+    /// Absolute path of the deployed target
+    pub target: PathBuf,
+    #[serde(default)]
+    pub mode: DeployMode,
+    /// sha256 of every deployed file keyed by path relative to the target (copy mode only)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub files: BTreeMap<String, String>,
+    /// Unix timestamp of the deployment
+    #[serde(default)]
+    pub deployed_at: u64,
+    /// Deployed with `--read-only`: files are write-protected and the repo is locked
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub read_only: bool,
+    /// systemd user units installed along with the deployment
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub units: Vec<String>,
+    /// "uid:gid" the deployment was given with `link --system`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    /// Environment variables the repo's [env] set for this deployment
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+}
+
+/// Where a stored repo came from, which decides what `update` does with it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum RepoSource {
+    /// Cloned with git; updates pull
+    Git { url: String },
+    /// Cloned with Mercurial; updates pull and update the working copy
+    Hg { url: String },
+    /// Cloned colocated with jujutsu; updates fetch and rebase onto trunk
+    Jj { url: String },
+    /// A local directory, symlinked into the store (always live) or copied (re-copied on update)
+    Local { path: PathBuf, copy: bool },
+    /// A tarball or zip, from a URL or local file; updates re-download and re-extract
+    Archive { location: String },
+}
+
+/// Local bookkeeping for a repo in the store.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RepoRecord {
+    /// Missing for repos installed before sources were recorded; those are treated as git
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<RepoSource>,
+    /// Unix timestamp of `dothub install`, 0 when installed before state existed
+    #[serde(default)]
+    pub installed_at: u64,
+    /// Number of `dothub update` runs that pulled new commits
+    #[serde(default)]
+    pub updates: u64,
+    /// Unix timestamp of the last update that pulled new commits
+    #[serde(default)]
+    pub last_updated: u64,
+    /// How often `dothub update` pulls the repo
+    #[serde(default, skip_serializing_if = "Frequency::is_always")]
+    pub frequency: Frequency,
+    /// Unix timestamp of the last update that checked the repo, changed or not
+    #[serde(default)]
+    pub last_checked: u64,
+    /// Tags given with `dothub tag`
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub tags: BTreeSet<String>,
+    /// Set while a read-only deployment pins the repo
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lock: Option<RepoLock>,
+    /// Commit at which the `dothub audit` findings were accepted, empty for
+    /// repos without git
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audit_accepted: Option<String>,
+    /// How far the repo's hooks are confined, set with `dothub sandbox`
+    #[serde(default, skip_serializing_if = "Sandbox::is_strict")]
+    pub sandbox: Sandbox,
+    /// Whether the repo's hooks and units may run, set with `dothub trust`
+    #[serde(default, skip_serializing_if = "Trust::is_prompt")]
+    pub trust: Trust,
+    /// sha256 of each asset in place, keyed by its dest in the repo
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub assets: BTreeMap<String, String>,
+    /// Unix timestamp of the update that found the upstream deleted or
+    /// renamed; cleared once an update reaches it again
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upstream_gone: Option<u64>,
+}
+
+/// Whether a repo may run the code it ships.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Trust {
+    /// Hooks and units never run
+    Untrusted,
+    /// Ask each time, showing what would run
+    #[default]
+    Prompt,
+    /// Run without asking
+    Trusted,
+}
+
+impl Trust {
+    fn is_prompt(&self) -> bool {
+        *self == Trust::Prompt
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Trust::Untrusted => "untrusted",
+            Trust::Prompt => "prompt",
+            Trust::Trusted => "trusted",
+        }
+    }
+}
+
+/// Confinement of a repo's hooks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Sandbox {
+    /// No network, read-only system, home hidden except the repo
+    #[default]
+    Strict,
+    /// Like strict, but with network access
+    Network,
+    /// No sandbox: the hook runs as you, with your environment
+    Off,
+}
+
+impl Sandbox {
+    fn is_strict(&self) -> bool {
+        *self == Sandbox::Strict
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Sandbox::Strict => "strict",
+            Sandbox::Network => "network",
+            Sandbox::Off => "off",
+        }
+    }
+}
+
+/// Update schedule for a repo, set with `dothub schedule`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Frequency {
+    /// Every `dothub update`
+    #[default]
+    Always,
+    Daily,
+    Weekly,
+    /// Only with `dothub update --force`
+    Manual,
+}
+
+impl Frequency {
+    fn is_always(&self) -> bool {
+        *self == Frequency::Always
+    }
+
+    /// Seconds between checks; None when only manual updates check the repo.
+    pub fn interval(self) -> Option<u64> {
+        match self {
+            Frequency::Always => Some(0),
+            Frequency::Daily => Some(24 * 60 * 60),
+            Frequency::Weekly => Some(7 * 24 * 60 * 60),
+            Frequency::Manual => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Frequency::Always => "always",
+            Frequency::Daily => "daily",
+            Frequency::Weekly => "weekly",
+            Frequency::Manual => "manual",
+        }
+    }
+}
+
+impl RepoRecord {
+    /// Seconds until the repo is due for its next check; 0 when due now and
+    /// None for manual repos.
+    pub fn due_in(&self, now: u64) -> Option<u64> {
+        let interval = self.frequency.interval()?;
+        Some((self.last_checked + interval).saturating_sub(now))
+    }
+}
+
+/// Where a read-only repo is pinned, and the branch to return to when unlocked.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RepoLock {
+    /// Commit checked out (detached); empty for repos that are not git
+    pub rev: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+}
+
+/// An organization's manifest, applied as a layer of its own.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OrgLayer {
+    /// Where the manifest is published, fetched again by `dothub org update`
+    pub url: String,
+    /// Targets the layer links
+    #[serde(default)]
+    pub applied: BTreeSet<String>,
+    #[serde(default)]
+    pub applied_at: u64,
+}
+
+/// One startup measurement of a target by `dothub perf <target>`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StartupRun {
+    pub at: u64,
+    /// Commit of the repo at the time, absent for repos without git
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rev: Option<String>,
+    /// Median of the timed starts
+    pub median_ms: f64,
+}
+
+/// A repo moved aside with `dothub archive`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ArchivedRepo {
+    #[serde(default)]
+    pub archived_at: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// Targets it was linked as, for relinking after `dothub unarchive`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub targets: Vec<String>,
+    /// Its entry in `repos`, put back by `dothub unarchive`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub record: Option<RepoRecord>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct State {
+    /// Deployments keyed by target name (e.g. nvim)
+    #[serde(default)]
+    pub links: BTreeMap<String, LinkRecord>,
+    /// Installed repos keyed by store name
+    #[serde(default)]
+    pub repos: BTreeMap<String, RepoRecord>,
+    /// Unix timestamp of the last `dothub update` run in which no repo failed
+    #[serde(default)]
+    pub last_update: u64,
+    /// Targets linked by the last `dothub apply`, unlinked once the manifest drops them
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub applied: BTreeSet<String>,
+    /// Manifests published by organizations, applied with `dothub org apply`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub orgs: BTreeMap<String, OrgLayer>,
+    /// Hub links starred with `dothub hub star`
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub favorites: BTreeSet<String>,
+    /// Repos moved to the store's archive, keyed by name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub archived: BTreeMap<String, ArchivedRepo>,
+    /// Startup measurements by target name, oldest first
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub startup: BTreeMap<String, Vec<StartupRun>>,
+}
+
+/// Location of the state file. Links are per-user, so state lives in the user's
+/// state dir rather than the (possibly shared) store.
+pub fn state_path() -> PathBuf {
+    if let Ok(p) = env::var("DOTHUB_STATE") {
+        return PathBuf::from(p);
+    }
+    if let Some(mut dir) = dirs::state_dir().or_else(dirs::data_local_dir) {
+        dir.push("dothub");
+        dir.push(STATE_FILE);
+        return dir;
+    }
+    crate::dothub_dir().join(STATE_FILE)
+}
+
+impl State {
+    pub fn load() -> Result<Self> {
+        let path = state_path();
+        let text = match crate::vfs::fs().read(&path) {
+            Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(State::default()),
+            Err(e) => return Err(e).with_context(|| format!("Reading {}", path.display())),
+        };
+        serde_json::from_str(&text).map_err(|e| {
+            anyhow::Error::new(
+                crate::error::Error::new(
+                    crate::error::Code::StateInvalid,
+                    format!("Parsing {}: {}", path.display(), e),
+                )
+                .hint(format!(
+                    "restore {} from a backup, or move it aside and relink with dothub link",
+                    path.display()
+                )),
+            )
+        })
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = state_path();
+        let fs = crate::vfs::fs();
+        if let Some(parent) = path.parent() {
+            fs.create_dir_all(parent)
+                .with_context(|| format!("Failed creating {}", parent.display()))?;
+        }
+        let text = serde_json::to_string_pretty(self).context("Serializing state")?;
+        fs.write(&path, text.as_bytes())
+            .with_context(|| format!("Writing {}", path.display()))
+    }
+}
+
+pub fn now_unix() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+pub fn hash_file(path: &Path) -> Result<String> {
+    let bytes = fs::read(path).with_context(|| format!("Reading {}", path.display()))?;
+    let digest = Sha256::digest(&bytes);
+    Ok(digest.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Hash every regular file under `root`, keyed by its path relative to `root`.
+pub fn hash_tree(root: &Path) -> Result<BTreeMap<String, String>> {
+    let _span = crate::perf::span(crate::perf::Phase::Fs, || crate::perf::repo_subject(root));
+    let mut out = BTreeMap::new();
+    if root.is_file() {
+        out.insert(String::new(), hash_file(root)?);
+        return Ok(out);
+    }
+    hash_tree_into(root, root, &mut out)?;
+    Ok(out)
+}
+
+fn hash_tree_into(root: &Path, dir: &Path, out: &mut BTreeMap<String, String>) -> Result<()> {
+    for entry in fs::read_dir(dir).with_context(|| format!("Reading {}", dir.display()))? {
+        let entry = entry?;
+        let path = entry.path();
+        let ft = entry.file_type()?;
+        if ft.is_dir() {
+            hash_tree_into(root, &path, out)?;
+        } else if ft.is_file() {
+            let rel = path
+                .strip_prefix(root)
+                .unwrap_or(&path)
+                .to_string_lossy()
+                .to_string();
+            out.insert(rel, hash_file(&path)?);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,136 @@
+use crate::git;
+use crate::output::{self, Tone};
+use crate::state::{self, State};
+use anyhow::Result;
+use comfy_table::Cell;
+use std::path::Path;
+
+/// Timestamp of the newest upstream commit we know about, without touching the network.
+pub fn last_upstream_commit(repo: &Path) -> Option<u64> {
+    git::output(repo, &["log", "-1", "--format=%ct", "@{upstream}"])
+        .or_else(|_| git::output(repo, &["log", "-1", "--format=%ct", "HEAD"]))
+        .ok()
+        .and_then(|s| s.parse().ok())
+}
+
+/// Fast-forwards recorded in the reflog. Covers pulls made before dothub kept
+/// state, until git expires the reflog.
+fn reflog_pulls(repo: &Path) -> u64 {
+    git::output(repo, &["reflog", "--format=%gs", "HEAD"])
+        .map(|log| log.lines().filter(|l| l.starts_with("pull")).count() as u64)
+        .unwrap_or(0)
+}
+
+pub fn cmd_stats(name: Option<&str>) -> Result<()> {
+    crate::ensure_store_dir()?;
+    let st = State::load()?;
+    let now = state::now_unix();
+    crate::usage::sync()?;
+    let loads = crate::usage::enabled()
+        .then(crate::usage::read)
+        .flatten();
+
+    let repos: Vec<_> = crate::installed_repos()?
+        .into_iter()
+        .filter(|(n, _)| name.is_none_or(|want| want == n))
+        .collect();
+    if repos.is_empty() {
+        match name {
+            Some(n) => println!("No repository named {} in the store.", n),
+            None => println!("No repositories installed in {}.", crate::dothub_dir().display()),
+        }
+        return Ok(());
+    }
+
+    let mut header = vec![
+        "Repo",
+        "Installed",
+        "Updates",
+        "Last pull",
+        "Upstream activity",
+        "Deployed",
+    ];
+    if loads.is_some() {
+        header.push("Last loaded");
+    }
+    let mut table = output::table(header);
+    let mut stale = Vec::new();
+    for (repo_name, path) in repos {
+        let rec = st.repos.get(&repo_name).cloned().unwrap_or_default();
+        let is_git = git::is_repo(&path);
+        let updates = if is_git {
+            rec.updates.max(reflog_pulls(&path))
+        } else {
+            rec.updates
+        };
+        let upstream = if is_git {
+            last_upstream_commit(&path).unwrap_or(0)
+        } else {
+            0
+        };
+        let deployed: Vec<String> = st
+            .links
+            .iter()
+            .filter(|(_, l)| l.name == repo_name)
+            .map(|(target, l)| {
+                if l.deployed_at == 0 {
+                    target.clone()
+                } else {
+                    format!("{} for {}", target, output::duration(now.saturating_sub(l.deployed_at)))
+                }
+            })
+            .collect();
+        let deployed_cell = if deployed.is_empty() {
+            output::cell("not deployed", Tone::Muted)
+        } else {
+            output::cell(deployed.join(", "), Tone::Ok)
+        };
+        let mut row = vec![
+            Cell::new(&repo_name),
+            Cell::new(output::ago(rec.installed_at, now)),
+            Cell::new(updates),
+            Cell::new(output::ago(rec.last_updated, now)),
+            Cell::new(output::ago(upstream, now)),
+            deployed_cell,
+        ];
+        if let Some(loads) = &loads {
+            let targets = st
+                .links
+                .iter()
+                .filter(|(target, l)| l.name == repo_name && crate::usage::trackable(target))
+                .map(|(target, _)| target)
+                .collect::<Vec<_>>();
+            let last = targets
+                .iter()
+                .filter_map(|target| loads.last.get(target.as_str()))
+                .max()
+                .copied();
+            for target in &targets {
+                let at = loads.last.get(target.as_str()).copied().unwrap_or(loads.since);
+                if now.saturating_sub(at) >= crate::usage::STALE {
+                    stale.push(target.to_string());
+                }
+            }
+            row.push(match (targets.is_empty(), last) {
+                (true, _) => output::cell("not tracked", Tone::Muted),
+                (false, Some(at)) => Cell::new(output::ago(at, now)),
+                (false, None) => output::cell(
+                    format!("not since {}", output::ago(loads.since, now)),
+                    Tone::Warn,
+                ),
+            });
+        }
+        table.add_row(row);
+    }
+    println!("{}", table);
+    if !stale.is_empty() {
+        println!(
+            "{}",
+            output::paint(
+                &format!("Not loaded in six months: {}", stale.join(", ")),
+                Tone::Warn
+            )
+        );
+    }
+    Ok(())
+}
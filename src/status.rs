@@ -0,0 +1,406 @@
+//! `dothub status` and its refreshing `dashboard --watch` variant.
+//!
+//! Running git in every repo is the slow part, so each repo's status is cached
+//! under a fingerprint of what git would look at: HEAD, the refs, the index,
+//! FETCH_HEAD, the config and the modification times in the work tree. As long
+//! as stat calls find the fingerprint unchanged, git isn't run again.
+
+use crate::git::{self, RepoStatus};
+use crate::output::{self, Table, Tone};
+use crate::state::{self, State};
+use anyhow::Result;
+use comfy_table::Cell;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, UNIX_EPOCH};
+
+/// Set by `--refresh`: ignore the cache and the daemon, and run git everywhere.
+static REFRESH: AtomicBool = AtomicBool::new(false);
+
+pub fn set_refresh(refresh: bool) {
+    REFRESH.store(refresh, Ordering::SeqCst);
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "detail", rename_all = "snake_case")]
+pub enum RepoState {
+    NotGit,
+    Error(String),
+    Git(RepoStatus),
+}
+
+/// Status of one stored repo at a point in time.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct StatusRow {
+    pub name: String,
+    pub linked: Vec<String>,
+    pub state: RepoState,
+}
+
+impl StatusRow {
+    pub fn is_dirty(&self) -> bool {
+        matches!(&self.state, RepoState::Git(st) if st.is_dirty())
+    }
+
+    pub fn behind(&self) -> u64 {
+        match &self.state {
+            RepoState::Git(st) => st.ahead_behind.map_or(0, |(_, behind)| behind),
+            _ => 0,
+        }
+    }
+}
+
+/// Git status of every repo in the store, sorted by name. Served by a running
+/// `dothub daemon` when there is one, computed directly otherwise.
+pub fn collect() -> Result<Vec<StatusRow>> {
+    if REFRESH.load(Ordering::SeqCst) {
+        return scan();
+    }
+    if let Some(rows) = crate::daemon::cached_status() {
+        crate::prompt::record(&rows);
+        return Ok(rows);
+    }
+    scan()
+}
+
+/// Status of every repo now, running git in those that changed since it was
+/// cached. The counts are also saved for `dothub prompt`.
+pub fn scan() -> Result<Vec<StatusRow>> {
+    let state = State::load()?;
+    let mut cache = Cache::load();
+    let mut rows = Vec::new();
+    for (name, path) in crate::installed_repos()? {
+        let repo_state = if !git::is_repo(&path) {
+            RepoState::NotGit
+        } else {
+            let key = fingerprint(&path);
+            match hit(cache.status.get(&name), &key) {
+                Some(cached) => RepoState::Git(cached.clone()),
+                None => match git::status(&path) {
+                    Ok(mut st) => {
+                        st.lfs_pointers = crate::lfs::pointers(&path).len();
+                        cache.put_status(&name, key, &st);
+                        RepoState::Git(st)
+                    }
+                    Err(e) => RepoState::Error(e.to_string()),
+                },
+            }
+        };
+        rows.push(StatusRow {
+            linked: crate::linked_targets(&state, &name),
+            name,
+            state: repo_state,
+        });
+    }
+    cache.status.retain(|name, _| rows.iter().any(|r| r.name == *name));
+    cache.save();
+    crate::prompt::record(&rows);
+    Ok(rows)
+}
+
+/// Origin URLs of `repos` by name, from the cache for those whose git config
+/// is unchanged.
+pub fn origins(repos: &[(String, PathBuf)]) -> BTreeMap<String, String> {
+    let mut cache = Cache::load();
+    let mut out = BTreeMap::new();
+    for (name, path) in repos {
+        let key = git_dir(path).and_then(|dir| mtime(&dir.join("config")).map(|t| t.to_string()));
+        let url = match hit(cache.origins.get(name), &key) {
+            Some(cached) => cached.clone(),
+            None => {
+                let Ok(url) = git::output(path, &["config", "--get", "remote.origin.url"]) else {
+                    continue;
+                };
+                let value = url.clone();
+                cache.origins.insert(name.clone(), Cached { key, value });
+                cache.changed = true;
+                url
+            }
+        };
+        out.insert(name.clone(), url);
+    }
+    cache.save();
+    out
+}
+
+/// Status and origins computed earlier, with the fingerprint they were
+/// computed under.
+#[derive(Default, Serialize, Deserialize)]
+struct Cache {
+    status: BTreeMap<String, Cached<RepoStatus>>,
+    origins: BTreeMap<String, Cached<String>>,
+    #[serde(skip)]
+    changed: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Cached<T> {
+    key: Option<String>,
+    value: T,
+}
+
+fn cache_path() -> PathBuf {
+    crate::http::cache_dir().join("repo-status.json")
+}
+
+/// The cached value, if it was computed under `key` and `--refresh` wasn't given.
+fn hit<'a, T>(cached: Option<&'a Cached<T>>, key: &Option<String>) -> Option<&'a T> {
+    let cached = cached.filter(|c| key.is_some() && c.key == *key)?;
+    (!REFRESH.load(Ordering::SeqCst)).then_some(&cached.value)
+}
+
+impl Cache {
+    fn load() -> Cache {
+        fs::read_to_string(cache_path())
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    fn put_status(&mut self, name: &str, key: Option<String>, st: &RepoStatus) {
+        let value = st.clone();
+        self.status.insert(name.to_string(), Cached { key, value });
+        self.changed = true;
+    }
+
+    /// Like the HTTP cache, this is an optimisation; failing to write it is not an error.
+    fn save(&self) {
+        if !self.changed {
+            return;
+        }
+        let path = cache_path();
+        if let Some(parent) = path.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+        if let Ok(text) = serde_json::to_string(self) {
+            // The daemon and a prompt refresh may write at once
+            let tmp = path.with_extension(format!("json.{}.tmp", std::process::id()));
+            if fs::write(&tmp, text).is_ok() {
+                let _ = fs::rename(&tmp, &path);
+            }
+        }
+    }
+}
+
+fn git_dir(path: &Path) -> Option<PathBuf> {
+    let dir = path.join(".git");
+    // Worktrees and submodules point elsewhere; they aren't cached
+    dir.is_dir().then_some(dir)
+}
+
+fn mtime(path: &Path) -> Option<u128> {
+    let modified = fs::symlink_metadata(path).ok()?.modified().ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_nanos())
+}
+
+/// Everything `git status` depends on, found with stat calls alone. None when
+/// the repo can't be fingerprinted and git has to be asked every time.
+fn fingerprint(path: &Path) -> Option<String> {
+    let dir = git_dir(path)?;
+    let mut text = fs::read_to_string(dir.join("HEAD")).ok()?;
+    for file in ["index", "FETCH_HEAD", "packed-refs", "config"] {
+        text.push_str(&format!("{}={:?}\n", file, mtime(&dir.join(file))));
+    }
+    let (mut newest, mut count) = (0, 0);
+    walk(&dir.join("refs"), &mut newest, &mut count);
+    walk(path, &mut newest, &mut count);
+    text.push_str(&format!("tree={} {}\n", newest, count));
+    let digest = Sha256::digest(text.as_bytes());
+    Some(digest.iter().take(16).map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Newest modification time and number of entries under `dir`, skipping
+/// `.git` directories. Editing, adding or removing a file shows up in one.
+fn walk(dir: &Path, newest: &mut u128, count: &mut usize) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    *newest = (*newest).max(mtime(dir).unwrap_or(0));
+    for entry in entries.flatten() {
+        if entry.file_name() == ".git" {
+            continue;
+        }
+        *count += 1;
+        let Ok(meta) = entry.metadata() else {
+            continue;
+        };
+        if let Ok(modified) = meta.modified() {
+            let nanos = modified.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_nanos());
+            *newest = (*newest).max(nanos);
+        }
+        if meta.is_dir() {
+            walk(&entry.path(), newest, count);
+        }
+    }
+}
+
+pub fn cmd_status(tags: &[String]) -> Result<()> {
+    crate::ensure_store_dir()?;
+    git::ensure_git()?;
+    let mut rows = collect()?;
+    if !tags.is_empty() {
+        let st = State::load()?;
+        let keep: BTreeSet<String> = crate::tags::filter(&st, crate::installed_repos()?, tags)
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        rows.retain(|r| keep.contains(&r.name));
+        if rows.is_empty() {
+            println!("No repositories tagged {}.", tags.join(" or "));
+            return Ok(());
+        }
+    }
+    if rows.is_empty() {
+        println!("No repositories installed in {}.", crate::dothub_dir().display());
+        return Ok(());
+    }
+    println!("{}", table(&rows, &BTreeSet::new()));
+    Ok(())
+}
+
+/// Table of `rows`; repos in `flagged` are marked as having changed for the worse.
+fn table(rows: &[StatusRow], flagged: &BTreeSet<String>) -> Table {
+    let mut table = output::table(["Repo", "Branch", "State", "Upstream", "Linked"]);
+    for row in rows {
+        let name_cell = if flagged.contains(&row.name) {
+            output::cell(format!("{} *", row.name), Tone::Error)
+        } else {
+            Cell::new(&row.name)
+        };
+        let linked = Cell::new(row.linked.join(", "));
+        let st = match &row.state {
+            RepoState::NotGit => {
+                table.add_row(vec![
+                    name_cell,
+                    Cell::new("-"),
+                    output::cell("not a git repo", Tone::Muted),
+                    Cell::new("-"),
+                    linked,
+                ]);
+                continue;
+            }
+            RepoState::Error(e) => {
+                table.add_row(vec![
+                    name_cell,
+                    Cell::new("-"),
+                    output::cell("error", Tone::Error),
+                    Cell::new(e),
+                    linked,
+                ]);
+                continue;
+            }
+            RepoState::Git(st) => st,
+        };
+        let state_cell = if st.lfs_pointers > 0 {
+            let state = if st.is_dirty() {
+                format!("dirty ({})", st.changes)
+            } else {
+                "clean".to_string()
+            };
+            output::cell(format!("{}, {} LFS pointer(s)", state, st.lfs_pointers), Tone::Warn)
+        } else if st.is_dirty() {
+            output::cell(format!("dirty ({})", st.changes), Tone::Warn)
+        } else {
+            output::cell("clean", Tone::Ok)
+        };
+        let upstream_cell = match st.ahead_behind {
+            None => output::cell("no upstream", Tone::Muted),
+            Some((0, 0)) => output::cell("up to date", Tone::Ok),
+            Some((ahead, behind)) => {
+                let mut parts = Vec::new();
+                if ahead > 0 {
+                    parts.push(format!("ahead {}", ahead));
+                }
+                if behind > 0 {
+                    parts.push(format!("behind {}", behind));
+                }
+                output::cell(parts.join(", "), Tone::Warn)
+            }
+        };
+        table.add_row(vec![
+            name_cell,
+            output::cell(&st.branch, Tone::Info),
+            state_cell,
+            upstream_cell,
+            linked,
+        ]);
+    }
+    table
+}
+
+/// Re-render the status table every `interval` seconds until interrupted,
+/// flagging repos that became dirty or fell further behind since the
+/// previous refresh. Only local tracking refs are compared; nothing is fetched.
+pub fn cmd_watch(interval: u64) -> Result<()> {
+    crate::ensure_store_dir()?;
+    git::ensure_git()?;
+    let interval = interval.max(1);
+    let mut previous: Option<BTreeMap<String, (bool, u64)>> = None;
+    loop {
+        let rows = collect()?;
+        let mut flagged = BTreeSet::new();
+        let mut notes = Vec::new();
+        if let Some(prev) = &previous {
+            for row in &rows {
+                let Some(&(was_dirty, was_behind)) = prev.get(&row.name) else {
+                    continue;
+                };
+                if row.is_dirty() && !was_dirty {
+                    flagged.insert(row.name.clone());
+                    notes.push(format!("{} became dirty", row.name));
+                }
+                if row.behind() > was_behind {
+                    flagged.insert(row.name.clone());
+                    notes.push(format!("{} fell behind by {}", row.name, row.behind() - was_behind));
+                }
+            }
+        }
+
+        if output::is_tty() {
+            // Clear the screen and move home, like watch(1)
+            print!("\x1b[2J\x1b[H");
+        }
+        println!(
+            "{}",
+            output::paint(
+                &format!("dothub status every {}s (Ctrl-C to stop)", interval),
+                Tone::Muted
+            )
+        );
+        if rows.is_empty() {
+            println!("No repositories installed in {}.", crate::dothub_dir().display());
+        } else {
+            println!("{}", table(&rows, &flagged));
+        }
+        for note in &notes {
+            println!("{}", output::paint(&format!("* {}", note), Tone::Warn));
+        }
+        println!(
+            "{}",
+            output::paint(
+                &format!("Refreshed {}", clock(state::now_unix())),
+                Tone::Muted
+            )
+        );
+        std::io::stdout().flush()?;
+
+        let snapshot: BTreeMap<String, (bool, u64)> = rows
+            .iter()
+            .map(|r| (r.name.clone(), (r.is_dirty(), r.behind())))
+            .collect();
+        previous = Some(snapshot);
+        thread::sleep(Duration::from_secs(interval));
+    }
+}
+
+/// UTC wall clock time (HH:MM:SS) for a unix timestamp.
+fn clock(ts: u64) -> String {
+    let secs = ts % 86_400;
+    format!("{:02}:{:02}:{:02} UTC", secs / 3600, secs / 60 % 60, secs % 60)
+}
@@ -0,0 +1,76 @@
+//! Tags group stored repos (e.g. `terminal`, `editor`, `work`) so `update`,
+//! `status` and `list` can act on a group with `--tag`. A repo's tags are the
+//! ones given with `dothub tag` plus any its `dothub.toml` declares.
+
+use crate::manifest::RepoManifest;
+use crate::state::State;
+use anyhow::{bail, Result};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+/// Every tag of the repo at `path`, from state and its manifest.
+pub fn repo_tags(st: &State, name: &str, path: &Path) -> BTreeSet<String> {
+    let mut tags = st
+        .repos
+        .get(name)
+        .map(|r| r.tags.clone())
+        .unwrap_or_default();
+    if let Ok(Some(manifest)) = RepoManifest::load(path) {
+        tags.extend(manifest.tags);
+    }
+    tags
+}
+
+/// Keep the repos carrying any of `wanted`; every repo when `wanted` is empty.
+pub fn filter(
+    st: &State,
+    repos: Vec<(String, PathBuf)>,
+    wanted: &[String],
+) -> Vec<(String, PathBuf)> {
+    if wanted.is_empty() {
+        return repos;
+    }
+    repos
+        .into_iter()
+        .filter(|(name, path)| {
+            let tags = repo_tags(st, name, path);
+            wanted.iter().any(|t| tags.contains(t))
+        })
+        .collect()
+}
+
+/// `dothub tag`: add or remove tags on a repo, or show them without any.
+pub fn cmd_tag(name: &str, tags: &[String], remove: bool) -> Result<()> {
+    let path = crate::dothub_dir().join(name);
+    if !path.exists() {
+        bail!(crate::error::repo_not_found(name, &path));
+    }
+    let mut st = State::load()?;
+    if !tags.is_empty() {
+        let rec = st.repos.entry(name.to_string()).or_default();
+        for tag in tags {
+            if remove {
+                rec.tags.remove(tag);
+            } else {
+                rec.tags.insert(tag.clone());
+            }
+        }
+        st.save()?;
+    }
+    let all = repo_tags(&st, name, &path);
+    if remove {
+        // Manifest tags belong to the repo and can't be removed from here
+        let kept: Vec<&String> = tags.iter().filter(|t| all.contains(*t)).collect();
+        if !kept.is_empty() {
+            let kept: Vec<&str> = kept.iter().map(|t| t.as_str()).collect();
+            println!("{} comes from dothub.toml and stays", kept.join(", "));
+        }
+    }
+    if all.is_empty() {
+        println!("{} has no tags.", name);
+    } else {
+        let all: Vec<&str> = all.iter().map(String::as_str).collect();
+        println!("{}: {}", name, all.join(", "));
+    }
+    Ok(())
+}
@@ -0,0 +1,131 @@
+//! Where a target is deployed.
+//!
+//! A target is normally the directory of that name under ~/.config. The
+//! registry here knows the exceptions: on WSL, `windows-terminal` and
+//! `vscode` are the Windows applications' settings under
+//! /mnt/c/Users/<you>/AppData, `termux` is ~/.termux, and `<target>@<appid>`
+//! is the target inside a Flatpak app's sandbox, ~/.var/app/<appid>/config.
+//! Symlinks made from
+//! WSL on a Windows drive are only followed inside WSL, and Android's shared
+//! storage has no symlinks at all, so targets there are always copied.
+
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::OnceLock;
+
+/// Targets that live in the Windows user profile under WSL, relative to it.
+const WINDOWS_TARGETS: &[(&str, &str)] = &[
+    (
+        "windows-terminal",
+        "AppData/Local/Packages/Microsoft.WindowsTerminal_8wekyb3d8bbwe/LocalState",
+    ),
+    ("vscode", "AppData/Roaming/Code/User"),
+];
+
+/// Path of `target_name` on this machine.
+pub fn path(target_name: &str) -> Result<PathBuf> {
+    if let Some((name, app_id)) = target_name.split_once('@') {
+        check_app_id(app_id)?;
+        let home = crate::owner::home()?;
+        return Ok(home.join(".var/app").join(app_id).join("config").join(name));
+    }
+    if target_name == "termux" {
+        let home = dirs::home_dir().context("Unable to determine home directory")?;
+        return Ok(home.join(".termux"));
+    }
+    if let Some((_, rel)) = WINDOWS_TARGETS.iter().find(|(name, _)| *name == target_name) {
+        if let Some(profile) = windows_profile() {
+            return Ok(profile.join(rel));
+        }
+    }
+    Ok(crate::owner::config_root()?.join(target_name))
+}
+
+/// Name of `target_name` inside the sandbox of Flatpak app `app_id`.
+pub fn flatpak(target_name: &str, app_id: &str) -> String {
+    format!("{}@{}", target_name, app_id)
+}
+
+/// Flatpak app ids are reverse-DNS names of at least three parts, such as
+/// org.wezfurlong.wezterm.
+pub fn check_app_id(app_id: &str) -> Result<()> {
+    let parts = app_id.split('.').collect::<Vec<_>>();
+    let valid = parts.len() >= 3
+        && parts.iter().all(|p| {
+            !p.is_empty() && p.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        });
+    if !valid {
+        bail!("'{}' is not a Flatpak app id (e.g. org.wezfurlong.wezterm)", app_id);
+    }
+    Ok(())
+}
+
+/// Why `target` has to be a copy rather than a symlink, if it does.
+pub fn copy_reason(target: &Path) -> Option<&'static str> {
+    if is_wsl() && on_windows_drive(target) {
+        return Some("Windows applications can't follow symlinks made in WSL");
+    }
+    if is_termux() && ["/storage", "/sdcard"].iter().any(|p| target.starts_with(p)) {
+        return Some("Android's shared storage doesn't support symlinks");
+    }
+    None
+}
+
+/// Running in Termux on Android, where $PREFIX holds what is under /usr
+/// elsewhere and there is no root, sudo, systemd or user namespaces.
+pub fn is_termux() -> bool {
+    std::env::var_os("TERMUX_VERSION").is_some()
+        || std::env::var("PREFIX").is_ok_and(|p| p.contains("/com.termux/"))
+}
+
+/// Running under the Windows Subsystem for Linux.
+pub fn is_wsl() -> bool {
+    static WSL: OnceLock<bool> = OnceLock::new();
+    *WSL.get_or_init(|| {
+        Path::new("/proc/sys/fs/binfmt_misc/WSLInterop").exists()
+            || fs::read_to_string("/proc/sys/kernel/osrelease")
+                .is_ok_and(|r| r.to_lowercase().contains("microsoft"))
+    })
+}
+
+/// /mnt/<drive>/..., where WSL mounts Windows drives.
+fn on_windows_drive(path: &Path) -> bool {
+    let mut parts = path.components().skip(1);
+    parts.next().is_some_and(|c| c.as_os_str() == "mnt")
+        && parts
+            .next()
+            .and_then(|c| c.as_os_str().to_str())
+            .is_some_and(|d| d.len() == 1 && d.chars().all(|c| c.is_ascii_alphabetic()))
+}
+
+/// The Windows user profile (e.g. /mnt/c/Users/me) when running under WSL.
+fn windows_profile() -> Option<PathBuf> {
+    static PROFILE: OnceLock<Option<PathBuf>> = OnceLock::new();
+    PROFILE
+        .get_or_init(|| {
+            if !is_wsl() {
+                return None;
+            }
+            let out = Command::new("cmd.exe")
+                .args(["/c", "echo %USERPROFILE%"])
+                .current_dir("/mnt/c")
+                .output()
+                .ok()?;
+            let windows = String::from_utf8_lossy(&out.stdout).trim().to_string();
+            to_wsl_path(&windows).ok()
+        })
+        .clone()
+}
+
+/// C:\Users\me -> /mnt/c/Users/me
+fn to_wsl_path(windows: &str) -> Result<PathBuf> {
+    let (drive, rest) = windows
+        .split_once(":\\")
+        .filter(|(d, _)| d.len() == 1)
+        .with_context(|| format!("Not a Windows path: {}", windows))?;
+    let mut path = PathBuf::from("/mnt").join(drive.to_lowercase());
+    path.extend(rest.split('\\').filter(|p| !p.is_empty()));
+    Ok(path)
+}
@@ -0,0 +1,164 @@
+use crate::git;
+use crate::manifest::RepoManifest;
+use crate::state::{self, State};
+use anyhow::{bail, Context, Result};
+use std::fs;
+
+/// A starter config shipped with dothub.
+pub struct Template {
+    pub config_type: &'static str,
+    pub name: &'static str,
+    pub description: &'static str,
+    /// Directory under ~/.config the config is read from
+    pub target: &'static str,
+    pub files: &'static [(&'static str, &'static str)],
+    /// Extra README notes, e.g. how to make the app read the linked directory
+    pub notes: &'static str,
+}
+
+pub const TEMPLATES: &[Template] = &[
+    Template {
+        config_type: "nvim",
+        name: "minimal",
+        description: "Single init.lua with sane options and no plugins",
+        target: "nvim",
+        files: &[("init.lua", include_str!("../templates/nvim/minimal/init.lua"))],
+        notes: "",
+    },
+    Template {
+        config_type: "nvim",
+        name: "lazy",
+        description: "lazy.nvim bootstrap with a lua/plugins directory for specs",
+        target: "nvim",
+        files: &[
+            ("init.lua", include_str!("../templates/nvim/lazy/init.lua")),
+            (
+                "lua/plugins/init.lua",
+                include_str!("../templates/nvim/lazy/lua/plugins/init.lua"),
+            ),
+        ],
+        notes: "",
+    },
+    Template {
+        config_type: "tmux",
+        name: "minimal",
+        description: "C-a prefix, mouse, sensible splits and a reload binding",
+        target: "tmux",
+        files: &[("tmux.conf", include_str!("../templates/tmux/minimal/tmux.conf"))],
+        notes: "",
+    },
+    Template {
+        config_type: "zsh",
+        name: "minimal",
+        description: "History, completion and a simple prompt without a framework",
+        target: "zsh",
+        files: &[(".zshrc", include_str!("../templates/zsh/minimal/.zshrc"))],
+        notes: "zsh only reads `~/.config/zsh` when `ZDOTDIR` points there. Add this to `~/.zshenv`:\n\n```bash\nexport ZDOTDIR=\"$HOME/.config/zsh\"\n```\n",
+    },
+];
+
+pub fn find(config_type: &str, name: &str) -> Option<&'static Template> {
+    TEMPLATES
+        .iter()
+        .find(|t| t.config_type == config_type && t.name == name)
+}
+
+pub fn cmd_templates() {
+    println!("Available templates (dothub new <type> --from-template <name>):");
+    for t in TEMPLATES {
+        println!("  {:<6} {:<8} {}", t.config_type, t.name, t.description);
+    }
+}
+
+pub fn cmd_new(
+    config_type: &str,
+    template: &str,
+    name: Option<&str>,
+    link: bool,
+) -> Result<()> {
+    let Some(tpl) = find(config_type, template) else {
+        cmd_templates();
+        bail!("No template '{}' for type '{}'", template, config_type);
+    };
+    crate::ensure_store_dir()?;
+
+    let name = name
+        .map(|n| n.to_string())
+        .unwrap_or_else(|| format!("my-{}", config_type));
+    let dest = crate::dothub_dir().join(&name);
+    if dest.exists() {
+        bail!(crate::error::Error::new(
+            crate::error::Code::RepoExists,
+            format!("{} already exists in the store", name),
+        )
+        .hint("choose another name with --name"));
+    }
+
+    for (rel, contents) in tpl.files {
+        let path = dest.join(rel);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed creating {}", parent.display()))?;
+        }
+        fs::write(&path, contents).with_context(|| format!("Writing {}", path.display()))?;
+    }
+
+    let description = format!("My {} config, started from the dothub {} template", config_type, tpl.name);
+    fs::write(dest.join("README.md"), readme(&name, tpl, &description))
+        .context("Writing README.md")?;
+    RepoManifest {
+        config_type: Some(tpl.config_type.to_string()),
+        target: Some(tpl.target.to_string()),
+        description: Some(description),
+        package: None,
+        tags: Vec::new(),
+        units: Vec::new(),
+        hooks: Default::default(),
+        modes: Default::default(),
+        env: Default::default(),
+        assets: Vec::new(),
+        provides: Default::default(),
+    }
+    .save(&dest)?;
+
+    if git::ensure_git().is_ok() {
+        git::output(&dest, &["init", "-q"])?;
+        git::output(&dest, &["add", "-A"])?;
+        let msg = format!("Start {} config from dothub template {}", config_type, tpl.name);
+        match git::commit(&dest, &msg) {
+            Ok(()) => {}
+            Err(e) if crate::error::code_of(&e) == Some(crate::error::Code::SigningFailed) => {
+                println!("Created a git repo without a commit: {}. Commit it in {} once the key is available, or with git commit --no-gpg-sign", e, dest.display())
+            }
+            Err(_) => {
+                println!("Created a git repo but could not commit (is user.name/user.email set?)")
+            }
+        }
+    }
+
+    let mut st = State::load()?;
+    st.repos.entry(name.clone()).or_default().installed_at = state::now_unix();
+    st.save()?;
+
+    println!("Created {} from {}/{} at {}", name, config_type, tpl.name, dest.display());
+    if link {
+        crate::cmd_link(&name, Some(tpl.target), false)?;
+    } else {
+        println!("Link it with: dothub link {}", name);
+    }
+    Ok(())
+}
+
+fn readme(name: &str, tpl: &Template, description: &str) -> String {
+    let mut out = format!(
+        "# {name}\n\n{description}.\n\n## Install\n\n```bash\ndothub install <repo-url> {name}\ndothub link {name} {target}\n```\n",
+        name = name,
+        description = description,
+        target = tpl.target
+    );
+    if !tpl.notes.is_empty() {
+        out.push_str("\n## Notes\n\n");
+        out.push_str(tpl.notes);
+    }
+    out
+}
@@ -0,0 +1,177 @@
+//! `dothub hub trending`: which hub entries are gaining stars fastest.
+//!
+//! Every online hub listing saves a snapshot of the star counts and the
+//! entries in the index. Trending compares the current counts with the
+//! snapshot from about a week back (or the oldest one there is), and lists
+//! entries added to the index since the previous snapshot. Snapshots are kept
+//! per index URL, so browsing another hub with `--url` doesn't mix them.
+
+use crate::hub::{self, HubEntry};
+use crate::output::{self, Tone};
+use crate::{forge, state};
+use anyhow::Result;
+use comfy_table::Cell;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+
+const SNAPSHOTS_FILE: &str = "hub-stars.json";
+const DAY: u64 = 24 * 60 * 60;
+/// Listings closer together than this update the last snapshot instead of
+/// adding one.
+const MIN_SPACING: u64 = 60 * 60;
+/// Snapshots older than this are dropped.
+const KEEP: u64 = 180 * DAY;
+
+#[derive(Default, Serialize, Deserialize)]
+struct Snapshot {
+    /// URL of the hub index
+    index: String,
+    at: u64,
+    /// Star count by link, for entries whose forge answered
+    stars: BTreeMap<String, u64>,
+    /// Every link in the index at the time
+    links: Vec<String>,
+}
+
+fn snapshots_path() -> PathBuf {
+    crate::http::cache_dir().join(SNAPSHOTS_FILE)
+}
+
+fn load() -> Vec<Snapshot> {
+    fs::read_to_string(snapshots_path())
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or_default()
+}
+
+/// Save the star counts from a listing of the hub at `index`. Offline
+/// listings only repeat cached counts, so they are not recorded.
+pub fn record(index: Option<&str>, links: &[String], results: &forge::MetaResults) {
+    if crate::http::is_offline() {
+        return;
+    }
+    let index = index.unwrap_or(hub::DEFAULT_HUB_URL);
+    let now = state::now_unix();
+    let mut snapshots = load();
+    snapshots.retain(|s| s.at + KEEP > now);
+    if let Some(pos) = snapshots.iter().rposition(|s| s.index == index) {
+        if snapshots[pos].at + MIN_SPACING > now {
+            snapshots.remove(pos);
+        }
+    }
+    snapshots.push(Snapshot {
+        index: index.to_string(),
+        at: now,
+        stars: results
+            .meta
+            .iter()
+            .map(|(link, meta)| (link.clone(), meta.stars))
+            .collect(),
+        links: links.to_vec(),
+    });
+    let path = snapshots_path();
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    // Like the HTTP cache, losing a snapshot is not worth failing over
+    if let Ok(text) = serde_json::to_string(&snapshots) {
+        let _ = fs::write(path, text);
+    }
+}
+
+pub fn cmd_trending(url: Option<&str>, days: u64, limit: usize) -> Result<()> {
+    let yaml = hub::fetch_index(url)?;
+    let items = hub::parse_index(&yaml, &[])?;
+    let links: Vec<String> = items.iter().map(|i| i.link.clone()).collect();
+    let spinner_stop = output::start_spinner("Downloading repository info..");
+    let results = forge::fetch_all(&links)?;
+    spinner_stop.store(true, Ordering::SeqCst);
+    eprintln!();
+
+    // Compare against what was saved before this run
+    let index = url.unwrap_or(hub::DEFAULT_HUB_URL);
+    let snapshots: Vec<Snapshot> = load().into_iter().filter(|s| s.index == index).collect();
+    record(url, &links, &results);
+    let entries = hub::rank(items, &results);
+    let now = state::now_unix();
+
+    let Some(previous) = snapshots.last() else {
+        println!("Saved the first snapshot of star counts; run this again in a few days to see trends.");
+        return Ok(());
+    };
+    let since = now.saturating_sub(days * DAY);
+    let baseline = snapshots
+        .iter()
+        .rev()
+        .find(|s| s.at <= since)
+        .unwrap_or(&snapshots[0]);
+
+    let mut gainers: Vec<(&HubEntry, u64, f64)> = entries
+        .iter()
+        .filter(|e| results.meta.contains_key(&e.link))
+        .filter_map(|e| {
+            let then = *baseline.stars.get(&e.link)?;
+            let gained = e.meta.stars.checked_sub(then).filter(|g| *g > 0)?;
+            let per_week = gained as f64 * (7 * DAY) as f64 / now.saturating_sub(baseline.at).max(1) as f64;
+            Some((e, gained, per_week))
+        })
+        .collect();
+    gainers.sort_by(|a, b| b.2.total_cmp(&a.2).then(b.1.cmp(&a.1)));
+    gainers.truncate(limit);
+
+    println!(
+        "{}",
+        output::paint(
+            &format!(
+                "Stars gained over the last {}",
+                output::duration(now.saturating_sub(baseline.at))
+            ),
+            Tone::Info
+        )
+    );
+    if gainers.is_empty() {
+        println!("No entry gained stars.");
+    } else {
+        let mut table = output::table(["#", "Stars", "Gained", "Per week", "Source", "Description"]);
+        for (idx, (entry, gained, per_week)) in gainers.iter().enumerate() {
+            table.add_row(vec![
+                Cell::new(idx + 1),
+                Cell::new(entry.meta.stars),
+                output::cell(format!("+{}", gained), Tone::Ok),
+                Cell::new(format!("{:.1}", per_week)),
+                Cell::new(&entry.link),
+                Cell::new(hub::truncate(entry.description().unwrap_or(""), 50)),
+            ]);
+        }
+        println!("{}", table);
+    }
+
+    let added: Vec<&HubEntry> = entries
+        .iter()
+        .filter(|e| !previous.links.contains(&e.link))
+        .collect();
+    if !added.is_empty() {
+        println!();
+        println!(
+            "{}",
+            output::paint(
+                &format!("New in the hub since your last refresh ({})", output::ago(previous.at, now)),
+                Tone::Info
+            )
+        );
+        let mut table = output::table(["Type", "Stars", "Source", "Description"]);
+        for entry in added {
+            table.add_row(vec![
+                Cell::new(&entry.config_type),
+                Cell::new(entry.meta.stars),
+                Cell::new(&entry.link),
+                Cell::new(hub::truncate(entry.description().unwrap_or(""), 50)),
+            ]);
+        }
+        println!("{}", table);
+    }
+    Ok(())
+}
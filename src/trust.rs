@@ -0,0 +1,92 @@
+//! Per-repo trust, consulted before running anything a repo ships.
+//!
+//! Hooks, systemd units and the variables a repo exports to every shell are
+//! code from the repo's author. A repo is
+//! `prompt` until told otherwise: each run shows what would run and asks.
+//! `untrusted` repos never run anything, `trusted` ones run without asking.
+
+use crate::output::{self, Tone};
+use crate::state::{State, Trust};
+use anyhow::{bail, Result};
+use std::io::{self, BufRead, IsTerminal, Write};
+
+/// Whether `what` (e.g. "the post_link hook") of repo `name` may run.
+/// `shown` is what the user is asked about under the prompt level.
+pub fn allow(name: &str, what: &str, shown: &str) -> Result<bool> {
+    let level = State::load()?
+        .repos
+        .get(name)
+        .map(|r| r.trust)
+        .unwrap_or_default();
+    let skipped = |why: &str| {
+        println!(
+            "{}",
+            output::paint(
+                &format!("Skipped {} of {}: {}", what, name, why),
+                Tone::Warn
+            )
+        );
+    };
+    match level {
+        Trust::Trusted => return Ok(true),
+        Trust::Untrusted => {
+            skipped("the repo is untrusted");
+            return Ok(false);
+        }
+        Trust::Prompt => {}
+    }
+    if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        skipped(&format!(
+            "no terminal to ask on (dothub trust {} trusted runs it without asking)",
+            name
+        ));
+        return Ok(false);
+    }
+    println!(
+        "{}",
+        output::paint(&format!("{} wants to run {}:", name, what), Tone::Warn)
+    );
+    for line in shown.lines() {
+        println!("  {}", line);
+    }
+    print!("Run it? [y/N] ");
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    if matches!(answer.trim(), "y" | "Y" | "yes") {
+        return Ok(true);
+    }
+    skipped("declined");
+    Ok(false)
+}
+
+/// `dothub trust`: show or set whether a repo may run its hooks and units.
+pub fn cmd_trust(name: &str, level: Option<Trust>) -> Result<()> {
+    let path = crate::dothub_dir().join(name);
+    if !path.exists() {
+        bail!(crate::error::repo_not_found(name, &path));
+    }
+    let mut st = State::load()?;
+    let record = st.repos.entry(name.to_string()).or_default();
+    let Some(level) = level else {
+        println!("Trust level of {}: {}", name, record.trust.name());
+        return Ok(());
+    };
+    record.trust = level;
+    st.save()?;
+    match level {
+        Trust::Untrusted => println!(
+            "{} never runs its hooks or units or exports its [env]",
+            name
+        ),
+        Trust::Prompt => println!(
+            "{} asks before running its hooks or units or exporting its [env]",
+            name
+        ),
+        Trust::Trusted => println!(
+            "{} runs its hooks and units and exports its [env] without asking",
+            name
+        ),
+    }
+    Ok(())
+}
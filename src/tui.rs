@@ -0,0 +1,1054 @@
+//! Interactive dashboard (`dothub dashboard`).
+//!
+//! Two tabs: the repos installed in the store and the hub index. A hub entry
+//! can be opened to read its README, fetched through the HTTP cache and
+//! rendered as plain text, and installed from there. Rows can be marked to
+//! update, unlink or install several at once, and `/` searches the stored
+//! repos like `dothub grep`. Colors and keys come from the
+//! `[tui]` section of the config file.
+
+mod keys;
+mod theme;
+
+use crate::config::Config;
+use crate::conflict::{self, Resolution};
+use crate::forge;
+use crate::hub::{self, HubEntry};
+use crate::markdown::{self, Document, Style};
+use crate::state::State;
+use anyhow::{bail, Context, Result};
+use crossterm::cursor;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+use crossterm::style::{Attribute, Print, ResetColor, SetAttribute, SetForegroundColor};
+use crossterm::terminal::{self, ClearType};
+use crossterm::{execute, queue};
+use keys::{Action, Keymap};
+use std::collections::BTreeSet;
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use theme::{Paint, Theme};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Tab {
+    Installed,
+    Hub,
+}
+
+struct InstalledRow {
+    name: String,
+    targets: Vec<String>,
+    origin: String,
+}
+
+struct Readme {
+    /// Index into the hub entries
+    entry: usize,
+    source: String,
+    doc: Document,
+    scroll: usize,
+    /// Selected link, as an index into `doc.links`
+    link: Option<usize>,
+}
+
+/// A `dothub grep` search: the query while it is typed, then its matches.
+struct Search {
+    query: String,
+    /// Still typing; Enter runs the search
+    editing: bool,
+    results: Vec<crate::grep::Match>,
+    scroll: usize,
+}
+
+/// Keys offered by the conflict dialog, handled in `handle_conflict_key`.
+const CONFLICT_CHOICES: [&str; 4] = [
+    "b  back up and replace",
+    "a  adopt into the store",
+    "s  skip",
+    "v  view differences",
+];
+
+/// An existing, unmanaged target found while linking from the dashboard.
+struct Conflict {
+    repo: String,
+    source: PathBuf,
+    target_name: String,
+    target: PathBuf,
+    /// File comparison, once the user asked to view it
+    details: Option<Vec<String>>,
+    scroll: usize,
+}
+
+/// A batch operation and the outcome of each item so far.
+struct Batch {
+    title: String,
+    /// Item and its result; None while pending or running
+    items: Vec<(String, Option<Result<String, String>>)>,
+    done: bool,
+}
+
+struct App {
+    tab: Tab,
+    installed: Vec<InstalledRow>,
+    hub: Option<Vec<HubEntry>>,
+    hub_url: Option<String>,
+    /// Links starred with `dothub hub star`, listed first and marked ★
+    favorites: BTreeSet<String>,
+    selected: [usize; 2],
+    /// Marked rows per tab: repo names on the installed tab, links on the hub
+    marked: [BTreeSet<String>; 2],
+    readme: Option<Readme>,
+    conflict: Option<Conflict>,
+    batch: Option<Batch>,
+    search: Option<Search>,
+    message: String,
+    quit: bool,
+    theme: Theme,
+    keymap: Keymap,
+}
+
+/// Raw mode and the alternate screen, restored when dropped.
+struct Screen;
+
+impl Screen {
+    fn enter() -> Result<Screen> {
+        terminal::enable_raw_mode().context("Enabling raw terminal mode")?;
+        execute!(io::stdout(), terminal::EnterAlternateScreen, cursor::Hide)?;
+        Ok(Screen)
+    }
+}
+
+impl Screen {
+    /// Run `f` on the normal screen, where trust prompts and hooks can talk
+    /// to the user, and wait for Enter before the dashboard comes back.
+    fn suspended<T>(f: impl FnOnce() -> T) -> Result<T> {
+        execute!(io::stdout(), cursor::Show, terminal::LeaveAlternateScreen)?;
+        terminal::disable_raw_mode()?;
+        let result = f();
+        print!("Press Enter to return to the dashboard");
+        io::stdout().flush()?;
+        io::stdin().lock().read_line(&mut String::new())?;
+        terminal::enable_raw_mode().context("Enabling raw terminal mode")?;
+        execute!(io::stdout(), terminal::EnterAlternateScreen, cursor::Hide)?;
+        Ok(result)
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        let _ = execute!(io::stdout(), cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+pub fn cmd_dashboard(url: Option<String>) -> Result<()> {
+    if crate::output::plain() {
+        // A full-screen interface doesn't read well; fall back to the status lines
+        return crate::status::cmd_status(&[]);
+    }
+    if !crate::output::is_tty() {
+        bail!("The dashboard needs an interactive terminal");
+    }
+    let config = Config::load()?;
+    let mut app = App {
+        tab: Tab::Installed,
+        installed: load_installed()?,
+        hub: None,
+        hub_url: url,
+        favorites: crate::state::State::load()?.favorites,
+        selected: [0, 0],
+        marked: Default::default(),
+        readme: None,
+        conflict: None,
+        batch: None,
+        search: None,
+        message: String::new(),
+        quit: false,
+        theme: Theme::from_config(&config.tui)?,
+        keymap: Keymap::from_config(&config.tui)?,
+    };
+
+    let _screen = Screen::enter()?;
+    let mut out = io::stdout();
+    while !app.quit {
+        app.draw(&mut out)?;
+        match event::read()? {
+            Event::Key(key) if key.kind == KeyEventKind::Press => {
+                app.message.clear();
+                app.handle_key(key, &mut out)?;
+            }
+            Event::Resize(..) => app.rerender_readme(),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn load_installed() -> Result<Vec<InstalledRow>> {
+    let state = State::load()?;
+    let mut rows = Vec::new();
+    for (name, path) in crate::installed_repos()? {
+        let origin = crate::git::output(&path, &["config", "--get", "remote.origin.url"])
+            .unwrap_or_else(|_| "-".to_string());
+        rows.push(InstalledRow {
+            targets: crate::linked_targets(&state, &name),
+            name,
+            origin,
+        });
+    }
+    Ok(rows)
+}
+
+impl App {
+    fn tab_index(&self) -> usize {
+        match self.tab {
+            Tab::Installed => 0,
+            Tab::Hub => 1,
+        }
+    }
+
+    fn row_count(&self) -> usize {
+        match self.tab {
+            Tab::Installed => self.installed.len(),
+            Tab::Hub => self.hub.as_ref().map_or(0, |h| h.len()),
+        }
+    }
+
+    fn move_selection(&mut self, delta: isize) {
+        let count = self.row_count();
+        let sel = &mut self.selected[self.tab_index()];
+        if count == 0 {
+            *sel = 0;
+            return;
+        }
+        *sel = (*sel as isize + delta).clamp(0, count as isize - 1) as usize;
+    }
+
+    /// Key identifying a row: the repo name or the hub link.
+    fn row_key(&self, idx: usize) -> Option<String> {
+        match self.tab {
+            Tab::Installed => self.installed.get(idx).map(|r| r.name.clone()),
+            Tab::Hub => self.hub.as_ref()?.get(idx).map(|e| e.link.clone()),
+        }
+    }
+
+    /// Marked rows in display order, or the selected row when none are marked.
+    fn targets(&self) -> Vec<String> {
+        let marked = &self.marked[self.tab_index()];
+        if marked.is_empty() {
+            return self
+                .row_key(self.selected[self.tab_index()])
+                .into_iter()
+                .collect();
+        }
+        (0..self.row_count())
+            .filter_map(|i| self.row_key(i))
+            .filter(|k| marked.contains(k))
+            .collect()
+    }
+
+    fn handle_key(&mut self, key: KeyEvent, out: &mut impl Write) -> Result<()> {
+        if let Some(batch) = &self.batch {
+            // The results stay up until the next key press
+            if batch.done {
+                self.batch = None;
+            }
+            return Ok(());
+        }
+        if self.conflict.is_some() {
+            return self.handle_conflict_key(key);
+        }
+        if self.search.as_ref().is_some_and(|s| s.editing) {
+            return self.handle_search_input(key, out);
+        }
+        let Some(action) = self.keymap.action(&key) else {
+            return Ok(());
+        };
+        if self.search.is_some() {
+            self.handle_search_action(action);
+            return Ok(());
+        }
+        if self.readme.is_some() {
+            return self.handle_readme_action(action, out);
+        }
+        let page = page_size();
+        match action {
+            Action::Quit => self.quit = true,
+            Action::Down => self.move_selection(1),
+            Action::Up => self.move_selection(-1),
+            Action::PageDown => self.move_selection(page as isize),
+            Action::PageUp => self.move_selection(-(page as isize)),
+            Action::Top => self.move_selection(isize::MIN / 2),
+            Action::Bottom => self.move_selection(isize::MAX / 2),
+            Action::NextTab => {
+                let next = match self.tab {
+                    Tab::Installed => Tab::Hub,
+                    Tab::Hub => Tab::Installed,
+                };
+                self.switch_tab(next, out)?;
+            }
+            Action::InstalledTab => self.switch_tab(Tab::Installed, out)?,
+            Action::HubTab => self.switch_tab(Tab::Hub, out)?,
+            Action::Refresh => self.refresh(out)?,
+            Action::Search => {
+                self.search = Some(Search {
+                    query: String::new(),
+                    editing: true,
+                    results: Vec::new(),
+                    scroll: 0,
+                })
+            }
+            Action::Open if self.tab == Tab::Hub => self.open_readme(out)?,
+            Action::Link if self.tab == Tab::Installed => self.link_selected()?,
+            Action::Mark => {
+                if let Some(key) = self.row_key(self.selected[self.tab_index()]) {
+                    let marked = &mut self.marked[self.tab_index()];
+                    if !marked.remove(&key) {
+                        marked.insert(key);
+                    }
+                    self.move_selection(1);
+                }
+            }
+            Action::Update if self.tab == Tab::Installed => self.update(out)?,
+            Action::Unlink if self.tab == Tab::Installed => self.unlink(out)?,
+            Action::Install if self.tab == Tab::Hub => {
+                let links = self.targets();
+                self.install(out, links)?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn handle_readme_action(&mut self, action: Action, out: &mut impl Write) -> Result<()> {
+        let page = page_size();
+        let Some(readme) = self.readme.as_mut() else {
+            return Ok(());
+        };
+        let max_scroll = readme.doc.lines.len().saturating_sub(page);
+        let links = readme.doc.links.len();
+        match action {
+            Action::Quit | Action::Back => self.readme = None,
+            Action::Down => readme.scroll = (readme.scroll + 1).min(max_scroll),
+            Action::Up => readme.scroll = readme.scroll.saturating_sub(1),
+            Action::PageDown => readme.scroll = (readme.scroll + page).min(max_scroll),
+            Action::PageUp => readme.scroll = readme.scroll.saturating_sub(page),
+            Action::Top => readme.scroll = 0,
+            Action::Bottom => readme.scroll = max_scroll,
+            Action::NextLink if links > 0 => {
+                readme.link = Some(readme.link.map_or(0, |l| (l + 1) % links));
+            }
+            Action::PrevLink if links > 0 => {
+                readme.link = Some(readme.link.map_or(links - 1, |l| (l + links - 1) % links));
+            }
+            Action::OpenLink => {
+                let url = match readme.link {
+                    Some(l) => readme.doc.links[l].clone(),
+                    None => self.hub.as_ref().map_or(String::new(), |h| {
+                        forge::RepoRef::parse(&h[readme.entry].link)
+                            .map_or_else(|| h[readme.entry].link.clone(), |r| r.web_url())
+                    }),
+                };
+                self.message = match open_url(&url) {
+                    Ok(()) => format!("Opened {}", url),
+                    Err(e) => format!("{:#}", e),
+                };
+            }
+            Action::Install => {
+                let entry = readme.entry;
+                let link = self
+                    .hub
+                    .as_ref()
+                    .and_then(|h| h.get(entry))
+                    .map(|e| e.link.clone());
+                self.install(out, link.into_iter().collect())?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Typing the search query; keys are text here, not actions.
+    fn handle_search_input(&mut self, key: KeyEvent, out: &mut impl Write) -> Result<()> {
+        let Some(search) = self.search.as_mut() else {
+            return Ok(());
+        };
+        match key.code {
+            KeyCode::Esc => self.search = None,
+            KeyCode::Backspace => {
+                search.query.pop();
+            }
+            KeyCode::Char(c) => search.query.push(c),
+            KeyCode::Enter if search.query.is_empty() => self.search = None,
+            KeyCode::Enter => {
+                self.message = format!("Searching for {}…", search.query);
+                self.draw(out)?;
+                let Some(search) = self.search.as_mut() else {
+                    return Ok(());
+                };
+                match crate::grep::search(&search.query, false, &[]) {
+                    Ok(results) => {
+                        self.message = format!("{} match(es)", results.len());
+                        search.results = results;
+                        search.editing = false;
+                    }
+                    Err(e) => self.message = format!("{:#}", e),
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn handle_search_action(&mut self, action: Action) {
+        let page = page_size();
+        let Some(search) = self.search.as_mut() else {
+            return;
+        };
+        let max_scroll = search.results.len().saturating_sub(page);
+        match action {
+            Action::Quit | Action::Back => self.search = None,
+            Action::Search => {
+                search.editing = true;
+                search.query.clear();
+            }
+            Action::Down => search.scroll = (search.scroll + 1).min(max_scroll),
+            Action::Up => search.scroll = search.scroll.saturating_sub(1),
+            Action::PageDown => search.scroll = (search.scroll + page).min(max_scroll),
+            Action::PageUp => search.scroll = search.scroll.saturating_sub(page),
+            Action::Top => search.scroll = 0,
+            Action::Bottom => search.scroll = max_scroll,
+            _ => {}
+        }
+    }
+
+    /// Dialog choices are fixed letters, shown in the dialog itself.
+    fn handle_conflict_key(&mut self, key: KeyEvent) -> Result<()> {
+        let Some(c) = self.conflict.as_mut() else {
+            return Ok(());
+        };
+        let resolution = match key.code {
+            KeyCode::Char('b') => Resolution::Backup,
+            KeyCode::Char('a') => Resolution::Adopt,
+            KeyCode::Char('s') | KeyCode::Esc => Resolution::Skip,
+            KeyCode::Char('v') => {
+                c.details = Some(conflict::describe(&c.target, &c.source)?);
+                return Ok(());
+            }
+            _ => {
+                match self.keymap.action(&key) {
+                    Some(Action::Down) => c.scroll += 1,
+                    Some(Action::Up) => c.scroll = c.scroll.saturating_sub(1),
+                    Some(Action::Quit) => self.conflict = None,
+                    _ => {}
+                }
+                return Ok(());
+            }
+        };
+        let Some(c) = self.conflict.take() else {
+            return Ok(());
+        };
+        if resolution == Resolution::Skip {
+            self.message =
+                crate::i18n::t("skipped-unchanged", &[("target", &c.target.display())]);
+            return Ok(());
+        }
+        let result = Screen::suspended(|| -> Result<String> {
+            let done = conflict::resolve(resolution, &c.target_name, &c.target)?;
+            let linked = crate::deploy_link(&c.repo, &c.source, &c.target_name, &c.target, false)?;
+            Ok(format!("{}. {}", done, linked))
+        })?;
+        self.message = result.unwrap_or_else(|e| format!("{:#}", e));
+        self.installed = load_installed()?;
+        Ok(())
+    }
+
+    /// Link the selected repo to the target declared in its dothub.toml.
+    fn link_selected(&mut self) -> Result<()> {
+        let Some(row) = self.installed.get(self.selected[0]) else {
+            return Ok(());
+        };
+        let repo = row.name.clone();
+        let (source, target_name, target) = match crate::link_paths(&repo, None) {
+            Ok(paths) => paths,
+            Err(e) => {
+                self.message = format!("{:#}; use `dothub link {} <target>`", e, repo);
+                return Ok(());
+            }
+        };
+        // The dashboard can't show the findings; `dothub audit` does
+        if let Err(e) = crate::audit::check_first_link(&repo, false) {
+            self.message = format!("{:#}; review it with `dothub audit {}`", e, repo);
+            return Ok(());
+        }
+        if conflict::is_unmanaged(&target_name, &target)? {
+            self.conflict = Some(Conflict {
+                repo,
+                source,
+                target_name,
+                target,
+                details: None,
+                scroll: 0,
+            });
+            return Ok(());
+        }
+        self.message =
+            Screen::suspended(|| crate::deploy_link(&repo, &source, &target_name, &target, false))?
+                .unwrap_or_else(|e| format!("{:#}", e));
+        self.installed = load_installed()?;
+        Ok(())
+    }
+
+    fn switch_tab(&mut self, tab: Tab, out: &mut impl Write) -> Result<()> {
+        self.tab = tab;
+        if tab == Tab::Hub && self.hub.is_none() {
+            self.load_hub(out)?;
+        }
+        Ok(())
+    }
+
+    fn refresh(&mut self, out: &mut impl Write) -> Result<()> {
+        match self.tab {
+            Tab::Installed => self.installed = load_installed()?,
+            Tab::Hub => self.load_hub(out)?,
+        }
+        self.move_selection(0);
+        Ok(())
+    }
+
+    fn load_hub(&mut self, out: &mut impl Write) -> Result<()> {
+        self.message = "Loading hub…".to_string();
+        self.draw(out)?;
+        let loaded = hub::fetch_index(self.hub_url.as_deref())
+            .and_then(|yaml| hub::parse_index(&yaml, &[]))
+            .and_then(|items| {
+                let links: Vec<String> = items.iter().map(|i| i.link.clone()).collect();
+                let results = forge::fetch_all(&links)?;
+                let mut entries = hub::rank(items, &results);
+                crate::favorites::sort_first(&self.favorites, &mut entries);
+                Ok(entries)
+            });
+        match loaded {
+            Ok(entries) => {
+                self.message = format!("{} hub entries", entries.len());
+                self.hub = Some(entries);
+            }
+            Err(e) => {
+                self.message = format!("{:#}", e);
+                self.hub = Some(Vec::new());
+            }
+        }
+        Ok(())
+    }
+
+    fn open_readme(&mut self, out: &mut impl Write) -> Result<()> {
+        let idx = self.selected[1];
+        let Some(entry) = self.hub.as_ref().and_then(|h| h.get(idx)) else {
+            return Ok(());
+        };
+        let (link, branch) = (entry.link.clone(), entry.meta.default_branch.clone());
+        let details = entry.info.markdown(&entry.config_type);
+        self.message = format!("Fetching README for {}…", link);
+        self.draw(out)?;
+        match forge::fetch_readme(&link, branch.as_deref()) {
+            Ok(source) => {
+                self.message.clear();
+                let source = format!("{}{}", details, source);
+                self.readme = Some(Readme {
+                    entry: idx,
+                    doc: markdown::render(&source, content_width()),
+                    source,
+                    scroll: 0,
+                    link: None,
+                });
+            }
+            Err(e) => self.message = format!("{:#}", e),
+        }
+        Ok(())
+    }
+
+    fn rerender_readme(&mut self) {
+        if let Some(r) = self.readme.as_mut() {
+            r.doc = markdown::render(&r.source, content_width());
+            r.scroll = r.scroll.min(r.doc.lines.len().saturating_sub(1));
+            r.link = r.link.filter(|l| *l < r.doc.links.len());
+        }
+    }
+
+    fn install(&mut self, out: &mut impl Write, links: Vec<String>) -> Result<()> {
+        // Entries that name a backend are installed with it
+        let infos: Vec<(String, hub::EntryInfo)> = self
+            .hub
+            .iter()
+            .flatten()
+            .filter(|e| links.contains(&e.link))
+            .map(|e| (e.link.clone(), e.info.clone()))
+            .collect();
+        self.run_batch(out, "Install", links, |link| {
+            let backend = match infos.iter().find(|(l, _)| l == link) {
+                Some((_, info)) => info.backend()?,
+                None => None,
+            };
+            let repo = crate::vcs::qualify(link, backend);
+            Ok(match crate::install_repo(&repo, None, false, false)? {
+                Some(name) => format!("installed as {}", name),
+                None => "already installed".to_string(),
+            })
+        })
+    }
+
+    fn update(&mut self, out: &mut impl Write) -> Result<()> {
+        let mut st = State::load()?;
+        self.run_batch(out, "Update", self.targets(), |name| {
+            let result = crate::update_repo(&mut st, name, &crate::dothub_dir().join(name));
+            if result.kind == crate::UpdateKind::Failed {
+                bail!("{}", result.detail);
+            }
+            Ok(format!("{} {}", result.status, result.detail)
+                .trim_end()
+                .to_string())
+        })?;
+        st.save()
+    }
+
+    fn unlink(&mut self, out: &mut impl Write) -> Result<()> {
+        let mut st = State::load()?;
+        self.run_batch(out, "Unlink", self.targets(), |name| {
+            let targets = crate::linked_targets(&st, name);
+            if targets.is_empty() {
+                return Ok("not linked".to_string());
+            }
+            let mut done = Vec::new();
+            for target in targets {
+                done.push(crate::unlink_target(&mut st, &target)?);
+            }
+            Ok(done.join("; "))
+        })?;
+        st.save()
+    }
+
+    /// Run `op` on every item, redrawing the progress pane as each finishes.
+    fn run_batch(
+        &mut self,
+        out: &mut impl Write,
+        verb: &str,
+        items: Vec<String>,
+        mut op: impl FnMut(&str) -> Result<String>,
+    ) -> Result<()> {
+        if items.is_empty() {
+            return Ok(());
+        }
+        self.batch = Some(Batch {
+            title: format!("{} ({})", verb, items.len()),
+            items: items.into_iter().map(|item| (item, None)).collect(),
+            done: false,
+        });
+        let mut i = 0;
+        while let Some(item) = self
+            .batch
+            .as_ref()
+            .and_then(|b| b.items.get(i))
+            .map(|(item, _)| item.clone())
+        {
+            self.draw(out)?;
+            let result = op(&item).map_err(|e| format!("{:#}", e));
+            if let Some(batch) = self.batch.as_mut() {
+                batch.items[i].1 = Some(result);
+            }
+            i += 1;
+        }
+        if let Some(batch) = self.batch.as_mut() {
+            batch.done = true;
+        }
+        self.marked[self.tab_index()].clear();
+        self.installed = load_installed()?;
+        Ok(())
+    }
+
+    fn draw(&self, out: &mut impl Write) -> Result<()> {
+        let (w, h) = terminal::size()?;
+        let (w, h) = (w as usize, h as usize);
+        queue!(out, terminal::Clear(ClearType::All))?;
+        match (&self.search, &self.readme) {
+            (Some(s), _) if !s.editing => self.draw_search(out, s, w, h)?,
+            (_, Some(r)) => self.draw_readme(out, r, w, h)?,
+            _ => self.draw_list(out, w, h)?,
+        }
+        if let Some(s) = self.search.as_ref().filter(|s| s.editing) {
+            if h >= 2 && self.message.is_empty() {
+                let prompt = format!("/{}█", s.query);
+                put(out, &self.theme, h - 1, &prompt, Paint::Accent, w)?;
+            }
+        }
+        if let Some(c) = &self.conflict {
+            self.draw_conflict(out, c, w, h)?;
+        }
+        if let Some(b) = &self.batch {
+            self.draw_batch(out, b, w, h)?;
+        }
+        out.flush()?;
+        Ok(())
+    }
+
+    fn draw_list(&self, out: &mut impl Write, w: usize, h: usize) -> Result<()> {
+        let tabs = match self.tab {
+            Tab::Installed => " dothub  [1 Installed]   2 Hub ",
+            Tab::Hub => " dothub   1 Installed   [2 Hub] ",
+        };
+        put(out, &self.theme, 0, tabs, Paint::Title, w)?;
+
+        let marked = &self.marked[self.tab_index()];
+        let mark = |key: &str| if marked.contains(key) { "● " } else { "  " };
+        let (header, rows): (String, Vec<(String, bool)>) = match self.tab {
+            Tab::Installed => (
+                format!("  {:<28} {:<20} {}", "NAME", "LINKED", "ORIGIN"),
+                self.installed
+                    .iter()
+                    .map(|r| {
+                        let linked = if r.targets.is_empty() {
+                            "-".to_string()
+                        } else {
+                            r.targets.join(", ")
+                        };
+                        let text = format!("{:<28} {:<20} {}", r.name, linked, r.origin);
+                        (format!("{}{}", mark(&r.name), text), false)
+                    })
+                    .collect(),
+            ),
+            Tab::Hub => (
+                format!(
+                    "  {:>6}    {:<8} {:<44} {}",
+                    "STARS", "TYPE", "SOURCE", "DESCRIPTION"
+                ),
+                self.hub
+                    .iter()
+                    .flatten()
+                    .map(|e| {
+                        let installed = crate::dothub_dir()
+                            .join(crate::derive_repo_name(&e.link))
+                            .exists();
+                        let desc = e.description().unwrap_or("");
+                        (
+                            format!(
+                                "{}{:>6} {}{} {:<8} {:<44} {}",
+                                mark(&e.link),
+                                e.meta.stars,
+                                if installed { "✓" } else { " " },
+                                if crate::favorites::is_favorite(&self.favorites, &e.link) {
+                                    "★"
+                                } else {
+                                    " "
+                                },
+                                e.config_type,
+                                e.link,
+                                desc
+                            ),
+                            installed,
+                        )
+                    })
+                    .collect(),
+            ),
+        };
+        put(out, &self.theme, 1, &header, Paint::Muted, w)?;
+
+        let visible = h.saturating_sub(3);
+        let selected = self.selected[self.tab_index()];
+        let offset = (selected + 1).saturating_sub(visible);
+        if rows.is_empty() {
+            let empty = match self.tab {
+                Tab::Installed => "No repositories installed. Press 2 to browse the hub.",
+                Tab::Hub => "No hub entries.",
+            };
+            put(out, &self.theme, 2, empty, Paint::Muted, w)?;
+        }
+        for (i, (text, installed)) in rows.iter().enumerate().skip(offset).take(visible) {
+            let paint = if i == selected {
+                Paint::Selected
+            } else if *installed {
+                Paint::Accent
+            } else {
+                Paint::Text
+            };
+            put(out, &self.theme, 2 + i - offset, text, paint, w)?;
+        }
+
+        let hints = match self.tab {
+            Tab::Installed => self.keymap.hints(&[
+                (&[Action::Down, Action::Up], "move"),
+                (&[Action::Mark], "mark"),
+                (&[Action::Update], "update"),
+                (&[Action::Unlink], "unlink"),
+                (&[Action::Link], "link"),
+                (&[Action::Search], "search"),
+                (&[Action::NextTab], "switch"),
+                (&[Action::Refresh], "refresh"),
+                (&[Action::Quit], "quit"),
+            ]),
+            Tab::Hub => self.keymap.hints(&[
+                (&[Action::Down, Action::Up], "move"),
+                (&[Action::Mark], "mark"),
+                (&[Action::Open], "readme"),
+                (&[Action::Install], "install"),
+                (&[Action::NextTab], "switch"),
+                (&[Action::Refresh], "refresh"),
+                (&[Action::Quit], "quit"),
+            ]),
+        };
+        self.footer(out, &hints, w, h)
+    }
+
+    fn draw_readme(&self, out: &mut impl Write, r: &Readme, w: usize, h: usize) -> Result<()> {
+        let title = self
+            .hub
+            .as_ref()
+            .and_then(|hub| hub.get(r.entry))
+            .map_or(String::new(), |e| format!(" README  {}", e.link));
+        put(out, &self.theme, 0, &title, Paint::Title, w)?;
+
+        let visible = h.saturating_sub(2);
+        let marker = r.link.map(|l| format!("[{}]", l + 1));
+        for (row, line) in r.doc.lines.iter().skip(r.scroll).take(visible).enumerate() {
+            let paint = match line.style {
+                Style::Heading => Paint::Heading,
+                Style::Code => Paint::Code,
+                Style::Quote | Style::Muted => Paint::Muted,
+                Style::Text => Paint::Text,
+            };
+            let paint = match &marker {
+                Some(m) if line.text.contains(m.as_str()) => Paint::Accent,
+                _ => paint,
+            };
+            put(
+                out,
+                &self.theme,
+                1 + row,
+                &format!(" {}", line.text),
+                paint,
+                w,
+            )?;
+        }
+
+        let hints = match r.link {
+            Some(l) => format!(
+                "[{}/{}] {}  {}",
+                l + 1,
+                r.doc.links.len(),
+                r.doc.links[l],
+                self.keymap.hints(&[
+                    (&[Action::OpenLink], "open"),
+                    (&[Action::NextLink, Action::PrevLink], "next/prev"),
+                    (&[Action::Back], "back"),
+                ])
+            ),
+            None => self.keymap.hints(&[
+                (&[Action::Down, Action::Up], "scroll"),
+                (&[Action::NextLink, Action::PrevLink], "select link"),
+                (&[Action::OpenLink], "open"),
+                (&[Action::Install], "install"),
+                (&[Action::Back], "back"),
+            ]),
+        };
+        self.footer(out, &hints, w, h)
+    }
+
+    fn draw_search(&self, out: &mut impl Write, s: &Search, w: usize, h: usize) -> Result<()> {
+        let title = format!(" Search  {}", s.query);
+        put(out, &self.theme, 0, &title, Paint::Title, w)?;
+        let visible = h.saturating_sub(2);
+        for (row, m) in s.results.iter().skip(s.scroll).take(visible).enumerate() {
+            let linked = m.target.as_deref().unwrap_or("-");
+            let text = format!(
+                " {:<20} {:<10} {}:{}  {}",
+                m.repo, linked, m.file, m.line, m.text
+            );
+            let paint = if m.target.is_some() {
+                Paint::Accent
+            } else {
+                Paint::Text
+            };
+            put(out, &self.theme, 1 + row, &text, paint, w)?;
+        }
+        if s.results.is_empty() {
+            put(out, &self.theme, 1, " No matches.", Paint::Muted, w)?;
+        }
+        let hints = self.keymap.hints(&[
+            (&[Action::Down, Action::Up], "scroll"),
+            (&[Action::Search], "new search"),
+            (&[Action::Back], "back"),
+        ]);
+        self.footer(out, &hints, w, h)
+    }
+
+    fn draw_conflict(&self, out: &mut impl Write, c: &Conflict, w: usize, h: usize) -> Result<()> {
+        let mut body = vec![
+            (
+                format!("{} already exists and was not", c.target.display()),
+                Paint::Text,
+            ),
+            ("deployed by dothub.".to_string(), Paint::Text),
+            (String::new(), Paint::Text),
+        ];
+        body.extend(
+            CONFLICT_CHOICES
+                .iter()
+                .map(|c| (c.to_string(), Paint::Accent)),
+        );
+        if let Some(details) = &c.details {
+            body.push((String::new(), Paint::Text));
+            body.extend(
+                details
+                    .iter()
+                    .skip(c.scroll)
+                    .map(|d| (d.clone(), Paint::Text)),
+            );
+        }
+        let title = format!(" Link {} to {} ", c.repo, c.target_name);
+        self.draw_box(out, &title, &body, w, h)
+    }
+
+    fn draw_batch(&self, out: &mut impl Write, b: &Batch, w: usize, h: usize) -> Result<()> {
+        let running = b.items.iter().position(|(_, r)| r.is_none());
+        let mut body: Vec<(String, Paint)> = b
+            .items
+            .iter()
+            .enumerate()
+            .map(|(i, (item, result))| match result {
+                Some(Ok(detail)) => (format!("✓ {}  {}", item, detail), Paint::Accent),
+                Some(Err(e)) => (format!("✗ {}  {}", item, e), Paint::Error),
+                None if Some(i) == running => (format!("… {}", item), Paint::Text),
+                None => (format!("  {}", item), Paint::Muted),
+            })
+            .collect();
+        // Keep the running item in view when the batch is taller than the pane
+        let room = h.saturating_sub(8).max(1);
+        let end = running.map_or(body.len(), |r| (r + 1).max(room).min(body.len()));
+        body.drain(..end.saturating_sub(room));
+        body.truncate(room);
+        body.push((String::new(), Paint::Text));
+        if b.done {
+            let failed = b
+                .items
+                .iter()
+                .filter(|(_, r)| matches!(r, Some(Err(_))))
+                .count();
+            let summary = format!(
+                "Done: {} succeeded, {} failed. Press any key.",
+                b.items.len() - failed,
+                failed
+            );
+            body.push((summary, Paint::Muted));
+        } else {
+            body.push(("Working…".to_string(), Paint::Muted));
+        }
+        self.draw_box(out, &format!(" {} ", b.title), &body, w, h)
+    }
+
+    /// A centered box with a title bar over whatever is on screen.
+    fn draw_box(
+        &self,
+        out: &mut impl Write,
+        title: &str,
+        body: &[(String, Paint)],
+        w: usize,
+        h: usize,
+    ) -> Result<()> {
+        let width = w.saturating_sub(4).min(72);
+        let height = (body.len() + 3).min(h.saturating_sub(2)).max(1);
+        let (left, top) = ((w - width) / 2, (h - height) / 2);
+        put_at(out, &self.theme, left, top, title, Paint::Title, width)?;
+        let blank = (String::new(), Paint::Text);
+        let lines = std::iter::once(&blank)
+            .chain(body)
+            .chain(std::iter::once(&blank));
+        for (i, (line, paint)) in lines.take(height - 1).enumerate() {
+            let text = format!("  {:<width$}", line, width = width);
+            put_at(out, &self.theme, left, top + 1 + i, &text, *paint, width)?;
+        }
+        Ok(())
+    }
+
+    fn footer(&self, out: &mut impl Write, hints: &str, w: usize, h: usize) -> Result<()> {
+        if h < 2 {
+            return Ok(());
+        }
+        if self.message.is_empty() {
+            put(out, &self.theme, h - 1, hints, Paint::Muted, w)
+        } else {
+            put(out, &self.theme, h - 1, &self.message, Paint::Accent, w)
+        }
+    }
+}
+
+fn put(
+    out: &mut impl Write,
+    theme: &Theme,
+    row: usize,
+    text: &str,
+    paint: Paint,
+    width: usize,
+) -> Result<()> {
+    put_at(out, theme, 0, row, text, paint, width)
+}
+
+fn put_at(
+    out: &mut impl Write,
+    theme: &Theme,
+    col: usize,
+    row: usize,
+    text: &str,
+    paint: Paint,
+    width: usize,
+) -> Result<()> {
+    let look = theme.look(paint);
+    let mut text: String = text.chars().take(width).collect();
+    if look.reverse {
+        // Fill the row so the highlight spans the full width
+        let len = text.chars().count();
+        text.push_str(&" ".repeat(width.saturating_sub(len)));
+    }
+    queue!(out, cursor::MoveTo(col as u16, row as u16))?;
+    if let Some(fg) = look.fg {
+        queue!(out, SetForegroundColor(fg))?;
+    }
+    if look.bold {
+        queue!(out, SetAttribute(Attribute::Bold))?;
+    }
+    if look.reverse {
+        queue!(out, SetAttribute(Attribute::Reverse))?;
+    }
+    queue!(out, Print(text), ResetColor, SetAttribute(Attribute::Reset))?;
+    Ok(())
+}
+
+/// Rows available for content: the terminal height minus title and footer.
+fn page_size() -> usize {
+    terminal::size()
+        .map(|(_, h)| (h as usize).saturating_sub(2))
+        .unwrap_or(20)
+        .max(1)
+}
+
+fn content_width() -> usize {
+    terminal::size()
+        .map(|(w, _)| (w as usize).saturating_sub(2))
+        .unwrap_or(78)
+}
+
+/// Open a URL with the desktop's default handler.
+pub fn open_url(url: &str) -> Result<()> {
+    let (program, args): (&str, Vec<&str>) = if cfg!(target_os = "macos") {
+        ("open", vec![url])
+    } else if cfg!(windows) {
+        ("cmd", vec!["/C", "start", "", url])
+    } else {
+        ("xdg-open", vec![url])
+    };
+    Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .with_context(|| format!("Failed to run {} to open {}", program, url))?;
+    Ok(())
+}
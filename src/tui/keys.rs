@@ -0,0 +1,198 @@
+//! Dashboard keybindings: vim-style defaults, replaceable per action from
+//! `[tui.keys]`.
+
+use crate::config::TuiConfig;
+use anyhow::{bail, Result};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Top,
+    Bottom,
+    NextTab,
+    InstalledTab,
+    HubTab,
+    Open,
+    Install,
+    Link,
+    Mark,
+    Update,
+    Unlink,
+    Refresh,
+    NextLink,
+    PrevLink,
+    OpenLink,
+    Search,
+    Back,
+    Quit,
+}
+
+/// Config name and default keys for every action, in matching order.
+const DEFAULTS: &[(&str, Action, &[&str])] = &[
+    ("up", Action::Up, &["k", "up"]),
+    ("down", Action::Down, &["j", "down"]),
+    ("page_up", Action::PageUp, &["pageup", "ctrl-b"]),
+    ("page_down", Action::PageDown, &["pagedown", "ctrl-f"]),
+    ("top", Action::Top, &["g", "home"]),
+    ("bottom", Action::Bottom, &["G", "end"]),
+    ("next_tab", Action::NextTab, &["tab", "backtab"]),
+    ("installed_tab", Action::InstalledTab, &["1"]),
+    ("hub_tab", Action::HubTab, &["2"]),
+    ("open", Action::Open, &["enter", "l"]),
+    ("install", Action::Install, &["i"]),
+    ("link", Action::Link, &["L"]),
+    ("mark", Action::Mark, &["space"]),
+    ("update", Action::Update, &["u"]),
+    ("unlink", Action::Unlink, &["x"]),
+    ("refresh", Action::Refresh, &["r"]),
+    ("next_link", Action::NextLink, &["n"]),
+    ("prev_link", Action::PrevLink, &["N"]),
+    ("open_link", Action::OpenLink, &["o"]),
+    ("search", Action::Search, &["/"]),
+    ("back", Action::Back, &["esc", "h", "backspace"]),
+    ("quit", Action::Quit, &["q"]),
+];
+
+struct Binding {
+    action: Action,
+    keys: Vec<(KeyCode, KeyModifiers)>,
+    /// Key names as configured, for the footer hints
+    names: Vec<String>,
+}
+
+pub struct Keymap {
+    bindings: Vec<Binding>,
+}
+
+impl Keymap {
+    pub fn from_config(cfg: &TuiConfig) -> Result<Keymap> {
+        for action in cfg.keys.keys() {
+            if !DEFAULTS.iter().any(|(name, _, _)| name == action) {
+                let names: Vec<&str> = DEFAULTS.iter().map(|(n, _, _)| *n).collect();
+                bail!(
+                    "Unknown action '{}' in [tui.keys]; expected one of {}",
+                    action,
+                    names.join(", ")
+                );
+            }
+        }
+        let mut bindings = Vec::new();
+        for (name, action, defaults) in DEFAULTS {
+            let names: Vec<String> = match cfg.keys.get(*name) {
+                Some(keys) => keys.clone(),
+                None => defaults.iter().map(|k| k.to_string()).collect(),
+            };
+            let mut keys = Vec::new();
+            for key in &names {
+                let Some(parsed) = parse_key(key) else {
+                    bail!("Invalid key '{}' for tui.keys.{}", key, name);
+                };
+                keys.push(parsed);
+            }
+            bindings.push(Binding {
+                action: *action,
+                keys,
+                names,
+            });
+        }
+        Ok(Keymap { bindings })
+    }
+
+    /// The action bound to a key press, if any. Ctrl-C always quits.
+    pub fn action(&self, key: &KeyEvent) -> Option<Action> {
+        if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+            return Some(Action::Quit);
+        }
+        // Terminals disagree on whether shifted letters carry SHIFT; ignore it
+        let mods = key.modifiers - KeyModifiers::SHIFT;
+        self.bindings
+            .iter()
+            .find(|b| {
+                b.keys
+                    .iter()
+                    .any(|(code, m)| *code == key.code && *m == mods)
+            })
+            .map(|b| b.action)
+    }
+
+    /// Footer text such as "j/k move  enter readme", from the first key of each action.
+    pub fn hints(&self, items: &[(&[Action], &str)]) -> String {
+        items
+            .iter()
+            .filter_map(|(actions, label)| {
+                let keys: Vec<&str> = actions.iter().filter_map(|a| self.first_key(*a)).collect();
+                (!keys.is_empty()).then(|| format!("{} {}", keys.join("/"), label))
+            })
+            .collect::<Vec<_>>()
+            .join("  ")
+    }
+
+    fn first_key(&self, action: Action) -> Option<&str> {
+        self.bindings
+            .iter()
+            .find(|b| b.action == action)
+            .and_then(|b| b.names.first())
+            .map(|s| s.as_str())
+    }
+}
+
+/// `spec` after the first of `prefixes` it starts with, in any case. Only
+/// ASCII is compared, so the rest is always cut at a character boundary.
+fn strip_modifier<'a>(spec: &'a str, prefixes: &[&str]) -> Option<&'a str> {
+    prefixes.iter().find_map(|prefix| {
+        let head = spec.get(..prefix.len())?;
+        head.eq_ignore_ascii_case(prefix)
+            .then(|| &spec[prefix.len()..])
+    })
+}
+
+/// Parse "j", "G", "enter", "ctrl-d", "alt-x", "f5" and similar.
+fn parse_key(spec: &str) -> Option<(KeyCode, KeyModifiers)> {
+    let mut mods = KeyModifiers::NONE;
+    let mut rest = spec;
+    loop {
+        if let Some(r) = strip_modifier(rest, &["ctrl-", "c-"]) {
+            mods |= KeyModifiers::CONTROL;
+            rest = r;
+        } else if let Some(r) = strip_modifier(rest, &["alt-", "m-"]) {
+            mods |= KeyModifiers::ALT;
+            rest = r;
+        } else {
+            break;
+        }
+    }
+    let mut chars = rest.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        // Terminals report ctrl-letter combinations as lowercase
+        let c = if mods.contains(KeyModifiers::CONTROL) {
+            c.to_ascii_lowercase()
+        } else {
+            c
+        };
+        return Some((KeyCode::Char(c), mods));
+    }
+    let code = match rest.to_lowercase().as_str() {
+        "enter" | "return" => KeyCode::Enter,
+        "esc" | "escape" => KeyCode::Esc,
+        "tab" => KeyCode::Tab,
+        "backtab" => KeyCode::BackTab,
+        "space" => KeyCode::Char(' '),
+        "backspace" => KeyCode::Backspace,
+        "delete" | "del" => KeyCode::Delete,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        f if f.starts_with('f') => KeyCode::F(f[1..].parse().ok()?),
+        _ => return None,
+    };
+    Some((code, mods))
+}
@@ -0,0 +1,150 @@
+//! Dashboard colors: a built-in dark or light palette plus per-role overrides
+//! from `[tui.colors]`.
+
+use crate::config::{ThemeMode, TuiConfig};
+use anyhow::{bail, Result};
+use crossterm::style::Color;
+use std::env;
+
+/// What a piece of text on screen is, which decides how it is drawn.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Paint {
+    Text,
+    Title,
+    Selected,
+    Muted,
+    Heading,
+    Code,
+    Accent,
+    Error,
+}
+
+impl Paint {
+    const ALL: [Paint; 8] = [
+        Paint::Text,
+        Paint::Title,
+        Paint::Selected,
+        Paint::Muted,
+        Paint::Heading,
+        Paint::Code,
+        Paint::Accent,
+        Paint::Error,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Paint::Text => "text",
+            Paint::Title => "title",
+            Paint::Selected => "selected",
+            Paint::Muted => "muted",
+            Paint::Heading => "heading",
+            Paint::Code => "code",
+            Paint::Accent => "accent",
+            Paint::Error => "error",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct Look {
+    pub fg: Option<Color>,
+    pub bold: bool,
+    /// Swap foreground and background, used for bars and the selection
+    pub reverse: bool,
+}
+
+pub struct Theme {
+    looks: [Look; 8],
+}
+
+impl Theme {
+    pub fn from_config(cfg: &TuiConfig) -> Result<Theme> {
+        let light = match cfg.theme {
+            ThemeMode::Auto => light_background(),
+            ThemeMode::Dark => false,
+            ThemeMode::Light => true,
+        };
+        let mut looks = Paint::ALL.map(|p| default_look(p, light));
+        for (role, value) in &cfg.colors {
+            let Some(idx) = Paint::ALL.iter().position(|p| p.name() == role) else {
+                let roles: Vec<&str> = Paint::ALL.iter().map(|p| p.name()).collect();
+                bail!(
+                    "Unknown color role '{}' in [tui.colors]; expected one of {}",
+                    role,
+                    roles.join(", ")
+                );
+            };
+            let Some(color) = parse_color(value) else {
+                bail!("Invalid color '{}' for tui.colors.{}", value, role);
+            };
+            looks[idx].fg = Some(color);
+        }
+        Ok(Theme { looks })
+    }
+
+    pub fn look(&self, paint: Paint) -> Look {
+        self.looks[paint as usize]
+    }
+}
+
+fn default_look(paint: Paint, light: bool) -> Look {
+    let fg = |dark: Color, on_light: Color| Some(if light { on_light } else { dark });
+    match paint {
+        Paint::Text => Look::default(),
+        Paint::Title | Paint::Selected => Look {
+            reverse: true,
+            ..Look::default()
+        },
+        Paint::Muted => Look {
+            fg: fg(Color::DarkGrey, Color::Grey),
+            ..Look::default()
+        },
+        Paint::Heading => Look {
+            fg: fg(Color::Cyan, Color::DarkBlue),
+            bold: true,
+            ..Look::default()
+        },
+        Paint::Code => Look {
+            fg: fg(Color::Yellow, Color::DarkMagenta),
+            ..Look::default()
+        },
+        Paint::Accent => Look {
+            fg: fg(Color::Green, Color::DarkGreen),
+            ..Look::default()
+        },
+        Paint::Error => Look {
+            fg: fg(Color::Red, Color::DarkRed),
+            ..Look::default()
+        },
+    }
+}
+
+/// COLORFGBG ("fg;bg", set by rxvt, Konsole and others) names the background
+/// color; 7 and 15 are light. Without it we assume a dark terminal.
+fn light_background() -> bool {
+    env::var("COLORFGBG")
+        .ok()
+        .and_then(|v| v.rsplit(';').next().and_then(|bg| bg.parse::<u8>().ok()))
+        .is_some_and(|bg| bg == 7 || bg == 15)
+}
+
+/// Color names ("dark_grey", "cyan"), ANSI numbers ("208") or hex ("#50fa7b").
+fn parse_color(value: &str) -> Option<Color> {
+    let value = value.trim();
+    if let Some(hex) = value.strip_prefix('#') {
+        // Checked before slicing, which would panic inside a multibyte character
+        if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+        return Some(Color::Rgb {
+            r: channel(0)?,
+            g: channel(2)?,
+            b: channel(4)?,
+        });
+    }
+    if let Ok(n) = value.parse::<u8>() {
+        return Some(Color::AnsiValue(n));
+    }
+    Color::try_from(value.replace(['-', ' '], "_").as_str()).ok()
+}
@@ -0,0 +1,160 @@
+//! systemd user units shipped by config repos.
+//!
+//! A repo lists its units in `dothub.toml` (`units = ["systemd/wallpaper.service"]`).
+//! Linking the repo links each unit into ~/.config/systemd/user, reloads the
+//! user manager and enables and starts the unit; unlinking stops, disables and
+//! removes them again.
+
+use crate::manifest::RepoManifest;
+use crate::output::{self, Tone};
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn unit_dir() -> Result<PathBuf> {
+    let config = dirs::config_dir().context("Unable to determine config directory")?;
+    Ok(config.join("systemd").join("user"))
+}
+
+/// Link, enable and start the units declared by repo `name`. Returns the unit
+/// names, to be recorded with the deployment.
+pub fn install(name: &str) -> Result<Vec<String>> {
+    let repo = crate::dothub_dir().join(name);
+    let Some(manifest) = RepoManifest::load(&repo)? else {
+        return Ok(Vec::new());
+    };
+    if manifest.units.is_empty() {
+        return Ok(Vec::new());
+    }
+    if crate::vfs::dry_run() {
+        crate::vfs::note(format!("start the units of {}", name));
+        return Ok(Vec::new());
+    }
+    // Units run the repo's programs, so they need the same trust as hooks
+    let shown: Vec<String> = manifest
+        .units
+        .iter()
+        .map(|unit| match fs::read_to_string(repo.join(unit)) {
+            Ok(text) => format!("# {}\n{}", unit, text.trim_end()),
+            Err(_) => format!("# {} (missing)", unit),
+        })
+        .collect();
+    if crate::targets::is_termux() {
+        println!(
+            "{}",
+            output::paint("Skipped the repo's systemd units: Termux has no systemd", Tone::Warn)
+        );
+        return Ok(Vec::new());
+    }
+    if !crate::trust::allow(name, "its systemd units", &shown.join("\n"))? {
+        return Ok(Vec::new());
+    }
+    let dir = unit_dir()?;
+    fs::create_dir_all(&dir).with_context(|| format!("Failed creating {}", dir.display()))?;
+
+    let mut names = Vec::new();
+    for unit in &manifest.units {
+        let source = repo.join(unit);
+        let Some(file_name) = source.file_name().map(|n| n.to_string_lossy().to_string()) else {
+            bail!("{} in dothub.toml is not a unit file", unit);
+        };
+        if !source.is_file() {
+            bail!("Unit {} declared in dothub.toml does not exist", source.display());
+        }
+        let link = dir.join(&file_name);
+        crate::protect::check(&link)?;
+        if fs::symlink_metadata(&link).is_ok() {
+            if !points_into_store(&link) {
+                println!(
+                    "{}",
+                    output::paint(
+                        &format!("Skipped {}; a unit of that name already exists", file_name),
+                        Tone::Warn
+                    )
+                );
+                continue;
+            }
+            fs::remove_file(&link).with_context(|| format!("Removing {}", link.display()))?;
+        }
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(&source, &link)
+            .with_context(|| format!("Failed creating symlink {}", link.display()))?;
+        #[cfg(not(unix))]
+        bail!("systemd units are only supported on Linux");
+        names.push(file_name);
+    }
+    if names.is_empty() {
+        return Ok(names);
+    }
+
+    // The units are in place either way; starting them is best effort
+    let reloaded = which::which("systemctl")
+        .context("systemctl not found")
+        .and_then(|_| systemctl(&["daemon-reload"]));
+    if let Err(e) = reloaded {
+        println!(
+            "{}",
+            output::paint(
+                &format!("Units were linked but not started: {:#}", e),
+                Tone::Warn
+            )
+        );
+        return Ok(names);
+    }
+    for unit in &names {
+        match systemctl(&["enable", "--now", unit]) {
+            Ok(()) => println!("Started {}", unit),
+            Err(e) => println!("{}", output::paint(&format!("{:#}", e), Tone::Warn)),
+        }
+    }
+    Ok(names)
+}
+
+/// Stop, disable and remove units installed for a deployment.
+pub fn remove(names: &[String]) -> Result<()> {
+    if names.is_empty() {
+        return Ok(());
+    }
+    if crate::vfs::dry_run() {
+        crate::vfs::note(format!("stop and remove {}", names.join(", ")));
+        return Ok(());
+    }
+    let dir = unit_dir()?;
+    let have_systemctl = which::which("systemctl").is_ok();
+    for unit in names {
+        if have_systemctl {
+            // A unit that was never started is fine to remove regardless
+            let _ = systemctl(&["disable", "--now", unit]);
+        }
+        let link = dir.join(unit);
+        if points_into_store(&link) {
+            fs::remove_file(&link).with_context(|| format!("Removing {}", link.display()))?;
+            println!("Removed {}", unit);
+        }
+    }
+    if have_systemctl {
+        let _ = systemctl(&["daemon-reload"]);
+    }
+    Ok(())
+}
+
+fn points_into_store(link: &Path) -> bool {
+    fs::read_link(link).is_ok_and(|dest| dest.starts_with(crate::dothub_dir()))
+}
+
+fn systemctl(args: &[&str]) -> Result<()> {
+    let out = Command::new("systemctl")
+        .arg("--user")
+        .args(args)
+        .output()
+        .context("Running systemctl")?;
+    if !out.status.success() {
+        bail!(
+            "systemctl --user {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&out.stderr).trim()
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,150 @@
+//! When your configs are actually loaded, kept on this machine only.
+//!
+//! Off until `track = true` under `[usage]` in the config. Then the
+//! `shell-init` script appends a line to `usage.log` next to the state file
+//! each time zsh, bash, fish or PowerShell starts, and a stub in nvim's site
+//! directory does the same for nvim (under `$NVIM_APPNAME`, so each nvim
+//! config counts on its own). `dothub stats` shows when each repo was last
+//! loaded and which deployed targets haven't been in six months. Nothing is
+//! sent anywhere; turning tracking off removes the stub.
+
+use crate::config::Config;
+use crate::Shell;
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+/// How long a config may go unloaded before `dothub stats` points it out.
+pub const STALE: u64 = 182 * 24 * 60 * 60;
+
+/// The log is rewritten with only the latest line per target past this size.
+const MAX_LOG: u64 = 256 * 1024;
+
+/// Shells whose startup is recorded, by their target name.
+const SHELLS: &[(Shell, &str)] = &[
+    (Shell::Zsh, "zsh"),
+    (Shell::Bash, "bash"),
+    (Shell::Fish, "fish"),
+    (Shell::PowerShell, "powershell"),
+];
+
+/// `<unix time> <target>` per load, after a `# since <unix time>` line.
+pub fn log_path() -> PathBuf {
+    crate::state::state_path().with_file_name("usage.log")
+}
+
+pub fn enabled() -> bool {
+    Config::load().is_ok_and(|c| c.usage.track)
+}
+
+/// nvim loads every file in this directory at startup.
+fn nvim_stub() -> Option<PathBuf> {
+    let data = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| dirs::home_dir().map(|h| h.join(".local/share")))?;
+    Some(data.join("nvim/site/plugin/dothub-usage.lua"))
+}
+
+/// Install the nvim stub and start the log when tracking is on; remove the
+/// stub when it is off. The log is kept either way.
+pub fn sync() -> Result<()> {
+    let Some(stub) = nvim_stub() else {
+        return Ok(());
+    };
+    if !enabled() {
+        if stub.exists() {
+            fs::remove_file(&stub).with_context(|| format!("Removing {}", stub.display()))?;
+        }
+        return Ok(());
+    }
+    let log = log_path();
+    if !log.exists() {
+        if let Some(dir) = log.parent() {
+            fs::create_dir_all(dir).with_context(|| format!("Creating {}", dir.display()))?;
+        }
+        fs::write(&log, format!("# since {}\n", crate::state::now_unix()))
+            .with_context(|| format!("Writing {}", log.display()))?;
+    }
+    let lua = format!(
+        "-- Written by dothub while [usage] track is on; records when this config loads\n\
+         local f = io.open([[{}]], \"a\")\n\
+         if f then\n  f:write(os.time(), \" \", vim.env.NVIM_APPNAME or \"nvim\", \"\\n\")\n  f:close()\nend\n",
+        log.display()
+    );
+    if fs::read_to_string(&stub).ok().as_deref() != Some(lua.as_str()) {
+        if let Some(dir) = stub.parent() {
+            fs::create_dir_all(dir).with_context(|| format!("Creating {}", dir.display()))?;
+        }
+        fs::write(&stub, lua).with_context(|| format!("Writing {}", stub.display()))?;
+    }
+    Ok(())
+}
+
+/// The line `shell-init` adds for `shell` while tracking is on.
+pub fn shell_hook(shell: Shell) -> Option<String> {
+    if !enabled() {
+        return None;
+    }
+    let (_, target) = SHELLS.iter().find(|(s, _)| *s == shell)?;
+    let log = log_path().to_string_lossy().to_string();
+    Some(match shell {
+        Shell::Fish => format!(
+            "printf '%s {}\\n' (date +%s) >> '{}'\n",
+            target,
+            log.replace('\'', "\\'")
+        ),
+        Shell::PowerShell => format!(
+            "Add-Content -Path '{}' -Value \"$([DateTimeOffset]::UtcNow.ToUnixTimeSeconds()) {}\"\n",
+            log.replace('\'', "''"),
+            target
+        ),
+        _ => format!(
+            "printf '%s {}\\n' \"$(date +%s)\" >> '{}'\n",
+            target,
+            log.replace('\'', "'\\''")
+        ),
+    })
+}
+
+/// Whether loads of `target_name` get recorded at all.
+pub fn trackable(target_name: &str) -> bool {
+    SHELLS.iter().any(|(_, t)| *t == target_name) || target_name.starts_with("nvim")
+}
+
+/// What the log says: when tracking began, and the last load of each target.
+pub struct Loads {
+    pub since: u64,
+    pub last: BTreeMap<String, u64>,
+}
+
+/// Read the log, compacting it when it has grown large.
+pub fn read() -> Option<Loads> {
+    let log = log_path();
+    let text = fs::read_to_string(&log).ok()?;
+    let mut since = None;
+    let mut last = BTreeMap::new();
+    for line in text.lines() {
+        if let Some(at) = line.strip_prefix("# since ") {
+            since = at.trim().parse().ok();
+            continue;
+        }
+        let Some((at, target)) = line.split_once(' ') else {
+            continue;
+        };
+        let Ok(at) = at.parse::<u64>() else {
+            continue;
+        };
+        let entry = last.entry(target.trim().to_string()).or_insert(0);
+        *entry = (*entry).max(at);
+    }
+    let since = since.or_else(|| last.values().min().copied())?;
+    if fs::metadata(&log).is_ok_and(|md| md.len() > MAX_LOG) {
+        let mut compact = format!("# since {}\n", since);
+        for (target, at) in &last {
+            compact.push_str(&format!("{} {}\n", at, target));
+        }
+        let _ = fs::write(&log, compact);
+    }
+    Some(Loads { since, last })
+}
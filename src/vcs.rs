@@ -0,0 +1,337 @@
+//! The tools stored repos are fetched and updated with.
+//!
+//! Each kind of [`RepoSource`] has a [`Backend`]: git, Mercurial, jujutsu
+//! (cloned colocated, so the store keeps a .git that status, blame and the
+//! rest read), tarballs and local directories. `dothub install` picks one from
+//! what it is given: a URL starting with `hg+`, `jj+` or `tar+` goes to
+//! Mercurial, jujutsu or the tarball backend, a URL ending in an archive
+//! extension is a tarball, and anything else is cloned with git. A hub entry
+//! can name its backend with `vcs:`, which adds the prefix for it.
+
+use crate::state::RepoSource;
+use anyhow::{bail, Context, Result};
+use std::path::Path;
+use std::process::Command;
+
+/// A backend a hub entry or `install --vcs` can ask for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Vcs {
+    Git,
+    /// Mercurial
+    Hg,
+    /// jujutsu, cloned colocated with git
+    Jj,
+    /// A tarball or zip, downloaded again on every update
+    Tarball,
+}
+
+impl Vcs {
+    fn prefix(self) -> &'static str {
+        match self {
+            Vcs::Git => "git+",
+            Vcs::Hg => "hg+",
+            Vcs::Jj => "jj+",
+            Vcs::Tarball => "tar+",
+        }
+    }
+}
+
+/// The backend a `git+`, `hg+`, `jj+` or `tar+` prefix asks for, and the URL
+/// without it.
+pub fn split(repo: &str) -> (Option<Vcs>, &str) {
+    for vcs in [Vcs::Git, Vcs::Hg, Vcs::Jj, Vcs::Tarball] {
+        if let Some(url) = repo.strip_prefix(vcs.prefix()) {
+            return (Some(vcs), url);
+        }
+    }
+    (None, repo)
+}
+
+/// `repo` with the prefix asking for `vcs`, unless it already has one.
+pub fn qualify(repo: &str, vcs: Option<Vcs>) -> String {
+    match vcs {
+        Some(vcs) if split(repo).0.is_none() => format!("{}{}", vcs.prefix(), repo),
+        _ => repo.to_string(),
+    }
+}
+
+/// What an update did.
+pub enum Update {
+    /// Something changed; `range` is the commits before and after, for
+    /// backends with git history
+    Updated {
+        detail: String,
+        range: Option<(String, String)>,
+    },
+    UpToDate,
+    Skipped {
+        status: &'static str,
+        detail: String,
+    },
+}
+
+impl Update {
+    fn skipped(status: &'static str, detail: impl Into<String>) -> Update {
+        Update::Skipped {
+            status,
+            detail: detail.into(),
+        }
+    }
+
+    /// Compare revisions from before and after pulling.
+    fn between(before: Option<String>, after: Option<String>) -> Update {
+        if before == after {
+            return Update::UpToDate;
+        }
+        let detail = format!(
+            "{} -> {}",
+            crate::short_sha(before.as_deref()),
+            crate::short_sha(after.as_deref())
+        );
+        Update::Updated {
+            detail,
+            range: before.zip(after),
+        }
+    }
+}
+
+pub trait Backend {
+    /// Put the repo at `dest`, which must not exist yet. With `progress`,
+    /// steps and the tool's own progress are shown on the terminal.
+    fn fetch(&self, dest: &Path, progress: bool) -> Result<()>;
+    /// Bring the repo at `path` up to date.
+    fn update(&self, path: &Path) -> Result<Update>;
+}
+
+/// The backend for `source`.
+pub fn backend(source: &RepoSource) -> Box<dyn Backend + '_> {
+    match source {
+        RepoSource::Git { url } => Box::new(Git { url }),
+        RepoSource::Hg { url } => Box::new(Mercurial { url }),
+        RepoSource::Jj { url } => Box::new(Jujutsu { url }),
+        RepoSource::Local { .. } | RepoSource::Archive { .. } => Box::new(Files { source }),
+    }
+}
+
+struct Git<'a> {
+    url: &'a str,
+}
+
+impl Backend for Git<'_> {
+    fn fetch(&self, dest: &Path, progress: bool) -> Result<()> {
+        crate::git::ensure_git()?;
+        let url = self.url;
+        let local = crate::is_local(url);
+        let mirror = if local {
+            None
+        } else {
+            crate::mirror::for_clone(url)
+        };
+        if crate::http::is_offline() && !local && mirror.is_none() {
+            bail!("Offline: only local repositories can be installed ({} is remote)", url);
+        }
+        let from = mirror
+            .as_ref()
+            .map_or(url.to_string(), |m| m.to_string_lossy().to_string());
+
+        let dest_arg = dest.to_string_lossy();
+        if progress {
+            println!(
+                "{}",
+                crate::i18n::t("cloning", &[("url", &url), ("dest", &dest.display())])
+            );
+            crate::git::network(Path::new("."), &["clone", &from, dest_arg.as_ref()], true)?;
+        } else {
+            crate::git::network(
+                Path::new("."),
+                &["clone", "-q", &from, dest_arg.as_ref()],
+                false,
+            )?;
+        }
+        if mirror.is_some() {
+            crate::git::output(dest, &["remote", "set-url", "origin", url])?;
+        }
+        crate::lfs::after_install(dest);
+        Ok(())
+    }
+
+    fn update(&self, path: &Path) -> Result<Update> {
+        if !crate::git::is_repo(path) {
+            return Ok(Update::skipped("skipped", "not a git repository"));
+        }
+        if crate::http::is_offline() {
+            return Ok(Update::skipped("skipped", "offline"));
+        }
+        crate::git::ensure_git()?;
+        // Fetches, then fast-forwards unless upstream was renamed or rewritten
+        let update = crate::recover::pull(path)?;
+        Ok(crate::lfs::after_update(path, update))
+    }
+}
+
+struct Mercurial<'a> {
+    url: &'a str,
+}
+
+impl Backend for Mercurial<'_> {
+    fn fetch(&self, dest: &Path, progress: bool) -> Result<()> {
+        ensure("hg", "Mercurial")?;
+        if crate::http::is_offline() && !crate::is_local(self.url) {
+            bail!("Offline: only local repositories can be installed ({} is remote)", self.url);
+        }
+        if progress {
+            println!("Cloning {} -> {} with Mercurial", self.url, dest.display());
+        }
+        let dest = dest.to_string_lossy();
+        run(Path::new("."), "hg", &["clone", "-q", self.url, &dest])?;
+        Ok(())
+    }
+
+    fn update(&self, path: &Path) -> Result<Update> {
+        if !path.join(".hg").is_dir() {
+            return Ok(Update::skipped("skipped", "not a Mercurial repository"));
+        }
+        if crate::http::is_offline() {
+            return Ok(Update::skipped("skipped", "offline"));
+        }
+        let revision = || run(path, "hg", &["log", "-r", ".", "-T", "{node}"]).ok();
+        let before = revision();
+        run(path, "hg", &["pull", "-u", "-q"])?;
+        // The range is for git log, which can't read Mercurial history
+        Ok(match Update::between(before, revision()) {
+            Update::Updated { detail, .. } => Update::Updated {
+                detail,
+                range: None,
+            },
+            update => update,
+        })
+    }
+}
+
+struct Jujutsu<'a> {
+    url: &'a str,
+}
+
+impl Backend for Jujutsu<'_> {
+    fn fetch(&self, dest: &Path, progress: bool) -> Result<()> {
+        ensure("jj", "jujutsu")?;
+        if crate::http::is_offline() && !crate::is_local(self.url) {
+            bail!("Offline: only local repositories can be installed ({} is remote)", self.url);
+        }
+        if progress {
+            println!("Cloning {} -> {} with jujutsu", self.url, dest.display());
+        }
+        let dest = dest.to_string_lossy();
+        run(
+            Path::new("."),
+            "jj",
+            &["git", "clone", "--colocate", self.url, &dest],
+        )?;
+        Ok(())
+    }
+
+    fn update(&self, path: &Path) -> Result<Update> {
+        if !path.join(".jj").is_dir() {
+            return Ok(Update::skipped("skipped", "not a jujutsu repository"));
+        }
+        if crate::http::is_offline() {
+            return Ok(Update::skipped("skipped", "offline"));
+        }
+        let trunk = || {
+            run(
+                path,
+                "jj",
+                &["log", "--no-graph", "-r", "trunk()", "-T", "commit_id"],
+            )
+            .ok()
+        };
+        let before = trunk();
+        run(path, "jj", &["git", "fetch"])?;
+        let after = trunk();
+        if before != after {
+            // Local changes move onto the new trunk, as git pull would leave them
+            run(path, "jj", &["rebase", "-d", "trunk()"])?;
+        }
+        Ok(Update::between(before, after))
+    }
+}
+
+/// Local directories and tarballs, which the store holds a link to or a
+/// copy of.
+struct Files<'a> {
+    source: &'a RepoSource,
+}
+
+impl Backend for Files<'_> {
+    fn fetch(&self, dest: &Path, progress: bool) -> Result<()> {
+        if progress {
+            match self.source {
+                RepoSource::Local { path, copy } => {
+                    let verb = if *copy { "Copying" } else { "Linking" };
+                    println!("{} {} -> {}", verb, path.display(), dest.display());
+                }
+                RepoSource::Archive { location } => {
+                    println!("Extracting {} -> {}", location, dest.display());
+                }
+                _ => {}
+            }
+        }
+        crate::source::install(self.source, dest)
+    }
+
+    fn update(&self, path: &Path) -> Result<Update> {
+        let detail = crate::source::refresh(self.source, path)?;
+        if matches!(self.source, RepoSource::Local { copy: false, .. }) {
+            return Ok(Update::skipped("live", detail));
+        }
+        Ok(Update::Updated {
+            detail,
+            range: None,
+        })
+    }
+}
+
+fn ensure(program: &str, name: &str) -> Result<()> {
+    if which::which(program).is_err() {
+        bail!(crate::error::Error::new(
+            crate::error::Code::ToolMissing,
+            format!("{} is not installed or not found in PATH", program)
+        )
+        .hint(format!("install {} with your package manager", name)));
+    }
+    Ok(())
+}
+
+/// Run `program` in `dir`, retrying failures that look like the network's,
+/// and return trimmed stdout.
+fn run(dir: &Path, program: &str, args: &[&str]) -> Result<String> {
+    let _span = crate::perf::span(crate::perf::Phase::Git, || match args.last() {
+        // Clones run outside the repo; name them after the destination
+        Some(dest) if args.contains(&"clone") => crate::perf::repo_subject(Path::new(dest)),
+        _ => crate::perf::repo_subject(dir),
+    });
+    let what = format!("{} {}", program, args.first().unwrap_or(&""));
+    crate::network::retry(&what, || {
+        let out = Command::new(program)
+            .current_dir(dir)
+            .args(args)
+            .output()
+            .with_context(|| format!("Running {} {} in {}", program, args.join(" "), dir.display()))?;
+        if !out.status.success() {
+            let stderr = String::from_utf8_lossy(&out.stderr);
+            let msg = format!(
+                "{} {} failed in {}: {}",
+                program,
+                args.join(" "),
+                dir.display(),
+                stderr.trim()
+            );
+            if crate::network::git_transient(&stderr) {
+                bail!(crate::network::Transient(msg));
+            }
+            bail!(msg);
+        }
+        Ok(String::from_utf8_lossy(&out.stdout).trim().to_string())
+    })
+}
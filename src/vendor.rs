@@ -0,0 +1,115 @@
+use crate::config::Config;
+use crate::git;
+use anyhow::{bail, Context, Result};
+use std::path::Path;
+
+/// Remote that keeps pointing at the original author's repo once a clone is vendored.
+const UPSTREAM_REMOTE: &str = "upstream";
+
+pub fn cmd_vendor(name: &str, url_override: Option<&str>) -> Result<()> {
+    git::ensure_git()?;
+    if crate::http::is_offline() {
+        bail!("Offline: vendoring needs network access to fetch and push");
+    }
+    let path = crate::dothub_dir().join(name);
+    if !git::is_repo(&path) {
+        bail!("Repository not found or not a git repo: {}", path.display());
+    }
+
+    let already_vendored = remote_url(&path, UPSTREAM_REMOTE).is_some();
+    let upstream = if already_vendored {
+        remote_url(&path, UPSTREAM_REMOTE)
+    } else {
+        remote_url(&path, "origin")
+    }
+    .with_context(|| format!("{} has no origin remote to vendor from", name))?;
+
+    let mirror = match url_override {
+        Some(u) => u.to_string(),
+        None => {
+            let template = Config::load()?.vendor.url_template.with_context(|| {
+                format!(
+                    "No mirror URL given. Pass --url or set vendor.url_template in {}",
+                    crate::config::config_path().display()
+                )
+            })?;
+            expand_template(&template, name, &upstream)
+        }
+    };
+
+    if !already_vendored {
+        git::output(&path, &["remote", "rename", "origin", UPSTREAM_REMOTE])?;
+        git::output(&path, &["remote", "add", "origin", &mirror])?;
+    } else if remote_url(&path, "origin").as_deref() != Some(mirror.as_str()) {
+        git::output(&path, &["remote", "set-url", "origin", &mirror])?;
+    }
+
+    println!("Fetching {}", upstream);
+    match git::network(&path, &["fetch", "--prune", "--tags", UPSTREAM_REMOTE], false) {
+        Ok(_) => {}
+        // A deleted upstream can still be mirrored from what was fetched before
+        Err(e) if crate::network::git_gone(&e.to_string()) => {
+            println!("{} is gone; mirroring the commits fetched from it before", upstream);
+        }
+        Err(e) => return Err(e),
+    }
+
+    // Push every upstream branch and tag. Pushes are never forced, so an upstream
+    // force-push is rejected instead of rewriting the mirror.
+    let branches = git::output(
+        &path,
+        &[
+            "for-each-ref",
+            "--format=%(refname:strip=3)",
+            &format!("refs/remotes/{}", UPSTREAM_REMOTE),
+        ],
+    )?;
+    let mut refspecs: Vec<String> = branches
+        .lines()
+        .filter(|b| !b.is_empty() && *b != "HEAD")
+        .map(|b| format!("refs/remotes/{}/{}:refs/heads/{}", UPSTREAM_REMOTE, b, b))
+        .collect();
+    if refspecs.is_empty() {
+        bail!("No branches found on {}", upstream);
+    }
+    refspecs.push("refs/tags/*:refs/tags/*".to_string());
+
+    println!("Pushing mirror to {}", mirror);
+    let mut args = vec!["push", "origin"];
+    args.extend(refspecs.iter().map(|s| s.as_str()));
+    git::network(&path, &args, false)?;
+
+    // Track the mirror so `dothub update` pulls from it
+    let branch = git::output(&path, &["symbolic-ref", "--short", "HEAD"])?;
+    git::network(&path, &["fetch", "origin"], false)?;
+    git::output(
+        &path,
+        &["branch", "--set-upstream-to", &format!("origin/{}", branch), &branch],
+    )?;
+
+    println!("Vendored {}: origin -> {}, upstream -> {}", name, mirror, upstream);
+    println!("Run `dothub vendor {}` again to sync new upstream commits into the mirror.", name);
+    Ok(())
+}
+
+fn remote_url(repo: &Path, remote: &str) -> Option<String> {
+    git::output(repo, &["remote", "get-url", remote]).ok()
+}
+
+fn expand_template(template: &str, name: &str, upstream: &str) -> String {
+    let (owner, repo) = owner_repo(upstream);
+    template
+        .replace("{name}", name)
+        .replace("{owner}", &owner)
+        .replace("{repo}", &repo)
+}
+
+/// Last two path segments of a git URL, handling both https and scp-like forms.
+fn owner_repo(url: &str) -> (String, String) {
+    let trimmed = url.trim_end_matches('/').trim_end_matches(".git");
+    let path = trimmed.rsplit_once(':').map(|(_, p)| p).unwrap_or(trimmed);
+    let mut segs = path.rsplit('/');
+    let repo = segs.next().unwrap_or("").to_string();
+    let owner = segs.next().unwrap_or("").to_string();
+    (owner, repo)
+}
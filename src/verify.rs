@@ -0,0 +1,189 @@
+use crate::{modes, owner};
+use crate::output::{self, Tone};
+use crate::state::{hash_file, DeployMode, LinkRecord, State};
+use anyhow::{bail, Result};
+use comfy_table::Cell;
+use std::fs;
+use std::path::Path;
+
+/// Something that changed on disk since dothub deployed a target.
+pub enum Problem {
+    Missing,
+    /// A symlink target was replaced by a real file or directory
+    Replaced,
+    /// The symlink now points somewhere other than the store
+    Repointed(String),
+    /// The symlink points into the store but the destination is gone
+    Broken,
+    /// A copied file was edited outside dothub
+    Modified(String),
+    /// A copied file was deleted outside dothub
+    Deleted(String),
+    /// A copied target was replaced by a symlink
+    NotACopy,
+    /// A `--system` deployment changed hands
+    Owner {
+        path: String,
+        expected: String,
+        actual: String,
+    },
+    /// A file's mode differs from the one in dothub.toml
+    Mode {
+        file: String,
+        expected: u32,
+        actual: u32,
+    },
+}
+
+impl Problem {
+    pub fn describe(&self) -> String {
+        match self {
+            Problem::Missing => "target is missing".to_string(),
+            Problem::Replaced => "symlink was replaced by a real file or directory".to_string(),
+            Problem::Repointed(to) => format!("symlink repointed to {}", to),
+            Problem::Broken => "symlink is broken (store path no longer exists)".to_string(),
+            Problem::Modified(f) => format!("{} modified outside dothub", f),
+            Problem::Deleted(f) => format!("{} deleted outside dothub", f),
+            Problem::NotACopy => "copied target was replaced by a symlink".to_string(),
+            Problem::Owner {
+                path,
+                expected,
+                actual,
+            } => format!("{} is owned by {}, deployed for {}", path, actual, expected),
+            Problem::Mode {
+                file,
+                expected,
+                actual,
+            } => format!("{} has mode {:o}, dothub.toml wants {:o}", file, actual, expected),
+        }
+    }
+}
+
+pub fn cmd_verify() -> Result<()> {
+    let state = State::load()?;
+    if state.links.is_empty() {
+        println!("No deployments recorded. Link something with dothub link first.");
+        return Ok(());
+    }
+
+    let mut problems = 0usize;
+    let mut table = output::table(["Target", "Status", "Detail"]);
+    for (target_name, record) in &state.links {
+        let found = check_record(record);
+        if found.is_empty() {
+            table.add_row(vec![
+                Cell::new(target_name),
+                output::cell("ok", Tone::Ok),
+                Cell::new(record.target.display()),
+            ]);
+            continue;
+        }
+        for p in found {
+            problems += 1;
+            table.add_row(vec![
+                Cell::new(target_name),
+                output::cell("FAIL", Tone::Error),
+                Cell::new(p.describe()),
+            ]);
+        }
+    }
+    println!("{}", table);
+
+    if problems > 0 {
+        bail!("{} problem(s) found", problems);
+    }
+    println!("All {} deployment(s) verified.", state.links.len());
+    Ok(())
+}
+
+pub fn check_record(record: &LinkRecord) -> Vec<Problem> {
+    let problems = check_deployment(record);
+    if !problems.is_empty() {
+        return problems;
+    }
+    if let Some(ids) = &record.owner {
+        if let Some((path, actual)) = owner::drift(&record.target, ids) {
+            return vec![Problem::Owner {
+                path: path.display().to_string(),
+                expected: ids.clone(),
+                actual,
+            }];
+        }
+    }
+    modes::drift(record)
+        .into_iter()
+        .map(|(file, expected, actual)| Problem::Mode {
+            file,
+            expected,
+            actual,
+        })
+        .collect()
+}
+
+fn check_deployment(record: &LinkRecord) -> Vec<Problem> {
+    let md = match fs::symlink_metadata(&record.target) {
+        Ok(md) => md,
+        Err(_) => return vec![Problem::Missing],
+    };
+    match record.mode {
+        DeployMode::Symlink => {
+            if !md.file_type().is_symlink() {
+                return vec![Problem::Replaced];
+            }
+            let points_to = match fs::read_link(&record.target) {
+                Ok(p) => p,
+                Err(_) => return vec![Problem::Broken],
+            };
+            let abs = if points_to.is_absolute() {
+                points_to
+            } else {
+                record
+                    .target
+                    .parent()
+                    .map(|p| p.join(&points_to))
+                    .unwrap_or(points_to)
+            };
+            if !same_path(&abs, &record.source) {
+                return vec![Problem::Repointed(abs.display().to_string())];
+            }
+            if !record.source.exists() {
+                return vec![Problem::Broken];
+            }
+            Vec::new()
+        }
+        DeployMode::Copy => {
+            if md.file_type().is_symlink() {
+                return vec![Problem::NotACopy];
+            }
+            let mut out = Vec::new();
+            for (rel, expected) in &record.files {
+                let path = if rel.is_empty() {
+                    record.target.clone()
+                } else {
+                    record.target.join(rel)
+                };
+                let shown = if rel.is_empty() {
+                    record.target.display().to_string()
+                } else {
+                    rel.clone()
+                };
+                match hash_file(&path) {
+                    Ok(actual) if &actual == expected => {}
+                    Ok(_) => out.push(Problem::Modified(shown)),
+                    Err(_) => out.push(Problem::Deleted(shown)),
+                }
+            }
+            out
+        }
+    }
+}
+
+fn same_path(a: &Path, b: &Path) -> bool {
+    if a == b {
+        return true;
+    }
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(x), Ok(y)) => x == y,
+        _ => false,
+    }
+}
@@ -0,0 +1,299 @@
+//! `dothub verify-repo`: the checks to pass before publishing a config repo.
+//!
+//! Three things keep a broken or leaky config off the hub: the linters of
+//! each config type the repo provides, the application starting headless on
+//! the config with a throwaway home (so nothing of yours is read or written),
+//! and a scan for credentials committed by mistake. `--install-hook` makes
+//! the repo's pre-push hook run them, so `git push` stops when one fails.
+
+use crate::lint;
+use crate::manifest::RepoManifest;
+use crate::output::{self, Tone};
+use anyhow::{bail, Context, Result};
+use std::env;
+use std::ffi::OsStr;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// Config type, program that has to be installed, and the shell command that
+/// starts it on the config and exits. It runs in the config's directory with
+/// HOME and the XDG directories in a throwaway one, where
+/// `$XDG_CONFIG_HOME/<type>` links to the config.
+pub const STARTUP: &[(&str, &str, &str)] = &[
+    ("nvim", "nvim", "nvim --headless -i NONE +qa!"),
+    ("zsh", "zsh", "ZDOTDIR=\"$PWD\" zsh -i -c exit"),
+    (
+        "bash",
+        "bash",
+        "for f in .bashrc bashrc; do [ -f \"$f\" ] && exec bash --rcfile \"$f\" -i -c exit; done; true",
+    ),
+    ("fish", "fish", "fish -c exit"),
+];
+
+/// Output that means the config failed to load even when the exit code is 0,
+/// as with nvim, which reports errors and quits anyway.
+const STARTUP_ERRORS: &[&str] = &[
+    "Error detected while processing",
+    "E5113:",
+    "E5108:",
+    "command not found",
+    "parse error",
+    "syntax error",
+];
+
+/// How long an application may take to start before it counts as hanging.
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// `dothub verify-repo <name>`: `name` is a stored repo, or a path to a
+/// checkout anywhere.
+pub fn cmd_verify_repo(name: &str, install_hook: bool) -> Result<()> {
+    let repo = repo_dir(name)?;
+    if install_hook {
+        return install(&repo);
+    }
+
+    let mut failed = Vec::new();
+    for (config_type, dir) in types(&repo) {
+        println!(
+            "{}",
+            output::paint(&format!("Lint: {}", config_type), Tone::Info)
+        );
+        if !lint::check(&dir, &config_type)? {
+            failed.push(format!("lint {}", config_type));
+        }
+        println!(
+            "{}",
+            output::paint(&format!("Startup: {}", config_type), Tone::Info)
+        );
+        if !startup(&dir, &config_type)? {
+            failed.push(format!("startup {}", config_type));
+        }
+    }
+    println!("{}", output::paint("Secrets", Tone::Info));
+    let leaks = crate::secrets::scan_dir(&repo)?;
+    if leaks.is_empty() {
+        println!("{}", output::paint("ok       no secrets found", Tone::Ok));
+    } else {
+        crate::secrets::print(&leaks);
+        failed.push("secrets".to_string());
+    }
+
+    if !failed.is_empty() {
+        bail!(
+            "{} failed: {}; fix them before publishing",
+            repo.display(),
+            failed.join(", ")
+        );
+    }
+    println!(
+        "{}",
+        output::paint(&format!("{} is ready to publish", repo.display()), Tone::Ok)
+    );
+    Ok(())
+}
+
+/// The directory of `name`: a stored repo, or a path when it has a slash or
+/// is `.`.
+pub fn repo_dir(name: &str) -> Result<PathBuf> {
+    let repo = if name.contains('/') || name == "." {
+        crate::source::expand_tilde(name)
+    } else {
+        crate::dothub_dir().join(name)
+    };
+    if !repo.is_dir() {
+        bail!(crate::error::repo_not_found(name, &repo));
+    }
+    // Named by its directory, which `.` doesn't say
+    Ok(fs::canonicalize(&repo).unwrap_or(repo))
+}
+
+/// The config types in `repo` and the directory of each: every type an
+/// all-in-one repo provides, or the one it is.
+pub fn types(repo: &Path) -> Vec<(String, PathBuf)> {
+    let manifest = RepoManifest::load(repo).ok().flatten();
+    match manifest {
+        Some(m) if !m.provides.is_empty() => m
+            .provides
+            .iter()
+            .map(|(config_type, dir)| {
+                (config_type.clone(), repo.join(dir.trim_start_matches("./")))
+            })
+            .collect(),
+        _ => {
+            let name = repo
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+            vec![(lint::config_type(repo, &name), repo.to_path_buf())]
+        }
+    }
+}
+
+/// Start the application of `config_type` headless on `dir`, printing how it
+/// went. Returns whether it loaded without errors; types without a startup
+/// check, and applications that aren't installed, pass.
+fn startup(dir: &Path, config_type: &str) -> Result<bool> {
+    let Some((_, program, command)) = STARTUP.iter().find(|(t, _, _)| *t == config_type) else {
+        println!(
+            "{}",
+            output::paint("skipped  no startup check for this type", Tone::Muted)
+        );
+        return Ok(true);
+    };
+    if which::which(program).is_err() {
+        println!(
+            "{}",
+            output::paint(
+                &format!("skipped  {} (not installed)", program),
+                Tone::Muted
+            )
+        );
+        return Ok(true);
+    }
+
+    let home = env::temp_dir().join(format!("dothub-verify-{}", std::process::id()));
+    let config = home.join(".config");
+    fs::create_dir_all(&config).with_context(|| format!("Creating {}", config.display()))?;
+    let dir = fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf());
+    crate::vfs::fs().symlink(&dir, &config.join(config_type))?;
+    let result = run(&dir, Some(&home), command, &[]);
+    let _ = fs::remove_dir_all(&home);
+    let (ok, report) = result?;
+
+    if ok {
+        println!(
+            "{}",
+            output::paint(&format!("ok       {}", program), Tone::Ok)
+        );
+    } else {
+        println!(
+            "{}",
+            output::paint(&format!("failed   {}", program), Tone::Error)
+        );
+        for line in report.lines() {
+            println!("  {}", line);
+        }
+    }
+    Ok(ok)
+}
+
+/// Run `command` in `dir` with `envs` set, and `home` as HOME unless it is
+/// yours, and whether it succeeded, with what it printed.
+pub fn run(
+    dir: &Path,
+    home: Option<&Path>,
+    command: &str,
+    envs: &[(&str, &OsStr)],
+) -> Result<(bool, String)> {
+    let mut cmd = Command::new("sh");
+    cmd.args(["-c", command])
+        .current_dir(dir)
+        .envs(envs.iter().copied());
+    if let Some(home) = home {
+        cmd.env("HOME", home)
+            .env("XDG_CONFIG_HOME", home.join(".config"))
+            .env("XDG_DATA_HOME", home.join(".local/share"))
+            .env("XDG_STATE_HOME", home.join(".local/state"))
+            .env("XDG_CACHE_HOME", home.join(".cache"));
+    }
+    let mut child = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Running sh")?;
+    // Read while it runs, so a chatty startup can't fill the pipes and stall
+    let readers = [
+        child
+            .stdout
+            .take()
+            .map(|out| Box::new(out) as Box<dyn Read + Send>),
+        child
+            .stderr
+            .take()
+            .map(|err| Box::new(err) as Box<dyn Read + Send>),
+    ]
+    .map(|pipe| {
+        std::thread::spawn(move || {
+            let mut text = String::new();
+            if let Some(mut pipe) = pipe {
+                let _ = pipe.read_to_string(&mut text);
+            }
+            text
+        })
+    });
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break Some(status);
+        }
+        if started.elapsed() > STARTUP_TIMEOUT {
+            let _ = child.kill();
+            let _ = child.wait();
+            break None;
+        }
+        // Short, so `dothub compare` can time startups by when they exit
+        std::thread::sleep(Duration::from_millis(2));
+    };
+    let report = readers
+        .into_iter()
+        .map(|reader| reader.join().unwrap_or_default())
+        .collect::<String>()
+        .trim()
+        .to_string();
+    let Some(status) = status else {
+        return Ok((
+            false,
+            format!(
+                "did not exit within {}s\n{}",
+                STARTUP_TIMEOUT.as_secs(),
+                report
+            ),
+        ));
+    };
+    let errors = STARTUP_ERRORS.iter().any(|e| report.contains(e));
+    Ok((status.success() && !errors, report))
+}
+
+/// Make the pre-push hook of `repo` run `dothub verify-repo` on it.
+fn install(repo: &Path) -> Result<()> {
+    if !crate::git::is_repo(repo) {
+        bail!("{} is not a git repository", repo.display());
+    }
+    // Honors core.hooksPath
+    let hook = crate::git::output(repo, &["rev-parse", "--git-path", "hooks/pre-push"])?;
+    let hook = repo.join(hook);
+    if let Ok(existing) = fs::read_to_string(&hook) {
+        if !existing.contains("dothub verify-repo") {
+            bail!(
+                "{} already has a pre-push hook; add `dothub verify-repo \"$(git rev-parse --show-toplevel)\"` to it",
+                repo.display()
+            );
+        }
+    }
+    let exe = env::current_exe().context("Finding the dothub executable")?;
+    let script = format!(
+        "#!/bin/sh\n# dothub verify-repo: lint, startup and secret checks before pushing\n\
+         exec '{}' verify-repo \"$(git rev-parse --show-toplevel)\"\n",
+        exe.display()
+    );
+    if let Some(parent) = hook.parent() {
+        fs::create_dir_all(parent).with_context(|| format!("Creating {}", parent.display()))?;
+    }
+    fs::write(&hook, script).with_context(|| format!("Writing {}", hook.display()))?;
+    crate::modes::set_mode(&hook, 0o755)?;
+    println!(
+        "{}",
+        output::paint(
+            &format!(
+                "git push in {} now runs dothub verify-repo first",
+                repo.display()
+            ),
+            Tone::Ok
+        )
+    );
+    Ok(())
+}
@@ -0,0 +1,634 @@
+//! Filesystem changes made by deploying: links, copies, backups, removals
+//! and the state file.
+//!
+//! They go through an [`Fs`] rather than std::fs. [`RealFs`] makes them; a
+//! [`MemFs`] keeps them in memory, either on its own, for tests, or over the
+//! real filesystem, reading through to it for whatever it hasn't changed.
+//! `--dry-run` runs a command against the latter and lists the changes it
+//! recorded, so a dry run takes exactly the path a real run would. Steps that
+//! are not filesystem changes (a clone, a hook, reloading an application) are
+//! skipped in a dry run and listed with [`note`].
+
+use crate::output::{self, Tone};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    File,
+    Dir,
+    Symlink,
+}
+
+pub trait Fs: Send + Sync {
+    /// What is at `path`, without following a symlink there.
+    fn kind(&self, path: &Path) -> Option<Kind>;
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf>;
+    fn create_dir_all(&self, path: &Path) -> io::Result<()>;
+    /// Write `bytes` to `path` through a sibling file, so a crash never
+    /// leaves it half-written.
+    fn write(&self, path: &Path, bytes: &[u8]) -> io::Result<()>;
+    fn symlink(&self, points_to: &Path, link: &Path) -> io::Result<()>;
+    /// Copy the file or directory `from` to `to`, leaving out .git.
+    fn copy_tree(&self, from: &Path, to: &Path) -> io::Result<()>;
+    /// Copy the file or directory `from` to `to` with its .git, history and
+    /// all, for moving a repo where renaming it isn't possible.
+    fn copy_all(&self, from: &Path, to: &Path) -> io::Result<()>;
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+    /// Remove a file, a symlink (not what it points to) or a whole directory.
+    fn remove(&self, path: &Path) -> io::Result<()>;
+    /// Move `path` to the desktop trash.
+    fn trash(&self, path: &Path) -> io::Result<()>;
+
+    fn exists(&self, path: &Path) -> bool {
+        self.kind(path).is_some()
+    }
+}
+
+/// The filesystem itself.
+pub struct RealFs;
+
+fn copy(from: &Path, to: &Path, with_git: bool) -> io::Result<()> {
+    let md = fs::symlink_metadata(from)?;
+    match kind_of(&md) {
+        Kind::File => fs::copy(from, to).map(|_| ()),
+        #[cfg(unix)]
+        Kind::Symlink => std::os::unix::fs::symlink(fs::read_link(from)?, to),
+        #[cfg(not(unix))]
+        Kind::Symlink => fs::copy(from, to).map(|_| ()),
+        Kind::Dir => {
+            fs::create_dir_all(to)?;
+            for entry in fs::read_dir(from)? {
+                let entry = entry?;
+                if with_git || entry.file_name() != ".git" {
+                    copy(&entry.path(), &to.join(entry.file_name()), with_git)?;
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+fn kind_of(md: &fs::Metadata) -> Kind {
+    if md.file_type().is_symlink() {
+        Kind::Symlink
+    } else if md.is_dir() {
+        Kind::Dir
+    } else {
+        Kind::File
+    }
+}
+
+impl Fs for RealFs {
+    fn kind(&self, path: &Path) -> Option<Kind> {
+        fs::symlink_metadata(path).ok().map(|md| kind_of(&md))
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        fs::read(path)
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        fs::read_link(path)
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        fs::create_dir_all(path)
+    }
+
+    fn write(&self, path: &Path, bytes: &[u8]) -> io::Result<()> {
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        fs::write(&tmp, bytes)?;
+        fs::rename(&tmp, path)
+    }
+
+    fn symlink(&self, points_to: &Path, link: &Path) -> io::Result<()> {
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(points_to, link)
+        }
+        #[cfg(windows)]
+        {
+            let resolved = link.parent().unwrap_or(Path::new(".")).join(points_to);
+            if resolved.is_dir() {
+                std::os::windows::fs::symlink_dir(points_to, link)
+            } else {
+                std::os::windows::fs::symlink_file(points_to, link)
+            }
+        }
+    }
+
+    fn copy_tree(&self, from: &Path, to: &Path) -> io::Result<()> {
+        copy(from, to, false)
+    }
+
+    fn copy_all(&self, from: &Path, to: &Path) -> io::Result<()> {
+        copy(from, to, true)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::rename(from, to)
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        match self.kind(path) {
+            Some(Kind::Dir) => fs::remove_dir_all(path),
+            _ => fs::remove_file(path),
+        }
+    }
+
+    fn trash(&self, path: &Path) -> io::Result<()> {
+        trash::delete(path).map_err(|e| io::Error::other(e.to_string()))
+    }
+}
+
+/// A change a [`MemFs`] recorded instead of making.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Change {
+    CreateDir(PathBuf),
+    Write(PathBuf),
+    Symlink { link: PathBuf, points_to: PathBuf },
+    Copy { from: PathBuf, to: PathBuf },
+    Rename { from: PathBuf, to: PathBuf },
+    Remove(PathBuf),
+    Trash(PathBuf),
+    /// A step outside the filesystem, skipped
+    Note(String),
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::CreateDir(path) => write!(f, "create {}", path.display()),
+            Change::Write(path) => write!(f, "write {}", path.display()),
+            Change::Symlink { link, points_to } => {
+                write!(f, "link {} -> {}", link.display(), points_to.display())
+            }
+            Change::Copy { from, to } => write!(f, "copy {} to {}", from.display(), to.display()),
+            Change::Rename { from, to } => {
+                write!(f, "move {} to {}", from.display(), to.display())
+            }
+            Change::Remove(path) => write!(f, "remove {}", path.display()),
+            Change::Trash(path) => write!(f, "move {} to the trash", path.display()),
+            Change::Note(text) => f.write_str(text),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Node {
+    /// A directory made here; everything in it is recorded too
+    Dir,
+    File(Vec<u8>),
+    Symlink(PathBuf),
+    /// What is at this real path, moved or copied here
+    Real(PathBuf),
+    /// Made by a step that isn't simulated, such as a clone, so its contents
+    /// are unknown; anything under it is taken to be a directory
+    Opaque,
+    Gone,
+}
+
+/// Where a path's contents come from.
+enum Found {
+    Node(Node),
+    Real(PathBuf),
+    Opaque,
+    Absent,
+}
+
+/// A filesystem in memory that records every change made to it, optionally
+/// over the real one.
+pub struct MemFs {
+    over_real: bool,
+    nodes: Mutex<BTreeMap<PathBuf, Node>>,
+    changes: Mutex<Vec<Change>>,
+}
+
+impl MemFs {
+    /// An empty filesystem.
+    pub fn new() -> MemFs {
+        MemFs {
+            over_real: false,
+            nodes: Mutex::new(BTreeMap::new()),
+            changes: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// The real filesystem as it is, with changes kept in memory.
+    pub fn over_real() -> MemFs {
+        MemFs {
+            over_real: true,
+            ..MemFs::new()
+        }
+    }
+
+    /// Changes recorded so far, in order.
+    pub fn changes(&self) -> Vec<Change> {
+        self.changes.lock().unwrap().clone()
+    }
+
+    fn record(&self, change: Change) {
+        self.changes.lock().unwrap().push(change);
+    }
+
+    /// Take `path` to exist, with contents a dry run can't know.
+    pub fn pretend_made(&self, path: &Path) {
+        self.nodes.lock().unwrap().insert(path.to_path_buf(), Node::Opaque);
+    }
+
+    fn find(&self, nodes: &BTreeMap<PathBuf, Node>, path: &Path) -> Found {
+        for at in path.ancestors() {
+            let Some(node) = nodes.get(at) else {
+                continue;
+            };
+            let rest = path.strip_prefix(at).unwrap_or(Path::new(""));
+            return match node {
+                Node::Opaque => Found::Opaque,
+                Node::Real(real) => Found::Real(real.join(rest)),
+                _ if rest.as_os_str().is_empty() => Found::Node(node.clone()),
+                // Children of recorded directories are recorded themselves
+                _ => Found::Absent,
+            };
+        }
+        if self.over_real {
+            Found::Real(path.to_path_buf())
+        } else {
+            Found::Absent
+        }
+    }
+
+    fn not_found(path: &Path) -> io::Error {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} does not exist", path.display()),
+        )
+    }
+
+    /// Replace whatever is at `path` (and under it) with `node`.
+    fn put(nodes: &mut BTreeMap<PathBuf, Node>, path: &Path, node: Node) {
+        nodes.retain(|p, _| !p.starts_with(path));
+        nodes.insert(path.to_path_buf(), node);
+    }
+
+    fn check_parent(&self, path: &Path) -> io::Result<()> {
+        match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => match self.kind(parent) {
+                Some(Kind::Dir) => Ok(()),
+                Some(Kind::Symlink) if self.over_real && parent.is_dir() => Ok(()),
+                _ => Err(Self::not_found(parent)),
+            },
+            _ => Ok(()),
+        }
+    }
+
+    fn check_absent(&self, path: &Path) -> io::Result<()> {
+        match self.kind(path) {
+            Some(_) => Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} already exists", path.display()),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// The subtree at `from` as nodes to put at `to`, its root first.
+    fn subtree(&self, nodes: &BTreeMap<PathBuf, Node>, from: &Path, to: &Path) -> Vec<(PathBuf, Node)> {
+        let mut tree = match self.find(nodes, from) {
+            Found::Real(real) => vec![(to.to_path_buf(), Node::Real(real))],
+            Found::Opaque => vec![(to.to_path_buf(), Node::Opaque)],
+            _ => Vec::new(),
+        };
+        // Changes recorded inside it go along
+        let skip_root = !tree.is_empty();
+        tree.extend(
+            nodes
+                .iter()
+                .filter(|(p, _)| p.starts_with(from) && !(skip_root && *p == from))
+                .map(|(p, node)| {
+                    let rest = p.strip_prefix(from).unwrap_or(Path::new(""));
+                    (to.join(rest), node.clone())
+                }),
+        );
+        tree
+    }
+}
+
+impl Default for MemFs {
+    fn default() -> Self {
+        MemFs::new()
+    }
+}
+
+impl Fs for MemFs {
+    fn kind(&self, path: &Path) -> Option<Kind> {
+        let nodes = self.nodes.lock().unwrap();
+        match self.find(&nodes, path) {
+            Found::Node(Node::Dir) | Found::Opaque => Some(Kind::Dir),
+            Found::Node(Node::File(_)) => Some(Kind::File),
+            Found::Node(Node::Symlink(_)) => Some(Kind::Symlink),
+            Found::Node(_) | Found::Absent => None,
+            Found::Real(real) => RealFs.kind(&real),
+        }
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        let found = self.find(&self.nodes.lock().unwrap(), path);
+        match found {
+            Found::Node(Node::File(bytes)) => Ok(bytes),
+            Found::Node(Node::Symlink(to)) => {
+                self.read(&path.parent().unwrap_or(Path::new("/")).join(to))
+            }
+            Found::Real(real) => fs::read(real),
+            _ => Err(Self::not_found(path)),
+        }
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        let found = self.find(&self.nodes.lock().unwrap(), path);
+        match found {
+            Found::Node(Node::Symlink(to)) => Ok(to),
+            Found::Real(real) => fs::read_link(real),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is not a symlink", path.display()),
+            )),
+        }
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        let missing: Vec<&Path> = path
+            .ancestors()
+            .take_while(|p| !p.as_os_str().is_empty() && self.kind(p).is_none())
+            .collect();
+        if missing.is_empty() {
+            return match self.kind(path) {
+                Some(Kind::Dir) | Some(Kind::Symlink) => Ok(()),
+                _ => Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("{} is not a directory", path.display()),
+                )),
+            };
+        }
+        let mut nodes = self.nodes.lock().unwrap();
+        for dir in missing.iter().rev() {
+            Self::put(&mut nodes, dir, Node::Dir);
+        }
+        drop(nodes);
+        self.record(Change::CreateDir(path.to_path_buf()));
+        Ok(())
+    }
+
+    fn write(&self, path: &Path, bytes: &[u8]) -> io::Result<()> {
+        self.check_parent(path)?;
+        if self.kind(path) == Some(Kind::Dir) {
+            return Err(io::Error::other(format!("{} is a directory", path.display())));
+        }
+        Self::put(&mut self.nodes.lock().unwrap(), path, Node::File(bytes.to_vec()));
+        self.record(Change::Write(path.to_path_buf()));
+        Ok(())
+    }
+
+    fn symlink(&self, points_to: &Path, link: &Path) -> io::Result<()> {
+        self.check_parent(link)?;
+        self.check_absent(link)?;
+        Self::put(
+            &mut self.nodes.lock().unwrap(),
+            link,
+            Node::Symlink(points_to.to_path_buf()),
+        );
+        self.record(Change::Symlink {
+            link: link.to_path_buf(),
+            points_to: points_to.to_path_buf(),
+        });
+        Ok(())
+    }
+
+    fn copy_tree(&self, from: &Path, to: &Path) -> io::Result<()> {
+        if !self.exists(from) {
+            return Err(Self::not_found(from));
+        }
+        self.check_parent(to)?;
+        let mut nodes = self.nodes.lock().unwrap();
+        let copied = self.subtree(&nodes, from, to);
+        for (path, node) in copied {
+            let skip = path
+                .strip_prefix(to)
+                .is_ok_and(|rest| rest.components().any(|c| c.as_os_str() == ".git"));
+            if !skip {
+                Self::put(&mut nodes, &path, node);
+            }
+        }
+        // A real directory copied here still has its .git underneath
+        Self::put(&mut nodes, &to.join(".git"), Node::Gone);
+        drop(nodes);
+        self.record(Change::Copy {
+            from: from.to_path_buf(),
+            to: to.to_path_buf(),
+        });
+        Ok(())
+    }
+
+    fn copy_all(&self, from: &Path, to: &Path) -> io::Result<()> {
+        if !self.exists(from) {
+            return Err(Self::not_found(from));
+        }
+        self.check_parent(to)?;
+        let mut nodes = self.nodes.lock().unwrap();
+        for (path, node) in self.subtree(&nodes, from, to) {
+            Self::put(&mut nodes, &path, node);
+        }
+        drop(nodes);
+        self.record(Change::Copy {
+            from: from.to_path_buf(),
+            to: to.to_path_buf(),
+        });
+        Ok(())
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        if !self.exists(from) {
+            return Err(Self::not_found(from));
+        }
+        self.check_parent(to)?;
+        let mut nodes = self.nodes.lock().unwrap();
+        let moved = self.subtree(&nodes, from, to);
+        Self::put(&mut nodes, from, Node::Gone);
+        for (path, node) in moved {
+            Self::put(&mut nodes, &path, node);
+        }
+        drop(nodes);
+        self.record(Change::Rename {
+            from: from.to_path_buf(),
+            to: to.to_path_buf(),
+        });
+        Ok(())
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        if !self.exists(path) {
+            return Err(Self::not_found(path));
+        }
+        Self::put(&mut self.nodes.lock().unwrap(), path, Node::Gone);
+        self.record(Change::Remove(path.to_path_buf()));
+        Ok(())
+    }
+
+    fn trash(&self, path: &Path) -> io::Result<()> {
+        if !self.exists(path) {
+            return Err(Self::not_found(path));
+        }
+        Self::put(&mut self.nodes.lock().unwrap(), path, Node::Gone);
+        self.record(Change::Trash(path.to_path_buf()));
+        Ok(())
+    }
+}
+
+/// Set by `--dry-run`: changes are recorded here instead of made.
+static DRY_RUN: OnceLock<Arc<MemFs>> = OnceLock::new();
+
+thread_local! {
+    /// A filesystem standing in for the real one on this thread, in tests.
+    static STAND_IN: RefCell<Option<Arc<dyn Fs>>> = const { RefCell::new(None) };
+}
+
+/// The filesystem commands make their changes through.
+pub fn fs() -> Arc<dyn Fs> {
+    if let Some(fs) = STAND_IN.with(|s| s.borrow().clone()) {
+        return fs;
+    }
+    match DRY_RUN.get() {
+        Some(dry) => dry.clone(),
+        None => Arc::new(RealFs),
+    }
+}
+
+/// Record changes from now on instead of making them.
+pub fn start_dry_run() {
+    DRY_RUN.get_or_init(|| Arc::new(MemFs::over_real()));
+}
+
+pub fn dry_run() -> bool {
+    DRY_RUN.get().is_some()
+}
+
+/// In a dry run, list a step that was skipped because it isn't a filesystem
+/// change.
+pub fn note(text: impl Into<String>) {
+    if let Some(dry) = DRY_RUN.get() {
+        dry.record(Change::Note(text.into()));
+    }
+}
+
+/// In a dry run, take `path` to have been made by a skipped step.
+pub fn pretend_made(path: &Path) {
+    if let Some(dry) = DRY_RUN.get() {
+        dry.pretend_made(path);
+    }
+}
+
+/// Print what a dry run would have changed.
+pub fn print_changes() {
+    let Some(dry) = DRY_RUN.get() else {
+        return;
+    };
+    let state = crate::state::state_path();
+    let changes: Vec<Change> = dry
+        .changes()
+        .into_iter()
+        .filter(|c| *c != Change::Write(state.clone()))
+        .collect();
+    if changes.is_empty() {
+        println!("{}", output::paint("Dry run: nothing would change", Tone::Ok));
+        return;
+    }
+    println!(
+        "{}",
+        output::paint("Dry run: nothing was changed. A real run would:", Tone::Info)
+    );
+    for change in changes {
+        let tone = match change {
+            Change::Remove(_) | Change::Trash(_) => Tone::Warn,
+            Change::Note(_) => Tone::Muted,
+            _ => Tone::Info,
+        };
+        println!("  {}", output::paint(&change.to_string(), tone));
+    }
+}
+
+/// Run `f` with `fs` standing in for the real filesystem on this thread.
+#[cfg(test)]
+pub fn with<T>(fs: Arc<dyn Fs>, f: impl FnOnce() -> T) -> T {
+    STAND_IN.with(|s| *s.borrow_mut() = Some(fs));
+    let result = f();
+    STAND_IN.with(|s| *s.borrow_mut() = None);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mem_fs_records_changes_in_order() {
+        let fs = MemFs::new();
+        fs.create_dir_all(Path::new("/home/.config")).unwrap();
+        fs.write(Path::new("/home/.config/a"), b"one").unwrap();
+        fs.rename(Path::new("/home/.config/a"), Path::new("/home/.config/b"))
+            .unwrap();
+        fs.symlink(Path::new("/store/repo"), Path::new("/home/.config/a"))
+            .unwrap();
+
+        assert_eq!(fs.read(Path::new("/home/.config/b")).unwrap(), b"one");
+        assert_eq!(fs.kind(Path::new("/home/.config/a")), Some(Kind::Symlink));
+        assert_eq!(
+            fs.read_link(Path::new("/home/.config/a")).unwrap(),
+            PathBuf::from("/store/repo")
+        );
+        assert_eq!(fs.changes().len(), 4);
+        assert_eq!(
+            fs.changes()[0],
+            Change::CreateDir(PathBuf::from("/home/.config"))
+        );
+        assert!(fs.write(Path::new("/missing/file"), b"").is_err());
+    }
+
+    #[test]
+    fn over_real_reads_through_and_changes_nothing() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path().join("repo");
+        std::fs::create_dir_all(repo.join(".git")).unwrap();
+        std::fs::write(repo.join("init.lua"), "x").unwrap();
+        let fs = MemFs::over_real();
+
+        let copy = dir.path().join("copy");
+        fs.copy_tree(&repo, &copy).unwrap();
+        assert_eq!(fs.read(&copy.join("init.lua")).unwrap(), b"x");
+        assert!(!fs.exists(&copy.join(".git")));
+        fs.remove(&repo).unwrap();
+        assert!(!fs.exists(&repo.join("init.lua")));
+
+        assert!(repo.join("init.lua").exists());
+        assert!(!copy.exists());
+    }
+
+    #[test]
+    fn remove_path_goes_through_the_stand_in() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("nvim");
+        std::fs::create_dir(&target).unwrap();
+        let fs = Arc::new(MemFs::over_real());
+
+        with(fs.clone(), || crate::remove_path(&target)).unwrap();
+
+        assert_eq!(fs.changes(), vec![Change::Remove(target.clone())]);
+        assert!(target.is_dir());
+    }
+}
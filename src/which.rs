@@ -0,0 +1,226 @@
+//! `dothub which`: how a target resolves, for when an application loads
+//! something other than what you expected.
+//!
+//! The chain is followed from the path the application reads, through the
+//! symlink or copy, to the stored repo and the directory in it, then the
+//! commit checked out there and where it came from. Anything else that
+//! changes what ends up loaded is listed after it: edits to a copy, a
+//! read-only lock, exported variables, and an XDG_CONFIG_HOME that sends the
+//! application somewhere else entirely.
+
+use crate::git;
+use crate::output::{self, Tone};
+use crate::state::{DeployMode, LinkRecord, RepoSource, State};
+use anyhow::{Context, Result};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn row(label: &str, value: impl AsRef<str>) {
+    println!("{:<9}{}", label, value.as_ref());
+}
+
+/// `base` joined with `rest`, without the trailing slash an empty `rest` adds.
+fn join(base: &Path, rest: &Path) -> PathBuf {
+    if rest.as_os_str().is_empty() {
+        base.to_path_buf()
+    } else {
+        base.join(rest)
+    }
+}
+
+/// `dothub which <target>`. A path inside a deployed target (e.g.
+/// ~/.config/nvim/init.lua) is followed to the file in the store.
+pub fn cmd_which(target: &str) -> Result<()> {
+    let st = State::load()?;
+    let (target_name, record, inside) = find(&st, target)?;
+    let path = match &record {
+        Some(record) => record.target.clone(),
+        None => crate::targets::path(&target_name)?,
+    };
+    row("Path", join(&path, &inside).display().to_string());
+
+    let Some(record) = record else {
+        let what = match fs::symlink_metadata(&path) {
+            Err(_) => "nothing is there".to_string(),
+            Ok(md) if md.file_type().is_symlink() => match fs::read_link(&path) {
+                Ok(dest) => format!("a symlink to {}", dest.display()),
+                Err(_) => "a symlink".to_string(),
+            },
+            Ok(md) if md.is_dir() => "a directory".to_string(),
+            Ok(_) => "a file".to_string(),
+        };
+        println!(
+            "{}",
+            output::paint(
+                &format!("{} is not deployed by dothub; {}", target_name, what),
+                Tone::Warn
+            )
+        );
+        xdg_note(&path);
+        return Ok(());
+    };
+
+    match record.mode {
+        DeployMode::Symlink => {
+            let points_to = fs::read_link(&record.target)
+                .map(|p| p.display().to_string())
+                .unwrap_or_else(|_| "(not a symlink any more)".to_string());
+            row("Link", format!("symlink -> {}", points_to));
+        }
+        DeployMode::Copy => row(
+            "Link",
+            format!(
+                "copy of {}, made {}",
+                record.source.display(),
+                output::ago(record.deployed_at, crate::state::now_unix())
+            ),
+        ),
+    }
+
+    let repo_name = record.name.split('/').next().unwrap_or(&record.name);
+    let repo = crate::dothub_dir().join(repo_name);
+    let mut repo_line = format!("{} ({})", repo_name, repo.display());
+    if let Ok(local) = fs::read_link(&repo) {
+        repo_line.push_str(&format!(" -> {}", local.display()));
+    }
+    row("Repo", repo_line);
+    let sub = record.source.strip_prefix(&repo).unwrap_or(Path::new(""));
+    let file = join(sub, &inside);
+    if !file.as_os_str().is_empty() {
+        let missing = !join(&record.source, &inside).exists();
+        row(
+            "Subpath",
+            format!(
+                "{}{}",
+                file.display(),
+                if missing { " (not in the repo)" } else { "" }
+            ),
+        );
+    }
+
+    let source = st.repos.get(repo_name).and_then(|r| r.source.clone());
+    if git::is_repo(&repo) {
+        let commit = git::head(&repo)
+            .map(|c| c[..c.len().min(10)].to_string())
+            .unwrap_or_else(|| "no commits".to_string());
+        let mut line = commit;
+        if let Ok(status) = git::status(&repo) {
+            line.push_str(&format!(" on {}", status.branch));
+            if status.is_dirty() {
+                line.push_str(&format!(", {} uncommitted change(s)", status.changes));
+            }
+        }
+        row("Commit", line);
+    }
+    let origin = git::output(&repo, &["remote", "get-url", "origin"])
+        .ok()
+        .filter(|_| git::is_repo(&repo));
+    let origin = match (&source, origin) {
+        (_, Some(url)) => Some(url),
+        (Some(RepoSource::Git { url } | RepoSource::Hg { url } | RepoSource::Jj { url }), _) => {
+            Some(url.clone())
+        }
+        (Some(RepoSource::Archive { location }), _) => Some(format!("{} (archive)", location)),
+        (Some(RepoSource::Local { path, copy }), _) => Some(format!(
+            "{} ({})",
+            path.display(),
+            if *copy { "copied" } else { "linked" }
+        )),
+        (None, None) => None,
+    };
+    if let Some(origin) = origin {
+        row("Origin", origin);
+    }
+
+    notes(&st, &record, &repo);
+    xdg_note(&path);
+    Ok(())
+}
+
+/// The target `arg` names, its deployment if dothub made one, and the path
+/// inside it when `arg` is a path below the target.
+fn find(st: &State, arg: &str) -> Result<(String, Option<LinkRecord>, PathBuf)> {
+    if !arg.contains('/') {
+        return Ok((arg.to_string(), st.links.get(arg).cloned(), PathBuf::new()));
+    }
+    let path = crate::source::expand_tilde(arg);
+    let path = if path.is_absolute() {
+        path
+    } else {
+        env::current_dir().context("Reading the current directory")?.join(path)
+    };
+    let found = st
+        .links
+        .iter()
+        .filter_map(|(name, rec)| {
+            let inside = path.strip_prefix(&rec.target).ok()?;
+            Some((name, rec, inside.to_path_buf()))
+        })
+        // The deepest target wins when one is inside another
+        .max_by_key(|(_, rec, _)| rec.target.components().count());
+    match found {
+        Some((name, rec, inside)) => Ok((name.clone(), Some(rec.clone()), inside)),
+        None => {
+            let name = path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+            Ok((name, None, PathBuf::new()))
+        }
+    }
+}
+
+/// What else decides what the application sees.
+fn notes(st: &State, record: &LinkRecord, repo: &Path) {
+    let mut notes = Vec::new();
+    for problem in crate::verify::check_record(record) {
+        notes.push((problem.describe(), Tone::Warn));
+    }
+    let repo_name = record.name.split('/').next().unwrap_or(&record.name);
+    if let Some(lock) = st.repos.get(repo_name).and_then(|r| r.lock.as_ref()) {
+        notes.push((
+            format!("read-only: {} is held at {}", repo_name, crate::short_sha(Some(&lock.rev))),
+            Tone::Info,
+        ));
+    }
+    for (var, value) in &record.env {
+        notes.push((format!("exports {}={}", var, value), Tone::Info));
+    }
+    if let Some(owner) = &record.owner {
+        notes.push((format!("deployed for {}", owner), Tone::Info));
+    }
+    if fs::symlink_metadata(repo).is_ok_and(|md| md.file_type().is_symlink()) {
+        notes.push((
+            "the store holds a link to a local directory, so edits there show up at once".to_string(),
+            Tone::Info,
+        ));
+    }
+    for (note, tone) in notes {
+        println!("{}", output::paint(&format!("  * {}", note), tone));
+    }
+}
+
+/// Applications read XDG_CONFIG_HOME, not ~/.config, when it is set.
+fn xdg_note(path: &Path) {
+    let Some(xdg) = env::var_os("XDG_CONFIG_HOME").map(PathBuf::from) else {
+        return;
+    };
+    let Some(home) = dirs::home_dir() else {
+        return;
+    };
+    let config = home.join(".config");
+    if xdg != config && path.starts_with(&config) {
+        println!(
+            "{}",
+            output::paint(
+                &format!(
+                    "  * XDG_CONFIG_HOME is {}, so applications look there instead of {}",
+                    xdg.display(),
+                    config.display()
+                ),
+                Tone::Warn
+            )
+        );
+    }
+}
@@ -0,0 +1,4 @@
+return {
+    -- Add plugin specs here, e.g.
+    -- { "folke/tokyonight.nvim", lazy = false, priority = 1000, config = function() vim.cmd.colorscheme("tokyonight") end },
+}
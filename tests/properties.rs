@@ -0,0 +1,58 @@
+//! Properties of apply and link over generated manifests and target names.
+
+mod support;
+
+use proptest::prelude::*;
+use std::fs;
+use support::{strategies, Sandbox};
+
+proptest! {
+    // Every case clones and links through the binary, so keep the count low
+    #![proptest_config(ProptestConfig::with_cases(32))]
+
+    #[test]
+    fn apply_links_every_target_and_then_has_nothing_to_do(spec in strategies::manifest(2)) {
+        let sb = Sandbox::new();
+        let urls = ["one", "two"]
+            .iter()
+            .map(|name| sb.remote(name, &[("top.conf", "\n"), ("sub/inner.conf", "\n")]).url())
+            .collect::<Vec<_>>();
+        let manifest = sb.manifest("manifest.toml", &spec.to_toml(&urls));
+        let manifest = manifest.to_string_lossy();
+
+        sb.run(&["apply", "--manifest", &manifest, "--unattended"]);
+
+        for name in spec.repos.keys() {
+            prop_assert!(sb.store().join(name).join("top.conf").exists());
+        }
+        for (target, (repo, sub)) in &spec.links {
+            let mut source = sb.store().join(repo);
+            if *sub {
+                source.push("sub");
+            }
+            prop_assert_eq!(fs::read_link(sb.target(target)).ok(), Some(source));
+        }
+        let plan = sb.root().join("plan.json");
+        sb.run(&["apply", "--manifest", &manifest, "--check", &plan.to_string_lossy()]);
+        let plan: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(plan).unwrap()).unwrap();
+        prop_assert_eq!(&plan["ops"], &serde_json::json!([]));
+    }
+
+    #[test]
+    fn unlink_undoes_link(target in strategies::target_name()) {
+        let sb = Sandbox::new();
+        let remote = sb.remote("repo", &[("file.conf", "\n")]);
+        sb.run(&["install", &remote.url(), "repo"]);
+
+        sb.run(&["link", "repo", &target]);
+        prop_assert_eq!(fs::read_link(sb.target(&target)).ok(), Some(sb.store().join("repo")));
+
+        sb.run(&["unlink", &target]);
+        let config = sb.home().join(".config");
+        prop_assert_eq!(fs::read_dir(&config).unwrap().count(), 0);
+        let state = fs::read_to_string(sb.state()).unwrap();
+        let state: serde_json::Value = serde_json::from_str(&state).unwrap();
+        prop_assert!(state["links"].get(&target).is_none(), "{}", state);
+    }
+}
@@ -0,0 +1,385 @@
+//! Shared fixtures for the integration tests.
+//!
+//! A [`Sandbox`] is a temporary home, store, state file, config and cache that
+//! the dothub binary is pointed at, with HTTP sent to a closed port, so a test
+//! never touches the real setup or the network. A [`FakeRemote`] is a bare repo behind a file:// URL
+//! that a test can push commits to, and [`Sandbox::script_git`] puts a `git`
+//! on PATH that fails chosen subcommands a set number of times before passing
+//! through to the real one. [`strategies`] generates manifests and target
+//! names for proptest.
+
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use tempfile::TempDir;
+
+/// Config section sending dothub's HTTP requests to a closed port, so none
+/// leave the machine. git over file:// URLs is unaffected.
+pub const NETWORK: &str = "[network]\nproxy = \"http://127.0.0.1:9\"\n";
+
+pub struct Sandbox {
+    dir: TempDir,
+}
+
+impl Sandbox {
+    pub fn new() -> Sandbox {
+        let dir = tempfile::Builder::new()
+            .prefix("dothub-test-")
+            .tempdir()
+            .expect("creating a temp dir");
+        for sub in ["home/.config", "store", "state", "cache", "remotes", "bin"] {
+            fs::create_dir_all(dir.path().join(sub)).expect("creating sandbox dirs");
+        }
+        let sandbox = Sandbox { dir };
+        sandbox.config("");
+        sandbox
+    }
+
+    pub fn root(&self) -> &Path {
+        self.dir.path()
+    }
+
+    pub fn home(&self) -> PathBuf {
+        self.root().join("home")
+    }
+
+    pub fn store(&self) -> PathBuf {
+        self.root().join("store")
+    }
+
+    pub fn state(&self) -> PathBuf {
+        self.root().join("state/state.json")
+    }
+
+    /// Where `target_name` is deployed, under ~/.config.
+    pub fn target(&self, target_name: &str) -> PathBuf {
+        self.home().join(".config").join(target_name)
+    }
+
+    /// Replace the config file with `text`, adding [`NETWORK`] and no retries
+    /// unless it has a `[network]` section of its own.
+    pub fn config(&self, text: &str) {
+        let text = if text.contains("[network]") {
+            text.to_string()
+        } else {
+            format!("{}\n{}retries = 0\n", text, NETWORK)
+        };
+        fs::write(self.root().join("config.toml"), text).expect("writing the config");
+    }
+
+    /// Write a manifest named `name` in the sandbox and return its path.
+    pub fn manifest(&self, name: &str, text: &str) -> PathBuf {
+        let path = self.root().join(name);
+        fs::write(&path, text).expect("writing the manifest");
+        path
+    }
+
+    /// dothub with every path it uses inside the sandbox.
+    pub fn cmd(&self) -> Command {
+        let mut cmd = Command::new(env!("CARGO_BIN_EXE_dothub"));
+        let path = std::env::var_os("PATH").unwrap_or_default();
+        let mut paths = vec![self.root().join("bin")];
+        paths.extend(std::env::split_paths(&path));
+        cmd.env("PATH", std::env::join_paths(paths).expect("joining PATH"))
+            .env("HOME", self.home())
+            .env("XDG_CONFIG_HOME", self.home().join(".config"))
+            .env("XDG_DATA_HOME", self.home().join(".local/share"))
+            .env("DOTHUB_DIR", self.store())
+            .env("DOTHUB_STATE", self.state())
+            .env("DOTHUB_CONFIG", self.root().join("config.toml"))
+            .env("DOTHUB_CACHE", self.root().join("cache"))
+            .env("DOTHUB_SOCKET", self.root().join("daemon.sock"))
+            .env_remove("DOTHUB_OFFLINE")
+            .env("FAKE_GIT_DIR", self.root().join("bin"))
+            .env("NO_COLOR", "1")
+            .env("DOTHUB_NO_KEYRING", "1")
+            .env("RUST_BACKTRACE", "0")
+            .env_remove("GITHUB_TOKEN")
+            .env_remove("GITLAB_TOKEN")
+            .current_dir(self.root());
+        cmd
+    }
+
+    /// Run dothub with `args`, which must succeed; returns stdout and stderr.
+    pub fn run(&self, args: &[&str]) -> String {
+        let out = self.output(args);
+        let text = text(&out);
+        assert!(
+            out.status.success(),
+            "dothub {} failed:\n{}",
+            args.join(" "),
+            text
+        );
+        text
+    }
+
+    /// Run dothub with `args`, which must fail; returns stdout and stderr.
+    pub fn fail(&self, args: &[&str]) -> String {
+        let out = self.output(args);
+        let text = text(&out);
+        assert!(
+            !out.status.success(),
+            "dothub {} succeeded:\n{}",
+            args.join(" "),
+            text
+        );
+        text
+    }
+
+    pub fn output(&self, args: &[&str]) -> Output {
+        self.cmd()
+            .args(args)
+            .stdin(std::process::Stdio::null())
+            .output()
+            .expect("running dothub")
+    }
+
+    /// A bare repo named `name` with `files` committed to it.
+    pub fn remote(&self, name: &str, files: &[(&str, &str)]) -> FakeRemote {
+        FakeRemote::new(&self.root().join("remotes"), name, files)
+    }
+
+    /// Make the `git` dothub runs fail the next `times` runs of `subcommand`
+    /// with `stderr`, then behave as usual.
+    pub fn script_git(&self, subcommand: &str, times: u32, stderr: &str) {
+        let bin = self.root().join("bin");
+        let real = which_git();
+        let script = format!(
+            r#"#!/bin/sh
+# Fails scripted subcommands, counting down FAKE_GIT_DIR/<subcommand>.left
+skip=0
+for arg in "$@"; do
+    if [ "$skip" = 1 ]; then skip=0; continue; fi
+    case "$arg" in
+        -C|-c) skip=1 ;;
+        -*) ;;
+        *) sub="$arg"; break ;;
+    esac
+done
+left="$FAKE_GIT_DIR/$sub.left"
+if [ -n "$sub" ] && [ -f "$left" ] && [ "$(cat "$left")" -gt 0 ]; then
+    echo $(( $(cat "$left") - 1 )) > "$left"
+    cat "$FAKE_GIT_DIR/$sub.stderr" >&2
+    exit 128
+fi
+exec '{}' "$@"
+"#,
+            real.display()
+        );
+        let git = bin.join("git");
+        fs::write(&git, script).expect("writing the fake git");
+        make_executable(&git);
+        fs::write(bin.join(format!("{}.left", subcommand)), times.to_string())
+            .expect("writing the fake git script");
+        fs::write(bin.join(format!("{}.stderr", subcommand)), stderr)
+            .expect("writing the fake git script");
+    }
+
+    /// Put a shell script named `program` on dothub's PATH, for tools the
+    /// machine may not have.
+    pub fn fake_tool(&self, program: &str, script: &str) {
+        let path = self.root().join("bin").join(program);
+        fs::write(&path, format!("#!/bin/sh\n{}", script)).expect("writing a fake tool");
+        make_executable(&path);
+    }
+
+    /// How many scripted failures of `subcommand` are still to come.
+    pub fn git_failures_left(&self, subcommand: &str) -> u32 {
+        fs::read_to_string(self.root().join("bin").join(format!("{}.left", subcommand)))
+            .ok()
+            .and_then(|t| t.trim().parse().ok())
+            .unwrap_or(0)
+    }
+}
+
+fn which_git() -> PathBuf {
+    let path = std::env::var_os("PATH").unwrap_or_default();
+    std::env::split_paths(&path)
+        .map(|dir| dir.join("git"))
+        .find(|git| git.is_file())
+        .expect("git on PATH")
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755)).expect("chmod");
+}
+
+#[cfg(not(unix))]
+fn make_executable(_path: &Path) {}
+
+pub fn text(out: &Output) -> String {
+    format!(
+        "{}{}",
+        String::from_utf8_lossy(&out.stdout),
+        String::from_utf8_lossy(&out.stderr)
+    )
+}
+
+/// Run git in `dir`, which must succeed, with a fixed identity.
+pub fn git(dir: &Path, args: &[&str]) -> String {
+    let out = Command::new(which_git())
+        .arg("-C")
+        .arg(dir)
+        .args(["-c", "user.name=Test", "-c", "user.email=test@example.com"])
+        .args([
+            "-c",
+            "init.defaultBranch=main",
+            "-c",
+            "commit.gpgsign=false",
+        ])
+        .args(args)
+        .output()
+        .expect("running git");
+    assert!(
+        out.status.success(),
+        "git {} failed in {}:\n{}",
+        args.join(" "),
+        dir.display(),
+        text(&out)
+    );
+    String::from_utf8_lossy(&out.stdout).trim().to_string()
+}
+
+/// A bare repo to clone and pull from, with a work tree to commit through.
+pub struct FakeRemote {
+    bare: PathBuf,
+    work: PathBuf,
+}
+
+impl FakeRemote {
+    fn new(root: &Path, name: &str, files: &[(&str, &str)]) -> FakeRemote {
+        let bare = root.join(format!("{}.git", name));
+        let work = root.join(format!("{}.work", name));
+        fs::create_dir_all(&work).expect("creating the remote's work tree");
+        git(&work, &["init", "-q"]);
+        let remote = FakeRemote { bare, work };
+        remote.commit(files, "Initial");
+        git(
+            root,
+            &[
+                "clone",
+                "-q",
+                "--bare",
+                &remote.work.to_string_lossy(),
+                &remote.bare.to_string_lossy(),
+            ],
+        );
+        git(
+            &remote.work,
+            &["remote", "add", "origin", &remote.bare.to_string_lossy()],
+        );
+        git(&remote.work, &["fetch", "-q", "origin"]);
+        git(&remote.work, &["branch", "-q", "-u", "origin/main"]);
+        remote
+    }
+
+    pub fn url(&self) -> String {
+        format!("file://{}", self.bare.display())
+    }
+
+    /// Commit `files` and, once the bare repo exists, push them to it.
+    pub fn commit(&self, files: &[(&str, &str)], message: &str) {
+        for (path, contents) in files {
+            let path = self.work.join(path);
+            fs::create_dir_all(path.parent().expect("file in a dir")).expect("creating dirs");
+            fs::write(&path, contents).expect("writing a remote file");
+        }
+        git(&self.work, &["add", "-A"]);
+        git(
+            &self.work,
+            &["commit", "-q", "--allow-empty", "-m", message],
+        );
+        if self.bare.exists() {
+            git(&self.work, &["push", "-q", "origin", "HEAD"]);
+        }
+    }
+
+    /// Replace the last commit with one of `files` and force-push it.
+    pub fn force_push(&self, files: &[(&str, &str)], message: &str) {
+        for (path, contents) in files {
+            fs::write(self.work.join(path), contents).expect("writing a remote file");
+        }
+        git(&self.work, &["add", "-A"]);
+        git(&self.work, &["commit", "-q", "--amend", "-m", message]);
+        git(&self.work, &["push", "-q", "--force", "origin", "HEAD"]);
+    }
+
+    /// Rename the remote's only branch, as when main replaces master.
+    pub fn rename_branch(&self, from: &str, to: &str) {
+        git(&self.work, &["branch", "-m", from, to]);
+        git(&self.work, &["push", "-q", "-u", "origin", to]);
+        git(
+            &self.bare,
+            &["symbolic-ref", "HEAD", &format!("refs/heads/{}", to)],
+        );
+        git(&self.work, &["push", "-q", "origin", "--delete", from]);
+    }
+
+    /// Head commit of the remote.
+    pub fn head(&self) -> String {
+        git(&self.work, &["rev-parse", "HEAD"])
+    }
+}
+
+/// proptest generators for the inputs dothub takes from users.
+pub mod strategies {
+    use proptest::prelude::*;
+    use std::collections::BTreeMap;
+
+    /// A store or repo name.
+    pub fn repo_name() -> impl Strategy<Value = String> {
+        "[a-z][a-z0-9_-]{0,10}"
+    }
+
+    /// A target name under ~/.config, clear of the names dothub maps elsewhere.
+    pub fn target_name() -> impl Strategy<Value = String> {
+        "[a-z][a-z0-9._-]{0,10}".prop_filter("reserved target", |t| {
+            t != "termux" && !t.ends_with('.') && !t.contains("..")
+        })
+    }
+
+    /// A manifest: repo names, each cloned from one of `remotes` remotes by
+    /// index, and targets linking a repo or its `sub` directory.
+    #[derive(Debug, Clone)]
+    pub struct ManifestSpec {
+        pub repos: BTreeMap<String, usize>,
+        pub links: BTreeMap<String, (String, bool)>,
+    }
+
+    impl ManifestSpec {
+        /// The manifest as TOML, with `urls` indexed as in `repos`.
+        pub fn to_toml(&self, urls: &[String]) -> String {
+            let mut text = String::from("[repos]\n");
+            for (name, remote) in &self.repos {
+                text.push_str(&format!("{} = \"{}\"\n", name, urls[*remote]));
+            }
+            text.push_str("\n[links]\n");
+            for (target, (repo, sub)) in &self.links {
+                let source = if *sub {
+                    format!("{}/sub", repo)
+                } else {
+                    repo.clone()
+                };
+                text.push_str(&format!("\"{}\" = \"{}\"\n", target, source));
+            }
+            text
+        }
+    }
+
+    pub fn manifest(remotes: usize) -> impl Strategy<Value = ManifestSpec> {
+        prop::collection::btree_map(repo_name(), 0..remotes, 1..4).prop_flat_map(|repos| {
+            let names: Vec<String> = repos.keys().cloned().collect();
+            let link = (prop::sample::select(names), any::<bool>());
+            prop::collection::btree_map(target_name(), link, 0..5).prop_map(move |links| {
+                ManifestSpec {
+                    repos: repos.clone(),
+                    links,
+                }
+            })
+        })
+    }
+}